@@ -0,0 +1,123 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//! Tiny serial shell as a reference for microcontroller ports and the firmware of serial
+//! bridges. Each line is a command e.g "on 10001 A" or "off 10001 A" that is encoded with
+//! encoding A and sent with protocol 1 via `transmit_code`.
+//!
+//! `execute` needs neither `std` nor an operating system. A firmware calls it for every line
+//! received on the UART with the board's transmitter pin in a `hal::EmbeddedHalPin`, a
+//! hardware timer in a `hal::EmbeddedHalDelay` and the UART as `core::fmt::Write`.
+//!
+//! On a host the example reads the lines from stdin and traces the pin:
+//!
+//! ```text
+//! echo "on 10001 A" | cargo run -p funksteckdose-core --features embedded-hal --example serial_shell
+//! ```
+
+use core::{convert::Infallible, fmt::Write};
+use embedded_hal::{delay::DelayNs, digital};
+use funksteckdose_core::{
+    hal::{EmbeddedHalDelay, EmbeddedHalPin},
+    transmit_code, Code, Delay, Device, Encoding, EncodingA, Pin, Protocol, Protocol1, State,
+};
+use std::io::{self, BufRead};
+
+/// Repeats of every command
+const REPEATS: usize = 10;
+
+/// Execute `line` and write the reply to `out`
+fn execute<P: Pin, D: Delay, W: Write>(
+    line: &str,
+    pin: &P,
+    delay: &D,
+    out: &mut W,
+) -> core::fmt::Result {
+    let mut words = line.split_whitespace();
+    let state = match words.next() {
+        Some("on") => State::On,
+        Some("off") => State::Off,
+        None => return Ok(()),
+        Some("help") => return writeln!(out, "on|off <group> <device> e.g on 10001 A"),
+        Some(command) => return writeln!(out, "error: unknown command {}", command),
+    };
+    let (group, device) = match (words.next(), words.next(), words.next()) {
+        (Some(group), Some(device), None) => (group, device),
+        _ => return writeln!(out, "error: usage {} <group> <device>", state),
+    };
+    let sent = device
+        .parse::<Device>()
+        .and_then(|device| <EncodingA>::encode(group, &device, &state))
+        .and_then(|code_word| {
+            let code_word = core::str::from_utf8(&code_word).expect("invalid code word");
+            Code::from_tri_state(code_word)
+        })
+        .and_then(|code| transmit_code(pin, delay, &Protocol1::values(), &code, REPEATS));
+    match sent {
+        Ok(()) => writeln!(out, "ok"),
+        Err(e) => writeln!(out, "error: {}", e),
+    }
+}
+
+/// Output pin of the host that counts the pulses
+#[derive(Default)]
+struct TracePin {
+    pulses: usize,
+}
+
+impl digital::ErrorType for TracePin {
+    type Error = Infallible;
+}
+
+impl digital::OutputPin for TracePin {
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.pulses += 1;
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// Timer of the host
+struct Sleep;
+
+impl DelayNs for Sleep {
+    fn delay_ns(&mut self, ns: u32) {
+        std::thread::sleep(std::time::Duration::from_nanos(u64::from(ns)));
+    }
+}
+
+fn main() {
+    let mut pin = EmbeddedHalPin::new(TracePin::default());
+    let delay = EmbeddedHalDelay::new(Sleep);
+    let mut reply = String::new();
+    for line in io::stdin().lock().lines() {
+        let line = line.expect("failed to read stdin");
+        reply.clear();
+        execute(&line, &pin, &delay, &mut reply).expect("failed to format reply");
+        let trace = pin.into_inner();
+        print!("{}", reply);
+        if trace.pulses > 0 {
+            println!("{} pulses", trace.pulses);
+        }
+        pin = EmbeddedHalPin::new(TracePin::default());
+    }
+}
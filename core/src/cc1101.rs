@@ -0,0 +1,243 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//! Driver for the TI CC1101 sub-GHz transceiver. Modules with a CC1101 have a proper
+//! synthesizer and PA and are far less noisy than the cheap OOK transmitters.
+//!
+//! The chip is configured over SPI for ASK/OOK in asynchronous serial mode. In TX the
+//! carrier follows the GDO0 pin, which is driven by any `Pin` just like a plain
+//! transmitter. `Cc1101Pin` bundles both and keeps the chip in TX while it is alive.
+
+use crate::{
+    error::Error,
+    hal::{hal_error, Exclusive},
+    Pin, Value,
+};
+use embedded_hal::spi::SpiDevice;
+
+/// Frequency of the reference crystal of common CC1101 modules
+pub const CRYSTAL: u32 = 26_000_000;
+
+/// PATABLE setting for about +10dBm at 433MHz
+pub const POWER_10DBM: u8 = 0xC0;
+/// PATABLE setting for about +5dBm at 433MHz
+pub const POWER_5DBM: u8 = 0x84;
+/// PATABLE setting for about 0dBm at 433MHz
+pub const POWER_0DBM: u8 = 0x60;
+/// PATABLE setting for about -10dBm at 433MHz
+pub const POWER_MINUS_10DBM: u8 = 0x34;
+
+// Configuration registers
+const IOCFG0: u8 = 0x02;
+const PKTCTRL0: u8 = 0x08;
+const FSCTRL1: u8 = 0x0B;
+const FREQ2: u8 = 0x0D;
+const FREQ1: u8 = 0x0E;
+const FREQ0: u8 = 0x0F;
+const MDMCFG4: u8 = 0x10;
+const MDMCFG3: u8 = 0x11;
+const MDMCFG2: u8 = 0x12;
+const MCSM0: u8 = 0x18;
+const FREND0: u8 = 0x22;
+const FSCAL3: u8 = 0x23;
+const FSCAL2: u8 = 0x24;
+const FSCAL1: u8 = 0x25;
+const FSCAL0: u8 = 0x26;
+const TEST2: u8 = 0x2C;
+const TEST1: u8 = 0x2D;
+const TEST0: u8 = 0x2E;
+const PATABLE: u8 = 0x3E;
+
+// Status registers
+const VERSION: u8 = 0x31;
+
+// Command strobes
+const SRES: u8 = 0x30;
+const SRX: u8 = 0x34;
+const STX: u8 = 0x35;
+const SIDLE: u8 = 0x36;
+
+const READ: u8 = 0x80;
+const BURST: u8 = 0x40;
+
+/// Radio settings
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Carrier frequency in Hz
+    pub frequency: u32,
+    /// PATABLE value used for the high level of the OOK signal e.g `POWER_10DBM`
+    pub power: u8,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            frequency: 433_920_000,
+            power: POWER_10DBM,
+        }
+    }
+}
+
+impl Config {
+    /// Value of the FREQ2..FREQ0 registers for `frequency`
+    ///
+    /// ```
+    /// use funksteckdose_core::cc1101::Config;
+    ///
+    /// assert_eq!(Config::default().frequency_word().unwrap(), 0x10B071);
+    /// ```
+    pub fn frequency_word(&self) -> Result<u32, Error> {
+        let bands = [
+            300_000_000..=348_000_000,
+            387_000_000..=464_000_000,
+            779_000_000..=928_000_000,
+        ];
+        if !bands.iter().any(|band| band.contains(&self.frequency)) {
+            return Err(Error::InvalidFrequency(self.frequency));
+        }
+        Ok(((u64::from(self.frequency) << 16) / u64::from(CRYSTAL)) as u32)
+    }
+}
+
+/// CC1101 on an SPI bus
+pub struct Cc1101<S: SpiDevice> {
+    spi: Exclusive<S>,
+}
+
+impl<S: SpiDevice> Cc1101<S> {
+    /// Wrap `spi`. The chip is not touched until `reset` or `configure`
+    pub fn new(spi: S) -> Cc1101<S> {
+        Cc1101 {
+            spi: Exclusive::new(spi),
+        }
+    }
+
+    /// Return the wrapped SPI device
+    pub fn into_inner(self) -> S {
+        self.spi.into_inner()
+    }
+
+    /// Chip version. Usually 0x14 and useful to check the wiring
+    pub fn version(&self) -> Result<u8, Error> {
+        // Status registers share their addresses with the strobes and need the burst bit
+        let mut buffer = [VERSION | READ | BURST, 0];
+        self.spi
+            .with(|spi| spi.transfer_in_place(&mut buffer))
+            .map_err(hal_error)?;
+        Ok(buffer[1])
+    }
+
+    /// Reset the chip to its power on defaults
+    pub fn reset(&self) -> Result<(), Error> {
+        self.strobe(SRES)
+    }
+
+    /// Configure ASK/OOK in asynchronous serial mode with GDO0 as data input
+    pub fn configure(&self, config: &Config) -> Result<(), Error> {
+        let frequency = config.frequency_word()?;
+        let registers = [
+            (IOCFG0, 0x0D),   // Serial data output in RX
+            (PKTCTRL0, 0x32), // Asynchronous serial mode, no whitening or CRC
+            (FSCTRL1, 0x06),
+            (FREQ2, (frequency >> 16) as u8),
+            (FREQ1, (frequency >> 8) as u8),
+            (FREQ0, frequency as u8),
+            (MDMCFG4, 0x87),
+            (MDMCFG3, 0x32),
+            (MDMCFG2, 0x30), // ASK/OOK without sync word
+            (MCSM0, 0x18),   // Calibrate when leaving IDLE
+            (FREND0, 0x11),  // OOK uses PATABLE 0 for low and 1 for high
+            (FSCAL3, 0xE9),
+            (FSCAL2, 0x2A),
+            (FSCAL1, 0x00),
+            (FSCAL0, 0x1F),
+            (TEST2, 0x81),
+            (TEST1, 0x35),
+            (TEST0, 0x09),
+        ];
+        for (register, value) in registers.iter() {
+            self.write(&[*register, *value])?;
+        }
+        self.set_power(config.power)
+    }
+
+    /// Set the PATABLE value of the high level
+    pub fn set_power(&self, power: u8) -> Result<(), Error> {
+        self.write(&[PATABLE | BURST, 0x00, power])
+    }
+
+    /// Enter TX. The carrier follows GDO0 from now on
+    pub fn transmit(&self) -> Result<(), Error> {
+        self.strobe(STX)
+    }
+
+    /// Enter RX. GDO0 outputs the demodulated signal
+    pub fn receive(&self) -> Result<(), Error> {
+        self.strobe(SRX)
+    }
+
+    /// Enter IDLE
+    pub fn idle(&self) -> Result<(), Error> {
+        self.strobe(SIDLE)
+    }
+
+    fn strobe(&self, strobe: u8) -> Result<(), Error> {
+        self.write(&[strobe])
+    }
+
+    fn write(&self, buffer: &[u8]) -> Result<(), Error> {
+        self.spi.with(|spi| spi.write(buffer)).map_err(hal_error)
+    }
+}
+
+/// `Pin` for a CC1101 in TX with `data` wired to GDO0
+pub struct Cc1101Pin<S: SpiDevice, P: Pin> {
+    chip: Cc1101<S>,
+    data: P,
+}
+
+impl<S: SpiDevice, P: Pin> Cc1101Pin<S, P> {
+    /// Configure `chip` with `config` and enter TX. `data` is set low first, so the
+    /// chip does not emit a carrier until something is sent.
+    pub fn new(chip: Cc1101<S>, data: P, config: &Config) -> Result<Cc1101Pin<S, P>, Error> {
+        data.set(&Value::Low)?;
+        chip.reset()?;
+        chip.configure(config)?;
+        chip.transmit()?;
+        Ok(Cc1101Pin { chip, data })
+    }
+
+    /// The chip e.g to change the power
+    pub fn chip(&self) -> &Cc1101<S> {
+        &self.chip
+    }
+
+    /// Put the chip into IDLE and return the chip and the data pin
+    pub fn into_inner(self) -> Result<(Cc1101<S>, P), Error> {
+        self.data.set(&Value::Low)?;
+        self.chip.idle()?;
+        Ok((self.chip, self.data))
+    }
+}
+
+impl<S: SpiDevice, P: Pin> Pin for Cc1101Pin<S, P> {
+    fn set(&self, value: &Value) -> Result<(), Error> {
+        self.data.set(value)
+    }
+}
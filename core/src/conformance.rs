@@ -0,0 +1,228 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Checks for third party implementations of [`Pin`] and [`InputPin`]
+//!
+//! Call the functions from the tests of a backend crate. Each returns
+//! `Error::NonConforming` with a description of the first violation:
+//!
+//! ```
+//! use funksteckdose_core::{conformance, error::Error, Pin, Value};
+//! use std::time::Duration;
+//!
+//! struct MyPin;
+//!
+//! impl Pin for MyPin {
+//!     fn set(&self, _value: &Value) -> Result<(), Error> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! conformance::check_pin(&MyPin, Duration::from_micros(50)).unwrap();
+//! ```
+//!
+//! New encodings get their standard tests from
+//! [`declare_encoding_tests!`](crate::declare_encoding_tests).
+
+use crate::{error::Error, InputPin, Pin, Value};
+use std::time::{Duration, Instant};
+
+/// Number of calls used to measure the latency of `Pin::set`
+pub const SAMPLES: usize = 1000;
+
+/// Run all checks for an output pin. `max_latency` is the longest time a single call to
+/// `Pin::set` may take. The shortest pulses of the built in protocols are 100µs, so
+/// everything above a few µs distorts the signal.
+pub fn check_pin<T: Pin>(pin: &T, max_latency: Duration) -> Result<(), Error> {
+    check_sequence(pin)?;
+    check_latency(pin, max_latency)?;
+    Ok(())
+}
+
+/// Run all checks for an output pin whose level can be read back through `input` e.g
+/// with a jumper wire or the recording of a mock.
+pub fn check_loopback<T: Pin, I: InputPin>(
+    pin: &T,
+    input: &I,
+    max_latency: Duration,
+) -> Result<(), Error> {
+    check_pin(pin, max_latency)?;
+    check_order(pin, input)
+}
+
+/// The pin accepts alternating and repeated values and can be left low
+pub fn check_sequence<T: Pin>(pin: &T) -> Result<(), Error> {
+    let sequence = [Value::Low, Value::High, Value::High, Value::Low, Value::Low];
+    for (index, value) in sequence.iter().enumerate() {
+        pin.set(value).map_err(|e| {
+            Error::NonConforming(format!("set {:?} at step {} failed: {}", value, index, e))
+        })?;
+    }
+    Ok(())
+}
+
+/// No call to `Pin::set` takes longer than `max_latency`. Returns the longest call.
+pub fn check_latency<T: Pin>(pin: &T, max_latency: Duration) -> Result<Duration, Error> {
+    let mut max = Duration::default();
+    for i in 0..SAMPLES {
+        let value = if i % 2 == 0 { Value::High } else { Value::Low };
+        let start = Instant::now();
+        pin.set(&value)?;
+        max = max.max(start.elapsed());
+    }
+    pin.set(&Value::Low)?;
+    if max > max_latency {
+        return Err(Error::NonConforming(format!(
+            "set took {:?} but at most {:?} are allowed",
+            max, max_latency
+        )));
+    }
+    Ok(max)
+}
+
+/// Every value is applied before `Pin::set` returns
+pub fn check_order<T: Pin, I: InputPin>(pin: &T, input: &I) -> Result<(), Error> {
+    let sequence = [
+        Value::High,
+        Value::Low,
+        Value::High,
+        Value::High,
+        Value::Low,
+    ];
+    for (index, value) in sequence.iter().enumerate() {
+        pin.set(value)?;
+        let read = input.get()?;
+        if read != *value {
+            return Err(Error::NonConforming(format!(
+                "read {:?} after setting {:?} at step {}",
+                read, value, index
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Generate the standard tests of an encoding from samples of group, device and state with
+/// the expected tri-state code word e.g taken from rc-switch or a recording of the remote.
+///
+/// The generated module checks that every sample encodes to its code word and decodes back
+/// to the sample, that code words consist of '0', '1' and 'F', have the same length and
+/// differ between samples and that the `invalid` addresses are rejected:
+///
+/// ```
+/// use funksteckdose_core::{declare_encoding_tests, Device, EncodingA, State};
+///
+/// declare_encoding_tests! {
+///     encoding_a: EncodingA,
+///     samples: [
+///         ("10011", Device::A, State::On) => "0FF000FFFF0F",
+///         ("10011", Device::A, State::Off) => "0FF000FFFFF0",
+///     ],
+///     invalid: [("1001", Device::A)],
+/// }
+/// ```
+#[macro_export]
+macro_rules! declare_encoding_tests {
+    (
+        $name:ident: $encoding:ty,
+        samples: [$(($group:expr, $device:expr, $state:expr) => $word:expr),+ $(,)?]
+        $(,)?
+    ) => {
+        $crate::declare_encoding_tests! {
+            $name: $encoding,
+            samples: [$(($group, $device, $state) => $word),+],
+            invalid: [],
+        }
+    };
+    (
+        $name:ident: $encoding:ty,
+        samples: [$(($group:expr, $device:expr, $state:expr) => $word:expr),+ $(,)?],
+        invalid: [$(($invalid_group:expr, $invalid_device:expr)),* $(,)?]
+        $(,)?
+    ) => {
+        #[cfg(test)]
+        mod $name {
+            #[allow(unused_imports)]
+            use super::*;
+            use $crate::Encoding;
+
+            type Sample = (String, $crate::Device, $crate::State);
+
+            fn samples() -> Vec<(Sample, &'static str)> {
+                vec![$((($group.to_string(), $device, $state), $word)),+]
+            }
+
+            #[test]
+            fn encodes_samples() {
+                for ((group, device, state), word) in samples() {
+                    let code_word = <$encoding>::encode(&group, &device, &state)
+                        .unwrap_or_else(|e| panic!("{} {} {}: {}", group, device, state, e));
+                    assert_eq!(
+                        String::from_utf8_lossy(&code_word),
+                        word,
+                        "{} {} {}",
+                        group,
+                        device,
+                        state
+                    );
+                }
+            }
+
+            #[test]
+            fn decodes_samples() {
+                for (sample, word) in samples() {
+                    assert_eq!(<$encoding>::decode(word.as_bytes()), Some(sample), "{}", word);
+                }
+            }
+
+            #[test]
+            fn code_words_are_tri_state() {
+                let length = samples()[0].1.len();
+                for (_, word) in samples() {
+                    assert!(word.bytes().all(|c| b"01F".contains(&c)), "{}", word);
+                    assert_eq!(word.len(), length, "{}", word);
+                }
+            }
+
+            #[test]
+            fn code_words_differ() {
+                let samples = samples();
+                for (i, (a, word)) in samples.iter().enumerate() {
+                    for (b, other) in &samples[i + 1..] {
+                        assert!(a == b || word != other, "{:?} and {:?}: {}", a, b, word);
+                    }
+                }
+            }
+
+            #[test]
+            fn rejects_invalid_addresses() {
+                $(
+                    let (group, device) = ($invalid_group.to_string(), $invalid_device);
+                    assert!(
+                        <$encoding>::encode(&group, &device, &$crate::State::On).is_err(),
+                        "{} {}",
+                        group,
+                        device
+                    );
+                )*
+            }
+        }
+    };
+}
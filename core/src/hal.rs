@@ -0,0 +1,154 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Adapters from the [embedded-hal](https://docs.rs/embedded-hal) digital traits to `Pin`
+//! and `InputPin`. Any board with an embedded-hal implementation e.g ESP32, STM32, nRF or
+//! Linux via linux-embedded-hal can drive a transmitter without further glue code.
+//!
+//! The embedded-hal traits take `&mut self`. The adapters serialize access with a mutex or
+//! a `RefCell` without `std`.
+
+use crate::{error::Error, Delay, InputPin, Pin, Value};
+use alloc::format;
+use core::fmt;
+use embedded_hal::{delay::DelayNs, digital};
+
+#[cfg(feature = "std")]
+pub(crate) struct Exclusive<P>(std::sync::Mutex<P>);
+
+#[cfg(feature = "std")]
+impl<P> Exclusive<P> {
+    pub(crate) fn new(p: P) -> Exclusive<P> {
+        Exclusive(std::sync::Mutex::new(p))
+    }
+
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut P) -> R) -> R {
+        f(&mut self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    pub(crate) fn into_inner(self) -> P {
+        self.0.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) struct Exclusive<P>(core::cell::RefCell<P>);
+
+#[cfg(not(feature = "std"))]
+impl<P> Exclusive<P> {
+    pub(crate) fn new(p: P) -> Exclusive<P> {
+        Exclusive(core::cell::RefCell::new(p))
+    }
+
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut P) -> R) -> R {
+        f(&mut self.0.borrow_mut())
+    }
+
+    pub(crate) fn into_inner(self) -> P {
+        self.0.into_inner()
+    }
+}
+
+pub(crate) fn hal_error<E: fmt::Debug>(e: E) -> Error {
+    Error::Hal(format!("{:?}", e))
+}
+
+/// `Pin` on top of an embedded-hal `OutputPin`
+pub struct EmbeddedHalPin<P: digital::OutputPin> {
+    pin: Exclusive<P>,
+}
+
+impl<P: digital::OutputPin> EmbeddedHalPin<P> {
+    /// Wrap `pin`
+    pub fn new(pin: P) -> EmbeddedHalPin<P> {
+        EmbeddedHalPin {
+            pin: Exclusive::new(pin),
+        }
+    }
+
+    /// Return the wrapped pin
+    pub fn into_inner(self) -> P {
+        self.pin.into_inner()
+    }
+}
+
+impl<P: digital::OutputPin> Pin for EmbeddedHalPin<P> {
+    fn set(&self, value: &Value) -> Result<(), Error> {
+        self.pin
+            .with(|pin| match value {
+                Value::High => pin.set_high(),
+                Value::Low => pin.set_low(),
+            })
+            .map_err(hal_error)
+    }
+}
+
+/// `InputPin` on top of an embedded-hal `InputPin`
+pub struct EmbeddedHalInputPin<P: digital::InputPin> {
+    pin: Exclusive<P>,
+}
+
+impl<P: digital::InputPin> EmbeddedHalInputPin<P> {
+    /// Wrap `pin`
+    pub fn new(pin: P) -> EmbeddedHalInputPin<P> {
+        EmbeddedHalInputPin {
+            pin: Exclusive::new(pin),
+        }
+    }
+
+    /// Return the wrapped pin
+    pub fn into_inner(self) -> P {
+        self.pin.into_inner()
+    }
+}
+
+impl<P: digital::InputPin> InputPin for EmbeddedHalInputPin<P> {
+    fn get(&self) -> Result<Value, Error> {
+        match self.pin.with(|pin| pin.is_high()).map_err(hal_error)? {
+            true => Ok(Value::High),
+            false => Ok(Value::Low),
+        }
+    }
+}
+
+/// `Delay` on top of an embedded-hal `DelayNs` e.g a hardware timer
+pub struct EmbeddedHalDelay<D: DelayNs> {
+    delay: Exclusive<D>,
+}
+
+impl<D: DelayNs> EmbeddedHalDelay<D> {
+    /// Wrap `delay`
+    pub fn new(delay: D) -> EmbeddedHalDelay<D> {
+        EmbeddedHalDelay {
+            delay: Exclusive::new(delay),
+        }
+    }
+
+    /// Return the wrapped delay
+    pub fn into_inner(self) -> D {
+        self.delay.into_inner()
+    }
+}
+
+impl<D: DelayNs> Delay for EmbeddedHalDelay<D> {
+    fn delay(&self, micros: u32) {
+        self.delay.with(|delay| delay.delay_us(micros))
+    }
+}
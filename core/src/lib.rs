@@ -0,0 +1,1878 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Encodings, protocols and pin interfaces of `funksteckdose` without any backend.
+//! Backends implement [`Pin`] and are provided by the `funksteckdose` crate.
+//!
+//! Without the default `std` feature the crate is `no_std` and needs an allocator only.
+//! [`transmit`] sends codes with any [`Pin`] and [`Delay`] e.g on a microcontroller.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, str};
+use error::Error;
+
+/// Errors of encodings, protocols and pins. The `funksteckdose` crate wraps them in its own
+/// error together with the errors of its services.
+pub mod error {
+    use alloc::{
+        boxed::Box,
+        string::{String, ToString},
+    };
+    use core::fmt;
+
+    /// Native error of a backend
+    pub type Source = Box<dyn core::error::Error + Send + Sync>;
+
+    #[derive(Debug)]
+    pub enum Error {
+        InvalidGroup(String),
+        InvalidDevice(String),
+        InvalidState(String),
+        InvalidCommand(String),
+        InvalidCode(String),
+        EncodingUnsupported(String),
+        InvalidProtocol(String),
+        InvalidFrequency(u32),
+        #[cfg(feature = "std")]
+        Io(std::io::Error),
+        Detached,
+        NonConforming(String),
+        Hal(String),
+        Gpio(Source),
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Error::InvalidGroup(e) => write!(f, "invalid group identifier: {}", e),
+                Error::InvalidDevice(e) => write!(f, "invalid device identifier: {}", e),
+                Error::InvalidState(e) => {
+                    write!(f, "invalid state: {}. Try on, off, 1, 0, true, false", e)
+                }
+                Error::InvalidCommand(e) => write!(f, "invalid command: {}", e),
+                Error::InvalidCode(e) => write!(f, "invalid code: {}", e),
+                Error::EncodingUnsupported(e) => write!(f, "unsupported encoding: {}", e),
+                Error::InvalidProtocol(e) => write!(f, "invalid protocol: {}", e),
+                Error::InvalidFrequency(e) => {
+                    write!(f, "frequency {}Hz is not supported by the radio", e)
+                }
+                #[cfg(feature = "std")]
+                Error::Io(e) => write!(f, "io error: {}", e),
+                Error::Detached => write!(f, "transmitter detached"),
+                Error::NonConforming(e) => write!(f, "non conforming backend: {}", e),
+                Error::Hal(e) => write!(f, "embedded-hal: {}", e),
+                Error::Gpio(e) => write!(f, "gpio: {}", e),
+            }
+        }
+    }
+
+    /// Copies keep the variant and the message. The native error of `Io` and `Gpio` cannot
+    /// be copied, their copies carry the message of the original and the kind of IO errors.
+    impl Clone for Error {
+        fn clone(&self) -> Error {
+            match self {
+                Error::InvalidGroup(e) => Error::InvalidGroup(e.clone()),
+                Error::InvalidDevice(e) => Error::InvalidDevice(e.clone()),
+                Error::InvalidState(e) => Error::InvalidState(e.clone()),
+                Error::InvalidCommand(e) => Error::InvalidCommand(e.clone()),
+                Error::InvalidCode(e) => Error::InvalidCode(e.clone()),
+                Error::EncodingUnsupported(e) => Error::EncodingUnsupported(e.clone()),
+                Error::InvalidProtocol(e) => Error::InvalidProtocol(e.clone()),
+                Error::InvalidFrequency(e) => Error::InvalidFrequency(*e),
+                #[cfg(feature = "std")]
+                Error::Io(e) => Error::Io(std::io::Error::new(e.kind(), e.to_string())),
+                Error::Detached => Error::Detached,
+                Error::NonConforming(e) => Error::NonConforming(e.clone()),
+                Error::Hal(e) => Error::Hal(e.clone()),
+                Error::Gpio(e) => Error::Gpio(Box::from(e.to_string())),
+            }
+        }
+    }
+
+    impl core::error::Error for Error {
+        fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+            match self {
+                #[cfg(feature = "std")]
+                Error::Io(e) => Some(e),
+                Error::Gpio(e) => Some(e.as_ref()),
+                _ => None,
+            }
+        }
+    }
+
+    impl From<Source> for Error {
+        fn from(e: Source) -> Error {
+            Error::Gpio(e)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl From<std::io::Error> for Error {
+        fn from(e: std::io::Error) -> Error {
+            Error::Io(e)
+        }
+    }
+
+    impl Error {
+        /// Wrap the native error of a backend e.g the error of a GPIO library. The error
+        /// stays available through `source()`.
+        pub fn gpio<E: core::error::Error + Send + Sync + 'static>(e: E) -> Error {
+            Error::Gpio(Box::new(e))
+        }
+
+        /// Stable error code that does not change with the message
+        pub fn code(&self) -> &'static str {
+            match self {
+                Error::InvalidGroup(_) => "invalid_group",
+                Error::InvalidDevice(_) => "invalid_device",
+                Error::InvalidState(_) => "invalid_state",
+                Error::InvalidCommand(_) => "invalid_command",
+                Error::InvalidCode(_) => "invalid_code",
+                Error::EncodingUnsupported(_) => "encoding_unsupported",
+                Error::InvalidProtocol(_) => "invalid_protocol",
+                Error::InvalidFrequency(_) => "invalid_frequency",
+                #[cfg(feature = "std")]
+                Error::Io(_) => "io",
+                Error::Detached => "detached",
+                Error::NonConforming(_) => "non_conforming",
+                Error::Hal(_) => "hal",
+                Error::Gpio(_) => "gpio",
+            }
+        }
+
+        /// True if the pin or another backend failed, false for invalid input
+        /// ```
+        /// use funksteckdose_core::error::Error;
+        /// assert!(Error::Hal("pin busy".into()).is_backend());
+        /// assert!(!Error::InvalidGroup("2".into()).is_backend());
+        /// ```
+        pub fn is_backend(&self) -> bool {
+            match self {
+                #[cfg(feature = "std")]
+                Error::Io(_) => true,
+                Error::Detached | Error::NonConforming(_) | Error::Hal(_) | Error::Gpio(_) => true,
+                _ => false,
+            }
+        }
+    }
+}
+
+/// Serialize and deserialize types as their string form e.g "on" for `State::On` or "A" for
+/// `Device::A`, the same as in configuration files and on the command line
+#[cfg(feature = "serde")]
+macro_rules! serde_str {
+    ($($t:ty),*) => {$(
+        impl serde::Serialize for $t {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $t {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <String as serde::Deserialize>::deserialize(deserializer)?
+                    .parse()
+                    .map_err(serde::de::Error::custom)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "serde")]
+serde_str!(
+    Group,
+    Device,
+    State,
+    Command,
+    RotaryAddress,
+    IntertechnoAddress,
+    BrennenstuhlAddress,
+    REVAddress
+);
+
+/// Checks for backend implementations
+#[cfg(feature = "std")]
+pub mod conformance;
+
+/// Adapters for embedded-hal pins
+#[cfg(feature = "embedded-hal")]
+pub mod hal;
+
+/// CC1101 transceiver driver
+#[cfg(feature = "cc1101")]
+pub mod cc1101;
+
+/// A group code as set with the dip switches of a socket e.g "10011". The first
+/// character is dip switch 1 and '1' means the switch is in the "on" position.
+///
+/// Parsing also accepts the formats of the other encodings: a decimal number like the
+/// rotary switch of encoding B e.g "3", the family code followed by the group of
+/// encoding C e.g "c2" and the group letter of REV sockets e.g "B". Anything else is
+/// rejected before it reaches an encoding.
+/// ```
+/// use funksteckdose_core::Group;
+/// assert!("10011".parse::<Group>().unwrap().is_dips());
+/// assert_eq!("3".parse::<Group>().unwrap().number(), Some(3));
+/// assert!("c2".parse::<Group>().is_ok());
+/// assert!("B".parse::<Group>().is_ok());
+/// assert!("1001x".parse::<Group>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Group(String);
+
+impl Group {
+    /// Create a group of `dips` dip switches from the decimal `value`. Switch 1 is the
+    /// most significant bit
+    /// ```
+    /// use funksteckdose_core::Group;
+    /// assert_eq!(Group::from_number(19, 5).unwrap().as_str(), "10011");
+    /// assert!(Group::from_number(32, 5).is_err());
+    /// ```
+    pub fn from_number(value: u32, dips: usize) -> Result<Group, Error> {
+        if dips == 0 || dips > 32 || (dips < 32 && value >> dips != 0) {
+            return Err(Error::InvalidGroup(format!("{} with {} dips", value, dips)));
+        }
+        Ok(Group(format!("{:0width$b}", value, width = dips)))
+    }
+
+    /// Create a group from the positions of the dip switches starting with switch 1
+    /// ```
+    /// use funksteckdose_core::Group;
+    /// let group = Group::from_dips([true, false, false, true, true]);
+    /// assert_eq!(group.as_str(), "10011");
+    /// ```
+    pub fn from_dips<D: AsRef<[bool]>>(dips: D) -> Group {
+        Group(
+            dips.as_ref()
+                .iter()
+                .map(|on| if *on { '1' } else { '0' })
+                .collect(),
+        )
+    }
+
+    /// Positions of the dip switches starting with switch 1
+    pub fn dips(&self) -> Vec<bool> {
+        self.0.chars().map(|c| c == '1').collect()
+    }
+
+    /// True if the group is a dip switch code
+    pub fn is_dips(&self) -> bool {
+        self.0.chars().all(|c| c == '0' || c == '1')
+    }
+
+    /// The group as a decimal number e.g the rotary switch of encoding B
+    pub fn number(&self) -> Option<u32> {
+        if self.0.chars().all(|c| c.is_ascii_digit()) {
+            self.0.parse().ok()
+        } else {
+            None
+        }
+    }
+
+    /// The group code
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl str::FromStr for Group {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let number = match chars.next() {
+            // The group letter of REV sockets
+            Some('a'..='d' | 'A'..='D') if chars.as_str().is_empty() => return Ok(Group(s.into())),
+            Some('a'..='p') => chars.as_str(),
+            _ => s,
+        };
+        if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+            return Err(Error::InvalidGroup(s.into()));
+        }
+        Ok(Group(s.into()))
+    }
+}
+
+/// A Device
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Device {
+    A,
+    B,
+    C,
+    D,
+    E,
+}
+
+impl Device {
+    /// Create a device from the position of its dip switch (1 to 5)
+    /// ```
+    /// use funksteckdose_core::Device;
+    /// assert_eq!(Device::from_dip_position(2).unwrap(), Device::B);
+    /// ```
+    pub fn from_dip_position(position: u8) -> Result<Device, Error> {
+        match position {
+            1 => Ok(Device::A),
+            2 => Ok(Device::B),
+            3 => Ok(Device::C),
+            4 => Ok(Device::D),
+            5 => Ok(Device::E),
+            _ => Err(Error::InvalidDevice(position.to_string())),
+        }
+    }
+}
+
+impl From<Device> for u8 {
+    fn from(d: Device) -> u8 {
+        match d {
+            Device::A => 1,
+            Device::B => 2,
+            Device::C => 3,
+            Device::D => 4,
+            Device::E => 5,
+        }
+    }
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Device::A => "A",
+            Device::B => "B",
+            Device::C => "C",
+            Device::D => "D",
+            Device::E => "E",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl str::FromStr for Device {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" | "a" | "A" | "10000" => Ok(Device::A),
+            "1" | "b" | "B" | "01000" => Ok(Device::B),
+            "2" | "c" | "C" | "00100" => Ok(Device::C),
+            "3" | "d" | "D" | "00010" => Ok(Device::D),
+            "4" | "e" | "E" | "00001" => Ok(Device::E),
+            _ => Err(Error::InvalidDevice(s.into())),
+        }
+    }
+}
+
+/// State to switch a socket to
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum State {
+    On,
+    Off,
+    /// Flip the last known state. Needs to be resolved with `State::resolve` before
+    /// encoding since the sockets have no feedback channel.
+    Toggle,
+}
+
+impl State {
+    /// Concrete state to send given the `last` known state of a socket. `Toggle` of a
+    /// socket with an unknown state switches it on.
+    /// ```
+    /// use funksteckdose_core::State;
+    /// assert_eq!(State::Toggle.resolve(Some(&State::On)), State::Off);
+    /// assert_eq!(State::Toggle.resolve(None), State::On);
+    /// assert_eq!(State::Off.resolve(Some(&State::Off)), State::Off);
+    /// ```
+    pub fn resolve(&self, last: Option<&State>) -> State {
+        match (self, last) {
+            (State::Toggle, Some(State::On)) => State::Off,
+            (State::Toggle, _) => State::On,
+            (state, _) => state.clone(),
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            State::On => write!(f, "on"),
+            State::Off => write!(f, "off"),
+            State::Toggle => write!(f, "toggle"),
+        }
+    }
+}
+
+impl str::FromStr for State {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "On" | "on" | "1" | "true" => Ok(State::On),
+            "Off" | "off" | "0" | "false" => Ok(State::Off),
+            "Toggle" | "toggle" => Ok(State::Toggle),
+            _ => Err(Error::InvalidState(s.into())),
+        }
+    }
+}
+
+/// Command for a socket. Dimmers take a level from 0 to `Command::MAX_LEVEL` in place of
+/// on and off.
+/// ```
+/// use funksteckdose_core::{Command, State};
+/// assert_eq!("dim:8".parse::<Command>().unwrap(), Command::Dim(8));
+/// assert_eq!(Command::On.state(), Some(State::On));
+/// assert!(Command::dim(16).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Command {
+    On,
+    Off,
+    Dim(u8),
+}
+
+impl Command {
+    /// Highest dim level
+    pub const MAX_LEVEL: u8 = 15;
+
+    /// Dim to `level`
+    pub fn dim(level: u8) -> Result<Command, Error> {
+        if level > Command::MAX_LEVEL {
+            return Err(Error::InvalidCommand(format!(
+                "dim level {} is not between 0 and {}",
+                level,
+                Command::MAX_LEVEL
+            )));
+        }
+        Ok(Command::Dim(level))
+    }
+
+    /// State of on and off. `None` for dim levels.
+    pub fn state(&self) -> Option<State> {
+        match self {
+            Command::On => Some(State::On),
+            Command::Off => Some(State::Off),
+            Command::Dim(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::On => write!(f, "on"),
+            Command::Off => write!(f, "off"),
+            Command::Dim(level) => write!(f, "dim:{}", level),
+        }
+    }
+}
+
+impl str::FromStr for Command {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(level) = s.strip_prefix("dim:") {
+            let level = level.parse().map_err(|_| Error::InvalidCommand(s.into()))?;
+            return Command::dim(level);
+        }
+        match s.parse::<State>() {
+            Ok(State::On) => Ok(Command::On),
+            Ok(State::Off) => Ok(Command::Off),
+            _ => Err(Error::InvalidCommand(s.into())),
+        }
+    }
+}
+
+/// Value to set a GPIO to
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Low,
+    High,
+}
+
+/// Encoding
+pub trait Encoding {
+    fn encode(group: &str, device: &Device, state: &State) -> Result<Vec<u8>, Error>;
+
+    /// Encode a parsed `Group`
+    fn encode_group(group: &Group, device: &Device, state: &State) -> Result<Vec<u8>, Error> {
+        Self::encode(group.as_str(), device, state)
+    }
+
+    /// Encode a `Command`. Encodings without dim levels fail to encode `Command::Dim`.
+    fn encode_command(group: &str, device: &Device, command: &Command) -> Result<Vec<u8>, Error> {
+        match command.state() {
+            Some(state) => Self::encode(group, device, &state),
+            None => Err(Error::EncodingUnsupported(format!(
+                "{} of {} {}",
+                command, group, device
+            ))),
+        }
+    }
+
+    /// Decode a received code word back into group, device and state
+    fn decode(_code_word: &[u8]) -> Option<(String, Device, State)> {
+        None
+    }
+}
+
+/// Variations of an encoding found in clone sockets that use the same framing
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct EncodingOptions {
+    /// Reverse the order of the group bits
+    pub reverse_group: bool,
+    /// Reverse the order of the device bits e.g A becomes E
+    pub reverse_device: bool,
+    /// Swap the on and off bits
+    pub invert_state: bool,
+}
+
+impl EncodingOptions {
+    /// Encode with `E` after applying the options
+    pub fn encode<E: Encoding>(
+        &self,
+        group: &str,
+        device: &Device,
+        state: &State,
+    ) -> Result<Vec<u8>, Error> {
+        let (group, device, state) = self.apply(group, device, state);
+        E::encode(&group, &device, &state)
+    }
+
+    /// Decode with `E` and revert the options
+    pub fn decode<E: Encoding>(&self, code_word: &[u8]) -> Option<(String, Device, State)> {
+        let (group, device, state) = E::decode(code_word)?;
+        Some(self.apply(&group, &device, &state))
+    }
+
+    // All options are their own inverse
+    fn apply(&self, group: &str, device: &Device, state: &State) -> (String, Device, State) {
+        let group = if self.reverse_group {
+            group.chars().rev().collect()
+        } else {
+            group.to_string()
+        };
+        let device = if self.reverse_device {
+            match device {
+                Device::A => Device::E,
+                Device::B => Device::D,
+                Device::C => Device::C,
+                Device::D => Device::B,
+                Device::E => Device::A,
+            }
+        } else {
+            device.clone()
+        };
+        let state = match state {
+            State::On if self.invert_state => State::Off,
+            State::Off if self.invert_state => State::On,
+            _ => state.clone(),
+        };
+        (group, device, state)
+    }
+}
+
+/// Encoding A - check [rc-switch](https://github.com/sui77/rc-switch/) for details
+///
+/// `DIPS` is the number of group dip switches. Most sockets have 5, some clones have 4 or
+/// 10. The group length is checked when the encoding is used, other values fail to compile:
+/// ```
+/// use funksteckdose_core::{Device, Encoding, EncodingA, State};
+/// let code_word = EncodingA::<4>::encode("1001", &Device::A, &State::On).unwrap();
+/// assert_eq!(code_word, b"0FF00FFFF0F");
+/// ```
+/// ```compile_fail
+/// use funksteckdose_core::{Device, Encoding, EncodingA, State};
+/// EncodingA::<3>::encode("100", &Device::A, &State::On);
+/// ```
+pub struct EncodingA<const DIPS: usize = 5>;
+
+impl<const DIPS: usize> EncodingA<DIPS> {
+    const SUPPORTED: () = assert!(
+        DIPS == 4 || DIPS == 5 || DIPS == 10,
+        "EncodingA supports 4, 5 or 10 group dips"
+    );
+}
+
+impl<const DIPS: usize> Encoding for EncodingA<DIPS> {
+    fn encode(group: &str, device: &Device, state: &State) -> Result<Vec<u8>, Error> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::SUPPORTED;
+        if group.len() != DIPS || group.chars().any(|c| c != '0' && c != '1') {
+            return Err(Error::InvalidGroup(group.into()));
+        }
+        let chars = group.chars();
+
+        let device = match device {
+            Device::A => "10000",
+            Device::B => "01000",
+            Device::C => "00100",
+            Device::D => "00010",
+            Device::E => "00001",
+        };
+
+        let chars = chars.chain(device.chars());
+
+        let chars = match *state {
+            State::On => chars.chain("10".chars()),
+            State::Off => chars.chain("01".chars()),
+            State::Toggle => return Err(Error::InvalidState(state.to_string())),
+        };
+
+        Ok(chars
+            .map(|c| match c {
+                '0' => b'F',
+                _ => b'0',
+            })
+            .collect())
+    }
+
+    fn decode(code_word: &[u8]) -> Option<(String, Device, State)> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::SUPPORTED;
+        if code_word.len() != DIPS + 7 {
+            return None;
+        }
+        let bits = code_word
+            .iter()
+            .map(|c| match c {
+                b'F' => Some('0'),
+                b'0' => Some('1'),
+                _ => None,
+            })
+            .collect::<Option<String>>()?;
+
+        let group = bits[0..DIPS].to_string();
+        let device = bits[DIPS..DIPS + 5].parse().ok()?;
+        let state = match &bits[DIPS + 5..DIPS + 7] {
+            "10" => State::On,
+            "01" => State::Off,
+            _ => return None,
+        };
+        Some((group, device, state))
+    }
+}
+
+/// Position of the two rotary switches of Encoding B sockets e.g "3:2"
+#[derive(Clone, Debug, PartialEq)]
+pub struct RotaryAddress {
+    /// Position of the first switch (1 to 4)
+    pub address: u8,
+    /// Position of the second switch (1 to 4)
+    pub channel: u8,
+}
+
+impl RotaryAddress {
+    /// Create a new address. Both positions must be in the range 1 to 4.
+    pub fn new(address: u8, channel: u8) -> Result<RotaryAddress, Error> {
+        if !(1..=4).contains(&address) {
+            return Err(Error::InvalidGroup(address.to_string()));
+        }
+        if !(1..=4).contains(&channel) {
+            return Err(Error::InvalidDevice(channel.to_string()));
+        }
+        Ok(RotaryAddress { address, channel })
+    }
+
+    /// Group and device to be used with `EncodingB`
+    pub fn group_device(&self) -> (String, Device) {
+        let device = Device::from_dip_position(self.channel).expect("invalid channel");
+        (self.address.to_string(), device)
+    }
+}
+
+impl fmt::Display for RotaryAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.address, self.channel)
+    }
+}
+
+impl str::FromStr for RotaryAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let address = parts
+            .next()
+            .and_then(|a| a.trim().parse().ok())
+            .ok_or_else(|| Error::InvalidGroup(s.into()))?;
+        let channel = parts
+            .next()
+            .and_then(|c| c.trim().parse().ok())
+            .ok_or_else(|| Error::InvalidDevice(s.into()))?;
+        RotaryAddress::new(address, channel)
+    }
+}
+
+/// Encoding B - check [rc-switch](https://github.com/sui77/rc-switch/) for details
+///
+/// The group is the position of the first rotary switch ("1" to "4") and the device the
+/// position of the second one (A to D). See `RotaryAddress`.
+pub struct EncodingB;
+
+impl Encoding for EncodingB {
+    fn encode(group: &str, device: &Device, state: &State) -> Result<Vec<u8>, Error> {
+        let address = match group.parse::<u8>() {
+            Ok(a) if (1..=4).contains(&a) => a,
+            _ => return Err(Error::InvalidGroup(group.into())),
+        };
+        let channel = match u8::from(device.clone()) {
+            c if c <= 4 => c,
+            _ => return Err(Error::InvalidDevice(device.to_string())),
+        };
+
+        let mut code_word = Vec::with_capacity(12);
+        code_word.extend((1..=4).map(|i| if i == address { b'0' } else { b'F' }));
+        code_word.extend((1..=4).map(|i| if i == channel { b'0' } else { b'F' }));
+        code_word.extend(b"FFF");
+        code_word.push(match *state {
+            State::On => b'F',
+            State::Off => b'0',
+            State::Toggle => return Err(Error::InvalidState(state.to_string())),
+        });
+        Ok(code_word)
+    }
+
+    fn decode(code_word: &[u8]) -> Option<(String, Device, State)> {
+        if code_word.len() != 12 || &code_word[8..11] != b"FFF" {
+            return None;
+        }
+        let position = |field: &[u8]| {
+            if field.iter().filter(|c| **c == b'0').count() != 1 {
+                return None;
+            }
+            field.iter().position(|c| *c == b'0').map(|p| p as u8 + 1)
+        };
+        let address =
+            RotaryAddress::new(position(&code_word[0..4])?, position(&code_word[4..8])?).ok()?;
+        let state = match code_word[11] {
+            b'F' => State::On,
+            b'0' => State::Off,
+            _ => return None,
+        };
+        let (group, device) = address.group_device();
+        Some((group, device, state))
+    }
+}
+
+/// Iterator over all addresses of an encoding as group and device. The length is known
+/// in advance.
+#[derive(Clone, Debug)]
+pub struct Addresses {
+    index: usize,
+    len: usize,
+    address: fn(usize) -> (String, Device),
+}
+
+impl Addresses {
+    fn new(len: usize, address: fn(usize) -> (String, Device)) -> Addresses {
+        Addresses {
+            index: 0,
+            len,
+            address,
+        }
+    }
+}
+
+impl Iterator for Addresses {
+    type Item = (String, Device);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.len {
+            self.index += 1;
+            Some((self.address)(self.index - 1))
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Addresses {}
+
+impl core::iter::FusedIterator for Addresses {}
+
+impl EncodingA {
+    /// All 160 addresses. Groups are ordered like binary numbers from "00000" to "11111"
+    /// and devices from A to E within a group.
+    /// ```
+    /// use funksteckdose_core::{Device, EncodingA};
+    /// let mut addresses = EncodingA::all_addresses();
+    /// assert_eq!(addresses.len(), 160);
+    /// assert_eq!(addresses.next(), Some(("00000".into(), Device::A)));
+    /// assert_eq!(addresses.last(), Some(("11111".into(), Device::E)));
+    /// ```
+    pub fn all_addresses() -> Addresses {
+        Addresses::new(32 * 5, |index| {
+            let group = format!("{:05b}", index / 5);
+            let device = Device::from_dip_position((index % 5) as u8 + 1).expect("invalid device");
+            (group, device)
+        })
+    }
+}
+
+impl EncodingB {
+    /// All 16 addresses ordered by address and channel i.e "1" A, "1" B ... "4" D
+    pub fn all_addresses() -> Addresses {
+        Addresses::new(4 * 4, |index| {
+            RotaryAddress::new((index / 4) as u8 + 1, (index % 4) as u8 + 1)
+                .expect("invalid rotary address")
+                .group_device()
+        })
+    }
+}
+
+/// Address of an Intertechno socket with family code e.g "c:2:4"
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntertechnoAddress {
+    /// Family code ('a' to 'p')
+    pub family: char,
+    /// Group (1 to 4)
+    pub group: u8,
+    /// Device (1 to 4)
+    pub device: u8,
+}
+
+impl IntertechnoAddress {
+    /// Create a new address. The family must be in the range 'a' to 'p', group and device
+    /// in the range 1 to 4.
+    pub fn new(family: char, group: u8, device: u8) -> Result<IntertechnoAddress, Error> {
+        let family = family.to_ascii_lowercase();
+        if !('a'..='p').contains(&family) {
+            return Err(Error::InvalidGroup(family.to_string()));
+        }
+        if !(1..=4).contains(&group) {
+            return Err(Error::InvalidGroup(group.to_string()));
+        }
+        if !(1..=4).contains(&device) {
+            return Err(Error::InvalidDevice(device.to_string()));
+        }
+        Ok(IntertechnoAddress {
+            family,
+            group,
+            device,
+        })
+    }
+
+    /// Group and device to be used with `EncodingC`. The group is the family code followed
+    /// by the group e.g "c2" and the device is A to D.
+    pub fn group_device(&self) -> (String, Device) {
+        let device = Device::from_dip_position(self.device).expect("invalid device");
+        (format!("{}{}", self.family, self.group), device)
+    }
+
+    fn from_group_device(group: &str, device: &Device) -> Result<IntertechnoAddress, Error> {
+        let invalid = || Error::InvalidGroup(group.into());
+        let mut chars = group.chars();
+        let family = chars.next().ok_or_else(invalid)?;
+        let number = chars.as_str().parse().map_err(|_| invalid())?;
+        let device = match u8::from(device.clone()) {
+            d if d <= 4 => d,
+            _ => return Err(Error::InvalidDevice(device.to_string())),
+        };
+        IntertechnoAddress::new(family, number, device)
+    }
+}
+
+impl fmt::Display for IntertechnoAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.family, self.group, self.device)
+    }
+}
+
+impl str::FromStr for IntertechnoAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':').map(str::trim);
+        let family = parts
+            .next()
+            .filter(|f| f.chars().count() == 1)
+            .and_then(|f| f.chars().next())
+            .ok_or_else(|| Error::InvalidGroup(s.into()))?;
+        let group = parts
+            .next()
+            .and_then(|g| g.parse().ok())
+            .ok_or_else(|| Error::InvalidGroup(s.into()))?;
+        let device = parts
+            .next()
+            .and_then(|d| d.parse().ok())
+            .ok_or_else(|| Error::InvalidDevice(s.into()))?;
+        IntertechnoAddress::new(family, group, device)
+    }
+}
+
+/// Encoding C (Intertechno) - check [rc-switch](https://github.com/sui77/rc-switch/) for
+/// details
+///
+/// The group is the family code followed by the group e.g "c2" and the device is A to D.
+/// See `IntertechnoAddress`.
+pub struct EncodingC;
+
+impl Encoding for EncodingC {
+    fn encode(group: &str, device: &Device, state: &State) -> Result<Vec<u8>, Error> {
+        let address = IntertechnoAddress::from_group_device(group, device)?;
+        let bits = |value: u8, count: u8| {
+            (0..count).map(move |bit| if value & (1 << bit) != 0 { b'F' } else { b'0' })
+        };
+
+        let mut code_word = Vec::with_capacity(12);
+        code_word.extend(bits(address.family as u8 - b'a', 4));
+        code_word.extend(bits(address.device - 1, 2));
+        code_word.extend(bits(address.group - 1, 2));
+        code_word.extend(b"0FF");
+        code_word.push(match *state {
+            State::On => b'F',
+            State::Off => b'0',
+            State::Toggle => return Err(Error::InvalidState(state.to_string())),
+        });
+        Ok(code_word)
+    }
+
+    fn decode(code_word: &[u8]) -> Option<(String, Device, State)> {
+        if code_word.len() != 12 || &code_word[8..11] != b"0FF" {
+            return None;
+        }
+        let value = |field: &[u8]| {
+            field
+                .iter()
+                .enumerate()
+                .try_fold(0u8, |value, (bit, c)| match c {
+                    b'F' => Some(value | 1 << bit),
+                    b'0' => Some(value),
+                    _ => None,
+                })
+        };
+        let family = (b'a' + value(&code_word[0..4])?) as char;
+        let device = value(&code_word[4..6])? + 1;
+        let group = value(&code_word[6..8])? + 1;
+        let state = match code_word[11] {
+            b'F' => State::On,
+            b'0' => State::Off,
+            _ => return None,
+        };
+        let (group, device) = IntertechnoAddress::new(family, group, device)
+            .ok()?
+            .group_device();
+        Some((group, device, state))
+    }
+}
+
+impl EncodingC {
+    /// All 256 addresses ordered by family, group and device i.e "a1" A, "a1" B ... "p4" D
+    pub fn all_addresses() -> Addresses {
+        Addresses::new(16 * 4 * 4, |index| {
+            IntertechnoAddress::new(
+                (b'a' + (index / 16) as u8) as char,
+                (index / 4 % 4) as u8 + 1,
+                (index % 4) as u8 + 1,
+            )
+            .expect("invalid intertechno address")
+            .group_device()
+        })
+    }
+}
+
+/// Address of a Brennenstuhl RCS 1000 N socket e.g "10011:B"
+///
+/// The five system code dip switches are found in the battery compartment of the remote and
+/// behind the screw cover of the socket. The unit is the letter A to E printed on the socket
+/// which is the one dip switch of the unit block that is switched on. Parsing accepts the
+/// dips followed by the letter with an optional ':' e.g "10011:B" or "10011B".
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrennenstuhlAddress {
+    /// System code dip switches 1 to 5, `true` is on (up)
+    pub dips: [bool; 5],
+    /// Unit letter printed on the socket
+    pub unit: Device,
+}
+
+impl BrennenstuhlAddress {
+    /// Create a new address from the system code dips and the unit letter (A to E)
+    pub fn new(dips: [bool; 5], unit: char) -> Result<BrennenstuhlAddress, Error> {
+        let unit = match unit.to_ascii_uppercase() {
+            letter @ 'A'..='E' => letter.to_string().parse()?,
+            _ => return Err(Error::InvalidDevice(unit.to_string())),
+        };
+        Ok(BrennenstuhlAddress { dips, unit })
+    }
+
+    /// Group and device to be used with `EncodingBrennenstuhl` or `EncodingA`
+    pub fn group_device(&self) -> (String, Device) {
+        (
+            Group::from_dips(self.dips).as_str().to_string(),
+            self.unit.clone(),
+        )
+    }
+}
+
+impl fmt::Display for BrennenstuhlAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (group, unit) = self.group_device();
+        write!(f, "{}:{}", group, unit)
+    }
+}
+
+impl str::FromStr for BrennenstuhlAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (dips, unit) = match s.char_indices().nth(5) {
+            Some((index, _)) => s.split_at(index),
+            None => return Err(Error::InvalidGroup(s.into())),
+        };
+        let group = dips.parse::<Group>()?;
+        if !group.is_dips() {
+            return Err(Error::InvalidGroup(s.into()));
+        }
+        let mut positions = [false; 5];
+        positions.copy_from_slice(&group.dips());
+        let mut unit = unit.strip_prefix(':').unwrap_or(unit).trim().chars();
+        match (unit.next(), unit.next()) {
+            (Some(letter), None) => BrennenstuhlAddress::new(positions, letter),
+            _ => Err(Error::InvalidDevice(s.into())),
+        }
+    }
+}
+
+/// Encoding of Brennenstuhl RCS 1000 N sockets
+///
+/// The group is the system code e.g "10011" and the device is the letter printed on the
+/// socket. See `BrennenstuhlAddress`. The code word is the one of `EncodingA`: the dips
+/// with on as '0' and off as 'F' followed by the state pair where the sockets expect "0F"
+/// for on and "F0" for off. Clones that swap the pair need `EncodingOptions::invert_state`.
+/// ```
+/// use funksteckdose_core::{BrennenstuhlAddress, Encoding, EncodingBrennenstuhl, State};
+/// let address: BrennenstuhlAddress = "10011:B".parse().unwrap();
+/// let (group, device) = address.group_device();
+/// let on = EncodingBrennenstuhl::encode(&group, &device, &State::On).unwrap();
+/// assert_eq!(on, b"0FF00F0FFF0F".to_vec());
+/// let off = EncodingBrennenstuhl::encode(&group, &device, &State::Off).unwrap();
+/// assert_eq!(off, b"0FF00F0FFFF0".to_vec());
+/// ```
+pub struct EncodingBrennenstuhl;
+
+impl Encoding for EncodingBrennenstuhl {
+    fn encode(group: &str, device: &Device, state: &State) -> Result<Vec<u8>, Error> {
+        <EncodingA>::encode(group, device, state)
+    }
+
+    fn decode(code_word: &[u8]) -> Option<(String, Device, State)> {
+        <EncodingA>::decode(code_word)
+    }
+}
+
+/// Address of a REV Telecontrol (Ritter) socket e.g "B2"
+///
+/// The group is the letter A to D of the slide switch on the back of the socket and the
+/// device the number 1 to 3 of the switch next to it. Parsing accepts the letter in either
+/// case followed by the number with an optional ':' e.g "B2", "b:2".
+#[derive(Clone, Debug, PartialEq)]
+pub struct REVAddress {
+    /// Group letter A to D
+    pub group: char,
+    /// Device number 1 to 3
+    pub device: u8,
+}
+
+impl REVAddress {
+    /// Create a new address from the group letter (A to D) and the device number (1 to 3)
+    pub fn new(group: char, device: u8) -> Result<REVAddress, Error> {
+        let group = match group.to_ascii_uppercase() {
+            letter @ 'A'..='D' => letter,
+            _ => return Err(Error::InvalidGroup(group.to_string())),
+        };
+        if !(1..=3).contains(&device) {
+            return Err(Error::InvalidDevice(device.to_string()));
+        }
+        Ok(REVAddress { group, device })
+    }
+
+    /// Group and device to be used with `EncodingREV`
+    pub fn group_device(&self) -> (String, Device) {
+        let device = Device::from_dip_position(self.device).expect("invalid device");
+        (self.group.to_string(), device)
+    }
+
+    // The address of `group` and `device` as passed to an encoding
+    fn from_group_device(group: &str, device: &Device) -> Result<REVAddress, Error> {
+        let mut chars = group.chars();
+        let letter = match (chars.next(), chars.next()) {
+            (Some(letter), None) => letter,
+            _ => return Err(Error::InvalidGroup(group.into())),
+        };
+        REVAddress::new(letter, u8::from(device.clone()))
+    }
+}
+
+impl fmt::Display for REVAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.group, self.device)
+    }
+}
+
+impl str::FromStr for REVAddress {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut chars = s.chars();
+        let group = chars.next().ok_or_else(|| Error::InvalidGroup(s.into()))?;
+        let device = chars
+            .as_str()
+            .trim_start_matches(':')
+            .parse()
+            .map_err(|_| Error::InvalidDevice(s.into()))?;
+        REVAddress::new(group, device)
+    }
+}
+
+/// Encoding of REV Telecontrol (Ritter) sockets, `getCodeWordD` of
+/// [rc-switch](https://github.com/sui77/rc-switch/)
+///
+/// The group is the letter "A" to "D" and the device is A to C for the numbers 1 to 3. See
+/// `REVAddress`. The code word has one '1' in the four group positions and one in the three
+/// device positions, all others are 'F', followed by "000" and "10" for on or "01" for off.
+/// ```
+/// use funksteckdose_core::{Encoding, EncodingREV, REVAddress, State};
+/// let address: REVAddress = "B2".parse().unwrap();
+/// let (group, device) = address.group_device();
+/// let on = EncodingREV::encode(&group, &device, &State::On).unwrap();
+/// assert_eq!(on, b"F1FFF1F00010".to_vec());
+/// assert_eq!(EncodingREV::decode(&on), Some((group, device, State::On)));
+/// ```
+pub struct EncodingREV;
+
+impl Encoding for EncodingREV {
+    fn encode(group: &str, device: &Device, state: &State) -> Result<Vec<u8>, Error> {
+        let address = REVAddress::from_group_device(group, device)?;
+        let index = address.group as u8 - b'A';
+
+        let mut code_word = Vec::with_capacity(12);
+        code_word.extend((0..4).map(|i| if i == index { b'1' } else { b'F' }));
+        code_word.extend((1..=3).map(|i| if i == address.device { b'1' } else { b'F' }));
+        code_word.extend(b"000");
+        code_word.extend(match *state {
+            State::On => b"10",
+            State::Off => b"01",
+            State::Toggle => return Err(Error::InvalidState(state.to_string())),
+        });
+        Ok(code_word)
+    }
+
+    fn decode(code_word: &[u8]) -> Option<(String, Device, State)> {
+        if code_word.len() != 12 || &code_word[7..10] != b"000" {
+            return None;
+        }
+        // Position of the only '1' in a field of 'F's
+        let selected = |field: &[u8]| {
+            if field.iter().any(|c| *c != b'1' && *c != b'F') {
+                return None;
+            }
+            let mut ones = field.iter().enumerate().filter(|(_, c)| **c == b'1');
+            match (ones.next(), ones.next()) {
+                (Some((index, _)), None) => Some(index as u8),
+                _ => None,
+            }
+        };
+        let group = (b'A' + selected(&code_word[0..4])?) as char;
+        let device = selected(&code_word[4..7])? + 1;
+        let state = match &code_word[10..12] {
+            b"10" => State::On,
+            b"01" => State::Off,
+            _ => return None,
+        };
+        let (group, device) = REVAddress::new(group, device).ok()?.group_device();
+        Some((group, device, state))
+    }
+}
+
+/// Interface for GPIO control
+///
+/// This is the interface for third party transmitter backends. An implementation must
+/// apply `value` before `set` returns, must accept setting the same value repeatedly and
+/// must return quickly because the pulse timing is done by the caller. Errors of the
+/// underlying library are returned with [`Error::gpio`](error::Error::gpio). Use the
+/// functions in `conformance` to test an implementation.
+pub trait Pin {
+    fn set(&self, value: &Value) -> Result<(), Error>;
+
+    /// Send a whole waveform of (level, µs) pulses at once e.g with DMA and leave the pin
+    /// low. `None` if the pin cannot, the caller then sets the pulses one by one.
+    fn transmit_waveform(&self, _pulses: &[(Value, u32)]) -> Option<Result<(), Error>> {
+        None
+    }
+}
+
+/// Interface for reading a GPIO
+pub trait InputPin {
+    fn get(&self) -> Result<Value, Error>;
+}
+
+/// Wait for a given number of microseconds
+pub trait Delay {
+    fn delay(&self, micros: u32);
+}
+
+impl<D: Delay + ?Sized> Delay for alloc::boxed::Box<D> {
+    fn delay(&self, micros: u32) {
+        (**self).delay(micros)
+    }
+}
+
+/// Send `code` `repeats` times with the timings of `values` and set `pin` low afterwards.
+/// Needs neither `std` nor any allocation e.g for bit-banging on a microcontroller.
+pub fn transmit_code<T: Pin + ?Sized, D: Delay + ?Sized>(
+    pin: &T,
+    delay: &D,
+    values: &ProtocolValues,
+    code: &Code,
+    repeats: usize,
+) -> Result<(), Error> {
+    let (first, second) = if values.inverted_signal() {
+        (Value::Low, Value::High)
+    } else {
+        (Value::High, Value::Low)
+    };
+    let pulse = |symbol: &HighLow| -> Result<(), Error> {
+        let micros = |factor: u64| (values.pulse_length() * factor).min(u64::from(u32::MAX)) as u32;
+        pin.set(&first)?;
+        delay.delay(micros(symbol.high));
+        pin.set(&second)?;
+        delay.delay(micros(symbol.low));
+        Ok(())
+    };
+    for _ in 0..repeats {
+        for i in (0..code.length()).rev() {
+            pulse(if code.code() & (1 << i) != 0 {
+                values.one()
+            } else {
+                values.zero()
+            })?;
+        }
+        pulse(values.sync_factor())?;
+    }
+    pin.set(&Value::Low)
+}
+
+/// Binary code of a tri-state code word of 1 to 32 '0', '1' and 'F'. Each tri-state bit is
+/// two bits.
+/// ```
+/// use funksteckdose_core::tri_state_code;
+/// assert_eq!(tri_state_code(b"0F1").unwrap(), 0b00_01_11);
+/// assert!(tri_state_code(b"0FX").is_err());
+/// assert!(tri_state_code(&[b'F'; 33]).is_err());
+/// ```
+pub fn tri_state_code(code_word: &[u8]) -> Result<u64, Error> {
+    let invalid = |reason: String| {
+        Error::InvalidCode(format!(
+            "{}: {}",
+            String::from_utf8_lossy(code_word),
+            reason
+        ))
+    };
+    if code_word.is_empty() || code_word.len() > 32 {
+        return Err(invalid(
+            "a tri-state code word has 1 to 32 characters".into(),
+        ));
+    }
+    code_word.iter().try_fold(0u64, |code, c| {
+        let bits = match c {
+            b'0' => 0b00,
+            b'F' => 0b01,
+            b'1' => 0b11,
+            _ => {
+                return Err(invalid(format!(
+                    "invalid tri-state bit '{}'",
+                    char::from(*c)
+                )))
+            }
+        };
+        Ok(code << 2 | bits)
+    })
+}
+
+/// A code as published for a socket. Codes are written as tri-state code word e.g
+/// "0FF0FFF0FF0F", as binary e.g "000101000101010001010001" or as decimal e.g 1332305 which are
+/// all the same code.
+/// ```
+/// use funksteckdose_core::Code;
+/// let code = Code::parse("0FF0FFF0FF0F").unwrap();
+/// assert_eq!(code, Code::from_decimal(1332305, 24).unwrap());
+/// assert_eq!(code.binary(), "000101000101010001010001");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Code {
+    code: u64,
+    length: usize,
+}
+
+impl Code {
+    /// Parse a code. Strings containing 'F' are tri-state code words, strings of '0' and '1'
+    /// are binary and other numbers are decimal with 24 bits like codesend. Use
+    /// `from_tri_state` for tri-state code words without 'F'.
+    pub fn parse(s: &str) -> Result<Code, Error> {
+        let s = s.trim();
+        if s.contains(['F', 'f']) {
+            Code::from_tri_state(s)
+        } else if !s.is_empty() && s.chars().all(|c| c == '0' || c == '1') {
+            Code::from_binary(s)
+        } else {
+            let code = s
+                .parse()
+                .map_err(|_| Error::InvalidCode(format!("{}: not a number", s)))?;
+            Code::from_decimal(code, 24)
+        }
+    }
+
+    /// Code of a tri-state code word of '0', '1' and 'F'
+    pub fn from_tri_state(s: &str) -> Result<Code, Error> {
+        let code_word = s.to_uppercase();
+        Ok(Code {
+            code: tri_state_code(code_word.as_bytes())?,
+            length: code_word.len() * 2,
+        })
+    }
+
+    /// Code of a string of '0' and '1'
+    pub fn from_binary(s: &str) -> Result<Code, Error> {
+        if s.is_empty() || s.len() > 64 {
+            return Err(Error::InvalidCode(format!(
+                "{}: a binary code has 1 to 64 bits",
+                s
+            )));
+        }
+        let code = u64::from_str_radix(s, 2)
+            .map_err(|_| Error::InvalidCode(format!("{}: not a binary number", s)))?;
+        Ok(Code {
+            code,
+            length: s.len(),
+        })
+    }
+
+    /// Code of a decimal number with `length` bits
+    pub fn from_decimal(code: u64, length: usize) -> Result<Code, Error> {
+        if length == 0 || length > 64 {
+            return Err(Error::InvalidCode(format!(
+                "{}: length {} is not between 1 and 64 bits",
+                code, length
+            )));
+        }
+        if length < 64 && code >> length != 0 {
+            return Err(Error::InvalidCode(format!(
+                "{}: does not fit into {} bits",
+                code, length
+            )));
+        }
+        Ok(Code { code, length })
+    }
+
+    /// The code as number
+    pub fn code(&self) -> u64 {
+        self.code
+    }
+
+    /// Number of bits
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// The code as decimal number
+    pub fn decimal(&self) -> String {
+        self.code.to_string()
+    }
+
+    /// The code as string of '0' and '1' with `length` characters
+    pub fn binary(&self) -> String {
+        format!("{:0width$b}", self.code, width = self.length)
+    }
+
+    /// The tri-state code word. None if the length is odd or a pair of bits is "10".
+    pub fn tri_state(&self) -> Option<String> {
+        if !self.length.is_multiple_of(2) {
+            return None;
+        }
+        (0..self.length / 2)
+            .rev()
+            .map(|i| match (self.code >> (i * 2)) & 3 {
+                0 => Some('0'),
+                1 => Some('F'),
+                3 => Some('1'),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.tri_state() {
+            Some(code_word) => write!(f, "{}", code_word),
+            None => write!(f, "{}", self.binary()),
+        }
+    }
+}
+
+impl str::FromStr for Code {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Code::parse(s)
+    }
+}
+
+/// Number of pulses
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HighLow {
+    pub high: u64,
+    pub low: u64,
+}
+
+impl HighLow {
+    pub const fn new(high: u64, low: u64) -> HighLow {
+        HighLow { high, low }
+    }
+}
+
+/// Format for protocol definitions
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProtocolValues {
+    pulse_length: u64,
+    sync_factor: HighLow,
+    zero: HighLow,
+    one: HighLow,
+    inverted_signal: bool,
+}
+
+impl ProtocolValues {
+    /// Create a protocol definition e.g for a protocol derived from a recording
+    pub const fn new(
+        pulse_length: u64,
+        sync_factor: HighLow,
+        zero: HighLow,
+        one: HighLow,
+        inverted_signal: bool,
+    ) -> ProtocolValues {
+        ProtocolValues {
+            pulse_length,
+            sync_factor,
+            zero,
+            one,
+            inverted_signal,
+        }
+    }
+
+    /// Same timings with another pulse length in µs
+    pub fn with_pulse_length(mut self, pulse_length: u64) -> ProtocolValues {
+        self.pulse_length = pulse_length;
+        self
+    }
+
+    /// Duration of one pulse in µs
+    pub fn pulse_length(&self) -> u64 {
+        self.pulse_length
+    }
+
+    /// Pulses of the sync symbol
+    pub fn sync_factor(&self) -> &HighLow {
+        &self.sync_factor
+    }
+
+    /// Pulses of a zero bit
+    pub fn zero(&self) -> &HighLow {
+        &self.zero
+    }
+
+    /// Pulses of a one bit
+    pub fn one(&self) -> &HighLow {
+        &self.one
+    }
+
+    /// True if the signal is inverted i.e low pulses are sent first
+    pub fn inverted_signal(&self) -> bool {
+        self.inverted_signal
+    }
+
+    /// Duration of a frame with the `length` lower bits of `code` and the sync
+    pub fn frame_micros(&self, code: u64, length: usize) -> u64 {
+        let symbol = |s: &HighLow| self.pulse_length * (s.high + s.low);
+        (0..length)
+            .map(|i| {
+                symbol(if code & (1 << i) != 0 {
+                    &self.one
+                } else {
+                    &self.zero
+                })
+            })
+            .sum::<u64>()
+            + symbol(&self.sync_factor)
+    }
+}
+
+/// A protocol definition
+pub trait Protocol {
+    fn values() -> ProtocolValues;
+
+    /// Burst sent before the first frame. None of the built in protocols needs one.
+    fn preamble() -> Option<Preamble> {
+        None
+    }
+}
+
+/// Declare a unit struct implementing `Protocol` with custom timings e.g for an exotic
+/// remote. The pulses of the sync, zero and one symbols are given as (high, low) factors
+/// of the pulse length in µs.
+/// ```
+/// use funksteckdose_core::{declare_protocol, Protocol};
+///
+/// declare_protocol! {
+///     /// Remote of the garage door
+///     pub struct GarageDoor {
+///         pulse_length: 300,
+///         sync: (1, 31),
+///         zero: (1, 3),
+///         one: (3, 1),
+///         inverted: false,
+///     }
+/// }
+///
+/// assert_eq!(GarageDoor::values().pulse_length(), 300);
+/// assert_eq!(GarageDoor::values().sync_factor().low, 31);
+/// ```
+#[macro_export]
+macro_rules! declare_protocol {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            pulse_length: $pulse_length:expr,
+            sync: ($sync_high:expr, $sync_low:expr),
+            zero: ($zero_high:expr, $zero_low:expr),
+            one: ($one_high:expr, $one_low:expr),
+            inverted: $inverted:expr $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name;
+
+        impl $crate::Protocol for $name {
+            fn values() -> $crate::ProtocolValues {
+                $crate::ProtocolValues::new(
+                    $pulse_length,
+                    $crate::HighLow::new($sync_high, $sync_low),
+                    $crate::HighLow::new($zero_high, $zero_low),
+                    $crate::HighLow::new($one_high, $one_low),
+                    $inverted,
+                )
+            }
+        }
+    };
+}
+
+/// Wake-up burst for receivers that need time for their AGC to settle before the
+/// first frame
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Preamble {
+    /// Number of pulses
+    pub pulses: u32,
+    /// Duration of the high part of a pulse in µs
+    pub high: u32,
+    /// Duration of the low part of a pulse in µs
+    pub low: u32,
+}
+
+impl Preamble {
+    /// Duration of the burst in µs
+    pub fn micros(&self) -> u64 {
+        u64::from(self.pulses) * (u64::from(self.high) + u64::from(self.low))
+    }
+}
+
+/// Protocol selected at runtime e.g with `Funksteckdose::with_protocol`. The values are
+/// those of protocol 1 until others are set.
+pub struct DynProtocol;
+
+impl Protocol for DynProtocol {
+    fn values() -> ProtocolValues {
+        Protocol1::values()
+    }
+}
+
+/// Protocol 1
+pub struct Protocol1;
+
+impl Protocol for Protocol1 {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 350,
+            sync_factor: HighLow::new(1, 31),
+            zero: HighLow::new(1, 3),
+            one: HighLow::new(3, 1),
+            inverted_signal: false,
+        }
+    }
+}
+
+/// Protocol 2
+pub struct Protocol2;
+
+impl Protocol for Protocol2 {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 650,
+            sync_factor: HighLow::new(1, 10),
+            zero: HighLow::new(1, 2),
+            one: HighLow::new(2, 1),
+            inverted_signal: false,
+        }
+    }
+}
+
+/// Protocol 3
+pub struct Protocol3;
+
+impl Protocol for Protocol3 {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 100,
+            sync_factor: HighLow::new(30, 71),
+            zero: HighLow::new(4, 11),
+            one: HighLow::new(9, 6),
+            inverted_signal: false,
+        }
+    }
+}
+
+/// Protocol 4
+pub struct Protocol4;
+
+impl Protocol for Protocol4 {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 380,
+            sync_factor: HighLow::new(1, 6),
+            zero: HighLow::new(1, 3),
+            one: HighLow::new(3, 1),
+            inverted_signal: false,
+        }
+    }
+}
+
+/// Protocol 5
+pub struct Protocol5;
+
+impl Protocol for Protocol5 {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 500,
+            sync_factor: HighLow::new(6, 14),
+            zero: HighLow::new(1, 2),
+            one: HighLow::new(2, 1),
+            inverted_signal: false,
+        }
+    }
+}
+
+/// Protocol HT6P20B
+pub struct ProtocolHT6P20B;
+
+impl Protocol for ProtocolHT6P20B {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 450,
+            sync_factor: HighLow::new(23, 1),
+            zero: HighLow::new(1, 2),
+            one: HighLow::new(2, 1),
+            inverted_signal: true,
+        }
+    }
+}
+
+/// Protocol HS2303-PT, i. e. used in AUKEY Remote
+pub struct ProtocolHS2303;
+
+impl Protocol for ProtocolHS2303 {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 150,
+            sync_factor: HighLow::new(2, 62),
+            zero: HighLow::new(1, 6),
+            one: HighLow::new(6, 1),
+            inverted_signal: false,
+        }
+    }
+}
+
+/// Protocol 6 is the number of the HT6P20B protocol in rc-switch
+pub type Protocol6 = ProtocolHT6P20B;
+
+/// Protocol 7 is the number of the HS2303-PT protocol in rc-switch
+pub type Protocol7 = ProtocolHS2303;
+
+/// Protocol 8, i. e. used in Conrad RS-200 receivers
+pub struct Protocol8;
+
+impl Protocol for Protocol8 {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 200,
+            sync_factor: HighLow::new(3, 130),
+            zero: HighLow::new(7, 16),
+            one: HighLow::new(3, 16),
+            inverted_signal: false,
+        }
+    }
+}
+
+/// Protocol 9, i. e. used in Conrad RS-200 transmitters
+pub struct Protocol9;
+
+impl Protocol for Protocol9 {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 200,
+            sync_factor: HighLow::new(130, 7),
+            zero: HighLow::new(16, 7),
+            one: HighLow::new(16, 3),
+            inverted_signal: true,
+        }
+    }
+}
+
+/// Protocol 10, i. e. used in 1ByOne doorbells
+pub struct Protocol10;
+
+impl Protocol for Protocol10 {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 365,
+            sync_factor: HighLow::new(18, 1),
+            zero: HighLow::new(3, 1),
+            one: HighLow::new(1, 3),
+            inverted_signal: true,
+        }
+    }
+}
+
+/// Protocol 11, i. e. used by HT12E encoders
+pub struct Protocol11;
+
+impl Protocol for Protocol11 {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 270,
+            sync_factor: HighLow::new(36, 1),
+            zero: HighLow::new(1, 2),
+            one: HighLow::new(2, 1),
+            inverted_signal: true,
+        }
+    }
+}
+
+/// Protocol 12, i. e. used by SM5212 encoders
+pub struct Protocol12;
+
+impl Protocol for Protocol12 {
+    fn values() -> ProtocolValues {
+        ProtocolValues {
+            pulse_length: 320,
+            sync_factor: HighLow::new(36, 1),
+            zero: HighLow::new(1, 2),
+            one: HighLow::new(2, 1),
+            inverted_signal: true,
+        }
+    }
+}
+
+/// An encoding together with the protocol and number of repeats its sockets expect.
+/// Choosing a scheme picks both type parameters of a transmitter at once instead of
+/// combining an encoding with a protocol its sockets do not understand.
+/// ```
+/// use funksteckdose_core::{Encoding, Protocol, Scheme, SchemeA, State, Device};
+/// let word = <SchemeA as Scheme>::Encoding::encode("10001", &Device::A, &State::On).unwrap();
+/// assert_eq!(word, b"0FFF00FFFF0F");
+/// assert_eq!(<SchemeA as Scheme>::Protocol::values().pulse_length(), 350);
+/// ```
+pub trait Scheme {
+    type Encoding: Encoding;
+    type Protocol: Protocol;
+
+    /// Frames sent per command
+    const REPEATS: usize = 10;
+}
+
+/// Sockets with 5 dip switches for the group and 5 for the device
+pub struct SchemeA;
+
+impl Scheme for SchemeA {
+    type Encoding = EncodingA;
+    type Protocol = Protocol1;
+}
+
+/// Sockets with two rotary or sliding switches
+pub struct SchemeB;
+
+impl Scheme for SchemeB {
+    type Encoding = EncodingB;
+    type Protocol = Protocol1;
+}
+
+/// Intertechno sockets with a family code and group and device numbers
+pub struct SchemeC;
+
+impl Scheme for SchemeC {
+    type Encoding = EncodingC;
+    type Protocol = Protocol1;
+}
+
+/// Brennenstuhl RCS 1000 N sockets
+pub struct SchemeBrennenstuhl;
+
+impl Scheme for SchemeBrennenstuhl {
+    type Encoding = EncodingBrennenstuhl;
+    type Protocol = Protocol1;
+}
+
+/// REV Telecontrol (Ritter) sockets
+pub struct SchemeREV;
+
+impl Scheme for SchemeREV {
+    type Encoding = EncodingREV;
+    type Protocol = Protocol1;
+}
@@ -0,0 +1,81 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Code words of `EncodingC` compared to `RCSwitch::getCodeWordC` of rc-switch
+
+use funksteckdose_core::{Device, Encoding, EncodingC, IntertechnoAddress, State};
+
+// Family, group, device, state and the code word returned by getCodeWordC
+const CODE_WORDS: &[(char, u8, u8, State, &str)] = &[
+    ('a', 1, 1, State::On, "000000000FFF"),
+    ('a', 1, 1, State::Off, "000000000FF0"),
+    ('b', 2, 3, State::On, "F0000FF00FFF"),
+    ('c', 3, 2, State::Off, "0F00F00F0FF0"),
+    ('e', 1, 4, State::On, "00F0FF000FFF"),
+    ('k', 4, 1, State::On, "0F0F00FF0FFF"),
+    ('p', 4, 4, State::Off, "FFFFFFFF0FF0"),
+    ('p', 4, 4, State::On, "FFFFFFFF0FFF"),
+];
+
+#[test]
+fn encode_matches_rc_switch() {
+    for (family, group, device, state, expected) in CODE_WORDS {
+        let address = IntertechnoAddress::new(*family, *group, *device).unwrap();
+        let (group, device) = address.group_device();
+        let code_word = EncodingC::encode(&group, &device, state).unwrap();
+        assert_eq!(
+            String::from_utf8(code_word).unwrap(),
+            *expected,
+            "{}",
+            address
+        );
+    }
+}
+
+#[test]
+fn decode_inverts_encode() {
+    for (group, device) in EncodingC::all_addresses() {
+        for state in &[State::On, State::Off] {
+            let code_word = EncodingC::encode(&group, &device, state).unwrap();
+            assert_eq!(
+                EncodingC::decode(&code_word),
+                Some((group.clone(), device.clone(), state.clone()))
+            );
+        }
+    }
+}
+
+#[test]
+fn invalid_addresses() {
+    assert!(IntertechnoAddress::new('q', 1, 1).is_err());
+    assert!(IntertechnoAddress::new('a', 0, 1).is_err());
+    assert!(IntertechnoAddress::new('a', 1, 5).is_err());
+    assert!(EncodingC::encode("a5", &Device::A, &State::On).is_err());
+    assert!(EncodingC::encode("10011", &Device::A, &State::On).is_err());
+    assert!(EncodingC::encode("a1", &Device::E, &State::On).is_err());
+}
+
+#[test]
+fn parse_address() {
+    let address: IntertechnoAddress = "C:2:4".parse().unwrap();
+    assert_eq!(address, IntertechnoAddress::new('c', 2, 4).unwrap());
+    assert_eq!(address.to_string(), "c:2:4");
+    assert_eq!(address.group_device(), ("c2".into(), Device::D));
+}
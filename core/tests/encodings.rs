@@ -0,0 +1,75 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Standard tests of the built-in encodings with code words of rc-switch
+
+use funksteckdose_core::{
+    declare_encoding_tests, Device, EncodingA, EncodingB, EncodingBrennenstuhl, EncodingC,
+    EncodingREV, State,
+};
+
+declare_encoding_tests! {
+    encoding_a: EncodingA,
+    samples: [
+        ("10011", Device::A, State::On) => "0FF000FFFF0F",
+        ("10011", Device::A, State::Off) => "0FF000FFFFF0",
+        ("00000", Device::E, State::On) => "FFFFFFFFF00F",
+        ("11111", Device::C, State::Off) => "00000FF0FFF0",
+    ],
+    invalid: [("1001", Device::A), ("10012", Device::A), ("100111", Device::A)],
+}
+
+declare_encoding_tests! {
+    encoding_b: EncodingB,
+    samples: [
+        ("1", Device::A, State::On) => "0FFF0FFFFFFF",
+        ("3", Device::B, State::Off) => "FF0FF0FFFFF0",
+        ("4", Device::D, State::On) => "FFF0FFF0FFFF",
+    ],
+    invalid: [("0", Device::A), ("5", Device::A), ("1", Device::E)],
+}
+
+declare_encoding_tests! {
+    encoding_c: EncodingC,
+    samples: [
+        ("a1", Device::A, State::On) => "000000000FFF",
+        ("b2", Device::C, State::On) => "F0000FF00FFF",
+        ("p4", Device::D, State::Off) => "FFFFFFFF0FF0",
+    ],
+    invalid: [("a5", Device::A), ("q1", Device::A), ("a1", Device::E)],
+}
+
+declare_encoding_tests! {
+    encoding_brennenstuhl: EncodingBrennenstuhl,
+    samples: [
+        ("10011", Device::B, State::On) => "0FF00F0FFF0F",
+        ("10011", Device::B, State::Off) => "0FF00F0FFFF0",
+    ],
+}
+
+declare_encoding_tests! {
+    encoding_rev: EncodingREV,
+    samples: [
+        ("A", Device::A, State::On) => "1FFF1FF00010",
+        ("B", Device::B, State::Off) => "F1FFF1F00001",
+        ("D", Device::C, State::On) => "FFF1FF100010",
+    ],
+    invalid: [("E", Device::A), ("AB", Device::A), ("A", Device::D)],
+}
@@ -0,0 +1,133 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//! Sending from async code with tokio.
+//!
+//! The pulses of a transmission must be timed to a few µs and a transmission takes several
+//! 100ms. Sending on an executor thread would block all other tasks for that time. The
+//! `*_async` functions of [`SharedTransmitter`] do the bit-banging on a blocking worker of
+//! the tokio runtime instead:
+//!
+//! ```
+//! use funksteckdose::{mock::NullPin, shared::SharedTransmitter, Device, EncodingA, Protocol1, State};
+//! use std::sync::Arc;
+//!
+//! type Funksteckdose = funksteckdose::Funksteckdose<NullPin, EncodingA, Protocol1>;
+//! let d: Funksteckdose = Funksteckdose::builder(NullPin).repeat_transmit(1).build().unwrap();
+//! let shared = Arc::new(SharedTransmitter::new(d));
+//! let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+//! runtime
+//!     .block_on(shared.send_async("10011", &Device::A, &State::On))
+//!     .unwrap();
+//! ```
+//!
+//! Backends with an async interface e.g a GPIO expander driven by an async I2C driver
+//! implement [`AsyncPin`] and are wrapped in a [`BlockingPin`].
+
+use crate::{
+    delay::Delay,
+    error::{CoreError, Error},
+    plan::TransmissionPlan,
+    shared::SharedTransmitter,
+    Device, Encoding, Pin, Protocol, State, Value,
+};
+use std::{future::Future, io, panic, sync::Arc};
+use tokio::{runtime::Handle, task};
+
+/// Interface for GPIO control with an async backend
+pub trait AsyncPin {
+    fn set(&self, value: &Value) -> impl Future<Output = Result<(), CoreError>> + Send;
+}
+
+/// `Pin` on top of an `AsyncPin`. Each `set` blocks on the future of the `AsyncPin`, so the
+/// pin must only be used outside of the executor threads e.g with the `*_async` functions
+/// of [`SharedTransmitter`].
+#[derive(Debug)]
+pub struct BlockingPin<A: AsyncPin> {
+    pin: A,
+    handle: Handle,
+}
+
+impl<A: AsyncPin> BlockingPin<A> {
+    /// Wrap `pin`. Panics if called outside of a tokio runtime.
+    pub fn new(pin: A) -> BlockingPin<A> {
+        BlockingPin {
+            pin,
+            handle: Handle::current(),
+        }
+    }
+
+    /// Return the wrapped pin
+    pub fn into_inner(self) -> A {
+        self.pin
+    }
+}
+
+impl<A: AsyncPin> Pin for BlockingPin<A> {
+    fn set(&self, value: &Value) -> Result<(), CoreError> {
+        self.handle.block_on(self.pin.set(value))
+    }
+}
+
+impl<T, E, P, D> SharedTransmitter<T, E, P, D>
+where
+    T: Pin + Send + 'static,
+    E: Encoding + Send + 'static,
+    P: Protocol + Send + 'static,
+    D: Delay + Send + 'static,
+{
+    /// See [`SharedTransmitter::send`]. Sends on a blocking worker of the tokio runtime.
+    pub async fn send_async(
+        self: &Arc<Self>,
+        group: &str,
+        device: &Device,
+        state: &State,
+    ) -> Result<(), Error> {
+        let (group, device, state) = (group.to_string(), device.clone(), state.clone());
+        self.blocking(move |shared| shared.send(&group, &device, &state))
+            .await
+    }
+
+    /// See [`SharedTransmitter::send_code`]. Sends on a blocking worker of the tokio runtime.
+    pub async fn send_code_async(self: &Arc<Self>, code: u64, length: usize) -> Result<(), Error> {
+        self.blocking(move |shared| shared.send_code(code, length))
+            .await
+    }
+
+    /// See [`SharedTransmitter::transmit`]. Sends on a blocking worker of the tokio runtime.
+    pub async fn transmit_async(self: &Arc<Self>, plan: TransmissionPlan) -> Result<(), Error> {
+        self.blocking(move |shared| shared.transmit(&plan)).await
+    }
+
+    async fn blocking<F>(self: &Arc<Self>, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&Self) -> Result<(), Error> + Send + 'static,
+    {
+        let shared = self.clone();
+        match task::spawn_blocking(move || f(&shared)).await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "runtime shut down before sending",
+            )
+            .into()),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Per socket circuit breakers.
+//!
+//! Sockets may be reached through different transmitters e.g a dedicated network bridge per
+//! room. If the transmitter of a socket fails `threshold` times in a row, the circuit of the
+//! socket opens and commands fail immediately with [`Error::CircuitOpen`] instead of running
+//! into the same timeout again. After `probe` one command is let through. If it succeeds the
+//! circuit closes, otherwise it stays open for another `probe` interval.
+
+use crate::{
+    delay::Delay,
+    error::{Category, Error},
+    Device, Encoding, Funksteckdose, Pin, Protocol, State,
+};
+use log::{info, warn};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// State of the circuit of a socket
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Circuit {
+    /// Commands are sent
+    Closed,
+    /// Commands fail fast
+    Open,
+    /// The next command is sent as a probe
+    HalfOpen,
+}
+
+impl fmt::Display for Circuit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Circuit::Closed => write!(f, "closed"),
+            Circuit::Open => write!(f, "open"),
+            Circuit::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Socket {
+    failures: u32,
+    opened: Option<Instant>,
+}
+
+/// Circuit breakers keyed by group and device
+#[derive(Debug)]
+pub struct Breaker {
+    threshold: u32,
+    probe: Duration,
+    sockets: Mutex<HashMap<(String, Device), Socket>>,
+}
+
+impl Default for Breaker {
+    fn default() -> Breaker {
+        Breaker::new(3, Duration::from_secs(30))
+    }
+}
+
+impl Breaker {
+    /// Open the circuit of a socket after `threshold` consecutive transmitter failures
+    /// and probe it every `probe`
+    pub fn new(threshold: u32, probe: Duration) -> Breaker {
+        Breaker {
+            threshold: threshold.max(1),
+            probe,
+            sockets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn sockets(&self) -> MutexGuard<'_, HashMap<(String, Device), Socket>> {
+        self.sockets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Current state of the circuit of a socket
+    pub fn circuit(&self, group: &str, device: &Device) -> Circuit {
+        match self.sockets().get(&(group.to_string(), device.clone())) {
+            Some(Socket {
+                opened: Some(opened),
+                ..
+            }) if opened.elapsed() < self.probe => Circuit::Open,
+            Some(Socket {
+                opened: Some(_), ..
+            }) => Circuit::HalfOpen,
+            _ => Circuit::Closed,
+        }
+    }
+
+    /// Close the circuit of a socket e.g after the transmitter was replaced
+    pub fn reset(&self, group: &str, device: &Device) {
+        self.sockets().remove(&(group.to_string(), device.clone()));
+    }
+
+    /// Run `f` unless the circuit of the socket is open. Transmitter failures and timeouts
+    /// returned by `f` count towards opening the circuit.
+    pub fn call<F, R>(&self, group: &str, device: &Device, f: F) -> Result<R, Error>
+    where
+        F: FnOnce() -> Result<R, Error>,
+    {
+        let key = (group.to_string(), device.clone());
+        match self.circuit(group, device) {
+            Circuit::Open => return Err(Error::CircuitOpen(format!("{} {}", group, device))),
+            Circuit::HalfOpen => {
+                // Restart the interval so that concurrent callers keep failing fast while
+                // the probe is in flight
+                if let Some(socket) = self.sockets().get_mut(&key) {
+                    socket.opened = Some(Instant::now());
+                }
+                info!("Probing socket {} {}", group, device);
+            }
+            Circuit::Closed => (),
+        }
+
+        let result = f();
+        let mut sockets = self.sockets();
+        match result {
+            Ok(_) => {
+                if let Some(socket) = sockets.remove(&key) {
+                    if socket.opened.is_some() {
+                        info!("Closing circuit of socket {} {}", group, device);
+                    }
+                }
+            }
+            Err(ref e) if e.category() == Category::Backend || e.category() == Category::Timing => {
+                let socket = sockets.entry(key).or_default();
+                socket.failures = socket.failures.saturating_add(1);
+                if socket.failures >= self.threshold {
+                    if socket.opened.is_none() {
+                        warn!(
+                            "Opening circuit of socket {} {} after {} failures",
+                            group, device, socket.failures
+                        );
+                    }
+                    socket.opened = Some(Instant::now());
+                }
+            }
+            Err(_) => (),
+        }
+        result
+    }
+
+    /// Send `state` to a socket through its circuit
+    pub fn send<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+        &self,
+        funksteckdose: &Funksteckdose<T, E, P, D>,
+        group: &str,
+        device: &Device,
+        state: &State,
+    ) -> Result<(), Error> {
+        self.call(group, device, || funksteckdose.send(group, device, state))
+    }
+}
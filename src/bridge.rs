@@ -0,0 +1,220 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Long running MQTT bridge.
+//!
+//! The bridge subscribes to the command topics of [`Topics`], hands every command to a
+//! handler and publishes the new state of the socket as acknowledgement. Dry runs publish
+//! the preview returned by the handler instead. The connection to the broker is
+//! reestablished after errors. After connecting the bridge publishes its banner, see
+//! [`mqtt`](crate::mqtt), followed by the Home Assistant discovery payloads set with
+//! [`Bridge::with_discovery`]. State changes e.g received from the original remotes are
+//! published as they arrive, see [`Bridge::with_changes`].
+
+use crate::{
+    error::Error,
+    mqtt::{CommandPayload, TopicVars, Topics},
+    plan::Preview,
+    state::StateChange,
+};
+use log::{debug, info, warn};
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use std::{sync::mpsc, thread, time::Duration};
+
+/// Pause before reconnecting to the broker
+const RECONNECT: Duration = Duration::from_secs(5);
+
+/// Bridge between a MQTT broker and a transmitter
+#[derive(Debug)]
+pub struct Bridge {
+    options: MqttOptions,
+    topics: Topics,
+    sockets: Vec<TopicVars>,
+    discovery: Vec<(String, String)>,
+    changes: Option<mpsc::Receiver<StateChange>>,
+}
+
+impl Bridge {
+    /// Bridge connecting with `options`. The last will of `options` is replaced by the
+    /// offline banner.
+    pub fn new(mut options: MqttOptions, topics: Topics) -> Bridge {
+        options.set_last_will(LastWill::new(
+            topics.banner_topic(),
+            topics.banner(false),
+            QoS::AtLeastOnce,
+            true,
+        ));
+        Bridge {
+            options,
+            topics,
+            sockets: Vec::new(),
+            discovery: Vec::new(),
+            changes: None,
+        }
+    }
+
+    /// Restrict the bridge to known sockets. Commands for other sockets are ignored.
+    /// Needed if the command topics address sockets by `{room}` and `{name}` instead of
+    /// `{group}` and `{device}`.
+    pub fn with_sockets(mut self, sockets: Vec<TopicVars>) -> Bridge {
+        self.sockets = sockets;
+        self
+    }
+
+    /// Retained topics and payloads published after each connect e.g from
+    /// `Topics::switch_discovery`
+    pub fn with_discovery(mut self, discovery: Vec<(String, String)>) -> Bridge {
+        self.discovery = discovery;
+        self
+    }
+
+    /// Publish the states of `changes` e.g from `StateStore::observe`
+    pub fn with_changes(mut self, changes: mpsc::Receiver<StateChange>) -> Bridge {
+        self.changes = Some(changes);
+        self
+    }
+
+    /// The socket addressed by the values of a command topic with group and device set
+    pub fn resolve(&self, vars: &TopicVars) -> Option<TopicVars> {
+        if self.sockets.is_empty() {
+            return match (&vars.group, &vars.device) {
+                (Some(_), Some(_)) => Some(vars.clone()),
+                _ => None,
+            };
+        }
+        let matches =
+            |given: &Option<String>, known: &Option<String>| given.is_none() || given == known;
+        self.sockets
+            .iter()
+            .find(|socket| {
+                matches(&vars.room, &socket.room)
+                    && matches(&vars.name, &socket.name)
+                    && matches(&vars.group, &socket.group)
+                    && (vars.device.is_none() || vars.device == socket.device)
+            })
+            .map(|socket| TopicVars {
+                prefix: vars.prefix.clone(),
+                ..socket.clone()
+            })
+    }
+
+    /// Receive commands until the process ends. `handle` sends a command to the socket in
+    /// the resolved topic values and returns the preview of dry runs.
+    pub fn run<F>(&mut self, mut handle: F) -> Result<(), Error>
+    where
+        F: FnMut(&TopicVars, &CommandPayload) -> Result<Option<Preview>, Error>,
+    {
+        // Room for the discovery payloads published at once after connecting
+        let capacity = 16 + self.discovery.len();
+        let (client, mut connection) = Client::new(self.options.clone(), capacity);
+        let filter = self.topics.command_filter();
+        if let Some(changes) = self.changes.take() {
+            let (client, topics, sockets) =
+                (client.clone(), self.topics.clone(), self.sockets.clone());
+            thread::spawn(move || {
+                for change in changes {
+                    let message = topics.state_message(&sockets, &change);
+                    let published = message.and_then(|(topic, payload)| {
+                        client
+                            .try_publish(topic, QoS::AtLeastOnce, true, payload)
+                            .map_err(mqtt_error)
+                    });
+                    if let Err(e) = published {
+                        warn!("Failed to publish state change: {}", e);
+                    }
+                }
+            });
+        }
+        for event in connection.iter() {
+            match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    // Subscriptions do not survive a clean session
+                    info!("Connected. Subscribing to {}", filter);
+                    client
+                        .try_subscribe(filter.as_str(), QoS::AtLeastOnce)
+                        .map_err(mqtt_error)?;
+                    client
+                        .try_publish(
+                            self.topics.banner_topic(),
+                            QoS::AtLeastOnce,
+                            true,
+                            self.topics.banner(true),
+                        )
+                        .map_err(mqtt_error)?;
+                    for (topic, payload) in &self.discovery {
+                        if let Err(e) = client.try_publish(
+                            topic.as_str(),
+                            QoS::AtLeastOnce,
+                            true,
+                            payload.as_str(),
+                        ) {
+                            warn!("Failed to publish discovery on {}: {}", topic, e);
+                        }
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    self.command(&client, &publish.topic, &publish.payload, &mut handle)
+                }
+                Ok(event) => debug!("{:?}", event),
+                Err(e) => {
+                    warn!("Connection to broker failed: {}", e);
+                    thread::sleep(RECONNECT);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn command<F>(&self, client: &Client, topic: &str, payload: &[u8], handle: &mut F)
+    where
+        F: FnMut(&TopicVars, &CommandPayload) -> Result<Option<Preview>, Error>,
+    {
+        let (vars, command) = match self.topics.parse_command(topic, payload) {
+            Some((vars, Ok(command))) => (vars, command),
+            Some((_, Err(e))) => return warn!("Ignoring command on {}: {}", topic, e),
+            None => return,
+        };
+        let vars = match self.resolve(&vars) {
+            Some(vars) => vars,
+            None => return warn!("Ignoring command on {}: unknown socket", topic),
+        };
+        let message = handle(&vars, &command).and_then(|preview| match preview {
+            Some(preview) => self.topics.preview_message(&vars, &preview),
+            None => Ok((
+                self.topics.state_topic(&vars)?,
+                self.topics.payload.encode(&command.state),
+            )),
+        });
+        match message {
+            Ok((topic, payload)) => {
+                // States are retained for clients connecting later, previews are not
+                let retain = !command.dry_run;
+                if let Err(e) = client.try_publish(topic, QoS::AtLeastOnce, retain, payload) {
+                    warn!("Failed to publish acknowledgement: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to handle command on {}: {}", topic, e),
+        }
+    }
+}
+
+fn mqtt_error(e: rumqttc::ClientError) -> Error {
+    Error::Mqtt(e.to_string())
+}
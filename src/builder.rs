@@ -0,0 +1,283 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Step by step construction of a [`Funksteckdose`].
+//!
+//! [`Funksteckdose::builder`] starts with the defaults of the protocol `P` and 10 repeats.
+//! Every setting has a chained setter and [`Builder::build`] checks that the settings fit
+//! together before the first transmission:
+//!
+//! ```
+//! use funksteckdose::{delay::Spin, mock::NullPin, Device, EncodingA, Funksteckdose};
+//! use funksteckdose::{Protocol1, State};
+//! use std::time::Duration;
+//!
+//! let d: Funksteckdose<NullPin, EncodingA, Protocol1, Spin> = Funksteckdose::builder(NullPin)
+//!     .repeat_transmit(3)
+//!     .delay(Spin)
+//!     .repeat_gap(Duration::from_millis(5))
+//!     .build()?;
+//! d.send("10001", &Device::A, &State::On)?;
+//!
+//! // 1000 repeats take longer than the default airtime limit of 10s
+//! let jammer = Funksteckdose::<NullPin, EncodingA, Protocol1>::builder(NullPin)
+//!     .repeat_transmit(1000)
+//!     .build();
+//! assert!(jammer.is_err());
+//! # Ok::<(), funksteckdose::error::Error>(())
+//! ```
+
+use crate::{
+    delay::Delay,
+    error::{CoreError, Error},
+    metrics::Metrics,
+    plan::TransmissionPlan,
+    quiet::QuietHours,
+    state::StateCache,
+    trigger::Trigger,
+    EnablePin, Encoding, EncodingOptions, Funksteckdose, Pin, Preamble, Protocol, ProtocolValues,
+    MAX_AIRTIME,
+};
+use std::{marker::PhantomData, sync::Arc, time::Duration};
+
+/// Settings of a [`Funksteckdose`] before it is built. See the `set_*` functions of
+/// [`Funksteckdose`] for the meaning of each setting.
+#[derive(Debug)]
+pub struct Builder<T: Pin, E: Encoding, P: Protocol, D: Delay> {
+    pin: T,
+    delay: D,
+    repeat_transmit: usize,
+    values: ProtocolValues,
+    options: EncodingOptions,
+    preamble: Option<Preamble>,
+    repeat_gap: Duration,
+    trigger: Option<Box<dyn Trigger + Send + Sync>>,
+    enable: Option<EnablePin>,
+    warm_up: Duration,
+    cool_down: Duration,
+    watchdog_factor: Option<u32>,
+    max_airtime: Option<Duration>,
+    quiet_hours: Option<QuietHours>,
+    state_cache: Option<StateCache>,
+    metrics: Option<Arc<Metrics>>,
+    #[cfg(all(feature = "realtime", target_os = "linux"))]
+    realtime: Option<i32>,
+    encoding: PhantomData<E>,
+    protocol: PhantomData<P>,
+}
+
+impl<T: Pin, E: Encoding, P: Protocol, D: Delay> Builder<T, E, P, D> {
+    pub(crate) fn new(pin: T, delay: D) -> Builder<T, E, P, D> {
+        Builder {
+            pin,
+            delay,
+            repeat_transmit: 10,
+            values: P::values(),
+            options: EncodingOptions::default(),
+            preamble: P::preamble(),
+            repeat_gap: Duration::from_millis(0),
+            trigger: None,
+            enable: None,
+            warm_up: Duration::from_millis(0),
+            cool_down: Duration::from_millis(0),
+            watchdog_factor: Some(4),
+            max_airtime: Some(MAX_AIRTIME),
+            quiet_hours: None,
+            state_cache: None,
+            metrics: None,
+            #[cfg(all(feature = "realtime", target_os = "linux"))]
+            realtime: None,
+            encoding: PhantomData,
+            protocol: PhantomData,
+        }
+    }
+
+    /// Number of frames of each transmission. Default: 10
+    pub fn repeat_transmit(mut self, repeat_transmit: usize) -> Self {
+        self.repeat_transmit = repeat_transmit;
+        self
+    }
+
+    /// Time the pulses with `delay` instead of [`delay::Hybrid`](crate::delay::Hybrid)
+    pub fn delay<D2: Delay>(self, delay: D2) -> Builder<T, E, P, D2> {
+        Builder {
+            pin: self.pin,
+            delay,
+            repeat_transmit: self.repeat_transmit,
+            values: self.values,
+            options: self.options,
+            preamble: self.preamble,
+            repeat_gap: self.repeat_gap,
+            trigger: self.trigger,
+            enable: self.enable,
+            warm_up: self.warm_up,
+            cool_down: self.cool_down,
+            watchdog_factor: self.watchdog_factor,
+            max_airtime: self.max_airtime,
+            quiet_hours: self.quiet_hours,
+            state_cache: self.state_cache,
+            metrics: self.metrics,
+            #[cfg(all(feature = "realtime", target_os = "linux"))]
+            realtime: self.realtime,
+            encoding: PhantomData,
+            protocol: PhantomData,
+        }
+    }
+
+    /// Replace the timings of `P` e.g with a protocol selected at runtime
+    pub fn protocol(mut self, values: ProtocolValues) -> Self {
+        self.values = values;
+        self
+    }
+
+    /// Encoding options for clone sockets
+    pub fn encoding_options(mut self, options: EncodingOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Preamble before the first frame. Default: the preamble of `P`
+    pub fn preamble(mut self, preamble: Option<Preamble>) -> Self {
+        self.preamble = preamble;
+        self
+    }
+
+    /// Silence between repeated frames in addition to the sync. Default: 0
+    pub fn repeat_gap(mut self, gap: Duration) -> Self {
+        self.repeat_gap = gap;
+        self
+    }
+
+    /// Wait for `trigger` before each transmission
+    pub fn trigger(mut self, trigger: Box<dyn Trigger + Send + Sync>) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+
+    /// Drive `enable` high while sending
+    pub fn enable_pin(mut self, enable: Box<dyn Pin + Send + Sync>) -> Self {
+        self.enable = Some(EnablePin(enable));
+        self
+    }
+
+    /// Wait `warm_up` before each transmission. Default: 0
+    pub fn warm_up(mut self, warm_up: Duration) -> Self {
+        self.warm_up = warm_up;
+        self
+    }
+
+    /// Wait `cool_down` after each transmission. Default: 0
+    pub fn cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    /// Abort transmissions that take longer than `factor` times their airtime. Default: 4
+    pub fn watchdog(mut self, factor: Option<u32>) -> Self {
+        self.watchdog_factor = factor;
+        self
+    }
+
+    /// Reject transmissions with an airtime above `max_airtime`. Default: [`MAX_AIRTIME`]
+    pub fn max_airtime(mut self, max_airtime: Option<Duration>) -> Self {
+        self.max_airtime = max_airtime;
+        self
+    }
+
+    /// Reduce background transmissions during `quiet_hours`
+    pub fn quiet_hours(mut self, quiet_hours: QuietHours) -> Self {
+        self.quiet_hours = Some(quiet_hours);
+        self
+    }
+
+    /// Track the last sent states in `cache` e.g to send `State::Toggle`
+    pub fn state_cache(mut self, cache: StateCache) -> Self {
+        self.state_cache = Some(cache);
+        self
+    }
+
+    /// Count with `metrics` e.g shared with other instances
+    pub fn metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Raise the thread to `SCHED_FIFO` with `priority` while sending
+    #[cfg(all(feature = "realtime", target_os = "linux"))]
+    pub fn realtime(mut self, priority: i32) -> Self {
+        self.realtime = Some(priority);
+        self
+    }
+
+    /// Check the settings and create the [`Funksteckdose`]. Fails for settings that would
+    /// make every transmission fail: no repeats, a pulse length of 0, a watchdog or airtime
+    /// limit of 0 and repeats, gaps and preamble that alone exceed the airtime limit.
+    pub fn build(self) -> Result<Funksteckdose<T, E, P, D>, Error> {
+        if self.repeat_transmit == 0 {
+            return Err(Error::InvalidConfig("at least one repeat is needed".into()));
+        }
+        if self.values.pulse_length() == 0 {
+            return Err(CoreError::InvalidProtocol("pulse length of 0".into()).into());
+        }
+        if self.watchdog_factor == Some(0) {
+            return Err(Error::InvalidConfig("watchdog factor of 0".into()));
+        }
+        if let Some(max_airtime) = self.max_airtime {
+            if max_airtime.is_zero() {
+                return Err(Error::InvalidConfig("airtime limit of 0".into()));
+            }
+            // The airtime without any code bits is the lower bound for every transmission
+            let gap = self.repeat_gap.as_micros().min(u128::from(u32::MAX)) as u32;
+            let airtime = TransmissionPlan::from_code(
+                &self.values,
+                0,
+                0,
+                self.repeat_transmit,
+                self.preamble.as_ref(),
+            )
+            .with_gap(gap)
+            .duration();
+            if airtime > max_airtime {
+                return Err(Error::AirtimeExceeded(airtime, max_airtime));
+            }
+        }
+
+        let mut funksteckdose =
+            Funksteckdose::with_delay(self.pin, self.repeat_transmit, self.delay);
+        funksteckdose.set_protocol(self.values);
+        funksteckdose.set_encoding_options(self.options);
+        funksteckdose.set_preamble(self.preamble);
+        funksteckdose.set_repeat_gap(self.repeat_gap);
+        funksteckdose.set_trigger(self.trigger);
+        funksteckdose.set_enable_pin(self.enable.map(|enable| enable.0));
+        funksteckdose.set_warm_up(self.warm_up);
+        funksteckdose.set_cool_down(self.cool_down);
+        funksteckdose.set_watchdog(self.watchdog_factor);
+        funksteckdose.set_max_airtime(self.max_airtime);
+        funksteckdose.set_quiet_hours(self.quiet_hours);
+        funksteckdose.set_state_cache(self.state_cache);
+        if let Some(metrics) = self.metrics {
+            funksteckdose.set_metrics(metrics);
+        }
+        #[cfg(all(feature = "realtime", target_os = "linux"))]
+        funksteckdose.set_realtime(self.realtime);
+        Ok(funksteckdose)
+    }
+}
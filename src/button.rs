@@ -0,0 +1,130 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Physical push buttons e.g a momentary wall switch wired to a GPIO
+
+use crate::{error::Error, state::StateStore, Device, InputPin, State, Value};
+use std::{
+    fmt, str,
+    time::{Duration, Instant},
+};
+
+/// What to send when a button is pressed
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    On,
+    Off,
+    /// Send the opposite of the last known state or on if unknown
+    Toggle,
+}
+
+impl Action {
+    /// The state to send for a socket
+    pub fn state(&self, states: &StateStore, group: &str, device: &Device) -> State {
+        match self {
+            Action::On => State::On,
+            Action::Off => State::Off,
+            Action::Toggle => State::Toggle.resolve(states.get(group, device)),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::On => write!(f, "on"),
+            Action::Off => write!(f, "off"),
+            Action::Toggle => write!(f, "toggle"),
+        }
+    }
+}
+
+impl str::FromStr for Action {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse()? {
+            State::On => Ok(Action::On),
+            State::Off => Ok(Action::Off),
+            State::Toggle => Ok(Action::Toggle),
+        }
+    }
+}
+
+/// A debounced push button
+#[derive(Debug)]
+pub struct Button<I: InputPin> {
+    pin: I,
+    active: Value,
+    debounce: Duration,
+    pressed: bool,
+    candidate: Option<(bool, Instant)>,
+}
+
+impl<I: InputPin> Button<I> {
+    /// Create a new button that is pressed when `pin` reads `active` for at least
+    /// `debounce`
+    pub fn new(pin: I, active: Value, debounce: Duration) -> Button<I> {
+        Button {
+            pin,
+            active,
+            debounce,
+            pressed: false,
+            candidate: None,
+        }
+    }
+
+    /// Sample the pin. Returns true once per press.
+    pub fn poll(&mut self) -> Result<bool, Error> {
+        let pressed = self.pin.get()? == self.active;
+        if pressed == self.pressed {
+            self.candidate = None;
+            return Ok(false);
+        }
+        match self.candidate {
+            Some((candidate, since)) if candidate == pressed => {
+                if since.elapsed() >= self.debounce {
+                    self.pressed = pressed;
+                    self.candidate = None;
+                    return Ok(pressed);
+                }
+            }
+            _ => self.candidate = Some((pressed, Instant::now())),
+        }
+        Ok(false)
+    }
+}
+
+/// Poll `buttons` every `interval` and call `f` with the index of every pressed button.
+/// Returns when `f` or reading a pin fails.
+pub fn watch<I, F>(buttons: &mut [Button<I>], interval: Duration, mut f: F) -> Result<(), Error>
+where
+    I: InputPin,
+    F: FnMut(usize) -> Result<(), Error>,
+{
+    loop {
+        for (index, button) in buttons.iter_mut().enumerate() {
+            if button.poll()? {
+                f(index)?;
+            }
+        }
+        std::thread::sleep(interval);
+    }
+}
@@ -0,0 +1,356 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Metadata of the protocols and encodings e.g for building selections in UIs
+//!
+//! Protocols are looked up by name in a process wide `ProtocolRegistry`. It contains the
+//! built-in protocols and the protocols added with `register_protocol` e.g from the
+//! `[[protocol]]` sections of a configuration. The configuration, the command line and the
+//! receiver all resolve protocol names here.
+//! ```
+//! use funksteckdose::{catalog, HighLow, ProtocolValues};
+//! let values = ProtocolValues::new(
+//!     420,
+//!     HighLow::new(1, 28),
+//!     HighLow::new(1, 3),
+//!     HighLow::new(3, 1),
+//!     false,
+//! );
+//! catalog::register_protocol("garage", values).unwrap();
+//! assert_eq!(catalog::protocol("garage").unwrap().pulse_length(), 420);
+//! assert!(catalog::protocols().iter().any(|p| p.name == "garage"));
+//! assert!(catalog::register_protocol("1", catalog::protocol("2").unwrap()).is_err());
+//! ```
+
+use crate::{
+    error::{CoreError, Error},
+    HighLow, Protocol, Protocol1, Protocol10, Protocol11, Protocol12, Protocol2, Protocol3,
+    Protocol4, Protocol5, Protocol6, Protocol7, Protocol8, Protocol9, ProtocolHS2303,
+    ProtocolHT6P20B, ProtocolValues,
+};
+use std::sync::{OnceLock, PoisonError, RwLock};
+
+/// Timings of a protocol
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProtocolInfo {
+    /// Name as accepted on the command line
+    pub name: &'static str,
+    /// Pulse length in µs
+    pub pulse_length: u64,
+    /// Pulses of the sync
+    pub sync: HighLow,
+    /// Pulses of a zero bit
+    pub zero: HighLow,
+    /// Pulses of a one bit
+    pub one: HighLow,
+    /// True if the signal is inverted
+    pub inverted: bool,
+}
+
+impl ProtocolInfo {
+    fn new(name: &'static str, values: &ProtocolValues) -> ProtocolInfo {
+        ProtocolInfo {
+            name,
+            pulse_length: values.pulse_length(),
+            sync: values.sync_factor().clone(),
+            zero: values.zero().clone(),
+            one: values.one().clone(),
+            inverted: values.inverted_signal(),
+        }
+    }
+}
+
+/// How sockets of an encoding are addressed
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum AddressModel {
+    /// A bank of dip switches for the group and one switch per device
+    DipSwitches { group_bits: usize, devices: usize },
+    /// Two rotary switches for the address and the channel
+    Rotary { addresses: u8, channels: u8 },
+    /// A family code and group and device numbers
+    Family {
+        families: u8,
+        groups: u8,
+        devices: u8,
+    },
+}
+
+/// Description of an encoding
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EncodingInfo {
+    /// Name as accepted on the command line
+    pub name: &'static str,
+    /// Short description
+    pub description: &'static str,
+    /// Address model
+    pub address: AddressModel,
+    /// Length of the code word in tri-state bits
+    pub code_word_length: usize,
+    /// False if the encoding cannot be used for sending yet
+    pub implemented: bool,
+}
+
+/// Protocols by name
+///
+/// A new registry contains the built-in protocols. Names are compared case insensitive.
+#[derive(Clone, Debug)]
+pub struct ProtocolRegistry {
+    protocols: Vec<(&'static str, ProtocolValues)>,
+}
+
+impl Default for ProtocolRegistry {
+    fn default() -> ProtocolRegistry {
+        ProtocolRegistry {
+            protocols: vec![
+                ("1", Protocol1::values()),
+                ("2", Protocol2::values()),
+                ("3", Protocol3::values()),
+                ("4", Protocol4::values()),
+                ("5", Protocol5::values()),
+                ("HT6P20B", ProtocolHT6P20B::values()),
+                ("HS2303", ProtocolHS2303::values()),
+                ("6", Protocol6::values()),
+                ("7", Protocol7::values()),
+                ("8", Protocol8::values()),
+                ("9", Protocol9::values()),
+                ("10", Protocol10::values()),
+                ("11", Protocol11::values()),
+                ("12", Protocol12::values()),
+            ],
+        }
+    }
+}
+
+impl ProtocolRegistry {
+    /// Create a registry with the built-in protocols
+    pub fn new() -> ProtocolRegistry {
+        ProtocolRegistry::default()
+    }
+
+    /// Add a protocol or replace a previously registered one. Built-in protocols cannot be
+    /// replaced. Registered names are kept for the lifetime of the program.
+    pub fn register(&mut self, name: &str, values: ProtocolValues) -> Result<(), Error> {
+        ProtocolRegistry::check_name(name)?;
+        match self.position(name) {
+            Some(index) => self.protocols[index].1 = values,
+            None => {
+                let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+                self.protocols.push((name, values));
+            }
+        }
+        Ok(())
+    }
+
+    /// Timings of the protocol with the given name
+    pub fn get(&self, name: &str) -> Option<ProtocolValues> {
+        self.position(name)
+            .map(|index| self.protocols[index].1.clone())
+    }
+
+    /// Names and timings in registration order, built-in protocols first
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &ProtocolValues)> {
+        self.protocols.iter().map(|(name, values)| (*name, values))
+    }
+
+    /// Metadata of all protocols
+    pub fn infos(&self) -> Vec<ProtocolInfo> {
+        self.iter()
+            .map(|(name, values)| ProtocolInfo::new(name, values))
+            .collect()
+    }
+
+    /// Fail for names that cannot be registered: empty ones and the built-in protocols
+    pub(crate) fn check_name(name: &str) -> Result<(), Error> {
+        if name.trim().is_empty() {
+            return Err(CoreError::InvalidProtocol("empty name".into()).into());
+        }
+        if ProtocolRegistry::default().get(name).is_some() {
+            return Err(
+                CoreError::InvalidProtocol(format!("{} is a built-in protocol", name)).into(),
+            );
+        }
+        Ok(())
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.protocols
+            .iter()
+            .position(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+}
+
+fn global() -> &'static RwLock<ProtocolRegistry> {
+    static REGISTRY: OnceLock<RwLock<ProtocolRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(ProtocolRegistry::new()))
+}
+
+/// Snapshot of the process wide registry
+pub fn registry() -> ProtocolRegistry {
+    global()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Add a protocol to the process wide registry. See `ProtocolRegistry::register`.
+pub fn register_protocol(name: &str, values: ProtocolValues) -> Result<(), Error> {
+    global()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .register(name, values)
+}
+
+/// All registered protocols, built-in protocols first
+pub fn protocols() -> Vec<ProtocolInfo> {
+    registry().infos()
+}
+
+/// Timings of the registered protocol with the given name
+pub fn protocol(name: &str) -> Option<ProtocolValues> {
+    global()
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(name)
+}
+
+/// Name of the first registered protocol with the timings of `values`
+/// ```
+/// use funksteckdose::{catalog, Protocol, Protocol2};
+/// assert_eq!(catalog::protocol_name(&Protocol2::values()), Some("2"));
+/// ```
+pub fn protocol_name(values: &ProtocolValues) -> Option<&'static str> {
+    registry()
+        .iter()
+        .find(|(_, registered)| *registered == values)
+        .map(|(name, _)| name)
+}
+
+/// All built-in encodings
+pub fn encodings() -> Vec<EncodingInfo> {
+    vec![
+        EncodingInfo {
+            name: "A",
+            description: "5 dip switches for the group and 5 for the device",
+            address: AddressModel::DipSwitches {
+                group_bits: 5,
+                devices: 5,
+            },
+            code_word_length: 12,
+            implemented: true,
+        },
+        EncodingInfo {
+            name: "B",
+            description: "Two rotary or sliding switches with 4 positions each",
+            address: AddressModel::Rotary {
+                addresses: 4,
+                channels: 4,
+            },
+            code_word_length: 12,
+            implemented: true,
+        },
+        EncodingInfo {
+            name: "C",
+            description: "Intertechno family code a-p, group 1-4 and device 1-4",
+            address: AddressModel::Family {
+                families: 16,
+                groups: 4,
+                devices: 4,
+            },
+            code_word_length: 12,
+            implemented: true,
+        },
+        EncodingInfo {
+            name: "Brennenstuhl",
+            description: "Brennenstuhl RCS 1000 N: 5 system code dips and the unit letter A-E",
+            address: AddressModel::DipSwitches {
+                group_bits: 5,
+                devices: 5,
+            },
+            code_word_length: 12,
+            implemented: true,
+        },
+        EncodingInfo {
+            name: "REV",
+            description: "REV Telecontrol (Ritter): group letter A-D and device 1-3",
+            address: AddressModel::Rotary {
+                addresses: 4,
+                channels: 3,
+            },
+            code_word_length: 12,
+            implemented: true,
+        },
+    ]
+}
+
+/// Evaluate `$body` with `$E` as the type of the built-in encoding named `$name` or
+/// `$unknown` for any other name. Names are the ones of `encodings()` and are compared case
+/// insensitive. This is the one place that maps names to encoding types, everything that
+/// selects an encoding at runtime goes through it.
+/// ```
+/// use funksteckdose::{with_encoding, Device, Encoding, EncodingBrennenstuhl, State};
+///
+/// let encode = |name: &str| {
+///     with_encoding!(name, E => E::encode("10011", &Device::B, &State::On).ok(), _ => None)
+/// };
+/// let expected = EncodingBrennenstuhl::encode("10011", &Device::B, &State::On).unwrap();
+/// assert_eq!(encode("brennenstuhl"), Some(expected));
+/// assert_eq!(encode("X"), None);
+/// ```
+#[macro_export]
+macro_rules! with_encoding {
+    ($name:expr, $E:ident => $body:expr, _ => $unknown:expr) => {
+        match $name.to_ascii_uppercase().as_str() {
+            "A" => {
+                type $E = $crate::EncodingA;
+                $body
+            }
+            "B" => {
+                type $E = $crate::EncodingB;
+                $body
+            }
+            "C" => {
+                type $E = $crate::EncodingC;
+                $body
+            }
+            "BRENNENSTUHL" => {
+                type $E = $crate::EncodingBrennenstuhl;
+                $body
+            }
+            "REV" => {
+                type $E = $crate::EncodingREV;
+                $body
+            }
+            _ => $unknown,
+        }
+    };
+}
+
+/// True if `name` is a built-in encoding, see `with_encoding!`
+/// ```
+/// use funksteckdose::catalog;
+/// assert!(catalog::is_encoding("rev"));
+/// assert!(!catalog::is_encoding("D"));
+/// ```
+pub fn is_encoding(name: &str) -> bool {
+    with_encoding!(name, _E => true, _ => false)
+}
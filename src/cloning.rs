@@ -0,0 +1,237 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Cloning of remotes
+//!
+//! A captured transmission of a remote is stored under a name and can be sent again
+//! verbatim or decoded and encoded again. [`Capture::verify`] checks that encoding the
+//! decoded command reproduces the captured waveform, so the socket can be controlled by
+//! address instead of by recording.
+//!
+//! Captures are saved to a file with one capture per line: `<name> <high>/<low> ...` with
+//! durations in µs. The last symbol is the sync.
+
+use crate::{
+    error::Error,
+    plan::{Symbol, TransmissionPlan},
+    Device, Encoding, HighLow, Protocol, State,
+};
+use std::{
+    fmt, fs,
+    io::{self, BufRead, Write},
+    path::Path,
+    str,
+};
+
+/// Relative deviation of durations accepted by default
+pub const TOLERANCE: f64 = 0.25;
+
+/// One frame recorded from a remote
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capture {
+    pub name: String,
+    /// Data symbols followed by the sync
+    pub symbols: Vec<Symbol>,
+}
+
+impl Capture {
+    /// Plan to send the captured frame `repeats` times
+    pub fn plan(&self, repeats: usize) -> TransmissionPlan {
+        TransmissionPlan::from_symbols(self.symbols.clone(), repeats)
+    }
+
+    /// Tri-state code word of the capture if it matches the timing of `P` within `tolerance`
+    pub fn code_word<P: Protocol>(&self, tolerance: f64) -> Option<Vec<u8>> {
+        let values = P::values();
+        let (_sync, data) = self.symbols.split_last()?;
+        let matches = |s: &Symbol, pulses: &HighLow| {
+            let expected = Symbol {
+                high: (values.pulse_length() * pulses.high) as u32,
+                low: (values.pulse_length() * pulses.low) as u32,
+            };
+            within(s, &expected, tolerance)
+        };
+        let bits = data
+            .iter()
+            .map(|s| {
+                if matches(s, values.zero()) {
+                    Some(false)
+                } else if matches(s, values.one()) {
+                    Some(true)
+                } else {
+                    None
+                }
+            })
+            .collect::<Option<Vec<bool>>>()?;
+        if bits.is_empty() || bits.len() % 2 != 0 {
+            return None;
+        }
+        bits.chunks(2)
+            .map(|pair| match pair {
+                [false, false] => Some(b'0'),
+                [false, true] => Some(b'F'),
+                [true, true] => Some(b'1'),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Decode the capture with `E` and `P`
+    pub fn decode<E: Encoding, P: Protocol>(
+        &self,
+        tolerance: f64,
+    ) -> Option<(String, Device, State)> {
+        E::decode(&self.code_word::<P>(tolerance)?)
+    }
+
+    /// Decode the capture and check that encoding the result again reproduces the captured
+    /// waveform within `tolerance`
+    pub fn verify<E: Encoding, P: Protocol>(
+        &self,
+        tolerance: f64,
+    ) -> Result<(String, Device, State), Error> {
+        let mismatch = |reason: &str| Error::CaptureMismatch(format!("{}: {}", self.name, reason));
+        let (group, device, state) = self
+            .decode::<E, P>(tolerance)
+            .ok_or_else(|| mismatch("cannot decode"))?;
+        let code_word = E::encode(&group, &device, &state)?;
+        self.compare(&TransmissionPlan::new::<P>(&code_word, 1, None)?, tolerance)?;
+        Ok((group, device, state))
+    }
+
+    /// Check that the frame of `plan` reproduces the captured waveform within `tolerance`
+    pub fn compare(&self, plan: &TransmissionPlan, tolerance: f64) -> Result<(), Error> {
+        let mismatch = |reason: &str| Error::CaptureMismatch(format!("{}: {}", self.name, reason));
+        if plan.frame.len() != self.symbols.len() {
+            return Err(mismatch("different number of symbols"));
+        }
+        let (expected_sync, expected) = plan.frame.split_last().expect("empty frame");
+        let (sync, captured) = self.symbols.split_last().expect("empty capture");
+        if let Some(index) = captured
+            .iter()
+            .zip(expected)
+            .position(|(c, e)| !within(c, e, tolerance))
+        {
+            return Err(mismatch(&format!("symbol {} differs", index)));
+        }
+        // The gap after the sync pulse often merges with the silence after the frame, so
+        // only the pulse is compared
+        if !within_ratio(sync.high, expected_sync.high, tolerance) {
+            return Err(mismatch("sync differs"));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for s in &self.symbols {
+            write!(f, " {}/{}", s.high, s.low)?;
+        }
+        Ok(())
+    }
+}
+
+impl str::FromStr for Capture {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidCapture(s.into());
+        let mut fields = s.split_whitespace();
+        let name = fields.next().ok_or_else(invalid)?.to_string();
+        let symbols = fields
+            .map(|symbol| {
+                let (high, low) = symbol.split_once('/')?;
+                Some(Symbol {
+                    high: high.parse().ok()?,
+                    low: low.parse().ok()?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        if symbols.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Capture { name, symbols })
+    }
+}
+
+/// Named captures
+#[derive(Clone, Debug, Default)]
+pub struct Captures {
+    captures: Vec<Capture>,
+}
+
+impl Captures {
+    /// Load captures from `path`. A missing file results in no captures.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Captures, Error> {
+        let mut captures = Captures::default();
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(captures),
+            Err(e) => return Err(e.into()),
+        };
+        for line in io::BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                captures.insert(line.parse()?);
+            }
+        }
+        Ok(captures)
+    }
+
+    /// Save the captures to `path`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        for capture in &self.captures {
+            writeln!(file, "{}", capture)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Store a capture. A capture with the same name is replaced.
+    pub fn insert(&mut self, capture: Capture) {
+        match self.captures.iter_mut().find(|c| c.name == capture.name) {
+            Some(existing) => *existing = capture,
+            None => self.captures.push(capture),
+        }
+    }
+
+    /// Capture with `name`
+    pub fn get(&self, name: &str) -> Option<&Capture> {
+        self.captures.iter().find(|c| c.name == name)
+    }
+
+    /// Iterate the captures in insertion order
+    pub fn iter(&self) -> impl Iterator<Item = &Capture> {
+        self.captures.iter()
+    }
+}
+
+fn within(captured: &Symbol, expected: &Symbol, tolerance: f64) -> bool {
+    within_ratio(captured.high, expected.high, tolerance)
+        && within_ratio(captured.low, expected.low, tolerance)
+}
+
+fn within_ratio(captured: u32, expected: u32, tolerance: f64) -> bool {
+    (f64::from(captured) - f64::from(expected)).abs() <= f64::from(expected) * tolerance
+}
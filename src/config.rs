@@ -0,0 +1,1044 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Socket registry loaded from a TOML file
+//!
+//! ```toml
+//! [[socket]]
+//! name = "kitchen-lamp"
+//! room = "kitchen"
+//! group = "10011"
+//! device = "A"
+//! watts = 60
+//!
+//! [[socket]]
+//! name = "garden-pump"
+//! group = "11111"
+//! device = "B"
+//! protocol = "2"
+//! preamble = { pulses = 8, high = 350, low = 350 }
+//! min_gap_ms = 500
+//!
+//! [[socket]]
+//! name = "garage"
+//! group = "01010"
+//! device = "A"
+//! protocol = "garage"
+//!
+//! [[protocol]]
+//! name = "garage"
+//! pulse_length = 420
+//! sync = { high = 1, low = 28 }
+//! zero = { high = 1, low = 3 }
+//! one = { high = 3, low = 1 }
+//!
+//! [[socket]]
+//! name = "new-heater"
+//! group = "11111"
+//! device = "C"
+//! virtual = true
+//!
+//! [[transmitter]]
+//! pin = 0
+//! warm_up_ms = 5
+//! cool_down_ms = 20
+//! enable_pin = 7
+//!
+//! [[button]]
+//! pin = 3
+//! socket = "kitchen-lamp"
+//! action = "toggle"
+//!
+//! [[macro]]
+//! name = "pairing"
+//! pairing = true
+//! steps = [
+//!     { socket = "kitchen-lamp", state = "on", repeat = 8, gap_ms = 500 },
+//!     { wait_ms = 1000 },
+//! ]
+//!
+//! [[scene]]
+//! name = "evening"
+//! order = "interleaved"
+//! steps = [
+//!     { socket = "kitchen-lamp", state = "on" },
+//!     { socket = "garden-pump", state = "off" },
+//! ]
+//!
+//! [[hook]]
+//! code = "0FFF0FFFFFFF"
+//! command = "sudo poweroff"
+//! holdoff_ms = 5000
+//!
+//! [quiet_hours]
+//! start = "22:00"
+//! end = "07:00"
+//! repeats = 2
+//!
+//! [store]
+//! backend = "sqlite"
+//! path = "/var/lib/funksteckdose/store.db"
+//!
+//! [mqtt]
+//! host = "broker"
+//! username = "${MQTT_USER}"
+//! password_file = "/run/secrets/mqtt_password"
+//! ```
+//!
+//! Credentials support `${VAR}` substitution from the environment and can be read from
+//! files with the `*_file` variants e.g for systemd credentials or Docker secrets.
+//!
+//! The quiet hours apply to the background transmissions of the command line e.g restored
+//! states, see `quiet`.
+
+use crate::{
+    button::Action,
+    catalog::{self, ProtocolRegistry},
+    energy::Meter,
+    error::{CoreError, Error},
+    hook,
+    macros::{self, Step},
+    metadata::Metadata,
+    plan::TransmissionPlan,
+    queue::{Command, Queue},
+    quiet::QuietHours,
+    receiver::Received,
+    scene::{self, Order},
+    store::{self, Backend},
+    with_encoding, Code, Device, EncodingA, EncodingOptions, HighLow, Preamble, ProtocolValues,
+    State,
+};
+use log::warn;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::HashMap, env, fmt, fs, io::Write, path::Path, str, time::Duration};
+
+/// Encodings tried by `Socket::learn` by default
+pub const LEARN_ENCODINGS: &[&str] = &["A", "B", "C", "REV"];
+
+/// A configured socket
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Socket {
+    /// Unique name
+    pub name: String,
+    /// Optional room the socket is located in
+    pub room: Option<String>,
+    /// Group according to the dip switches e.g "10011"
+    pub group: String,
+    /// Device e.g "A" or "10000"
+    #[serde(deserialize_with = "from_str")]
+    pub device: Device,
+    /// Encoding A, B, C, Brennenstuhl or REV. Default: A
+    #[serde(default = "default_encoding")]
+    pub encoding: String,
+    /// Protocol. Default: 1
+    #[serde(default = "default_protocol")]
+    pub protocol: String,
+    /// WebAssembly module that encodes the socket instead of the encoding, see `plugin`
+    pub plugin: Option<String>,
+    /// Transmitter pin. Default: the pin selected on the command line
+    pub pin: Option<u16>,
+    /// Bit order and state variations of clone sockets
+    #[serde(flatten)]
+    pub options: EncodingOptions,
+    /// Wake-up burst before the first frame. Default: the preamble of the protocol
+    pub preamble: Option<Preamble>,
+    /// Power draw of the appliance in W for energy estimates
+    pub watts: Option<f64>,
+    /// Minimum time between two queued commands for receivers that ignore commands arriving
+    /// too soon in ms. Default: 0
+    #[serde(default)]
+    pub min_gap_ms: u64,
+    /// Track the socket without sending anything e.g to test automations before the socket
+    /// is installed. Default: false
+    #[serde(default, rename = "virtual")]
+    pub is_virtual: bool,
+    /// Icon, area, label and sort position for user interfaces
+    #[serde(default)]
+    pub meta: Metadata,
+}
+
+impl Socket {
+    /// The physical address of the socket
+    pub fn address(&self) -> (String, String, Device) {
+        (
+            self.encoding.to_ascii_uppercase(),
+            self.group.clone(),
+            self.device.clone(),
+        )
+    }
+
+    /// Plan sending `state` with the encoding, protocol, options and preamble of the socket
+    pub fn plan(&self, state: &State, repeats: usize) -> Result<TransmissionPlan, Error> {
+        let code_word = match self.plugin {
+            Some(_) => self.encode_plugin(state)?,
+            None => self.encode(state)?,
+        };
+        TransmissionPlan::with_values(
+            &self.protocol_values()?,
+            &code_word,
+            repeats,
+            self.preamble.as_ref(),
+        )
+    }
+
+    fn encode(&self, state: &State) -> Result<Vec<u8>, CoreError> {
+        let (group, device, options) = (&self.group, &self.device, &self.options);
+        // The number of dips of encoding A is only known at runtime
+        match (self.encoding.eq_ignore_ascii_case("A"), group.len()) {
+            (true, 4) => return options.encode::<EncodingA<4>>(group, device, state),
+            (true, 10) => return options.encode::<EncodingA<10>>(group, device, state),
+            _ => (),
+        }
+        with_encoding!(
+            self.encoding,
+            E => options.encode::<E>(group, device, state),
+            _ => Err(CoreError::EncodingUnsupported(format!(
+                "socket {}: {}",
+                self.name, self.encoding
+            )))
+        )
+    }
+
+    #[cfg(feature = "wasm")]
+    fn encode_plugin(&self, state: &State) -> Result<Vec<u8>, Error> {
+        use crate::plugin::WasmEncoder;
+        use std::{
+            collections::hash_map::Entry,
+            sync::{Mutex, OnceLock, PoisonError},
+        };
+
+        // Compiling a module is expensive. Each plugin is loaded on its first use and shared by
+        // the sockets using it.
+        static PLUGINS: OnceLock<Mutex<HashMap<String, WasmEncoder>>> = OnceLock::new();
+        let path = self.plugin.as_deref().unwrap_or_default();
+        let mut plugins = PLUGINS
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let encoder = match plugins.entry(path.into()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(WasmEncoder::load(path)?),
+        };
+        encoder.encode(&self.group, &self.device, state)
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn encode_plugin(&self, _state: &State) -> Result<Vec<u8>, Error> {
+        Err(CoreError::EncodingUnsupported(format!(
+            "socket {}: plugins require the wasm feature",
+            self.name
+        ))
+        .into())
+    }
+
+    /// Socket named `name` that is addressed like the remote that sent `received`. `None`
+    /// if none or more than one of `encodings` decodes the code, see [`Socket::candidates`].
+    /// ```
+    /// use funksteckdose::{config::{Socket, LEARN_ENCODINGS}, receiver::Received, Device};
+    /// let received = Received {
+    ///     protocol: "1",
+    ///     code: 1380689,
+    ///     length: 24,
+    ///     pulse_length: 350,
+    /// };
+    /// let socket = Socket::learn("bedroom", &received, LEARN_ENCODINGS).unwrap();
+    /// assert_eq!((socket.group.as_str(), &socket.device), ("10001", &Device::B));
+    /// assert!(socket.to_toml().starts_with("[[socket]]\nname = \"bedroom\"\n"));
+    /// ```
+    pub fn learn(name: &str, received: &Received, encodings: &[&str]) -> Option<Socket> {
+        let mut candidates = Socket::candidates(name, received, encodings);
+        if candidates.len() == 1 {
+            candidates.pop()
+        } else {
+            None
+        }
+    }
+
+    /// A socket named `name` for each of `encodings` that decodes `received`. Codes of
+    /// some encodings overlap e.g `0FFFF0FFFFF0` is "10000 A off" in encoding A and
+    /// "1 2 off" in encoding B.
+    /// ```
+    /// use funksteckdose::{config::{Socket, LEARN_ENCODINGS}, receiver::Received};
+    /// let received = Received {
+    ///     protocol: "1",
+    ///     code: 0b000101010100010101010100,
+    ///     length: 24,
+    ///     pulse_length: 350,
+    /// };
+    /// let candidates = Socket::candidates("lamp", &received, LEARN_ENCODINGS);
+    /// let encodings: Vec<_> = candidates.iter().map(|s| s.encoding.as_str()).collect();
+    /// assert_eq!(encodings, ["A", "B"]);
+    /// assert!(Socket::learn("lamp", &received, LEARN_ENCODINGS).is_none());
+    /// assert!(Socket::learn("lamp", &received, &["B"]).is_some());
+    /// ```
+    pub fn candidates(name: &str, received: &Received, encodings: &[&str]) -> Vec<Socket> {
+        encodings
+            .iter()
+            .filter_map(|encoding| {
+                let encoding = encoding.to_ascii_uppercase();
+                let decoded = with_encoding!(encoding, E => received.decode::<E>(), _ => None);
+                decoded.map(|(group, device, _)| Socket {
+                    name: name.into(),
+                    room: None,
+                    group,
+                    device,
+                    encoding,
+                    protocol: received.protocol.into(),
+                    plugin: None,
+                    pin: None,
+                    options: EncodingOptions::default(),
+                    preamble: None,
+                    watts: None,
+                    min_gap_ms: 0,
+                    is_virtual: false,
+                    meta: Metadata::default(),
+                })
+            })
+            .collect()
+    }
+
+    /// The name, room, address and protocol as `[[socket]]` table of a configuration file
+    pub fn to_toml(&self) -> String {
+        let string = |s: &str| toml::Value::from(s).to_string();
+        let mut table = format!("[[socket]]\nname = {}\n", string(&self.name));
+        if let Some(ref room) = self.room {
+            table += &format!("room = {}\n", string(room));
+        }
+        table += &format!(
+            "group = {}\ndevice = {}\nencoding = {}\nprotocol = {}\n",
+            string(&self.group),
+            string(&self.device.to_string()),
+            string(&self.encoding),
+            string(&self.protocol)
+        );
+        table
+    }
+
+    /// Minimum time between two queued commands for the socket
+    pub fn min_gap(&self) -> Duration {
+        Duration::from_millis(self.min_gap_ms)
+    }
+
+    /// Values of the topic placeholders of the socket
+    #[cfg(feature = "mqtt")]
+    pub fn topic_vars(&self) -> crate::mqtt::TopicVars {
+        crate::mqtt::TopicVars {
+            prefix: None,
+            room: self.room.clone(),
+            name: Some(self.name.clone()),
+            group: Some(self.group.clone()),
+            device: Some(self.device.clone()),
+        }
+    }
+
+    /// Timings of the configured protocol for `Funksteckdose::with_protocol`
+    pub fn protocol_values(&self) -> Result<ProtocolValues, Error> {
+        catalog::protocol(&self.protocol).ok_or_else(|| {
+            Error::InvalidConfig(format!(
+                "socket {}: unknown protocol {}",
+                self.name, self.protocol
+            ))
+        })
+    }
+}
+
+/// A protocol that is not built in e.g derived from a recording
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ProtocolDefinition {
+    /// Name used by the `protocol` of sockets
+    pub name: String,
+    /// Duration of one pulse in µs
+    pub pulse_length: u64,
+    /// Pulses of the sync symbol
+    pub sync: HighLow,
+    /// Pulses of a zero bit
+    pub zero: HighLow,
+    /// Pulses of a one bit
+    pub one: HighLow,
+    /// Low pulses are sent first. Default: false
+    #[serde(default)]
+    pub inverted: bool,
+}
+
+impl ProtocolDefinition {
+    /// Timings of the protocol
+    pub fn values(&self) -> ProtocolValues {
+        ProtocolValues::new(
+            self.pulse_length,
+            self.sync.clone(),
+            self.zero.clone(),
+            self.one.clone(),
+            self.inverted,
+        )
+    }
+}
+
+/// Settings of a transmitter
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Transmitter {
+    /// Transmitter pin
+    pub pin: u16,
+    /// Wait before each transmission in ms. Default: 0
+    #[serde(default)]
+    pub warm_up_ms: u64,
+    /// Wait after each transmission in ms. Default: 0
+    #[serde(default)]
+    pub cool_down_ms: u64,
+    /// Pin driven high while sending e.g the supply switch of the module
+    #[serde(default)]
+    pub enable_pin: Option<u16>,
+}
+
+impl Transmitter {
+    /// Wait before each transmission
+    pub fn warm_up(&self) -> Duration {
+        Duration::from_millis(self.warm_up_ms)
+    }
+
+    /// Wait after each transmission
+    pub fn cool_down(&self) -> Duration {
+        Duration::from_millis(self.cool_down_ms)
+    }
+}
+
+/// A push button switching a socket
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Button {
+    /// Input pin
+    pub pin: u16,
+    /// Name of the socket
+    pub socket: String,
+    /// on, off or toggle. Default: toggle
+    #[serde(
+        default = "default_action",
+        deserialize_with = "from_str",
+        serialize_with = "display"
+    )]
+    pub action: Action,
+    /// Debounce time in ms. Default: 50
+    #[serde(default = "default_debounce")]
+    pub debounce_ms: u64,
+    /// True if the button pulls the pin high. Default: false
+    #[serde(default)]
+    pub active_high: bool,
+}
+
+/// A step of a macro
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum MacroStep {
+    /// Send `state` to `socket` `repeat` times with `gap_ms` in between
+    Send {
+        socket: String,
+        #[serde(deserialize_with = "from_str")]
+        state: State,
+        #[serde(default = "default_repeat")]
+        repeat: usize,
+        #[serde(default)]
+        gap_ms: u64,
+    },
+    /// Pause
+    Wait { wait_ms: u64 },
+}
+
+/// A named sequence of commands
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Macro {
+    pub name: String,
+    pub steps: Vec<MacroStep>,
+    /// The macro pairs sockets and needs confirmation. Default: false
+    #[serde(default)]
+    pub pairing: bool,
+}
+
+/// A command of a scene
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SceneStep {
+    pub socket: String,
+    #[serde(deserialize_with = "from_str")]
+    pub state: State,
+}
+
+/// A named set of commands sent together
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Scene {
+    pub name: String,
+    pub steps: Vec<SceneStep>,
+    /// sequential or interleaved. Default: sequential
+    #[serde(default, deserialize_with = "from_str", serialize_with = "display")]
+    pub order: Order,
+}
+
+/// A command run when a code is received
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Hook {
+    /// Tri-state code word, binary or decimal code
+    pub code: String,
+    /// Shell command
+    pub command: String,
+    /// Time after running during which the code is ignored. Default: 1000
+    #[serde(default = "default_holdoff_ms")]
+    pub holdoff_ms: u64,
+}
+
+/// User name and password given inline, via `${VAR}` or in files
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Credentials {
+    pub username: Option<String>,
+    pub username_file: Option<String>,
+    pub password: Option<String>,
+    pub password_file: Option<String>,
+}
+
+impl Credentials {
+    /// The user name with environment variables substituted or read from its file
+    pub fn username(&self) -> Result<Option<String>, Error> {
+        secret(&self.username, &self.username_file)
+    }
+
+    /// The password with environment variables substituted or read from its file
+    pub fn password(&self) -> Result<Option<String>, Error> {
+        secret(&self.password, &self.password_file)
+    }
+}
+
+/// MQTT broker connection and topics
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Mqtt {
+    /// Broker host. Default: localhost
+    #[serde(default = "default_mqtt_host")]
+    pub host: String,
+    /// Broker port. Default: 1883
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    /// Client id. Default: funksteckdose
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    /// Value of `{prefix}` in topic templates
+    pub prefix: Option<String>,
+    /// Template of command topics e.g "{prefix}/{room}/{name}/set"
+    pub command_topic: Option<String>,
+    /// Template of state topics e.g "{prefix}/{room}/{name}/state"
+    pub state_topic: Option<String>,
+    /// Template of topics for previews of dry runs e.g "{prefix}/{room}/{name}/preview"
+    pub preview_topic: Option<String>,
+    /// Template of topics for on time and energy e.g "{prefix}/{room}/{name}/energy"
+    pub energy_topic: Option<String>,
+    /// Payload format: on-off, numeric or json
+    pub payload: Option<String>,
+    /// Publish Home Assistant discovery payloads for the sockets. Default: true
+    #[serde(default = "default_mqtt_discovery")]
+    pub discovery: bool,
+}
+
+#[cfg(feature = "mqtt")]
+impl Mqtt {
+    /// Topic configuration with defaults for unset values
+    pub fn topics(&self) -> Result<crate::mqtt::Topics, Error> {
+        let mut topics = crate::mqtt::Topics::default();
+        if let Some(ref prefix) = self.prefix {
+            topics.prefix = prefix.clone();
+        }
+        if let Some(ref command) = self.command_topic {
+            topics.command = command.parse()?;
+        }
+        if let Some(ref state) = self.state_topic {
+            topics.state = state.parse()?;
+        }
+        if let Some(ref preview) = self.preview_topic {
+            topics.preview = preview.parse()?;
+        }
+        if let Some(ref energy) = self.energy_topic {
+            topics.energy = energy.parse()?;
+        }
+        if let Some(ref payload) = self.payload {
+            topics.payload = payload.parse()?;
+        }
+        Ok(topics)
+    }
+
+    /// Home Assistant discovery topics and payloads of the switches of `sockets`. Empty if
+    /// discovery is disabled.
+    pub fn discovery(&self, sockets: &[Socket]) -> Result<Vec<(String, String)>, Error> {
+        if !self.discovery {
+            return Ok(Vec::new());
+        }
+        let topics = self.topics()?;
+        sockets
+            .iter()
+            .map(|socket| topics.switch_discovery(&socket.topic_vars(), &socket.meta))
+            .collect()
+    }
+
+    /// Connection options of the broker
+    pub fn options(&self) -> Result<rumqttc::MqttOptions, Error> {
+        let mut options =
+            rumqttc::MqttOptions::new(self.client_id.clone(), self.host.clone(), self.port);
+        if let Some(username) = self.credentials.username()? {
+            options.set_credentials(username, self.credentials.password()?.unwrap_or_default());
+        }
+        Ok(options)
+    }
+}
+
+/// HTTP server
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Http {
+    /// Address to listen on. Default: 127.0.0.1:8080, only reachable from this host
+    #[serde(default = "default_http_listen")]
+    pub listen: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
+}
+
+/// Persistence backend
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Store {
+    /// json, sled or sqlite. Default: json
+    #[serde(
+        default = "default_store_backend",
+        deserialize_with = "from_str",
+        serialize_with = "display"
+    )]
+    pub backend: Backend,
+    /// File or directory of the store
+    pub path: String,
+}
+
+impl Store {
+    /// Open the configured store
+    pub fn open(&self) -> Result<Box<dyn store::Store>, Error> {
+        store::open(self.backend, &self.path)
+    }
+}
+
+/// A problem found in a configuration
+#[derive(Clone, Debug, PartialEq)]
+pub enum Conflict {
+    /// More than one socket uses this name
+    Name(String),
+    /// The sockets with these names share one physical address
+    Address(Vec<String>),
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Conflict::Name(name) => write!(f, "duplicate socket name \"{}\"", name),
+            Conflict::Address(names) => write!(
+                f,
+                "sockets {} share the same encoding, group and device",
+                names.join(", ")
+            ),
+        }
+    }
+}
+
+/// Configuration file contents
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Config {
+    #[serde(default, rename = "socket")]
+    pub sockets: Vec<Socket>,
+    #[serde(default, rename = "transmitter")]
+    pub transmitters: Vec<Transmitter>,
+    #[serde(default, rename = "button")]
+    pub buttons: Vec<Button>,
+    #[serde(default, rename = "macro")]
+    pub macros: Vec<Macro>,
+    #[serde(default, rename = "scene")]
+    pub scenes: Vec<Scene>,
+    #[serde(default, rename = "hook")]
+    pub hooks: Vec<Hook>,
+    /// Protocols added to the `catalog` by `register_protocols` e.g when the configuration
+    /// is loaded
+    #[serde(default, rename = "protocol")]
+    pub protocols: Vec<ProtocolDefinition>,
+    pub mqtt: Option<Mqtt>,
+    pub http: Option<Http>,
+    /// Reduced background transmissions at night
+    pub quiet_hours: Option<QuietHours>,
+    /// Persistence of states, counters, captures and history
+    pub store: Option<Store>,
+}
+
+impl Config {
+    /// Load a configuration from `path` and register its protocols. Duplicate names are an
+    /// error, sockets sharing an address are logged as a warning.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, Error> {
+        let config: Config = fs::read_to_string(path)?.parse()?;
+        config.register_protocols()?;
+        Ok(config)
+    }
+
+    /// Add the protocols of the configuration to the `catalog` so that sockets, the command
+    /// line and the receiver accept their names. Parsing a configuration leaves the catalog
+    /// untouched, `load` does both.
+    /// ```
+    /// use funksteckdose::{catalog, config::Config};
+    ///
+    /// let config: Config = r#"
+    ///     [[protocol]]
+    ///     name = "gate"
+    ///     pulse_length = 420
+    ///     sync = { high = 1, low = 28 }
+    ///     zero = { high = 1, low = 3 }
+    ///     one = { high = 3, low = 1 }
+    /// "#
+    /// .parse()
+    /// .unwrap();
+    /// assert!(catalog::protocol("gate").is_none());
+    /// config.register_protocols().unwrap();
+    /// assert_eq!(catalog::protocol("gate").unwrap().pulse_length(), 420);
+    /// ```
+    pub fn register_protocols(&self) -> Result<(), Error> {
+        for protocol in &self.protocols {
+            catalog::register_protocol(&protocol.name, protocol.values())?;
+        }
+        Ok(())
+    }
+
+    /// The configuration with all defaults filled in as TOML that parses back into the same
+    /// configuration
+    ///
+    /// ```
+    /// use funksteckdose::config::Config;
+    ///
+    /// let config: Config = r#"
+    ///     [[socket]]
+    ///     name = "lamp"
+    ///     group = "10001"
+    ///     device = "B"
+    /// "#
+    /// .parse()
+    /// .unwrap();
+    /// let toml = config.to_toml().unwrap();
+    /// assert!(toml.contains("encoding = \"A\""));
+    /// assert_eq!(toml.parse::<Config>().unwrap(), config);
+    /// ```
+    pub fn to_toml(&self) -> Result<String, Error> {
+        // Going through a value puts plain values before tables as required by TOML
+        toml::Value::try_from(self)
+            .map(|value| value.to_string())
+            .map_err(|e| Error::InvalidConfig(e.to_string()))
+    }
+
+    /// On time accounting for all sockets with their configured wattage
+    pub fn meter(&self) -> Meter {
+        let mut meter = Meter::new();
+        for socket in &self.sockets {
+            meter.insert(&socket.group, &socket.device);
+            if let Some(watts) = socket.watts {
+                meter.set_watts(&socket.group, &socket.device, watts);
+            }
+        }
+        meter
+    }
+
+    /// Settings of the transmitter on `pin`
+    pub fn transmitter(&self, pin: u16) -> Option<&Transmitter> {
+        self.transmitters.iter().find(|t| t.pin == pin)
+    }
+
+    /// Find the socket with `name`
+    pub fn socket(&self, name: &str) -> Option<&Socket> {
+        self.sockets.iter().find(|s| s.name == name)
+    }
+
+    /// Append `socket` to the configuration file at `path`, which is created if missing.
+    /// Comments and formatting of the file are kept. Fails if the configuration has a socket
+    /// with the same name.
+    pub fn append_socket(path: &Path, socket: &Socket) -> Result<(), Error> {
+        if path.exists() && Config::load(path)?.socket(&socket.name).is_some() {
+            return Err(Error::InvalidConfig(format!(
+                "socket {} exists already",
+                socket.name
+            )));
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        write!(file, "\n{}", socket.to_toml())?;
+        Ok(())
+    }
+
+    /// Set the minimum gaps of the sockets on `queue`
+    pub fn apply_min_gaps(&self, queue: &mut Queue) {
+        for socket in &self.sockets {
+            queue.set_min_gap(&socket.group, &socket.device, socket.min_gap());
+        }
+    }
+
+    /// The sockets in the order they are presented: by area, sort position and label
+    pub fn sockets_by_area(&self) -> Vec<&Socket> {
+        let mut sockets = self.sockets.iter().collect::<Vec<_>>();
+        sockets.sort_by(|a, b| a.meta.order(&a.name, &b.meta, &b.name));
+        sockets
+    }
+
+    /// Resolve the macro with `name` into commands
+    pub fn macro_(&self, name: &str) -> Result<macros::Macro, Error> {
+        let m = self
+            .macros
+            .iter()
+            .find(|m| m.name == name)
+            .ok_or_else(|| Error::InvalidConfig(format!("unknown macro \"{}\"", name)))?;
+        let steps = m
+            .steps
+            .iter()
+            .map(|step| match step {
+                MacroStep::Send {
+                    socket,
+                    state,
+                    repeat,
+                    gap_ms,
+                } => {
+                    let socket = self.socket(socket).ok_or_else(|| {
+                        Error::InvalidConfig(format!(
+                            "macro \"{}\" refers to unknown socket \"{}\"",
+                            name, socket
+                        ))
+                    })?;
+                    Ok(Step::Send {
+                        command: Command::new(&socket.group, &socket.device, state),
+                        repeat: *repeat,
+                        gap: Duration::from_millis(*gap_ms),
+                    })
+                }
+                MacroStep::Wait { wait_ms } => Ok(Step::Wait(Duration::from_millis(*wait_ms))),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(macros::Macro {
+            name: m.name.clone(),
+            steps,
+            pairing: m.pairing,
+        })
+    }
+
+    /// Commands run on received codes
+    pub fn hooks(&self) -> Result<hook::Hooks, Error> {
+        let hooks = self
+            .hooks
+            .iter()
+            .map(|h| {
+                let code = Code::parse(&h.code)
+                    .map_err(|e| Error::InvalidConfig(format!("hook \"{}\": {}", h.command, e)))?;
+                Ok(hook::Hook {
+                    holdoff: Duration::from_millis(h.holdoff_ms),
+                    ..hook::Hook::new(code, &h.command)
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(hook::Hooks::new(hooks))
+    }
+
+    /// Resolve the scene with `name` into commands
+    pub fn scene(&self, name: &str) -> Result<scene::Scene, Error> {
+        let s = self
+            .scenes
+            .iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| Error::InvalidConfig(format!("unknown scene \"{}\"", name)))?;
+        let commands = s
+            .steps
+            .iter()
+            .map(|step| {
+                let socket = self.socket(&step.socket).ok_or_else(|| {
+                    Error::InvalidConfig(format!(
+                        "scene \"{}\" refers to unknown socket \"{}\"",
+                        name, step.socket
+                    ))
+                })?;
+                Ok(Command::new(&socket.group, &socket.device, &step.state))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(scene::Scene {
+            name: s.name.clone(),
+            commands,
+            order: s.order,
+        })
+    }
+
+    /// Duplicate names and sockets sharing an address
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+
+        let mut names = HashMap::new();
+        for socket in &self.sockets {
+            let count = names.entry(socket.name.as_str()).or_insert(0);
+            *count += 1;
+            if *count == 2 {
+                conflicts.push(Conflict::Name(socket.name.clone()));
+            }
+        }
+
+        // Virtual sockets are never sent and cannot conflict with real ones
+        let mut addresses: Vec<(_, Vec<String>)> = Vec::new();
+        for socket in self.sockets.iter().filter(|s| !s.is_virtual) {
+            let address = socket.address();
+            match addresses.iter_mut().find(|(a, _)| *a == address) {
+                Some((_, names)) => names.push(socket.name.clone()),
+                None => addresses.push((address, vec![socket.name.clone()])),
+            }
+        }
+        conflicts.extend(
+            addresses
+                .into_iter()
+                .filter(|(_, names)| names.len() > 1)
+                .map(|(_, names)| Conflict::Address(names)),
+        );
+
+        conflicts
+    }
+}
+
+impl str::FromStr for Config {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let config: Config = toml::from_str(s).map_err(|e| Error::InvalidConfig(e.to_string()))?;
+        for protocol in &config.protocols {
+            ProtocolRegistry::check_name(&protocol.name)?;
+        }
+        for conflict in config.conflicts() {
+            match conflict {
+                Conflict::Name(_) => return Err(Error::InvalidConfig(conflict.to_string())),
+                Conflict::Address(_) => warn!("{}", conflict),
+            }
+        }
+        if let Some(button) = config
+            .buttons
+            .iter()
+            .find(|b| config.socket(&b.socket).is_none())
+        {
+            return Err(Error::InvalidConfig(format!(
+                "button on pin {} refers to unknown socket \"{}\"",
+                button.pin, button.socket
+            )));
+        }
+        for m in &config.macros {
+            config.macro_(&m.name)?;
+        }
+        Ok(config)
+    }
+}
+
+/// Replace `${VAR}` by the value of the environment variable `VAR`
+pub fn substitute_env(s: &str) -> Result<String, Error> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| Error::InvalidConfig(format!("unterminated variable in \"{}\"", s)))?;
+        let name = &rest[start + 2..start + end];
+        let value = env::var(name).map_err(|_| {
+            Error::InvalidConfig(format!("environment variable {} is not set", name))
+        })?;
+        result.push_str(&rest[..start]);
+        result.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn secret(value: &Option<String>, file: &Option<String>) -> Result<Option<String>, Error> {
+    match (value, file) {
+        (Some(_), Some(_)) => Err(Error::InvalidConfig(
+            "a secret and its *_file variant are mutually exclusive".into(),
+        )),
+        (Some(value), None) => substitute_env(value).map(Some),
+        (None, Some(file)) => {
+            let content = fs::read_to_string(substitute_env(file)?)?;
+            Ok(Some(content.trim_end_matches(&['\r', '\n'][..]).into()))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+fn default_holdoff_ms() -> u64 {
+    1000
+}
+
+fn default_mqtt_host() -> String {
+    "localhost".into()
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_client_id() -> String {
+    "funksteckdose".into()
+}
+
+fn default_mqtt_discovery() -> bool {
+    true
+}
+
+fn default_http_listen() -> String {
+    "127.0.0.1:8080".into()
+}
+
+fn default_store_backend() -> Backend {
+    Backend::Json
+}
+
+fn default_encoding() -> String {
+    "A".into()
+}
+
+fn default_protocol() -> String {
+    "1".into()
+}
+
+fn default_action() -> Action {
+    Action::Toggle
+}
+
+fn default_repeat() -> usize {
+    1
+}
+
+fn default_debounce() -> u64 {
+    50
+}
+
+fn display<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: fmt::Display,
+{
+    serializer.collect_str(value)
+}
+
+fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: str::FromStr,
+    T::Err: fmt::Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(de::Error::custom)
+}
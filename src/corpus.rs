@@ -0,0 +1,231 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//! Corpus of transmissions captured from real remotes
+//!
+//! Every file `*.txt` of a corpus directory describes one remote model: the encoding and
+//! protocol of its sockets and captured frames with the command they are expected to
+//! decode to. Empty lines and lines starting with '#' are ignored:
+//!
+//! ```text
+//! # Captured with a RXB6 on a Raspberry Pi 3
+//! remote: Brennenstuhl RCS 1000 N
+//! encoding: Brennenstuhl
+//! protocol: 1
+//! 10011 A on: 334/1036 1062/318 ... 351/10873
+//! ```
+//!
+//! A frame is written like a [`Capture`]: `<high>/<low>` durations in µs with the sync last.
+//! [`Entry::check`] feeds every frame to the receiver and compares the encoding of the
+//! expected command at the received pulse length with the frame, so a contributed remote
+//! keeps decoder and encoders honest.
+
+use crate::{
+    catalog,
+    cloning::{self, Capture},
+    error::{CoreError, Error},
+    plan::TransmissionPlan,
+    receiver::Decoder,
+    with_encoding, Device, Encoding, State,
+};
+use std::{fmt, fs, path::Path, str};
+
+/// Frames fed to the decoder. It needs to see the sync twice.
+const REPEATS: usize = 3;
+
+type Encode = fn(&str, &Device, &State) -> Result<Vec<u8>, CoreError>;
+type Decode = fn(&[u8]) -> Option<(String, Device, State)>;
+
+fn codec(encoding: &str) -> Option<(Encode, Decode)> {
+    with_encoding!(encoding, E => Some((E::encode as Encode, E::decode as Decode)), _ => None)
+}
+
+/// A captured frame and the command it is expected to decode to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Case {
+    pub group: String,
+    pub device: Device,
+    pub state: State,
+    /// Frame named by file and line
+    pub capture: Capture,
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}:", self.group, self.device, self.state)?;
+        for s in &self.capture.symbols {
+            write!(f, " {}/{}", s.high, s.low)?;
+        }
+        Ok(())
+    }
+}
+
+/// Captures of one remote model
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// Manufacturer and model of the remote
+    pub remote: String,
+    /// Name of the encoding as in `catalog::encodings`
+    pub encoding: String,
+    /// Name of the protocol as in `catalog::protocols`
+    pub protocol: String,
+    pub cases: Vec<Case>,
+}
+
+impl Entry {
+    /// Parse an entry. `name` identifies the cases in errors e.g the file name.
+    pub fn parse(name: &str, s: &str) -> Result<Entry, Error> {
+        let invalid = |line: usize, reason: &str| {
+            Error::InvalidCapture(format!("{}:{}: {}", name, line, reason))
+        };
+        let (mut remote, mut encoding, mut protocol) = (None, None, None);
+        let mut cases = Vec::new();
+        for (index, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| invalid(index + 1, "missing ':'"))?;
+            let value = value.trim();
+            match key.trim() {
+                "remote" => remote = Some(value.to_string()),
+                "encoding" => encoding = Some(value.to_string()),
+                "protocol" => protocol = Some(value.to_string()),
+                command => {
+                    let mut words = command.split_whitespace();
+                    let (group, device, state) =
+                        match (words.next(), words.next(), words.next(), words.next()) {
+                            (Some(group), Some(device), Some(state), None) => {
+                                (group, device.parse()?, state.parse()?)
+                            }
+                            _ => {
+                                return Err(invalid(index + 1, "expected <group> <device> <state>"))
+                            }
+                        };
+                    let capture = format!("{}:{} {}", name, index + 1, value).parse()?;
+                    cases.push(Case {
+                        group: group.to_string(),
+                        device,
+                        state,
+                        capture,
+                    });
+                }
+            }
+        }
+        let entry = Entry {
+            remote: remote.ok_or_else(|| invalid(0, "missing remote"))?,
+            encoding: encoding.ok_or_else(|| invalid(0, "missing encoding"))?,
+            protocol: protocol.ok_or_else(|| invalid(0, "missing protocol"))?,
+            cases,
+        };
+        if codec(&entry.encoding).is_none() {
+            return Err(
+                CoreError::EncodingUnsupported(format!("{}: {}", name, entry.encoding)).into(),
+            );
+        }
+        if catalog::protocol(&entry.protocol).is_none() {
+            return Err(CoreError::InvalidProtocol(format!("{}: {}", name, entry.protocol)).into());
+        }
+        Ok(entry)
+    }
+
+    /// Check every case with the receiver and the encoder. Returns the failed checks.
+    pub fn check(&self) -> Vec<Error> {
+        self.cases
+            .iter()
+            .filter_map(|case| self.check_case(case).err())
+            .collect()
+    }
+
+    fn check_case(&self, case: &Case) -> Result<(), Error> {
+        let capture = &case.capture;
+        let mismatch =
+            |reason: String| Error::CaptureMismatch(format!("{}: {}", capture.name, reason));
+        let (encode, decode) = codec(&self.encoding).expect("unsupported encoding");
+        let values = catalog::protocol(&self.protocol).expect("unknown protocol");
+        let expected = (case.group.clone(), case.device.clone(), case.state.clone());
+
+        let mut decoder = Decoder::default();
+        let received = (0..REPEATS)
+            .flat_map(|_| capture.symbols.iter().flat_map(|s| vec![s.high, s.low]))
+            .find_map(|d| decoder.push(d))
+            .ok_or_else(|| mismatch("not received".into()))?;
+        let timing = catalog::protocol(received.protocol).expect("received unknown protocol");
+        if timing.sync_factor() != values.sync_factor()
+            || timing.zero() != values.zero()
+            || timing.one() != values.one()
+            || timing.inverted_signal() != values.inverted_signal()
+        {
+            return Err(mismatch(format!("received protocol {}", received.protocol)));
+        }
+        match received
+            .code_word()
+            .and_then(|code_word| decode(&code_word))
+        {
+            Some(ref decoded) if decoded == &expected => (),
+            Some((group, device, state)) => {
+                return Err(mismatch(format!("decoded {} {} {}", group, device, state)))
+            }
+            None => return Err(mismatch(format!("cannot decode {}", received))),
+        }
+
+        // Remotes are rarely exact, the encoding is compared at the pulse length of the remote
+        let code_word = encode(&case.group, &case.device, &case.state)?;
+        let values = values.with_pulse_length(received.pulse_length);
+        let plan = TransmissionPlan::with_values(&values, &code_word, 1, None)?;
+        capture.compare(&plan, cloning::TOLERANCE)
+    }
+}
+
+/// All entries of a corpus directory
+#[derive(Clone, Debug, Default)]
+pub struct Corpus {
+    entries: Vec<Entry>,
+}
+
+impl Corpus {
+    /// Load the files `*.txt` of `dir` in the order of their names
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Corpus, Error> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.retain(|path| path.extension().map(|e| e == "txt").unwrap_or(false));
+        paths.sort();
+        let entries = paths
+            .iter()
+            .map(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                Entry::parse(&name, &fs::read_to_string(path)?)
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Corpus { entries })
+    }
+
+    /// The entries of the corpus
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Check all entries. Returns the failed checks.
+    pub fn check(&self) -> Vec<Error> {
+        self.entries.iter().flat_map(Entry::check).collect()
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Command stream of the daemon
+//!
+//! The daemon keeps the transmitter set up and reads one command per line from stdin or a
+//! named pipe: `<name|group/device> <state>` e.g `kitchen-lamp on` or `10001/A off`. Empty
+//! lines and lines starting with `#` are skipped.
+//!
+//! ```
+//! use funksteckdose::{daemon::{self, Target}, Device, State};
+//!
+//! let input = "# lights\nkitchen-lamp on\n10001/A off\n";
+//! let mut sent = Vec::new();
+//! daemon::serve(input.as_bytes(), |request| {
+//!     sent.push((request.target.clone(), request.state.clone()));
+//!     Ok(())
+//! })
+//! .unwrap();
+//! assert_eq!(sent[0], (Target::Name("kitchen-lamp".into()), State::On));
+//! assert_eq!(sent[1], (Target::Address("10001".parse()?, Device::A), State::Off));
+//! # Ok::<(), funksteckdose::error::Error>(())
+//! ```
+
+use crate::{
+    error::{CoreError, Error},
+    Device, Group, State,
+};
+use log::{info, warn};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+    str,
+};
+
+/// Socket addressed by a command
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    /// Name of a socket in the configuration
+    Name(String),
+    /// Group and device e.g `10001/A`
+    Address(Group, Device),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Name(name) => write!(f, "{}", name),
+            Target::Address(group, device) => write!(f, "{}/{}", group, device),
+        }
+    }
+}
+
+impl str::FromStr for Target {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((group, device)) => Ok(Target::Address(group.parse()?, device.parse()?)),
+            None => Ok(Target::Name(s.into())),
+        }
+    }
+}
+
+/// A command line of the stream
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    pub target: Target,
+    pub state: State,
+}
+
+impl Request {
+    /// Parse a line. `None` for empty lines and comments.
+    pub fn parse_line(line: &str) -> Result<Option<Request>, Error> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        line.parse().map(Some)
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.target, self.state)
+    }
+}
+
+impl str::FromStr for Request {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some(target), Some(state), None) => Ok(Request {
+                target: target.parse()?,
+                state: state.parse()?,
+            }),
+            _ => Err(CoreError::InvalidCommand(s.into()).into()),
+        }
+    }
+}
+
+/// Handle the commands of `reader` until the end of the input. Invalid lines and failures
+/// of `handle` are logged and the next line is read.
+pub fn serve<R, F>(reader: R, mut handle: F) -> Result<(), Error>
+where
+    R: BufRead,
+    F: FnMut(&Request) -> Result<(), Error>,
+{
+    for line in reader.lines() {
+        let line = line?;
+        match Request::parse_line(&line) {
+            Ok(Some(request)) => {
+                if let Err(e) = handle(&request) {
+                    warn!("Failed to handle \"{}\": {}", request, e);
+                }
+            }
+            Ok(None) => (),
+            Err(e) => warn!("Skipping \"{}\": {}", line.trim(), e),
+        }
+    }
+    Ok(())
+}
+
+/// Handle the commands of stdin until it is closed
+pub fn serve_stdin<F>(handle: F) -> Result<(), Error>
+where
+    F: FnMut(&Request) -> Result<(), Error>,
+{
+    serve(io::stdin().lock(), handle)
+}
+
+/// Handle the commands written to the named pipe at `path`. The pipe is opened again
+/// whenever the last writer closed it, so this only returns on errors.
+pub fn serve_fifo<F>(path: &Path, mut handle: F) -> Result<(), Error>
+where
+    F: FnMut(&Request) -> Result<(), Error>,
+{
+    info!("Reading commands from {}", path.display());
+    loop {
+        // Opening blocks until a writer opens the pipe
+        let fifo = File::open(path)?;
+        serve(BufReader::new(fifo), &mut handle)?;
+    }
+}
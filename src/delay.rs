@@ -0,0 +1,230 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Strategies to wait between pin transitions
+
+use crate::{error::Error, Pin, Value};
+use std::{
+    fmt, hint,
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+pub use funksteckdose_core::Delay;
+
+/// Busy wait. Most accurate but occupies a CPU core for the whole transmission.
+#[derive(Clone, Debug, Default)]
+pub struct Spin;
+
+impl Delay for Spin {
+    fn delay(&self, micros: u32) {
+        if micros > 0 {
+            let now = Instant::now();
+            let micros = u128::from(micros);
+            while now.elapsed().as_micros() < micros {}
+        }
+    }
+}
+
+/// Busy wait with `spin_loop` hints that yields the thread while more than `threshold`
+/// is left. Only the last part of a delay is spun tightly which keeps neighbours on
+/// shared hosts e.g containers responsive.
+#[derive(Clone, Debug)]
+pub struct YieldingSpin {
+    threshold: Duration,
+}
+
+impl YieldingSpin {
+    /// Create a new instance that spins tightly for the last `threshold` of a delay
+    pub fn new(threshold: Duration) -> YieldingSpin {
+        YieldingSpin { threshold }
+    }
+}
+
+impl Default for YieldingSpin {
+    fn default() -> YieldingSpin {
+        YieldingSpin::new(Duration::from_micros(200))
+    }
+}
+
+impl Delay for YieldingSpin {
+    fn delay(&self, micros: u32) {
+        let duration = Duration::from_micros(micros.into());
+        let now = Instant::now();
+        loop {
+            let elapsed = now.elapsed();
+            if elapsed >= duration {
+                break;
+            }
+            if duration - elapsed > self.threshold {
+                thread::yield_now();
+            } else {
+                hint::spin_loop();
+            }
+        }
+    }
+}
+
+/// Sleep. Uses almost no CPU but every delay overshoots by the wake-up latency of the
+/// scheduler which is too much for short pulses on most systems.
+#[derive(Clone, Debug, Default)]
+pub struct Sleep;
+
+impl Delay for Sleep {
+    fn delay(&self, micros: u32) {
+        thread::sleep(Duration::from_micros(micros.into()));
+    }
+}
+
+/// Sleep for all but the last `spin` of a delay and busy wait for the rest. Long gaps
+/// like the sync of a frame hardly use CPU while short pulses are as accurate as with
+/// [`Spin`].
+#[derive(Clone, Debug)]
+pub struct Hybrid {
+    spin: Duration,
+}
+
+impl Hybrid {
+    /// Create a new instance that spins for the last `spin` of a delay. `spin` should
+    /// exceed the wake-up latency of the scheduler.
+    pub fn new(spin: Duration) -> Hybrid {
+        Hybrid { spin }
+    }
+}
+
+impl Default for Hybrid {
+    fn default() -> Hybrid {
+        Hybrid::new(Duration::from_micros(1000))
+    }
+}
+
+impl Delay for Hybrid {
+    fn delay(&self, micros: u32) {
+        let duration = Duration::from_micros(micros.into());
+        let now = Instant::now();
+        if duration > self.spin {
+            thread::sleep(duration - self.spin);
+        }
+        while now.elapsed() < duration {
+            hint::spin_loop();
+        }
+    }
+}
+
+/// Name of a built-in delay strategy e.g from the command line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// [`Spin`]
+    Spin,
+    /// [`YieldingSpin`]
+    YieldingSpin,
+    /// [`Sleep`]
+    Sleep,
+    /// [`Hybrid`]
+    #[default]
+    Hybrid,
+}
+
+impl Strategy {
+    /// The strategy with its default settings
+    pub fn delay(self) -> Box<dyn Delay + Send + Sync> {
+        match self {
+            Strategy::Spin => Box::new(Spin),
+            Strategy::YieldingSpin => Box::new(YieldingSpin::default()),
+            Strategy::Sleep => Box::new(Sleep),
+            Strategy::Hybrid => Box::new(Hybrid::default()),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::Spin => write!(f, "spin"),
+            Strategy::YieldingSpin => write!(f, "yielding-spin"),
+            Strategy::Sleep => write!(f, "sleep"),
+            Strategy::Hybrid => write!(f, "hybrid"),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spin" => Ok(Strategy::Spin),
+            "yielding-spin" => Ok(Strategy::YieldingSpin),
+            "sleep" => Ok(Strategy::Sleep),
+            "hybrid" => Ok(Strategy::Hybrid),
+            _ => Err(Error::InvalidConfig(format!(
+                "unknown delay strategy \"{}\". Try spin, yielding-spin, sleep, hybrid",
+                s
+            ))),
+        }
+    }
+}
+
+/// Measured timing overhead of a pin and delay combination
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Calibration {
+    /// Average duration of a `Pin::set` call
+    pub set_overhead: Duration,
+    /// Average time a delay takes longer than requested
+    pub delay_overshoot: Duration,
+}
+
+impl Calibration {
+    /// Measure the overhead of `pin` and `delay` with `samples` iterations. The pin is
+    /// driven low during the measurement.
+    pub fn measure<T: Pin, D: Delay>(
+        pin: &T,
+        delay: &D,
+        samples: u32,
+    ) -> Result<Calibration, Error> {
+        let samples = samples.max(1);
+
+        let now = Instant::now();
+        for _ in 0..samples {
+            pin.set(&Value::Low)?;
+        }
+        let set_overhead = now.elapsed() / samples;
+
+        const REQUESTED: u32 = 10;
+        let now = Instant::now();
+        for _ in 0..samples {
+            delay.delay(REQUESTED);
+        }
+        let delay_overshoot = (now.elapsed() / samples)
+            .checked_sub(Duration::from_micros(REQUESTED.into()))
+            .unwrap_or_default();
+
+        Ok(Calibration {
+            set_overhead,
+            delay_overshoot,
+        })
+    }
+
+    /// Microseconds to subtract from every delay that follows a pin transition
+    pub fn compensation(&self) -> u32 {
+        (self.set_overhead + self.delay_overshoot).as_micros() as u32
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Assumed on time and estimated energy use per socket.
+//!
+//! The sockets do not report their power draw. The on time is accumulated from the commanded
+//! or observed states and multiplied with the configured wattage of a socket. The estimate
+//! is only as good as the assumption that the sockets follow the commands.
+
+use crate::{
+    error::Error,
+    state::StateChange,
+    store::{Store, Table},
+    Device, State,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone, Debug, Default)]
+struct Usage {
+    /// Sum of the completed on periods
+    on_time: Duration,
+    /// Start of the current on period
+    on_since: Option<SystemTime>,
+    watts: Option<f64>,
+}
+
+impl Usage {
+    fn on_time(&self, now: SystemTime) -> Duration {
+        let current = self
+            .on_since
+            .and_then(|since| now.duration_since(since).ok())
+            .unwrap_or_default();
+        self.on_time + current
+    }
+}
+
+/// On time and energy of a socket
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reading {
+    pub group: String,
+    pub device: Device,
+    /// Total on time including the current on period
+    pub on_time: Duration,
+    /// Configured power draw
+    pub watts: Option<f64>,
+}
+
+impl Reading {
+    /// Estimated energy in Wh if the wattage of the socket is known
+    pub fn energy_wh(&self) -> Option<f64> {
+        self.watts
+            .map(|watts| watts * self.on_time.as_secs_f64() / 3600.0)
+    }
+}
+
+/// On time accounting for a set of sockets
+#[derive(Clone, Debug, Default)]
+pub struct Meter {
+    sockets: HashMap<(String, Device), Usage>,
+}
+
+impl Meter {
+    /// Create a new meter without sockets
+    pub fn new() -> Meter {
+        Meter::default()
+    }
+
+    /// Register a socket that has not been on yet
+    pub fn insert(&mut self, group: &str, device: &Device) {
+        self.usage(group, device);
+    }
+
+    /// Set the power draw of a socket when it is on
+    pub fn set_watts(&mut self, group: &str, device: &Device, watts: f64) {
+        self.usage(group, device).watts = Some(watts);
+    }
+
+    fn usage(&mut self, group: &str, device: &Device) -> &mut Usage {
+        self.sockets
+            .entry((group.into(), device.clone()))
+            .or_default()
+    }
+
+    /// Account a state of a socket at `time`. Repeated states do not restart the current
+    /// on period.
+    pub fn record(&mut self, group: &str, device: &Device, state: &State, time: SystemTime) {
+        let usage = self.usage(group, device);
+        match (state, usage.on_since) {
+            (State::On, None) => usage.on_since = Some(time),
+            (State::Off, Some(since)) => {
+                usage.on_time += time.duration_since(since).unwrap_or_default();
+                usage.on_since = None;
+            }
+            _ => (),
+        }
+    }
+
+    /// Account a state change that happened now
+    pub fn observe(&mut self, change: &StateChange) {
+        self.record(
+            &change.group,
+            &change.device,
+            &change.state,
+            SystemTime::now(),
+        );
+    }
+
+    /// Reading of a socket at `now`
+    pub fn reading(&self, group: &str, device: &Device, now: SystemTime) -> Option<Reading> {
+        self.sockets
+            .get(&(group.into(), device.clone()))
+            .map(|usage| Reading {
+                group: group.into(),
+                device: device.clone(),
+                on_time: usage.on_time(now),
+                watts: usage.watts,
+            })
+    }
+
+    /// Readings of all sockets at `now` ordered by group and device
+    pub fn readings(&self, now: SystemTime) -> Vec<Reading> {
+        let mut readings = self
+            .sockets
+            .iter()
+            .map(|((group, device), usage)| Reading {
+                group: group.clone(),
+                device: device.clone(),
+                on_time: usage.on_time(now),
+                watts: usage.watts,
+            })
+            .collect::<Vec<_>>();
+        readings.sort_by(|a, b| {
+            (&a.group, u8::from(a.device.clone())).cmp(&(&b.group, u8::from(b.device.clone())))
+        });
+        readings
+    }
+
+    /// Persist the on times in the counters of `store`
+    pub fn save(&self, store: &mut dyn Store) -> Result<(), Error> {
+        for ((group, device), usage) in &self.sockets {
+            let key = format!("{} {}", group, device);
+            store.put(
+                Table::Counters,
+                &format!("on_time_ms {}", key),
+                &(usage.on_time.as_millis() as u64).to_string(),
+            )?;
+            let since = usage.on_since.map(millis).unwrap_or(0);
+            store.put(
+                Table::Counters,
+                &format!("on_since_ms {}", key),
+                &since.to_string(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Restore the on times from the counters of `store`
+    pub fn load(&mut self, store: &dyn Store) -> Result<(), Error> {
+        for (key, value) in store.entries(Table::Counters)? {
+            let mut fields = key.split_whitespace();
+            let (name, group, device) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(name), Some(group), Some(device)) => (name, group, device.parse()?),
+                _ => continue,
+            };
+            let value = value
+                .parse::<u64>()
+                .map_err(|_| Error::Store(format!("invalid counter {} \"{}\"", key, value)))?;
+            match name {
+                "on_time_ms" => self.usage(group, &device).on_time = Duration::from_millis(value),
+                "on_since_ms" if value > 0 => {
+                    self.usage(group, &device).on_since =
+                        Some(UNIX_EPOCH + Duration::from_millis(value))
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Prometheus text format of the readings at the time of formatting
+impl fmt::Display for Meter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let readings = self.readings(SystemTime::now());
+        writeln!(f, "# TYPE funksteckdose_on_seconds_total counter")?;
+        for r in &readings {
+            writeln!(
+                f,
+                "funksteckdose_on_seconds_total{{group=\"{}\",device=\"{}\"}} {}",
+                r.group,
+                r.device,
+                r.on_time.as_secs()
+            )?;
+        }
+        writeln!(f, "# TYPE funksteckdose_energy_wh_total counter")?;
+        for r in &readings {
+            if let Some(energy) = r.energy_wh() {
+                writeln!(
+                    f,
+                    "funksteckdose_energy_wh_total{{group=\"{}\",device=\"{}\"}} {:.3}",
+                    r.group, r.device, energy
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Errors of the transmitter, its services and the command line. Errors of encodings,
+//! protocols and pins are the ones of `funksteckdose_core` wrapped in `Error::Core`.
+
+pub use funksteckdose_core::error::{Error as CoreError, Source};
+use std::{fmt, io, time::Duration};
+
+#[derive(Debug)]
+pub enum Error {
+    /// Error of an encoding, protocol or pin
+    Core(CoreError),
+    Cancelled,
+    Expired(String),
+    InvalidConfig(String),
+    InvalidHistoryEntry(String),
+    InvalidHistoryIndex(usize),
+    InvalidPeriod(String),
+    Timeout(Duration),
+    AirtimeExceeded(Duration, Duration),
+    InvalidTopicTemplate(String),
+    InvalidPayload(String),
+    InvalidPayloadFormat(String),
+    InvalidLogFormat(String),
+    InvalidLirc(String),
+    Plugin(String),
+    InvalidQuietHours(String),
+    Quiet(String),
+    InvalidCapture(String),
+    CaptureMismatch(String),
+    CircuitOpen(String),
+    Store(String),
+    Unconfirmed(String),
+    RateLimited(Duration),
+    InvalidSignal(String),
+    Mqtt(String),
+    InvalidSnapshot(String),
+    QueueFull(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Core(e) => e.fmt(f),
+            Error::Cancelled => write!(f, "cancelled"),
+            Error::Expired(e) => write!(f, "command expired: {}", e),
+            Error::InvalidConfig(e) => write!(f, "invalid config: {}", e),
+            Error::InvalidHistoryEntry(e) => write!(f, "invalid history entry: {}", e),
+            Error::InvalidHistoryIndex(e) => write!(f, "no history entry with index {}", e),
+            Error::InvalidPeriod(e) => write!(f, "invalid period: {}", e),
+            Error::Timeout(e) => write!(f, "transmission timed out after {:?}", e),
+            Error::AirtimeExceeded(airtime, limit) => write!(
+                f,
+                "airtime of {:?} exceeds the limit of {:?}. Reduce the repeats",
+                airtime, limit
+            ),
+            Error::InvalidTopicTemplate(e) => write!(f, "invalid topic template: {}", e),
+            Error::InvalidPayload(e) => write!(f, "invalid payload: {}", e),
+            Error::InvalidPayloadFormat(e) => write!(
+                f,
+                "invalid payload format: {}. Try on-off, numeric, json",
+                e
+            ),
+            Error::InvalidLogFormat(e) => write!(f, "invalid log format: {}. Try text, json", e),
+            Error::InvalidLirc(e) => write!(f, "invalid lirc config: {}", e),
+            Error::Plugin(e) => write!(f, "encoder plugin failed: {}", e),
+            Error::InvalidQuietHours(e) => {
+                write!(f, "invalid quiet hours: {}. Try 22:00-07:00/2", e)
+            }
+            Error::Quiet(e) => write!(f, "suppressed during quiet hours: {}", e),
+            Error::InvalidCapture(e) => write!(f, "invalid capture: {}", e),
+            Error::CaptureMismatch(e) => write!(f, "capture does not match its encoding: {}", e),
+            Error::CircuitOpen(e) => write!(f, "circuit open for socket {}", e),
+            Error::Store(e) => write!(f, "store: {}", e),
+            Error::Unconfirmed(e) => write!(f, "not confirmed: {}", e),
+            Error::RateLimited(e) => write!(f, "rate limited. Retry in {:?}", e),
+            Error::InvalidSignal(e) => write!(f, "invalid test signal: {}", e),
+            Error::Mqtt(e) => write!(f, "mqtt: {}", e),
+            Error::InvalidSnapshot(e) => write!(f, "invalid state snapshot: {}", e),
+            Error::QueueFull(e) => write!(f, "queue full with {} commands", e),
+        }
+    }
+}
+
+/// Copies keep the variant and the message, see the copies of `CoreError`
+impl Clone for Error {
+    fn clone(&self) -> Error {
+        match self {
+            Error::Core(e) => Error::Core(e.clone()),
+            Error::Cancelled => Error::Cancelled,
+            Error::Expired(e) => Error::Expired(e.clone()),
+            Error::InvalidConfig(e) => Error::InvalidConfig(e.clone()),
+            Error::InvalidHistoryEntry(e) => Error::InvalidHistoryEntry(e.clone()),
+            Error::InvalidHistoryIndex(e) => Error::InvalidHistoryIndex(*e),
+            Error::InvalidPeriod(e) => Error::InvalidPeriod(e.clone()),
+            Error::Timeout(e) => Error::Timeout(*e),
+            Error::AirtimeExceeded(a, b) => Error::AirtimeExceeded(*a, *b),
+            Error::InvalidTopicTemplate(e) => Error::InvalidTopicTemplate(e.clone()),
+            Error::InvalidPayload(e) => Error::InvalidPayload(e.clone()),
+            Error::InvalidPayloadFormat(e) => Error::InvalidPayloadFormat(e.clone()),
+            Error::InvalidLogFormat(e) => Error::InvalidLogFormat(e.clone()),
+            Error::InvalidLirc(e) => Error::InvalidLirc(e.clone()),
+            Error::Plugin(e) => Error::Plugin(e.clone()),
+            Error::InvalidQuietHours(e) => Error::InvalidQuietHours(e.clone()),
+            Error::Quiet(e) => Error::Quiet(e.clone()),
+            Error::InvalidCapture(e) => Error::InvalidCapture(e.clone()),
+            Error::CaptureMismatch(e) => Error::CaptureMismatch(e.clone()),
+            Error::CircuitOpen(e) => Error::CircuitOpen(e.clone()),
+            Error::Store(e) => Error::Store(e.clone()),
+            Error::Unconfirmed(e) => Error::Unconfirmed(e.clone()),
+            Error::RateLimited(e) => Error::RateLimited(*e),
+            Error::InvalidSignal(e) => Error::InvalidSignal(e.clone()),
+            Error::Mqtt(e) => Error::Mqtt(e.clone()),
+            Error::InvalidSnapshot(e) => Error::InvalidSnapshot(e.clone()),
+            Error::QueueFull(e) => Error::QueueFull(*e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            // The message is the one of the core error
+            Error::Core(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<CoreError> for Error {
+    fn from(e: CoreError) -> Error {
+        Error::Core(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Core(CoreError::Io(e))
+    }
+}
+
+/// Coarse classification of errors
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// The pin or another backend failed
+    Backend,
+    /// Bad configuration or arguments
+    InvalidInput,
+    /// A transmission violated its timing
+    Timing,
+    /// No room for more commands
+    QueueFull,
+    /// Sending would exceed the allowed airtime
+    DutyCycle,
+    /// Cancelled or expired before sending
+    Aborted,
+}
+
+impl Category {
+    /// All categories
+    pub const ALL: [Category; 6] = [
+        Category::Backend,
+        Category::InvalidInput,
+        Category::Timing,
+        Category::QueueFull,
+        Category::DutyCycle,
+        Category::Aborted,
+    ];
+
+    /// Stable name e.g for metrics labels
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Backend => "backend",
+            Category::InvalidInput => "invalid_input",
+            Category::Timing => "timing",
+            Category::QueueFull => "queue_full",
+            Category::DutyCycle => "duty_cycle",
+            Category::Aborted => "aborted",
+        }
+    }
+}
+
+impl Error {
+    /// Wrap the native error of a backend, see `CoreError::gpio`
+    pub fn gpio<E: std::error::Error + Send + Sync + 'static>(e: E) -> Error {
+        Error::Core(CoreError::gpio(e))
+    }
+
+    /// Stable error code that does not change with the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Core(e) => e.code(),
+            Error::Cancelled => "cancelled",
+            Error::Expired(_) => "expired",
+            Error::InvalidConfig(_) => "invalid_config",
+            Error::InvalidHistoryEntry(_) => "invalid_history_entry",
+            Error::InvalidHistoryIndex(_) => "invalid_history_index",
+            Error::InvalidPeriod(_) => "invalid_period",
+            Error::Timeout(_) => "timeout",
+            Error::AirtimeExceeded(..) => "airtime_exceeded",
+            Error::InvalidTopicTemplate(_) => "invalid_topic_template",
+            Error::InvalidPayload(_) => "invalid_payload",
+            Error::InvalidPayloadFormat(_) => "invalid_payload_format",
+            Error::InvalidLogFormat(_) => "invalid_log_format",
+            Error::InvalidLirc(_) => "invalid_lirc",
+            Error::Plugin(_) => "plugin",
+            Error::InvalidQuietHours(_) => "invalid_quiet_hours",
+            Error::Quiet(_) => "quiet",
+            Error::InvalidCapture(_) => "invalid_capture",
+            Error::CaptureMismatch(_) => "capture_mismatch",
+            Error::CircuitOpen(_) => "circuit_open",
+            Error::Store(_) => "store",
+            Error::Unconfirmed(_) => "unconfirmed",
+            Error::RateLimited(_) => "rate_limited",
+            Error::InvalidSignal(_) => "invalid_signal",
+            Error::Mqtt(_) => "mqtt",
+            Error::InvalidSnapshot(_) => "invalid_snapshot",
+            Error::QueueFull(_) => "queue_full",
+        }
+    }
+
+    /// Category of the error
+    /// ```
+    /// use funksteckdose::error::{Category, CoreError, Error};
+    /// use std::time::Duration;
+    /// let error = Error::RateLimited(Duration::from_secs(1));
+    /// assert_eq!(error.category(), Category::DutyCycle);
+    /// let error = Error::from(CoreError::Hal("pin busy".into()));
+    /// assert_eq!(error.category(), Category::Backend);
+    /// ```
+    pub fn category(&self) -> Category {
+        match self {
+            Error::Core(e) if e.is_backend() => Category::Backend,
+            Error::Plugin(_) | Error::CircuitOpen(_) | Error::Store(_) | Error::Mqtt(_) => {
+                Category::Backend
+            }
+            Error::Timeout(_) => Category::Timing,
+            Error::QueueFull(_) => Category::QueueFull,
+            Error::AirtimeExceeded(..) | Error::RateLimited(_) => Category::DutyCycle,
+            Error::Cancelled | Error::Expired(_) | Error::Quiet(_) => Category::Aborted,
+            _ => Category::InvalidInput,
+        }
+    }
+}
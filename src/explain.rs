@@ -0,0 +1,144 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{
+    error::Error, tri_state_code, Device, Encoding, HighLow, Protocol, ProtocolValues, State,
+};
+use std::{fmt, time::Duration};
+
+/// Everything that happens between a command and the pulses on the pin
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    /// Tri-state code word e.g "0FFF0FFFFF0F"
+    pub code_word: String,
+    /// Binary code
+    pub code: u64,
+    /// Length of `code` in bits
+    pub length: usize,
+    /// Pulse length in µs
+    pub pulse_length: u64,
+    /// Pulses of a zero bit
+    pub zero: HighLow,
+    /// Pulses of a one bit
+    pub one: HighLow,
+    /// Pulses of the sync
+    pub sync: HighLow,
+    /// True if the signal is inverted
+    pub inverted: bool,
+    /// Number of frames
+    pub repeat_transmit: usize,
+    /// Duration of one frame including the sync
+    pub frame: Duration,
+}
+
+impl Explanation {
+    /// Explain a command encoded with `E` and sent with `P`
+    pub fn new<E: Encoding, P: Protocol>(
+        group: &str,
+        device: &Device,
+        state: &State,
+        repeat_transmit: usize,
+    ) -> Result<Explanation, Error> {
+        Explanation::with_values::<E>(&P::values(), group, device, state, repeat_transmit)
+    }
+
+    /// Explain a command encoded with `E` and sent with the timings of `values` e.g of a
+    /// protocol selected at runtime
+    /// ```
+    /// use funksteckdose::{catalog, explain::Explanation, Device, EncodingB, State};
+    /// let values = catalog::protocol("2").unwrap();
+    /// let explanation =
+    ///     Explanation::with_values::<EncodingB>(&values, "3", &Device::B, &State::On, 10).unwrap();
+    /// assert_eq!(explanation.pulse_length, 650);
+    /// ```
+    pub fn with_values<E: Encoding>(
+        values: &ProtocolValues,
+        group: &str,
+        device: &Device,
+        state: &State,
+        repeat_transmit: usize,
+    ) -> Result<Explanation, Error> {
+        let code_word = E::encode(group, device, state)?;
+        Explanation::from_values(values, &code_word, repeat_transmit)
+    }
+
+    /// Explain the transmission of a tri-state code word with `P`. Fails on invalid code
+    /// words, see `tri_state_code`.
+    pub fn from_code_word<P: Protocol>(
+        code_word: &[u8],
+        repeat_transmit: usize,
+    ) -> Result<Explanation, Error> {
+        Explanation::from_values(&P::values(), code_word, repeat_transmit)
+    }
+
+    /// Explain the transmission of a tri-state code word with the timings of `values`
+    pub fn from_values(
+        values: &ProtocolValues,
+        code_word: &[u8],
+        repeat_transmit: usize,
+    ) -> Result<Explanation, Error> {
+        let code = tri_state_code(code_word)?;
+        let length = code_word.len() * 2;
+        Ok(Explanation {
+            code_word: String::from_utf8_lossy(code_word).into(),
+            code,
+            length,
+            pulse_length: values.pulse_length(),
+            zero: values.zero().clone(),
+            one: values.one().clone(),
+            sync: values.sync_factor().clone(),
+            inverted: values.inverted_signal(),
+            repeat_transmit,
+            frame: Duration::from_micros(values.frame_micros(code, length)),
+        })
+    }
+
+    /// Total duration of all frames
+    pub fn airtime(&self) -> Duration {
+        self.frame * self.repeat_transmit as u32
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pulses = |s: &HighLow| {
+            format!(
+                "{:>2} x high {:>6}µs, {:>2} x low {:>6}µs",
+                s.high,
+                s.high * self.pulse_length,
+                s.low,
+                s.low * self.pulse_length
+            )
+        };
+        writeln!(f, "tri-state:  {}", self.code_word)?;
+        writeln!(f, "binary:     {:0width$b}", self.code, width = self.length)?;
+        writeln!(f, "decimal:    {}", self.code)?;
+        writeln!(f, "hex:        {:#X}", self.code)?;
+        writeln!(f, "length:     {} bits", self.length)?;
+        writeln!(f, "pulse:      {}µs", self.pulse_length)?;
+        writeln!(f, "zero:       {}", pulses(&self.zero))?;
+        writeln!(f, "one:        {}", pulses(&self.one))?;
+        writeln!(f, "sync:       {}", pulses(&self.sync))?;
+        writeln!(f, "inverted:   {}", self.inverted)?;
+        writeln!(f, "frame:      {:?}", self.frame)?;
+        writeln!(f, "repeats:    {}", self.repeat_transmit)?;
+        write!(f, "airtime:    {:?}", self.airtime())
+    }
+}
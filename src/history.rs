@@ -0,0 +1,264 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A bounded list of the last transmitted commands. The history can be saved to and
+//! loaded from a file with one entry per line:
+//! `<unix time in ms> <group> <device> <state> [<encoding> <protocol>]`.
+
+use crate::{delay::Delay, error::Error, Device, Encoding, Funksteckdose, Pin, Protocol, State};
+use std::{
+    collections::VecDeque,
+    fmt, fs,
+    io::{self, BufRead, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A transmitted command
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub time: SystemTime,
+    pub group: String,
+    pub device: Device,
+    pub state: State,
+    /// Encoding the command was sent with. `None` for entries recorded without.
+    pub encoding: Option<String>,
+    /// Name of the protocol the command was sent with
+    pub protocol: Option<String>,
+}
+
+impl Entry {
+    /// Entry for a command sent now
+    pub fn new(group: &str, device: &Device, state: &State) -> Entry {
+        Entry {
+            time: SystemTime::now(),
+            group: group.into(),
+            device: device.clone(),
+            state: state.clone(),
+            encoding: None,
+            protocol: None,
+        }
+    }
+
+    /// Record the encoding and protocol the command was sent with e.g to replay it the same
+    /// way
+    /// ```
+    /// use funksteckdose::{history::Entry, Device, State};
+    /// let entry = Entry::new("10001", &Device::A, &State::On).sent_with("B", "2");
+    /// assert!(entry.to_string().ends_with(" 10001 A on B 2"));
+    /// let entry: Entry = "1700000000000 10001 A on B 2".parse().unwrap();
+    /// assert_eq!(entry.protocol.as_deref(), Some("2"));
+    /// ```
+    pub fn sent_with(mut self, encoding: &str, protocol: &str) -> Entry {
+        self.encoding = Some(encoding.into());
+        self.protocol = Some(protocol.into());
+        self
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let millis = self
+            .time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        write!(
+            f,
+            "{} {} {} {}",
+            millis, self.group, self.device, self.state
+        )?;
+        if let (Some(encoding), Some(protocol)) = (&self.encoding, &self.protocol) {
+            write!(f, " {} {}", encoding, protocol)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Entry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidHistoryEntry(s.into());
+        let mut fields = s.split_whitespace();
+        let millis = fields
+            .next()
+            .and_then(|m| m.parse::<u64>().ok())
+            .ok_or_else(invalid)?;
+        let group = fields.next().ok_or_else(invalid)?.into();
+        let device = fields.next().ok_or_else(invalid)?.parse()?;
+        let state = fields.next().ok_or_else(invalid)?.parse()?;
+        let (encoding, protocol) = match (fields.next(), fields.next()) {
+            (Some(encoding), Some(protocol)) => (Some(encoding.into()), Some(protocol.into())),
+            (None, _) => (None, None),
+            _ => return Err(invalid()),
+        };
+        if fields.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Entry {
+            time: UNIX_EPOCH + Duration::from_millis(millis),
+            group,
+            device,
+            state,
+            encoding,
+            protocol,
+        })
+    }
+}
+
+/// Commands sent to a socket within a period
+#[derive(Clone, Debug, PartialEq)]
+pub struct Activity {
+    pub group: String,
+    pub device: Device,
+    /// Number of commands
+    pub switches: u64,
+    /// Estimated time the socket was on. Periods before the first command are not included.
+    pub on_time: Duration,
+    /// Time of the most recent command
+    pub last: SystemTime,
+    /// State of the most recent command
+    pub state: State,
+}
+
+/// Parse a period e.g "500us", "20ms", "90s", "30m", "12h" or "7d"
+pub fn parse_period(s: &str) -> Result<Duration, Error> {
+    let invalid = || Error::InvalidPeriod(s.into());
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (value, unit) = s.split_at(split);
+    let value = value.parse::<u64>().map_err(|_| invalid())?;
+    let (micros, seconds) = match unit {
+        "us" | "µs" => (1, 0),
+        "ms" => (1000, 0),
+        "s" => (0, 1),
+        "m" => (0, 60),
+        "h" => (0, 60 * 60),
+        "d" => (0, 24 * 60 * 60),
+        "w" => (0, 7 * 24 * 60 * 60),
+        _ => return Err(invalid()),
+    };
+    if micros > 0 {
+        value.checked_mul(micros).map(Duration::from_micros)
+    } else {
+        value.checked_mul(seconds).map(Duration::from_secs)
+    }
+    .ok_or_else(invalid)
+}
+
+/// The last `capacity` transmitted commands
+#[derive(Clone, Debug)]
+pub struct History {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+}
+
+impl History {
+    /// Create a new empty history keeping at most `capacity` entries
+    pub fn new(capacity: usize) -> History {
+        History {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Load a history from `path`. A missing file results in an empty history.
+    pub fn load<P: AsRef<Path>>(path: P, capacity: usize) -> Result<History, Error> {
+        let mut history = History::new(capacity);
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(history),
+            Err(e) => return Err(e.into()),
+        };
+        for line in io::BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                history.record(line.parse()?);
+            }
+        }
+        Ok(history)
+    }
+
+    /// Save the history to `path`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        for entry in self.entries.iter().rev() {
+            writeln!(file, "{}", entry)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Record a transmitted command
+    pub fn push(&mut self, group: &str, device: &Device, state: &State) {
+        self.record(Entry::new(group, device, state));
+    }
+
+    /// Record the entry of a transmitted command e.g with the encoding and protocol set
+    pub fn record(&mut self, entry: Entry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_back();
+        }
+        self.entries.push_front(entry);
+    }
+
+    /// Entry at `index`. Index 0 is the most recent command.
+    pub fn get(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(index)
+    }
+
+    /// Iterate the entries starting with the most recent
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// True if no command is recorded
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Send the entry at `index` again and record it as the most recent command. The entry
+    /// is sent with `funksteckdose`, which should use the encoding and protocol of the entry.
+    pub fn replay<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+        &mut self,
+        index: usize,
+        funksteckdose: &Funksteckdose<T, E, P, D>,
+    ) -> Result<(), Error> {
+        let entry = self
+            .get(index)
+            .cloned()
+            .ok_or(Error::InvalidHistoryIndex(index))?;
+        funksteckdose.send(&entry.group, &entry.device, &entry.state)?;
+        self.record(Entry {
+            time: SystemTime::now(),
+            ..entry
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! External commands run when a code is received.
+//!
+//! A hook turns any remote button into a trigger for scripts e.g shutting down the host.
+//! The command runs with `sh -c` and finds the received code in the environment:
+//!
+//! | Variable | Value |
+//! |----------|-------|
+//! | `FUNKSTECKDOSE_CODE` | Code as decimal number |
+//! | `FUNKSTECKDOSE_LENGTH` | Number of bits |
+//! | `FUNKSTECKDOSE_CODE_WORD` | Tri-state code word if the code is one |
+//! | `FUNKSTECKDOSE_PROTOCOL` | Name of the protocol |
+//! | `FUNKSTECKDOSE_PULSE_LENGTH` | Pulse length in µs |
+//!
+//! Remotes repeat their frames, so a hook does not run again within its hold-off.
+
+use crate::{error::Error, receiver::Received, Code};
+use log::{info, warn};
+use std::{
+    process::Command,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A command run when `code` is received
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hook {
+    pub code: Code,
+    /// Shell command
+    pub command: String,
+    /// Time after running during which the code is ignored
+    pub holdoff: Duration,
+}
+
+impl Hook {
+    /// Create a hook with a hold-off of one second
+    pub fn new(code: Code, command: &str) -> Hook {
+        Hook {
+            code,
+            command: command.into(),
+            holdoff: Duration::from_secs(1),
+        }
+    }
+
+    /// True if `received` is the code of the hook
+    pub fn matches(&self, received: &Received) -> bool {
+        received.code == self.code.code() && received.length == self.code.length()
+    }
+
+    /// Start the command. Its exit status is logged once it terminates.
+    pub fn run(&self, received: &Received) -> Result<(), Error> {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(&self.command)
+            .env("FUNKSTECKDOSE_CODE", received.code.to_string())
+            .env("FUNKSTECKDOSE_LENGTH", received.length.to_string())
+            .env("FUNKSTECKDOSE_PROTOCOL", received.protocol)
+            .env(
+                "FUNKSTECKDOSE_PULSE_LENGTH",
+                received.pulse_length.to_string(),
+            );
+        if let Some(code_word) = received.code_word() {
+            command.env(
+                "FUNKSTECKDOSE_CODE_WORD",
+                String::from_utf8_lossy(&code_word).as_ref(),
+            );
+        }
+        info!("Running \"{}\" for code {}", self.command, self.code);
+        let mut child = command.spawn()?;
+        let name = self.command.clone();
+        thread::spawn(move || match child.wait() {
+            Ok(status) if status.success() => (),
+            Ok(status) => warn!("\"{}\" failed: {}", name, status),
+            Err(e) => warn!("Failed to wait for \"{}\": {}", name, e),
+        });
+        Ok(())
+    }
+}
+
+/// A set of hooks and the time each ran last
+#[derive(Clone, Debug, Default)]
+pub struct Hooks {
+    hooks: Vec<(Hook, Option<Instant>)>,
+}
+
+impl Hooks {
+    /// Create a set of hooks
+    pub fn new(hooks: Vec<Hook>) -> Hooks {
+        Hooks {
+            hooks: hooks.into_iter().map(|hook| (hook, None)).collect(),
+        }
+    }
+
+    /// True if there are no hooks
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Run the hooks matching `received` that are not in their hold-off. Returns the
+    /// number of started commands.
+    pub fn handle(&mut self, received: &Received) -> Result<usize, Error> {
+        let now = Instant::now();
+        let mut started = 0;
+        for (hook, last) in self.hooks.iter_mut() {
+            if !hook.matches(received) {
+                continue;
+            }
+            if last.is_some_and(|last| now.duration_since(last) < hook.holdoff) {
+                continue;
+            }
+            *last = Some(now);
+            hook.run(received)?;
+            started += 1;
+        }
+        Ok(started)
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Small HTTP server to switch sockets e.g with the RESTful switch of Home Assistant
+//!
+//! Sockets are addressed by group and device or by the name of a configured socket:
+//!
+//! * `POST /switch/{group}/{device}` or `POST /switch/{name}` with `{"state":"on"}` or
+//!   just `on` as body sends the state
+//! * `GET` on the same paths returns the last sent state e.g `{"state":"on"}`, `null` if
+//!   nothing was sent yet
+//! * `GET /history` returns the sent commands, the most recent first, if a history is kept
+//!   e.g `[{"time":1700000000000,"group":"10001","device":"A","state":"on","encoding":"A",
+//!   "protocol":"1"}]`
+//!
+//! Bad requests are answered with status 400 and `{"error":"..."}`, failures to send with
+//! status 500. With credentials set, requests need basic authentication and are answered
+//! with status 401 otherwise. Basic authentication is sent in plain text, so use it in a
+//! trusted network only.
+//!
+//! ```
+//! use funksteckdose::{daemon::Target, http, State};
+//!
+//! let (status, body) = http::dispatch("POST", "/switch/10001/A", "{\"state\":\"on\"}", |target, state| {
+//!     assert_eq!(target, &Target::Address("10001".parse()?, "A".parse()?));
+//!     Ok(state.cloned())
+//! });
+//! assert_eq!((status, body.as_str()), (200, "{\"state\":\"on\"}"));
+//! let (status, _) = http::dispatch("POST", "/switch/10001/A", "dim", |_, state| Ok(state.cloned()));
+//! assert_eq!(status, 400);
+//! # Ok::<(), funksteckdose::error::Error>(())
+//! ```
+
+use crate::{
+    daemon::Target,
+    error::{Category, Error},
+    history::Entry,
+    State,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{debug, info, warn};
+use serde_json::{json, Value};
+use std::{io, time::UNIX_EPOCH};
+use tiny_http::{Header, Response};
+
+/// Prefix of the switch paths
+const PREFIX: &str = "/switch/";
+
+/// Path of the history of sent commands
+const HISTORY: &str = "/history";
+
+/// HTTP server switching sockets
+pub struct Server {
+    server: tiny_http::Server,
+    /// Expected value of the Authorization header
+    authorization: Option<String>,
+}
+
+impl Server {
+    /// Listen on `address` e.g "0.0.0.0:8080"
+    pub fn bind(address: &str) -> Result<Server, Error> {
+        let server = tiny_http::Server::http(address).map_err(io::Error::other)?;
+        info!("Listening on {}", address);
+        Ok(Server {
+            server,
+            authorization: None,
+        })
+    }
+
+    /// Require basic authentication with `username` and `password`
+    pub fn with_credentials(mut self, username: &str, password: &str) -> Server {
+        let token = STANDARD.encode(format!("{}:{}", username, password));
+        self.authorization = Some(format!("Basic {}", token));
+        self
+    }
+
+    /// Answer requests until the process ends. `handle` sends the given state to the
+    /// target or only looks it up with `None` and returns the last sent state.
+    pub fn run<F>(&self, handle: F) -> Result<(), Error>
+    where
+        F: FnMut(&Target, Option<&State>) -> Result<Option<State>, Error>,
+    {
+        self.run_with_history(handle, || None)
+    }
+
+    /// Like `run` and answer `GET /history` with the entries returned by `history`, the
+    /// most recent first. `None` is answered with status 404 e.g if no history is kept.
+    pub fn run_with_history<F, H>(&self, mut handle: F, mut history: H) -> Result<(), Error>
+    where
+        F: FnMut(&Target, Option<&State>) -> Result<Option<State>, Error>,
+        H: FnMut() -> Option<Vec<Entry>>,
+    {
+        for mut request in self.server.incoming_requests() {
+            let mut body = String::new();
+            let method = request.method().as_str().to_string();
+            let is_history = request.url().split('?').next() == Some(HISTORY);
+            let (status, body) = match request.as_reader().read_to_string(&mut body) {
+                Ok(_) if !self.is_authorized(&request) => (401, error("unauthorized")),
+                Ok(_) if is_history => history_response(&method, history()),
+                Ok(_) => dispatch(&method, request.url(), &body, &mut handle),
+                Err(e) => (400, error(&e.to_string())),
+            };
+            debug!("{} {}: {}", request.method(), request.url(), status);
+            let mut response = Response::from_string(body)
+                .with_status_code(status)
+                .with_header(header("Content-Type", "application/json"));
+            if status == 401 {
+                response.add_header(header("WWW-Authenticate", "Basic realm=\"funksteckdose\""));
+            }
+            if let Err(e) = request.respond(response) {
+                warn!("Failed to respond: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn is_authorized(&self, request: &tiny_http::Request) -> bool {
+        match self.authorization {
+            Some(ref authorization) => request
+                .headers()
+                .iter()
+                .any(|h| h.field.equiv("Authorization") && h.value == authorization.as_str()),
+            None => true,
+        }
+    }
+}
+
+/// Status and JSON body of the response to a request
+pub fn dispatch<F>(method: &str, url: &str, body: &str, mut handle: F) -> (u16, String)
+where
+    F: FnMut(&Target, Option<&State>) -> Result<Option<State>, Error>,
+{
+    let path = url.split('?').next().unwrap_or_default();
+    let target = match path.strip_prefix(PREFIX) {
+        Some(target) if !target.is_empty() => target.trim_end_matches('/'),
+        _ => return (404, error("not found")),
+    };
+    let target = match target.parse::<Target>() {
+        Ok(target) => target,
+        Err(e) => return (400, error(&e.to_string())),
+    };
+    let state = match method {
+        "GET" => None,
+        "POST" | "PUT" => match parse_state(body) {
+            Ok(state) => Some(state),
+            Err(e) => return (400, error(&e.to_string())),
+        },
+        _ => return (405, error("method not allowed")),
+    };
+    match handle(&target, state.as_ref()) {
+        Ok(state) => {
+            let state = state.map(|state| state.to_string());
+            (200, json!({ "state": state }).to_string())
+        }
+        Err(e) => {
+            warn!("Failed to switch {}: {}", target, e);
+            let status = match e.category() {
+                Category::InvalidInput => 400,
+                _ => 500,
+            };
+            (status, error(&e.to_string()))
+        }
+    }
+}
+
+/// Status and JSON body of the response to a request for the history
+fn history_response(method: &str, entries: Option<Vec<Entry>>) -> (u16, String) {
+    let entries = match (method, entries) {
+        ("GET", Some(entries)) => entries,
+        ("GET", None) => return (404, error("no history")),
+        _ => return (405, error("method not allowed")),
+    };
+    let entries = entries
+        .iter()
+        .map(|entry| {
+            let time = entry.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+            json!({
+                "time": time.as_millis() as u64,
+                "group": entry.group,
+                "device": entry.device.to_string(),
+                "state": entry.state.to_string(),
+                "encoding": entry.encoding,
+                "protocol": entry.protocol,
+            })
+        })
+        .collect::<Vec<_>>();
+    (200, Value::Array(entries).to_string())
+}
+
+/// State of a body `{"state":"on"}` or `on`
+fn parse_state(body: &str) -> Result<State, Error> {
+    match serde_json::from_str::<Value>(body) {
+        Ok(Value::Object(object)) => match object.get("state") {
+            Some(Value::String(state)) => Ok(state.parse()?),
+            _ => Err(Error::InvalidPayload(body.into())),
+        },
+        _ => Ok(body.trim().trim_matches('"').parse()?),
+    }
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field, value).expect("invalid header")
+}
+
+fn error(message: &str) -> String {
+    json!({ "error": message }).to_string()
+}
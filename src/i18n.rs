@@ -0,0 +1,585 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//! Translations of the messages of the command line tool.
+//!
+//! Only the command line tool is translated. Errors of the library are English and stable
+//! so they can be matched by scripts and searched for. A translated message embeds the
+//! library error unchanged:
+//!
+//! ```
+//! use funksteckdose::i18n::{Action, Lang, Message};
+//!
+//! let message = Message::Failed(Action::Send, &"io error: busy");
+//! assert_eq!(message.text(Lang::De), "Senden fehlgeschlagen: io error: busy");
+//! ```
+//!
+//! Durations are printed with the unit that fits best, see [`duration`].
+
+use crate::error::Error;
+use std::{env, fmt, str::FromStr, time::Duration};
+
+/// Language of the command line tool
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+}
+
+impl Lang {
+    /// Language of a locale like "de_DE.UTF-8". `None` for unsupported languages.
+    pub fn from_locale(locale: &str) -> Option<Lang> {
+        let language = locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default();
+        language.parse().ok()
+    }
+
+    /// Language of the locale in `LC_ALL`, `LC_MESSAGES` or `LANG`. English if none of
+    /// them is set to a supported language.
+    pub fn from_env() -> Lang {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .and_then(|locale| Lang::from_locale(&locale))
+            .unwrap_or_default()
+    }
+
+    /// Separator of the fractional digits
+    fn decimal_separator(self) -> char {
+        match self {
+            Lang::En => '.',
+            Lang::De => ',',
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lang::En => write!(f, "en"),
+            Lang::De => write!(f, "de"),
+        }
+    }
+}
+
+impl FromStr for Lang {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "en" => Ok(Lang::En),
+            "de" => Ok(Lang::De),
+            _ => Err(Error::InvalidConfig(format!(
+                "unknown language \"{}\". Try en, de",
+                s
+            ))),
+        }
+    }
+}
+
+/// `value` with `precision` fractional digits and the decimal separator of `lang`
+pub fn decimal(lang: Lang, value: f64, precision: usize) -> String {
+    format!("{:.*}", precision, value).replace('.', &lang.decimal_separator().to_string())
+}
+
+/// `duration` in µs below 1ms, in ms below 1s and in s otherwise e.g "350 µs", "12.5 ms"
+/// or "5 s". Fractional digits are omitted if they are zero.
+///
+/// ```
+/// use funksteckdose::i18n::{duration, Lang};
+/// use std::time::Duration;
+///
+/// assert_eq!(duration(Lang::En, Duration::from_micros(350)), "350 µs");
+/// assert_eq!(duration(Lang::De, Duration::from_micros(1500)), "1,5 ms");
+/// assert_eq!(duration(Lang::De, Duration::from_millis(4999)), "5 s");
+/// ```
+pub fn duration(lang: Lang, duration: Duration) -> String {
+    let micros = duration.as_micros();
+    let (value, unit) = if micros < 1_000 {
+        (micros as f64, "µs")
+    } else if micros < 1_000_000 {
+        (micros as f64 / 1e3, "ms")
+    } else {
+        (duration.as_secs_f64(), "s")
+    };
+    let precision = if (value * 10.0).round() % 10.0 == 0.0 {
+        0
+    } else {
+        1
+    };
+    format!("{} {}", decimal(lang, value, precision), unit)
+}
+
+/// Operations of the command line tool that can fail
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    BrokerConfig,
+    Calibrate,
+    Encode,
+    HttpServer,
+    ImportStates,
+    InitLogger,
+    LearnSocket,
+    LoadConfig,
+    LoadHistory,
+    LoadLirc,
+    LoadStates,
+    MqttBridge,
+    OpenStore,
+    Pair,
+    QueryHistory,
+    ReadCommands,
+    ReadStates,
+    Receive,
+    Replay,
+    RequestGpio,
+    RunHook,
+    SaveHistory,
+    Send,
+    StoreState,
+    TopicConfig,
+    WriteStates,
+}
+
+impl Action {
+    fn text(self, lang: Lang) -> &'static str {
+        match lang {
+            Lang::En => match self {
+                Action::BrokerConfig => "Invalid broker configuration",
+                Action::Calibrate => "Failed to calibrate",
+                Action::Encode => "Failed to encode",
+                Action::HttpServer => "HTTP server failed",
+                Action::ImportStates => "Failed to import states",
+                Action::InitLogger => "Failed to initialize logger",
+                Action::LearnSocket => "Failed to add the socket",
+                Action::LoadConfig => "Failed to load configuration",
+                Action::LoadHistory => "Failed to load history",
+                Action::LoadLirc => "Failed to load LIRC configuration",
+                Action::LoadStates => "Failed to load states",
+                Action::MqttBridge => "MQTT bridge failed",
+                Action::OpenStore => "Failed to open store",
+                Action::Pair => "Failed to pair",
+                Action::QueryHistory => "Failed to query history",
+                Action::ReadCommands => "Failed to read commands",
+                Action::ReadStates => "Failed to read states",
+                Action::Receive => "Failed to receive",
+                Action::Replay => "Failed to replay",
+                Action::RequestGpio => "Failed to request GPIO line",
+                Action::RunHook => "Failed to run hook",
+                Action::SaveHistory => "Failed to save history",
+                Action::Send => "Failed to send",
+                Action::StoreState => "Failed to store state",
+                Action::TopicConfig => "Invalid topic configuration",
+                Action::WriteStates => "Failed to write states",
+            },
+            Lang::De => match self {
+                Action::BrokerConfig => "Ungültige Broker-Konfiguration",
+                Action::Calibrate => "Kalibrierung fehlgeschlagen",
+                Action::Encode => "Codierung fehlgeschlagen",
+                Action::HttpServer => "HTTP-Server fehlgeschlagen",
+                Action::ImportStates => "Import der Zustände fehlgeschlagen",
+                Action::InitLogger => "Initialisierung des Loggings fehlgeschlagen",
+                Action::LearnSocket => "Hinzufügen der Steckdose fehlgeschlagen",
+                Action::LoadConfig => "Laden der Konfiguration fehlgeschlagen",
+                Action::LoadHistory => "Laden des Verlaufs fehlgeschlagen",
+                Action::LoadLirc => "Laden der LIRC-Konfiguration fehlgeschlagen",
+                Action::LoadStates => "Laden der Zustände fehlgeschlagen",
+                Action::MqttBridge => "MQTT-Bridge fehlgeschlagen",
+                Action::OpenStore => "Öffnen des Speichers fehlgeschlagen",
+                Action::Pair => "Anlernen fehlgeschlagen",
+                Action::QueryHistory => "Abfrage des Verlaufs fehlgeschlagen",
+                Action::ReadCommands => "Lesen der Befehle fehlgeschlagen",
+                Action::ReadStates => "Lesen der Zustände fehlgeschlagen",
+                Action::Receive => "Empfang fehlgeschlagen",
+                Action::Replay => "Wiederholung fehlgeschlagen",
+                Action::RequestGpio => "Anfordern der GPIO-Leitung fehlgeschlagen",
+                Action::RunHook => "Ausführen des Hooks fehlgeschlagen",
+                Action::SaveHistory => "Speichern des Verlaufs fehlgeschlagen",
+                Action::Send => "Senden fehlgeschlagen",
+                Action::StoreState => "Speichern des Zustands fehlgeschlagen",
+                Action::TopicConfig => "Ungültige Topic-Konfiguration",
+                Action::WriteStates => "Schreiben der Zustände fehlgeschlagen",
+            },
+        }
+    }
+}
+
+/// Functions that are only available with some cargo features
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Feature {
+    Backend,
+    Hooks,
+    HistoryQueries,
+    HttpServer,
+    MqttBridge,
+    Realtime,
+    SocketNames,
+    StateSnapshots,
+}
+
+impl Feature {
+    /// Alternative feature sets providing the function
+    fn features(self) -> &'static [&'static str] {
+        match self {
+            Feature::Backend => &["wiringpi", "gpio-cdev"],
+            Feature::Hooks | Feature::SocketNames => &["config"],
+            Feature::HistoryQueries => &["sqlite"],
+            Feature::HttpServer => &["http"],
+            Feature::MqttBridge => &["mqtt,config"],
+            Feature::Realtime => &["realtime"],
+            Feature::StateSnapshots => &["json,config"],
+        }
+    }
+
+    fn text(self, lang: Lang) -> &'static str {
+        match lang {
+            Lang::En => match self {
+                Feature::Backend => "No hardware backend available",
+                Feature::Hooks => "Hooks need the configuration",
+                Feature::HistoryQueries => "History queries need the SQLite store",
+                Feature::HttpServer => "The HTTP server is not available",
+                Feature::MqttBridge => "The MQTT bridge is not available",
+                Feature::Realtime => "Real-time scheduling is not available",
+                Feature::SocketNames => "Socket names need the configuration",
+                Feature::StateSnapshots => "State snapshots are not available",
+            },
+            Lang::De => match self {
+                Feature::Backend => "Kein Hardware-Backend verfügbar",
+                Feature::Hooks => "Hooks benötigen die Konfiguration",
+                Feature::HistoryQueries => "Abfragen des Verlaufs benötigen den SQLite-Speicher",
+                Feature::HttpServer => "Der HTTP-Server ist nicht verfügbar",
+                Feature::MqttBridge => "Die MQTT-Bridge ist nicht verfügbar",
+                Feature::Realtime => "Echtzeit-Scheduling ist nicht verfügbar",
+                Feature::SocketNames => "Namen von Steckdosen benötigen die Konfiguration",
+                Feature::StateSnapshots => "Sicherungen der Zustände sind nicht verfügbar",
+            },
+        }
+    }
+}
+
+/// Message of the command line tool
+pub enum Message<'a> {
+    /// An operation failed with an error of the library
+    Failed(Action, &'a dyn fmt::Display),
+    /// The tool was built without the features needed
+    NotBuilt(Feature),
+    /// Like `NotBuilt(Feature::Backend)` with a hint to `--no-hardware`
+    NoBackend,
+    ConfirmPairing,
+    MissingSection(&'a str),
+    UnknownEncoding(&'a str),
+    /// The encoding has no address list to scan
+    UnscannableEncoding(&'a str),
+    UnknownProtocol(&'a str),
+    UnknownRawCode(&'a str),
+    UnknownSocket(&'a str),
+    /// Decimal code sent by codesend
+    SendingCode(u64),
+    /// Test signal with its pattern and duration
+    SendingSignal(&'a dyn fmt::Display, Duration),
+    /// Waiting for the remote of the socket with this name
+    Learning(&'a str),
+    /// A received code that no encoding decodes
+    Unlearnable(&'a dyn fmt::Display),
+    /// Code that decodes with more than one of the listed encodings
+    AmbiguousCode {
+        received: &'a dyn fmt::Display,
+        encodings: &'a str,
+    },
+    Learned(&'a str),
+    NothingLearned,
+    /// Representations of a code. `None` if the code has no tri-state code word.
+    Code {
+        decimal: &'a str,
+        binary: &'a str,
+        tri_state: Option<&'a str>,
+    },
+    DecodedA {
+        group: &'a str,
+        device: &'a dyn fmt::Display,
+        state: &'a dyn fmt::Display,
+    },
+    DecodedB {
+        address: &'a str,
+        channel: u8,
+        state: &'a dyn fmt::Display,
+    },
+    DecodedC {
+        family: &'a str,
+        group: &'a str,
+        device: u8,
+        state: &'a dyn fmt::Display,
+    },
+    DecodedREV {
+        group: &'a str,
+        device: u8,
+        state: &'a dyn fmt::Display,
+    },
+    /// Protocol derived from a LIRC raw code
+    RawCode {
+        remote: &'a str,
+        name: &'a str,
+        values: &'a crate::ProtocolValues,
+        code: u64,
+        length: usize,
+    },
+    /// LIRC raw code without a known protocol
+    RawSymbols {
+        remote: &'a str,
+        name: &'a str,
+        symbols: usize,
+    },
+}
+
+impl<'a> Message<'a> {
+    /// The message in `lang`
+    pub fn text(&self, lang: Lang) -> String {
+        let de = lang == Lang::De;
+        match self {
+            Message::Failed(action, e) => format!("{}: {}", action.text(lang), e),
+            Message::NotBuilt(feature) => {
+                let features = feature.features().join(if de { " oder " } else { " or " });
+                match lang {
+                    Lang::En => {
+                        format!("{}. Build with --features {}", feature.text(lang), features)
+                    }
+                    Lang::De => {
+                        format!("{}. Mit --features {} bauen", feature.text(lang), features)
+                    }
+                }
+            }
+            Message::NoBackend => match lang {
+                Lang::En => format!(
+                    "{} or use --no-hardware",
+                    Message::NotBuilt(Feature::Backend).text(lang)
+                ),
+                Lang::De => format!(
+                    "{} oder --no-hardware verwenden",
+                    Message::NotBuilt(Feature::Backend).text(lang)
+                ),
+            },
+            Message::ConfirmPairing if de => {
+                "Alle anderen selbstlernenden Steckdosen ausschalten und --confirm angeben".into()
+            }
+            Message::ConfirmPairing => {
+                "Switch off all other self-learning sockets and pass --confirm".into()
+            }
+            Message::MissingSection(section) if de => {
+                format!("Die Konfiguration hat keinen Abschnitt [{}]", section)
+            }
+            Message::MissingSection(section) => {
+                format!("The configuration has no [{}] section", section)
+            }
+            Message::UnknownEncoding(encoding) if de => {
+                format!("Unbekannte Codierung: {}", encoding)
+            }
+            Message::UnknownEncoding(encoding) => format!("Unknown encoding: {}", encoding),
+            Message::UnscannableEncoding(encoding) if de => {
+                format!("Codierung {} kann nicht durchsucht werden", encoding)
+            }
+            Message::UnscannableEncoding(encoding) => {
+                format!("Cannot scan encoding {}", encoding)
+            }
+            Message::UnknownProtocol(protocol) if de => {
+                format!("Unbekanntes Protokoll: {}", protocol)
+            }
+            Message::UnknownProtocol(protocol) => format!("Unknown protocol: {}", protocol),
+            Message::UnknownRawCode(name) if de => format!("Kein Rohcode namens {}", name),
+            Message::UnknownRawCode(name) => format!("No raw code named {}", name),
+            Message::UnknownSocket(socket) if de => format!("Unbekannte Steckdose {}", socket),
+            Message::UnknownSocket(socket) => format!("Unknown socket {}", socket),
+            Message::SendingCode(code) if de => format!("sende Code[{}]", code),
+            Message::SendingCode(code) => format!("sending code[{}]", code),
+            Message::SendingSignal(pattern, d) if de => {
+                format!("sende Signal {} für {}", pattern, duration(lang, *d))
+            }
+            Message::SendingSignal(pattern, d) => {
+                format!("sending {} signal for {}", pattern, duration(lang, *d))
+            }
+            Message::Learning(name) if de => {
+                format!("Eine Taste der Fernbedienung von {} drücken", name)
+            }
+            Message::Learning(name) => format!("Press a button of the remote of {}", name),
+            Message::Unlearnable(received) if de => {
+                format!("Ignoriere {}: keine passende Codierung", received)
+            }
+            Message::Unlearnable(received) => {
+                format!("Ignoring {}: no matching encoding", received)
+            }
+            Message::AmbiguousCode {
+                received,
+                encodings,
+            } if de => format!(
+                "{} passt zu den Codierungen {}, bitte mit --encoding eine auswählen",
+                received, encodings
+            ),
+            Message::AmbiguousCode {
+                received,
+                encodings,
+            } => format!(
+                "{} matches the encodings {}, select one with --encoding",
+                received, encodings
+            ),
+            Message::Learned(name) if de => {
+                format!("Steckdose {} zur Konfiguration hinzugefügt:", name)
+            }
+            Message::Learned(name) => format!("Added socket {} to the configuration:", name),
+            Message::NothingLearned if de => "Kein Code der Fernbedienung empfangen".into(),
+            Message::NothingLearned => "No code of the remote received".into(),
+            Message::Code {
+                decimal,
+                binary,
+                tri_state,
+            } => {
+                let labels = if de {
+                    ["dezimal:", "binär:", "tri-state:"]
+                } else {
+                    ["decimal:", "binary:", "tri-state:"]
+                };
+                format!(
+                    "{:<10} {}\n{:<10} {}\n{:<10} {}",
+                    labels[0],
+                    decimal,
+                    labels[1],
+                    binary,
+                    labels[2],
+                    tri_state.unwrap_or("-")
+                )
+            }
+            Message::DecodedA {
+                group,
+                device,
+                state,
+            } if de => format!("  Codierung A: Gruppe {} Gerät {} {}", group, device, state),
+            Message::DecodedA {
+                group,
+                device,
+                state,
+            } => format!("  encoding A: group {} device {} {}", group, device, state),
+            Message::DecodedB {
+                address,
+                channel,
+                state,
+            } if de => format!("  Codierung B: Adresse {}:{} {}", address, channel, state),
+            Message::DecodedB {
+                address,
+                channel,
+                state,
+            } => format!("  encoding B: address {}:{} {}", address, channel, state),
+            Message::DecodedC {
+                family,
+                group,
+                device,
+                state,
+            } => format!(
+                "  {} C: intertechno {}:{}:{} {}",
+                if de { "Codierung" } else { "encoding" },
+                family,
+                group,
+                device,
+                state
+            ),
+            Message::DecodedREV {
+                group,
+                device,
+                state,
+            } if de => format!(
+                "  Codierung REV: Gruppe {} Gerät {} {}",
+                group, device, state
+            ),
+            Message::DecodedREV {
+                group,
+                device,
+                state,
+            } => format!(
+                "  encoding REV: group {} device {} {}",
+                group, device, state
+            ),
+            Message::RawCode {
+                remote,
+                name,
+                values,
+                code,
+                length,
+            } => {
+                let pulses = |s: &crate::HighLow| format!("{}/{}", s.high, s.low);
+                let pulse_length = duration(lang, Duration::from_micros(values.pulse_length()));
+                if de {
+                    format!(
+                        "{} {}: Pulslänge {} Sync {} Null {} Eins {} Code {:#X} Länge {}",
+                        remote,
+                        name,
+                        pulse_length,
+                        pulses(values.sync_factor()),
+                        pulses(values.zero()),
+                        pulses(values.one()),
+                        code,
+                        length
+                    )
+                } else {
+                    format!(
+                        "{} {}: pulse length {} sync {} zero {} one {} code {:#X} length {}",
+                        remote,
+                        name,
+                        pulse_length,
+                        pulses(values.sync_factor()),
+                        pulses(values.zero()),
+                        pulses(values.one()),
+                        code,
+                        length
+                    )
+                }
+            }
+            Message::RawSymbols {
+                remote,
+                name,
+                symbols,
+            } if de => format!(
+                "{} {}: {} Symbole ohne bekanntes Protokoll",
+                remote, name, symbols
+            ),
+            Message::RawSymbols {
+                remote,
+                name,
+                symbols,
+            } => format!(
+                "{} {}: {} symbols without a known protocol",
+                remote, name, symbols
+            ),
+        }
+    }
+}
+
+/// Column titles of the activity table: socket, switches, on time, energy, last activity
+pub fn activity_columns(lang: Lang) -> [&'static str; 5] {
+    match lang {
+        Lang::En => ["socket", "switches", "on time", "energy", "last activity"],
+        Lang::De => [
+            "Steckdose",
+            "Schaltungen",
+            "Einschaltdauer",
+            "Energie",
+            "letzte Aktivität",
+        ],
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//! Self-learning sockets of Intertechno, KlikAanKlikUit, Nexa, HomeEasy EU and compatible
+//! brands.
+//!
+//! These sockets have no dip switches. They learn the 26 bit id of a remote while in
+//! learning mode and listen to it from then on. The protocol has nothing in common with the
+//! tri-state encodings: every bit is sent as two pulses and a frame is framed by a start and
+//! a stop pulse. A frame has 32 bits:
+//!
+//! | bits | content                      |
+//! |------|------------------------------|
+//! | 26   | id of the remote             |
+//! | 1    | group: all units of the id   |
+//! | 1    | on or off                    |
+//! | 4    | unit                         |
+//!
+//! Dimmers accept a 36 bit frame with a dim symbol instead of the on/off bit and the dim
+//! level in the last 4 bits.
+//!
+//! ```
+//! use funksteckdose::{intertechno::{Action, Command}, mock::NullPin, Funksteckdose, EncodingA, Protocol1, State};
+//!
+//! let command = Command::new(0x12_3456, 2, Action::Switch(State::On)).unwrap();
+//! let d: Funksteckdose<_, EncodingA, Protocol1> =
+//!     Funksteckdose::builder(NullPin).repeat_transmit(1).build().unwrap();
+//! d.send_intertechno(&command).unwrap();
+//! ```
+
+use crate::{
+    error::{CoreError, Error},
+    plan::Symbol,
+    plan::TransmissionPlan,
+    Code, State,
+};
+use std::fmt;
+
+/// Pulse length in µs
+pub const PULSE_LENGTH: u32 = 260;
+
+/// Largest id of a remote
+pub const MAX_ID: u32 = (1 << 26) - 1;
+
+/// What a command does
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Switch on or off
+    Switch(State),
+    /// Set the level of a dimmer from 0 to 15
+    Dim(u8),
+}
+
+impl From<&crate::Command> for Action {
+    fn from(command: &crate::Command) -> Action {
+        match command {
+            crate::Command::On => Action::Switch(State::On),
+            crate::Command::Off => Action::Switch(State::Off),
+            crate::Command::Dim(level) => Action::Dim(*level),
+        }
+    }
+}
+
+/// A command for a self-learning socket
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    id: u32,
+    unit: Option<u8>,
+    action: Action,
+}
+
+/// A bit of the frame. `Dim` is the third symbol in place of the on/off bit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Bit {
+    Zero,
+    One,
+    Dim,
+}
+
+impl Command {
+    /// Command for `unit` 0 to 15 of the remote `id`
+    pub fn new(id: u32, unit: u8, action: Action) -> Result<Command, Error> {
+        if unit > 15 {
+            return Err(
+                CoreError::InvalidDevice(format!("unit {} is not between 0 and 15", unit)).into(),
+            );
+        }
+        Command::with_unit(id, Some(unit), action)
+    }
+
+    /// Command for all units of the remote `id`
+    pub fn group(id: u32, action: Action) -> Result<Command, Error> {
+        Command::with_unit(id, None, action)
+    }
+
+    fn with_unit(id: u32, unit: Option<u8>, action: Action) -> Result<Command, Error> {
+        if id > MAX_ID {
+            return Err(
+                CoreError::InvalidGroup(format!("id {} does not fit into 26 bits", id)).into(),
+            );
+        }
+        if let Action::Dim(level) = action {
+            if level > 15 {
+                return Err(CoreError::InvalidState(format!(
+                    "dim level {} is not between 0 and 15",
+                    level
+                ))
+                .into());
+            }
+        }
+        Ok(Command { id, unit, action })
+    }
+
+    /// Id of the remote
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Unit. `None` for group commands.
+    pub fn unit(&self) -> Option<u8> {
+        self.unit
+    }
+
+    /// Action
+    pub fn action(&self) -> &Action {
+        &self.action
+    }
+
+    fn bits(&self) -> Vec<Bit> {
+        let number = |value: u32, length: usize| {
+            (0..length).rev().map(move |i| {
+                if value & (1 << i) != 0 {
+                    Bit::One
+                } else {
+                    Bit::Zero
+                }
+            })
+        };
+        let flag = |value: bool| if value { Bit::One } else { Bit::Zero };
+        let mut bits: Vec<Bit> = number(self.id, 26).collect();
+        bits.push(flag(self.unit.is_none()));
+        bits.push(match self.action {
+            Action::Switch(ref state) => flag(*state == State::On),
+            Action::Dim(_) => Bit::Dim,
+        });
+        bits.extend(number(u32::from(self.unit.unwrap_or(0)), 4));
+        if let Action::Dim(level) = self.action {
+            bits.extend(number(u32::from(level), 4));
+        }
+        bits
+    }
+
+    /// The frame as binary code with the dim symbol sent as zero, e.g for logging
+    pub fn code(&self) -> Code {
+        let bits = self.bits();
+        let code = bits
+            .iter()
+            .fold(0u64, |code, bit| (code << 1) | u64::from(*bit == Bit::One));
+        Code::from_decimal(code, bits.len()).expect("frame longer than 64 bits")
+    }
+
+    /// Symbols of a frame from the start to the stop pulse
+    pub fn frame(&self) -> Vec<Symbol> {
+        let t = PULSE_LENGTH;
+        let short = Symbol { high: t, low: t };
+        let long = Symbol {
+            high: t,
+            low: 5 * t,
+        };
+        let start = Symbol {
+            high: t,
+            low: 10 * t + t / 2,
+        };
+        let stop = Symbol {
+            high: t,
+            low: 40 * t,
+        };
+        let mut frame = vec![start];
+        for bit in self.bits() {
+            frame.extend_from_slice(&match bit {
+                Bit::Zero => [short.clone(), long.clone()],
+                Bit::One => [long.clone(), short.clone()],
+                Bit::Dim => [short.clone(), short.clone()],
+            });
+        }
+        frame.push(stop);
+        frame
+    }
+
+    /// Plan sending the command `repeats` times
+    pub fn plan(&self, repeats: usize) -> TransmissionPlan {
+        let code = self.code();
+        TransmissionPlan {
+            code: code.code(),
+            length: code.length(),
+            ..TransmissionPlan::from_symbols(self.frame(), repeats)
+        }
+    }
+
+    /// Decode a frame from the start to the stop pulse e.g a capture of a remote. The pulse
+    /// length is taken from the start pulse, so remotes with a slightly different timing
+    /// like the 275µs of HomeEasy EU are decoded as well.
+    /// ```
+    /// use funksteckdose::{intertechno::{Action, Command}, State};
+    /// let command = Command::new(0x12_3456, 2, Action::Switch(State::On)).unwrap();
+    /// assert_eq!(Command::decode(&command.frame()), Some(command));
+    /// ```
+    pub fn decode(frame: &[Symbol]) -> Option<Command> {
+        let (start, rest) = frame.split_first()?;
+        let (stop, symbols) = rest.split_last()?;
+        let t = start.high.max(1);
+        if !(8 * t..=13 * t).contains(&start.low) || stop.low < 20 * t {
+            return None;
+        }
+        let long = |s: &Symbol| match s.low {
+            low if low < 2 * t + t / 2 => Some(false),
+            low if (3 * t..=7 * t).contains(&low) => Some(true),
+            _ => None,
+        };
+        let bits = symbols
+            .chunks(2)
+            .map(|pair| match pair {
+                [first, second] => match (long(first)?, long(second)?) {
+                    (false, true) => Some(Bit::Zero),
+                    (true, false) => Some(Bit::One),
+                    (false, false) => Some(Bit::Dim),
+                    (true, true) => None,
+                },
+                _ => None,
+            })
+            .collect::<Option<Vec<Bit>>>()?;
+        let number = |bits: &[Bit]| {
+            bits.iter().try_fold(0u32, |value, bit| match bit {
+                Bit::Zero => Some(value << 1),
+                Bit::One => Some(value << 1 | 1),
+                Bit::Dim => None,
+            })
+        };
+        if bits.len() != 32 && bits.len() != 36 {
+            return None;
+        }
+        let id = number(&bits[..26])?;
+        let group = number(&bits[26..27])? == 1;
+        let unit = number(&bits[28..32])? as u8;
+        let action = match (bits[27], bits.len()) {
+            (Bit::Dim, 36) => Action::Dim(number(&bits[32..36])? as u8),
+            (Bit::One, 32) => Action::Switch(State::On),
+            (Bit::Zero, 32) => Action::Switch(State::Off),
+            _ => return None,
+        };
+        if group {
+            Command::group(id, action).ok()
+        } else {
+            Command::new(id, unit, action).ok()
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "id {}", self.id)?;
+        match self.unit {
+            Some(unit) => write!(f, " unit {}", unit)?,
+            None => write!(f, " group")?,
+        }
+        match self.action {
+            Action::Switch(ref state) => write!(f, " {}", state),
+            Action::Dim(level) => write!(f, " dim {}", level),
+        }
+    }
+}
@@ -1,430 +1,915 @@
-/// Copyright © 2019 Felix Obenhuber
-///
-/// Permission is hereby granted, free of charge, to any person obtaining a copy
-/// of this software and associated documentation files (the "Software"), to deal
-/// in the Software without restriction, including without limitation the rights
-/// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
-/// copies of the Software, and to permit persons to whom the Software is
-/// furnished to do so, subject to the following conditions:
-///
-/// The above copyright notice and this permission notice shall be included in all
-/// copies or substantial portions of the Software.
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
 //
-/// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
-/// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
-/// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
-/// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
-/// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
-/// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
-/// SOFTWARE.
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+pub use funksteckdose_core::*;
 
+use delay::{Calibration, Hybrid};
 use error::Error;
-use log::debug;
+use log::{debug, error, info, warn};
+use metrics::Metrics;
+use plan::{TransmissionPlan, Waveform};
+use quiet::{Priority, QuietHours};
+use state::StateCache;
+use std::cell::{Cell, RefCell};
 use std::marker::PhantomData;
-use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use trigger::Trigger;
 
-/// Error
-pub mod error {
-    use failure::Fail;
+/// Errors of the transmitter and its services
+pub mod error;
 
-    #[derive(Debug, Fail)]
-    pub enum Error {
-        #[fail(display = "invalid group identifier: {}", _0)]
-        InvalidGroup(String),
-        #[fail(display = "invalid device identifier: {}", _0)]
-        InvalidDevice(String),
-        #[fail(display = "invalid state: {}. Try on, off, 1, 0, true, false", _0)]
-        InvalidState(String),
-    }
-}
+/// Counters for monitoring
+pub mod metrics;
 
-/// A Device
-#[derive(Clone, Debug, PartialEq)]
-pub enum Device {
-    A,
-    B,
-    C,
-    D,
-    E,
-}
+/// Push buttons
+pub mod button;
 
-impl From<Device> for u8 {
-    fn from(d: Device) -> u8 {
-        match d {
-            Device::A => 1,
-            Device::B => 2,
-            Device::C => 3,
-            Device::D => 4,
-            Device::E => 5,
-        }
-    }
-}
+/// Metadata of protocols and encodings
+pub mod catalog;
 
-impl str::FromStr for Device {
-    type Err = Error;
+/// Delay strategies
+pub mod delay;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "0" | "a" | "A" | "10000" => Ok(Device::A),
-            "1" | "b" | "B" | "01000" => Ok(Device::B),
-            "2" | "c" | "C" | "00100" => Ok(Device::C),
-            "3" | "d" | "D" | "00010" => Ok(Device::D),
-            "4" | "e" | "E" | "00001" => Ok(Device::E),
-            _ => Err(Error::InvalidDevice(s.into())),
-        }
-    }
-}
+/// Construction of transmitters
+pub mod builder;
 
-/// State to switch a socket to
-#[derive(Clone, Debug, PartialEq)]
-pub enum State {
-    On,
-    Off,
-}
+/// Socket registry
+#[cfg(feature = "config")]
+pub mod config;
 
-impl str::FromStr for State {
-    type Err = Error;
+/// Alignment of transmissions to external events
+pub mod trigger;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "On" | "on" | "1" | "true" => Ok(State::On),
-            "Off" | "off" | "0" | "false" => Ok(State::Off),
-            _ => Err(Error::InvalidState(s.into())),
-        }
-    }
-}
+/// Human readable breakdown of a transmission
+pub mod explain;
 
-/// Value to set a GPIO to
-#[derive(Clone, Debug, PartialEq)]
-pub enum Value {
-    Low,
-    High,
-}
+/// Pulses of a transmission before it is sent
+pub mod plan;
 
-/// Encoding
-pub trait Encoding {
-    fn encode(group: &str, device: &Device, state: &State) -> Result<Vec<u8>, Error>;
-}
+/// History of transmitted commands
+pub mod history;
 
-/// Encoding A - check [rc-switch](https://github.com/sui77/rc-switch/) for details
-pub struct EncodingA;
+/// Commands waiting for transmission
+pub mod queue;
 
-impl Encoding for EncodingA {
-    fn encode(group: &str, device: &Device, state: &State) -> Result<Vec<u8>, Error> {
-        if group.len() != 5 || group.chars().any(|c| c != '0' && c != '1') {
-            return Err(Error::InvalidGroup(group.into()));
-        }
-        let chars = group.chars();
+/// Tracking of assumed socket states
+pub mod state;
 
-        let device = match device {
-            Device::A => "10000",
-            Device::B => "01000",
-            Device::C => "00100",
-            Device::D => "00010",
-            Device::E => "00001",
-        };
+/// Timed command sequences
+pub mod macros;
 
-        let chars = chars.chain(device.chars());
+/// Pins without hardware
+pub mod mock;
 
-        let chars = match *state {
-            State::On => chars.chain("10".chars()),
-            State::Off => chars.chain("01".chars()),
-        };
+/// Text and JSON log output
+pub mod logging;
 
-        Ok(chars
-            .map(|c| match c {
-                '0' => b'F',
-                _ => b'0',
-            })
-            .collect())
-    }
-}
+/// Reattaching failed transmitters
+pub mod recovery;
 
-/// Encoding B - check [rc-switch](https://github.com/sui77/rc-switch/) for details
-pub struct EncodingB;
+/// Import of LIRC raw codes
+pub mod lirc;
 
-impl Encoding for EncodingB {
-    fn encode(_group: &str, _device: &Device, _state: &State) -> Result<Vec<u8>, Error> {
-        unimplemented!()
-    }
-}
+/// Command stream of the daemon
+pub mod daemon;
 
-/// Encoding C - check [rc-switch](https://github.com/sui77/rc-switch/) for details
-pub struct EncodingC;
+/// Reduced transmissions at night
+pub mod quiet;
 
-impl Encoding for EncodingC {
-    fn encode(_group: &str, _device: &Device, _state: &State) -> Result<Vec<u8>, Error> {
-        unimplemented!()
-    }
-}
+/// Captures of remotes
+pub mod cloning;
 
-/// Interface for GPIO control
-pub trait Pin {
-    fn set(&self, value: &Value) -> Result<(), Error>;
-}
+/// Captured transmissions of real remotes to check decoder and encoders
+pub mod corpus;
+
+/// Fail fast for sockets whose transmitter keeps failing
+pub mod breaker;
+
+/// Persistence of states, counters, captures and history
+pub mod store;
+
+/// On time and energy estimates
+pub mod energy;
+
+/// Confirmation and rate limits of pairing transmissions
+pub mod pairing;
+
+/// Diagnostic waveforms
+pub mod signal;
+
+/// Sets of commands sent together
+pub mod scene;
+
+/// Reception and decoding of codes
+pub mod receiver;
+
+/// Transmitters used from several threads
+pub mod shared;
+
+/// Commands run on received codes
+pub mod hook;
+
+/// Translations of command line messages
+pub mod i18n;
+
+/// Decoding of sent commands with the receiver
+pub mod loopback;
+
+/// Self-learning Intertechno sockets
+pub mod intertechno;
+
+/// Icons, areas and labels of sockets for user interfaces
+pub mod metadata;
+
+/// Encoders loaded from WebAssembly modules
+#[cfg(feature = "wasm")]
+pub mod plugin;
+
+/// MQTT topics and payloads
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+
+/// MQTT bridge
+#[cfg(feature = "mqtt")]
+pub mod bridge;
+
+/// HTTP control server
+#[cfg(feature = "http")]
+pub mod http;
+
+/// Sending from async code
+#[cfg(feature = "async")]
+pub mod asynchronous;
+
+/// Real-time scheduling during transmissions
+#[cfg(all(feature = "realtime", target_os = "linux"))]
+pub mod realtime;
+
+/// Handle to a Funksteckdose system with the encoding and protocol of the scheme `S`
+pub type SchemeFunksteckdose<T, S, D = Hybrid> =
+    Funksteckdose<T, <S as Scheme>::Encoding, <S as Scheme>::Protocol, D>;
+
+/// Default limit of the airtime of a single transmission
+pub const MAX_AIRTIME: Duration = Duration::from_secs(10);
 
 /// Handle to a Funksteckdose system
+///
+/// Pulses are timed with the delay strategy `D`. The default [`delay::Hybrid`] sleeps
+/// through long gaps and spins for the rest, see [`delay`] for the alternatives.
+///
+/// A handle is not `Sync` because concurrent transmissions through one pin garble each
+/// other. Use [`shared::SharedTransmitter`] to send from several threads.
+/// ```compile_fail
+/// use funksteckdose::{mock::NullPin, EncodingA, Funksteckdose, Protocol1};
+/// fn sync<S: Sync>() {}
+/// sync::<Funksteckdose<NullPin, EncodingA, Protocol1>>();
+/// ```
 #[derive(Debug)]
-pub struct Funksteckdose<T: Pin, E: Encoding, P: Protocol> {
+pub struct Funksteckdose<T: Pin, E: Encoding, P: Protocol, D: Delay = Hybrid> {
     pin: T,
     repeat_transmit: usize,
+    delay: D,
+    values: ProtocolValues,
+    compensation: u32,
+    watchdog_factor: Option<u32>,
+    max_airtime: Option<Duration>,
+    options: EncodingOptions,
+    trigger: Option<Box<dyn Trigger + Send + Sync>>,
+    enable: Option<EnablePin>,
+    preamble: Option<Preamble>,
+    repeat_gap: u32,
+    #[cfg(all(feature = "realtime", target_os = "linux"))]
+    realtime: Option<i32>,
+    quiet_hours: Option<QuietHours>,
+    state_cache: RefCell<Option<StateCache>>,
+    warm_up: Duration,
+    cool_down: Duration,
+    healthy: AtomicBool,
+    metrics: Arc<Metrics>,
     protocol: PhantomData<P>,
     encoding: PhantomData<E>,
+    not_sync: PhantomData<Cell<()>>,
 }
 
-impl<T: Pin, E: Encoding, P: Protocol> Funksteckdose<T, E, P> {
+/// Supply or enable line of the transmitter module
+struct EnablePin(Box<dyn Pin + Send + Sync>);
+
+impl std::fmt::Debug for EnablePin {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "EnablePin")
+    }
+}
+
+impl<T: Pin, E: Encoding, P: Protocol, D: Delay + Default> Funksteckdose<T, E, P, D> {
     /// Create a new instance with a given pin and default protocol
-    /// ```
+    /// ```ignore
     /// type Funksteckdose = funksteckdose::Funksteckdose<WiringPiPin, EncodingA, Protocol1>;
-    /// let pin = WiringPiPin::new(0);
+    /// let pin = WiringPiPin::new(0)?;
     /// let d: Funksteckdose = Funksteckdose::new(pin);
     /// ```
-    pub fn new(pin: T) -> Funksteckdose<T, E, P> {
-        Self::with_repeat_transmit(pin, 10)
+    pub fn new(pin: T) -> Funksteckdose<T, E, P, D> {
+        Self::with_delay(pin, 10, D::default())
     }
 
     /// Create a new instance with a given pin and transmit count
-    /// ```
+    /// ```ignore
     /// type Funksteckdose = funksteckdose::Funksteckdose<WiringPiPin, EncodingA, Protocol1>;
-    /// let pin = WiringPiPin::new(0);
-    /// let d: Funksteckdose = Funksteckdose::with_repeat_transmit(pin, 5);
+    /// let pin = WiringPiPin::new(0)?;
+    /// let d: Funksteckdose = Funksteckdose::builder(pin).repeat_transmit(5).build()?;
+    /// ```
+    #[deprecated(note = "use `Funksteckdose::builder(pin).repeat_transmit(n).build()`")]
+    pub fn with_repeat_transmit(pin: T, repeat_transmit: usize) -> Funksteckdose<T, E, P, D> {
+        Self::with_delay(pin, repeat_transmit, D::default())
+    }
+}
+
+impl<T: Pin, E: Encoding, P: Protocol, D: Delay + Default> Funksteckdose<T, E, P, D> {
+    /// Create a new instance with the encoding, protocol and repeats of the scheme `S`
     /// ```
-    pub fn with_repeat_transmit(pin: T, repeat_transmit: usize) -> Funksteckdose<T, E, P> {
+    /// use funksteckdose::{mock::NullPin, Device, SchemeBrennenstuhl, SchemeFunksteckdose, State};
+    /// use funksteckdose::Funksteckdose;
+    /// let d: SchemeFunksteckdose<NullPin, SchemeBrennenstuhl> =
+    ///     Funksteckdose::with_scheme::<SchemeBrennenstuhl>(NullPin);
+    /// d.send("10011", &Device::B, &State::On).expect("Failed to send");
+    /// ```
+    pub fn with_scheme<S: Scheme<Encoding = E, Protocol = P>>(pin: T) -> Funksteckdose<T, E, P, D> {
+        Self::with_delay(pin, S::REPEATS, D::default())
+    }
+}
+
+impl<T: Pin, E: Encoding, P: Protocol> Funksteckdose<T, E, P> {
+    /// Start building an instance for `pin`, see [`builder`]
+    /// ```
+    /// use funksteckdose::{mock::NullPin, EncodingA, Funksteckdose, Protocol1};
+    /// let d: Funksteckdose<NullPin, EncodingA, Protocol1> =
+    ///     Funksteckdose::builder(NullPin).repeat_transmit(5).build()?;
+    /// # Ok::<(), funksteckdose::error::Error>(())
+    /// ```
+    pub fn builder(pin: T) -> builder::Builder<T, E, P, Hybrid> {
+        builder::Builder::new(pin, Hybrid::default())
+    }
+}
+
+impl<T: Pin, E: Encoding, D: Delay + Default> Funksteckdose<T, E, DynProtocol, D> {
+    /// Create a new instance with a protocol selected at runtime
+    /// ```ignore
+    /// type Funksteckdose = funksteckdose::Funksteckdose<WiringPiPin, EncodingA, DynProtocol>;
+    /// let pin = WiringPiPin::new(0)?;
+    /// let values = catalog::protocol("2").expect("Unknown protocol");
+    /// let d: Funksteckdose = Funksteckdose::with_protocol(pin, values);
+    /// ```
+    pub fn with_protocol(pin: T, values: ProtocolValues) -> Funksteckdose<T, E, DynProtocol, D> {
+        let mut funksteckdose = Self::new(pin);
+        funksteckdose.set_protocol(values);
+        funksteckdose
+    }
+}
+
+impl<T: Pin, E: Encoding, P: Protocol, D: Delay> Funksteckdose<T, E, P, D> {
+    /// Create a new instance with a given pin, transmit count and delay strategy
+    /// ```ignore
+    /// type Funksteckdose =
+    ///     funksteckdose::Funksteckdose<WiringPiPin, EncodingA, Protocol1, YieldingSpin>;
+    /// let pin = WiringPiPin::new(0)?;
+    /// let d: Funksteckdose = Funksteckdose::with_delay(pin, 5, YieldingSpin::default());
+    /// ```
+    pub fn with_delay(pin: T, repeat_transmit: usize, delay: D) -> Funksteckdose<T, E, P, D> {
         Funksteckdose {
             pin,
             repeat_transmit,
+            delay,
+            values: P::values(),
+            compensation: 0,
+            watchdog_factor: Some(4),
+            max_airtime: Some(MAX_AIRTIME),
+            options: EncodingOptions::default(),
+            trigger: None,
+            enable: None,
+            preamble: P::preamble(),
+            repeat_gap: 0,
+            #[cfg(all(feature = "realtime", target_os = "linux"))]
+            realtime: None,
+            quiet_hours: None,
+            state_cache: RefCell::new(None),
+            warm_up: Duration::from_millis(0),
+            cool_down: Duration::from_millis(0),
+            healthy: AtomicBool::new(true),
+            metrics: Arc::new(Metrics::default()),
             protocol: PhantomData,
             encoding: PhantomData,
+            not_sync: PhantomData,
         }
     }
 
     /// Send a control sequence to give group and device.
     /// The group is coded like the dip switches in the devices e.g "10010"
-    /// ```
+    /// ```ignore
     /// type Funksteckdose = funksteckdose::Funksteckdose<WiringPiPin, EncodingA, Protocol1>;
-    /// let pin = WiringPiPin::new(0);
-    /// let d: Funksteckdose = Funksteckdose::with_repeat_transmit(pin, 5);
+    /// let pin = WiringPiPin::new(0)?;
+    /// let d: Funksteckdose = Funksteckdose::builder(pin).repeat_transmit(5).build()?;
     /// d.send("10001", &Device::A, &State::On).expect("Failed to send");
     /// ```
     pub fn send(&self, group: &str, device: &Device, state: &State) -> Result<(), Error> {
-        let code_word = E::encode(group, device, state)?;
-        self.send_tri_state(&code_word)
-    }
-
-    fn send_tri_state(&self, code_word: &[u8]) -> Result<(), Error> {
-        let code = code_word.iter().fold(0u64, |mut code, c| {
-            code <<= 2u64;
-            match c {
-                b'0' => (),           // bit pattern 00
-                b'F' => code |= 1u64, // bit pattern 01
-                b'1' => code |= 3u64, // bit pattern 11
-                _ => unreachable!(),
+        self.send_with_priority(group, device, state, Priority::Interactive)
+    }
+
+    /// Send a command to a group that is already parsed and validated
+    /// ```
+    /// use funksteckdose::{mock::NullPin, Device, EncodingA, Funksteckdose, Group, Protocol1, State};
+    /// let d: Funksteckdose<NullPin, EncodingA, Protocol1> = Funksteckdose::new(NullPin);
+    /// let group: Group = "10001".parse()?;
+    /// d.send_group(&group, &Device::A, &State::On)?;
+    /// # Ok::<(), funksteckdose::error::Error>(())
+    /// ```
+    pub fn send_group(&self, group: &Group, device: &Device, state: &State) -> Result<(), Error> {
+        self.send(group.as_str(), device, state)
+    }
+
+    /// Send a `Command` e.g a dim level. Dim levels need an encoding that supports them,
+    /// see `intertechno::Action` for self-learning dimmers.
+    /// ```
+    /// use funksteckdose::{mock::NullPin, Command, Device, EncodingA, Funksteckdose, Protocol1};
+    /// let d: Funksteckdose<NullPin, EncodingA, Protocol1> = Funksteckdose::new(NullPin);
+    /// d.send_command("10001", &Device::A, &Command::On)?;
+    /// assert!(d.send_command("10001", &Device::A, &Command::Dim(8)).is_err());
+    /// # Ok::<(), funksteckdose::error::Error>(())
+    /// ```
+    pub fn send_command(
+        &self,
+        group: &str,
+        device: &Device,
+        command: &Command,
+    ) -> Result<(), Error> {
+        if let Some(state) = command.state() {
+            return self.send(group, device, &state);
+        }
+        let code_word = E::encode_command(group, device, command)?;
+        let plan = TransmissionPlan::with_values(
+            &self.values,
+            &code_word,
+            self.repeat_transmit,
+            self.preamble.as_ref(),
+        )?
+        .with_gap(self.repeat_gap);
+        self.send_plan(
+            &plan,
+            &Code::from_tri_state(&String::from_utf8_lossy(&code_word))?,
+        )
+    }
+
+    /// Send a command with `priority`. Background transmissions are reduced or suppressed
+    /// during the quiet hours.
+    pub fn send_with_priority(
+        &self,
+        group: &str,
+        device: &Device,
+        state: &State,
+        priority: Priority,
+    ) -> Result<(), Error> {
+        let state = &self.resolve(group, device, state);
+        let plan = self.plan(group, device, state);
+        self.send_planned(group, device, state, priority, plan, false)
+    }
+
+    // Send the plan of a command with a resolved state. Applies the quiet hours, updates
+    // the state cache and logs the outcome. Nothing is sent to virtual sockets.
+    fn send_planned(
+        &self,
+        group: &str,
+        device: &Device,
+        state: &State,
+        priority: Priority,
+        plan: Result<TransmissionPlan, Error>,
+        is_virtual: bool,
+    ) -> Result<(), Error> {
+        let start = Instant::now();
+        let result = plan.and_then(|mut plan| {
+            if let Some(ref quiet_hours) = self.quiet_hours {
+                plan.repeats = quiet_hours.repeats(priority, plan.repeats);
+                if plan.repeats == 0 {
+                    return Err(Error::Quiet(format!("{} {} {}", group, device, state)));
+                }
             }
-            code
+            if is_virtual {
+                info!(
+                    "Not sending {} {} {} to virtual socket",
+                    group, device, state
+                );
+                self.remember(group, device, state);
+                return Ok(());
+            }
+            self.execute(&plan)?;
+            self.remember(group, device, state);
+            info!(
+                event = "send",
+                group = group,
+                device:% = device,
+                state:% = state,
+                code = plan.code,
+                duration_us = start.elapsed().as_micros() as u64;
+                "Sent {} {} {}", group, device, state
+            );
+            Ok(())
         });
+        if let Err(ref e) = result {
+            warn!(
+                event = "error",
+                group = group,
+                device:% = device,
+                state:% = state,
+                error = e.code(),
+                category = e.category().name();
+                "Failed to send {} {} {}: {}", group, device, state, e
+            );
+        }
+        if !is_virtual {
+            self.metrics.record(&result);
+        }
+        result
+    }
 
-        // Transmit the first 'length' bits of the integer 'code'. The
-        // bits are sent from MSB to LSB, i.e., first the bit at position length-1,
-        // then the bit at position length-2, and so on, till finally the bit at position 0.
-        let (first, second) = if P::values().inverted_signal {
-            (Value::Low, Value::High)
-        } else {
-            (Value::High, Value::Low)
-        };
-        let length = code_word.len() * 2;
-        for _ in 0..self.repeat_transmit {
-            debug!("Sending code: {:#X} length: {}", code, length);
-            let one = P::values().one;
-            let zero = P::values().zero;
-            for i in (0..length).rev() {
-                let s = if code & (1 << i) != 0 { &one } else { &zero };
-                self.transmit(s, &first, &second)?;
+    /// Concrete state to send. `State::Toggle` is resolved with the state cache if set.
+    fn resolve(&self, group: &str, device: &Device, state: &State) -> State {
+        match *self.state_cache.borrow() {
+            Some(ref cache) => cache.resolve(group, device, state),
+            None => state.clone(),
+        }
+    }
+
+    /// Record a sent state in the state cache. The command is already on air so a
+    /// failure to persist the state is logged only.
+    fn remember(&self, group: &str, device: &Device, state: &State) {
+        if let Some(ref mut cache) = *self.state_cache.borrow_mut() {
+            if let Err(e) = cache.record(group, device, state) {
+                warn!("Failed to record state of {} {}: {}", group, device, e);
             }
-            self.transmit(&P::values().sync_factor, &first, &second)?;
         }
+    }
 
-        // Disable transmit after sending (i.e., for inverted protocols)
-        self.pin.set(&Value::Low)?;
-        Ok(())
+    /// Send the `length` bits of a raw `code` e.g a code captured from the remote of a
+    /// self-learning socket
+    /// ```ignore
+    /// type Funksteckdose = funksteckdose::Funksteckdose<WiringPiPin, EncodingA, Protocol1>;
+    /// let d: Funksteckdose = Funksteckdose::new(WiringPiPin::new(0)?);
+    /// d.send_code(5393, 24).expect("Failed to send");
+    /// ```
+    pub fn send_code(&self, code: u64, length: usize) -> Result<(), Error> {
+        let code = Code::from_decimal(code, length)?;
+        let plan = TransmissionPlan::from_code(
+            &self.values,
+            code.code(),
+            code.length(),
+            self.repeat_transmit,
+            self.preamble.as_ref(),
+        )
+        .with_gap(self.repeat_gap);
+        self.send_plan(&plan, &code)
     }
 
-    fn transmit(&self, pulses: &HighLow, first: &Value, second: &Value) -> Result<(), Error> {
-        self.pin.set(first)?;
-        Self::delay((P::values().pulse_length * pulses.high) as u32);
-        self.pin.set(second)?;
-        Self::delay((P::values().pulse_length * pulses.low) as u32);
-        Ok(())
+    /// Send a tri-state code word e.g "0FF0FFF0FF0F"
+    pub fn send_tri_state(&self, code_word: &str) -> Result<(), Error> {
+        let code = Code::from_tri_state(code_word)?;
+        let plan = TransmissionPlan::with_values(
+            &self.values,
+            code_word.to_uppercase().as_bytes(),
+            self.repeat_transmit,
+            self.preamble.as_ref(),
+        )?
+        .with_gap(self.repeat_gap);
+        self.send_plan(&plan, &code)
     }
 
-    fn delay(micros: u32) {
-        if micros > 0 {
-            let now = std::time::Instant::now();
-            let micros = u128::from(micros);
-            while now.elapsed().as_micros() < micros {}
-        }
+    /// Send a command to a self-learning socket. The protocol and encoding of the instance
+    /// are not used, the command has its own timing.
+    pub fn send_intertechno(&self, command: &intertechno::Command) -> Result<(), Error> {
+        let plan = command.plan(self.repeat_transmit).with_gap(self.repeat_gap);
+        self.send_plan(&plan, &command.code())
     }
-}
 
-/// Number of pulses
-#[derive(Clone, Debug)]
-pub struct HighLow {
-    pub high: u64,
-    pub low: u64,
-}
+    /// Send a command to a socket of a configuration with the encoding, protocol, options
+    /// and preamble of the socket like `send`. Nothing is sent to virtual sockets.
+    #[cfg(feature = "config")]
+    pub fn send_socket(&self, socket: &config::Socket, state: &State) -> Result<(), Error> {
+        self.send_socket_with_priority(socket, state, Priority::Interactive)
+    }
 
-impl HighLow {
-    fn new(high: u64, low: u64) -> HighLow {
-        HighLow { high, low }
+    /// `send_socket` with `priority`, see `send_with_priority`
+    #[cfg(feature = "config")]
+    pub fn send_socket_with_priority(
+        &self,
+        socket: &config::Socket,
+        state: &State,
+        priority: Priority,
+    ) -> Result<(), Error> {
+        let (group, device) = (&socket.group, &socket.device);
+        let state = &self.resolve(group, device, state);
+        let plan = socket
+            .plan(state, self.repeat_transmit)
+            .map(|plan| plan.with_gap(self.repeat_gap));
+        self.send_planned(group, device, state, priority, plan, socket.is_virtual)
     }
-}
 
-/// Format for protocol definitions
-#[derive(Clone, Debug)]
-pub struct ProtocolValues {
-    pulse_length: u64,
-    sync_factor: HighLow,
-    zero: HighLow,
-    one: HighLow,
-    inverted_signal: bool,
-}
+    fn send_plan(&self, plan: &TransmissionPlan, code: &Code) -> Result<(), Error> {
+        let start = Instant::now();
+        let result = self.execute(plan);
+        match result {
+            Ok(()) => info!(
+                event = "send",
+                code = code.code(),
+                length = code.length(),
+                duration_us = start.elapsed().as_micros() as u64;
+                "Sent code {}", code
+            ),
+            Err(ref e) => warn!(
+                event = "error",
+                code = code.code(),
+                length = code.length(),
+                error = e.code(),
+                category = e.category().name();
+                "Failed to send code {}: {}", code, e
+            ),
+        }
+        self.metrics.record(&result);
+        result
+    }
 
-/// A protocol definition
-pub trait Protocol {
-    fn values() -> ProtocolValues;
-}
+    /// Plan the transmission of a command without sending it
+    pub fn plan(
+        &self,
+        group: &str,
+        device: &Device,
+        state: &State,
+    ) -> Result<TransmissionPlan, Error> {
+        let code_word = self.options.encode::<E>(group, device, state)?;
+        Ok(TransmissionPlan::with_values(
+            &self.values,
+            &code_word,
+            self.repeat_transmit,
+            self.preamble.as_ref(),
+        )?
+        .with_gap(self.repeat_gap))
+    }
 
-/// Protocol 1
-pub struct Protocol1;
+    /// Execute a plan created with `plan`
+    pub fn transmit(&self, plan: &TransmissionPlan) -> Result<(), Error> {
+        let result = self.execute(plan);
+        self.metrics.record(&result);
+        result
+    }
 
-impl Protocol for Protocol1 {
-    fn values() -> ProtocolValues {
-        ProtocolValues {
-            pulse_length: 350,
-            sync_factor: HighLow::new(1, 31),
-            zero: HighLow::new(1, 3),
-            one: HighLow::new(3, 1),
-            inverted_signal: false,
-        }
+    /// Waveform of a command without sending it e.g to print or check the exact timing
+    /// ```
+    /// use funksteckdose::{mock::NullPin, Device, EncodingA, Funksteckdose, Protocol1, State};
+    /// let d: Funksteckdose<NullPin, EncodingA, Protocol1> =
+    ///     Funksteckdose::builder(NullPin).repeat_transmit(2).build().unwrap();
+    /// let waveform = d.build_waveform("10001", &Device::A, &State::On).unwrap();
+    /// assert_eq!(waveform.len(), 2 * 25 * 2);
+    /// d.transmit_waveform(&waveform).unwrap();
+    /// ```
+    pub fn build_waveform(
+        &self,
+        group: &str,
+        device: &Device,
+        state: &State,
+    ) -> Result<Waveform, Error> {
+        self.plan(group, device, state)
+            .map(|plan| Waveform::from(&plan))
     }
-}
 
-/// Protocol 2
-pub struct Protocol2;
+    /// Send a waveform created with `build_waveform` or `Waveform::from`
+    pub fn transmit_waveform(&self, waveform: &Waveform) -> Result<(), Error> {
+        let result = self.execute_waveform(waveform);
+        self.metrics.record(&result);
+        result
+    }
 
-impl Protocol for Protocol2 {
-    fn values() -> ProtocolValues {
-        ProtocolValues {
-            pulse_length: 650,
-            sync_factor: HighLow::new(1, 10),
-            zero: HighLow::new(1, 2),
-            one: HighLow::new(2, 1),
-            inverted_signal: false,
+    /// Execute several plans with their frames interleaved i.e the first frame of every
+    /// plan, then the second frame of every plan and so on. All sockets receive a frame
+    /// after the airtime of one frame per plan instead of after all frames of the
+    /// preceding plans.
+    pub fn transmit_interleaved(&self, plans: &[TransmissionPlan]) -> Result<(), Error> {
+        let result = self.execute_all(plans);
+        for _ in plans {
+            self.metrics.record(&result);
         }
+        result
     }
-}
 
-/// Protocol 3
-pub struct Protocol3;
+    /// Replace the timings of `P` e.g with a protocol selected at runtime
+    pub fn set_protocol(&mut self, values: ProtocolValues) {
+        self.values = values;
+    }
 
-impl Protocol for Protocol3 {
-    fn values() -> ProtocolValues {
-        ProtocolValues {
-            pulse_length: 100,
-            sync_factor: HighLow::new(30, 71),
-            zero: HighLow::new(4, 11),
-            one: HighLow::new(9, 6),
-            inverted_signal: false,
-        }
+    /// Timings used for sending
+    pub fn protocol(&self) -> &ProtocolValues {
+        &self.values
     }
-}
 
-/// Protocol 4
-pub struct Protocol4;
+    /// Set the encoding options for clone sockets
+    pub fn set_encoding_options(&mut self, options: EncodingOptions) {
+        self.options = options;
+    }
 
-impl Protocol for Protocol4 {
-    fn values() -> ProtocolValues {
-        ProtocolValues {
-            pulse_length: 380,
-            sync_factor: HighLow::new(1, 6),
-            zero: HighLow::new(1, 3),
-            one: HighLow::new(3, 1),
-            inverted_signal: false,
+    /// Wait for `trigger` before each transmission e.g to align it to a zero crossing
+    /// of the mains voltage
+    pub fn set_trigger(&mut self, trigger: Option<Box<dyn Trigger + Send + Sync>>) {
+        self.trigger = trigger;
+    }
+
+    /// Drive `enable` high before each transmission and low afterwards e.g the enable line
+    /// or a transistor switching the supply of the transmitter module. Combine it with
+    /// `set_warm_up` for modules that need time to start their oscillator.
+    /// ```
+    /// use funksteckdose::{mock::{NullPin, RecordingPin}, Device, EncodingA, Protocol1, State};
+    /// use funksteckdose::{Funksteckdose, Value};
+    /// let enable = RecordingPin::new();
+    /// let mut d: Funksteckdose<NullPin, EncodingA, Protocol1> = Funksteckdose::new(NullPin);
+    /// d.set_enable_pin(Some(Box::new(enable.clone())));
+    /// d.send("10011", &Device::A, &State::On)?;
+    /// assert_eq!(enable.values(), vec![Value::High, Value::Low]);
+    /// # Ok::<(), funksteckdose::error::Error>(())
+    /// ```
+    pub fn set_enable_pin(&mut self, enable: Option<Box<dyn Pin + Send + Sync>>) {
+        self.enable = enable.map(EnablePin);
+    }
+
+    /// Send `preamble` before the first frame of each transmission. Overrides the
+    /// preamble of the protocol.
+    pub fn set_preamble(&mut self, preamble: Option<Preamble>) {
+        self.preamble = preamble;
+    }
+
+    /// Keep `gap` of silence between repeated frames in addition to the sync. Real remotes
+    /// pause between frames and some receivers miss frames sent back-to-back. The gap is
+    /// applied with µs resolution. Default: 0
+    pub fn set_repeat_gap(&mut self, gap: Duration) {
+        self.repeat_gap = gap.as_micros().min(u128::from(u32::MAX)) as u32;
+    }
+
+    /// Raise the thread to `SCHED_FIFO` with `priority` and lock the memory while the
+    /// pulses are sent, see [`realtime`]. `None` disables it. Default: `None`
+    #[cfg(all(feature = "realtime", target_os = "linux"))]
+    pub fn set_realtime(&mut self, priority: Option<i32>) {
+        self.realtime = priority;
+    }
+
+    /// Reduce background transmissions during `quiet_hours`
+    pub fn set_quiet_hours(&mut self, quiet_hours: Option<QuietHours>) {
+        self.quiet_hours = quiet_hours;
+    }
+
+    /// Track the last sent state of each socket in `cache` to send `State::Toggle`.
+    /// Without a cache toggling fails since the sockets have no feedback channel.
+    /// ```
+    /// use funksteckdose::{mock::NullPin, state::StateCache, Device, EncodingA};
+    /// use funksteckdose::{Funksteckdose, Protocol1, State};
+    /// let mut d: Funksteckdose<NullPin, EncodingA, Protocol1> = Funksteckdose::new(NullPin);
+    /// d.set_state_cache(Some(StateCache::new()));
+    /// d.send("10001", &Device::A, &State::Toggle).expect("Failed to send");
+    /// assert_eq!(d.last_state("10001", &Device::A), Some(State::On));
+    /// ```
+    pub fn set_state_cache(&mut self, cache: Option<StateCache>) {
+        self.state_cache = RefCell::new(cache);
+    }
+
+    /// Last sent state of a socket if a state cache is set
+    pub fn last_state(&self, group: &str, device: &Device) -> Option<State> {
+        self.state_cache
+            .borrow()
+            .as_ref()
+            .and_then(|cache| cache.get(group, device).cloned())
+    }
+
+    /// Wait `warm_up` before each transmission e.g until the oscillator of the transmitter
+    /// is stable. The warm-up is applied before waiting for the trigger. Default: 0
+    pub fn set_warm_up(&mut self, warm_up: Duration) {
+        self.warm_up = warm_up;
+    }
+
+    /// Wait `cool_down` after each transmission e.g to give receivers a gap after the
+    /// last frame. Default: 0
+    pub fn set_cool_down(&mut self, cool_down: Duration) {
+        self.cool_down = cool_down;
+    }
+
+    /// Abort transmissions that take longer than `factor` times their airtime. `None`
+    /// disables the watchdog. Default: 4
+    ///
+    /// The elapsed time is only checked between pulses of bit banged transmissions, a
+    /// pin that blocks in `set` is not interrupted. Pins that send whole waveforms with
+    /// `transmit_waveform` e.g pigpiod with DMA are not checked at all.
+    pub fn set_watchdog(&mut self, factor: Option<u32>) {
+        self.watchdog_factor = factor;
+    }
+
+    /// Reject transmissions with an airtime above `max_airtime` e.g caused by a custom
+    /// protocol or a repeat count that would jam the band for seconds. `None` disables
+    /// the limit. Default: [`MAX_AIRTIME`]
+    pub fn set_max_airtime(&mut self, max_airtime: Option<Duration>) {
+        self.max_airtime = max_airtime;
+    }
+
+    /// Counters of sent commands and errors. Share them with `set_metrics` to aggregate
+    /// several instances.
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Use `metrics` for counting
+    pub fn set_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = metrics;
+    }
+
+    /// False if the last transmission was aborted by the watchdog
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+
+    /// Measure the overhead of the pin and delay and shorten all pulses accordingly.
+    /// Most useful for slow backends like sysfs or I2C expanders.
+    pub fn calibrate(&mut self) -> Result<Calibration, Error> {
+        let calibration = Calibration::measure(&self.pin, &self.delay, 1000)?;
+        debug!("Calibrated timing: {:?}", calibration);
+        self.compensation = calibration.compensation();
+        Ok(calibration)
+    }
+
+    fn execute(&self, plan: &TransmissionPlan) -> Result<(), Error> {
+        self.execute_all(std::slice::from_ref(plan))
+    }
+
+    /// Send the plans in rounds. Each round sends the next frame of every plan that has
+    /// frames left. A preamble is sent before the first frame of its plan.
+    fn execute_all(&self, plans: &[TransmissionPlan]) -> Result<(), Error> {
+        for plan in plans {
+            debug!("Sending code: {:#X} length: {}", plan.code, plan.length);
         }
+        self.execute_waveform(&Waveform::interleave(plans))
     }
-}
 
-/// Protocol 4
-pub struct Protocol5;
+    fn execute_waveform(&self, waveform: &Waveform) -> Result<(), Error> {
+        let airtime = waveform.duration();
+        if let Some(max_airtime) = self.max_airtime {
+            if airtime > max_airtime {
+                return Err(Error::AirtimeExceeded(airtime, max_airtime));
+            }
+        }
+        // Abort if the transmission takes much longer than its airtime e.g because the
+        // backend is very slow. Only checked between bit banged pulses, see `set_watchdog`.
+        let limit = self.watchdog_factor.map(|factor| airtime * factor);
 
-impl Protocol for Protocol5 {
-    fn values() -> ProtocolValues {
-        ProtocolValues {
-            pulse_length: 500,
-            sync_factor: HighLow::new(6, 14),
-            zero: HighLow::new(1, 2),
-            one: HighLow::new(2, 1),
-            inverted_signal: false,
+        // The transmitter is powered only while sending, also if sending fails
+        if let Some(ref enable) = self.enable {
+            enable.0.set(&Value::High)?;
         }
+        let result = self.send_pulses(waveform, limit);
+        match self.enable {
+            Some(ref enable) => result.and(enable.0.set(&Value::Low).map_err(Error::from))?,
+            None => result?,
+        }
+        self.finish()
     }
-}
 
+    fn send_pulses(&self, waveform: &Waveform, limit: Option<Duration>) -> Result<(), Error> {
+        if self.warm_up > Duration::from_millis(0) {
+            thread::sleep(self.warm_up);
+        }
+        if let Some(ref trigger) = self.trigger {
+            trigger.wait()?;
+        }
+
+        // Pins that send whole waveforms e.g with DMA need no timing from us
+        if let Some(result) = self.pin.transmit_waveform(&waveform.pulses) {
+            return Ok(result?);
+        }
 
-/// Protocol HT6P20B
-pub struct ProtocolHT6P20B;
+        let result = self.bit_bang(&waveform.pulses, limit);
 
-impl Protocol for ProtocolHT6P20B {
-    fn values() -> ProtocolValues {
-        ProtocolValues {
-            pulse_length: 450,
-            sync_factor: HighLow::new(23, 1),
-            zero: HighLow::new(1, 2),
-            one: HighLow::new(2, 1),
-            inverted_signal: true,
+        if let Err(Error::Timeout(elapsed)) = result {
+            error!(
+                "Transmission aborted after {:?}. Marking transmitter unhealthy",
+                elapsed
+            );
+            self.healthy.store(false, Ordering::SeqCst);
+            let _ = self.pin.set(&Value::Low);
+            return result;
         }
+        result?;
+
+        // Disable transmit after sending (i.e., for inverted protocols)
+        Ok(self.pin.set(&Value::Low)?)
     }
-}
 
-/// Protocol HS2303-PT, i. e. used in AUKEY Remote
-pub struct ProtocolHS2303;
+    /// Time the pulses by busy waiting, with real-time scheduling if enabled
+    fn bit_bang(&self, pulses: &[(Value, u32)], limit: Option<Duration>) -> Result<(), Error> {
+        #[cfg(all(feature = "realtime", target_os = "linux"))]
+        let _realtime = self.realtime.and_then(|priority| {
+            realtime::Realtime::enter(priority)
+                .map_err(|e| warn!("Sending without real-time scheduling: {}", e))
+                .ok()
+        });
+        let start = Instant::now();
+        pulses.iter().try_for_each(|(value, micros)| {
+            self.pin.set(value)?;
+            self.delay.delay(micros.saturating_sub(self.compensation));
+            match limit {
+                Some(limit) if start.elapsed() > limit => Err(Error::Timeout(start.elapsed())),
+                _ => Ok(()),
+            }
+        })
+    }
 
-impl Protocol for ProtocolHS2303 {
-    fn values() -> ProtocolValues {
-        ProtocolValues {
-            pulse_length: 150,
-            sync_factor: HighLow::new(2, 62),
-            zero: HighLow::new(1, 6),
-            one: HighLow::new(6, 1),
-            inverted_signal: false,
+    fn finish(&self) -> Result<(), Error> {
+        self.healthy.store(true, Ordering::SeqCst);
+        if self.cool_down > Duration::from_millis(0) {
+            thread::sleep(self.cool_down);
         }
+        Ok(())
     }
 }
 
 /// A implementation of Pin to be used with wiringpi on a Raspberry
 ///
-///```
-/// let pin = WiringPiPin::new(0);
-/// let funksteckdose = Funksteckdose::new(pin, 1).unwrap();
-/// funksteckdose.send("10011", "10000", State::On);
+/// The wiringpi C library terminates the process on hosts it does not know. The pins check
+/// the host first and return an error instead, so a binary built with all features still
+/// runs elsewhere e.g with the `gpio-cdev` backend.
+///
+///```ignore
+/// let pin = WiringPiPin::new(0)?;
+/// let funksteckdose = Funksteckdose::new(pin);
+/// funksteckdose.send("10011", &Device::A, &State::On)?;
 ///```
 #[cfg(feature = "wiringpi")]
 pub mod wiringpi {
-    use super::{Error, Pin, Value};
+    use super::{error::CoreError, InputPin, Pin, Value};
+    use std::fs;
+
+    /// Check that the host is a Raspberry Pi that wiringpi can handle. wiringpi identifies
+    /// the board by the `Hardware` and `Revision` lines of `/proc/cpuinfo`.
+    pub fn check_host() -> Result<(), CoreError> {
+        if !cfg!(any(target_arch = "arm", target_arch = "aarch64")) {
+            return Err(unsupported(&format!(
+                "{} is not a Raspberry Pi",
+                std::env::consts::ARCH
+            )));
+        }
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo")
+            .map_err(|e| unsupported(&format!("cannot read /proc/cpuinfo: {}", e)))?;
+        let field = |name: &str| {
+            cpuinfo.lines().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                Some(value.trim()).filter(|_| key.trim() == name)
+            })
+        };
+        match (field("Hardware"), field("Revision")) {
+            (Some(hardware), Some(_)) if hardware.starts_with("BCM") => Ok(()),
+            (Some(hardware), _) => Err(unsupported(&format!("unknown board {}", hardware))),
+            _ => Err(unsupported("no board information in /proc/cpuinfo")),
+        }
+    }
+
+    fn unsupported(reason: &str) -> CoreError {
+        CoreError::Gpio(format!("wiringpi: {}. Try the gpio-cdev backend", reason).into())
+    }
 
     pub struct WiringPiPin {
         pin: wiringpi::pin::OutputPin<wiringpi::pin::WiringPi>,
     }
 
     impl WiringPiPin {
-        pub fn new(pin: u16) -> WiringPiPin {
+        /// Set up wiringpi and use `pin` as output. Fails on hosts that wiringpi does not
+        /// support, see `check_host`.
+        pub fn new(pin: u16) -> Result<WiringPiPin, CoreError> {
+            check_host()?;
             let pi = wiringpi::setup();
-            WiringPiPin {
+            Ok(WiringPiPin {
                 pin: pi.output_pin(pin),
-            }
+            })
         }
     }
 
     impl Pin for WiringPiPin {
-        fn set(&self, value: &Value) -> Result<(), Error> {
+        fn set(&self, value: &Value) -> Result<(), CoreError> {
             match value {
                 Value::High => self.pin.digital_write(wiringpi::pin::Value::High),
                 Value::Low => self.pin.digital_write(wiringpi::pin::Value::Low),
@@ -432,4 +917,235 @@ pub mod wiringpi {
             Ok(())
         }
     }
+
+    pub struct WiringPiInputPin {
+        pin: wiringpi::pin::InputPin<wiringpi::pin::WiringPi>,
+    }
+
+    impl WiringPiInputPin {
+        /// Set up wiringpi and use `pin` as input. Fails on hosts that wiringpi does not
+        /// support, see `check_host`.
+        pub fn new(pin: u16) -> Result<WiringPiInputPin, CoreError> {
+            check_host()?;
+            let pi = wiringpi::setup();
+            Ok(WiringPiInputPin {
+                pin: pi.input_pin(pin),
+            })
+        }
+    }
+
+    impl InputPin for WiringPiInputPin {
+        fn get(&self) -> Result<Value, CoreError> {
+            match self.pin.digital_read() {
+                wiringpi::pin::Value::High => Ok(Value::High),
+                wiringpi::pin::Value::Low => Ok(Value::Low),
+            }
+        }
+    }
+}
+
+/// Implementations of Pin and InputPin on top of the Linux GPIO character device e.g
+/// `/dev/gpiochip0`. Lines are addressed by their offset which is the BCM number on a
+/// Raspberry Pi.
+///
+///```ignore
+/// let pin = GpioCdevPin::new("/dev/gpiochip0", 17)?;
+/// let funksteckdose: Funksteckdose<_, EncodingA, Protocol1> = Funksteckdose::new(pin);
+/// funksteckdose.send("10011", &Device::A, &State::On)?;
+///```
+#[cfg(feature = "gpio-cdev")]
+pub mod gpio_cdev {
+    use super::{error::CoreError, InputPin, Pin, Value};
+    use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+    use std::path::Path;
+
+    const CONSUMER: &str = "funksteckdose";
+
+    fn request<P: AsRef<Path>>(
+        chip: P,
+        offset: u32,
+        flags: LineRequestFlags,
+    ) -> Result<LineHandle, CoreError> {
+        Chip::new(chip)
+            .and_then(|mut chip| chip.get_line(offset))
+            .and_then(|line| line.request(flags, 0, CONSUMER))
+            .map_err(CoreError::gpio)
+    }
+
+    pub struct GpioCdevPin {
+        line: LineHandle,
+    }
+
+    impl GpioCdevPin {
+        /// Request line `offset` of `chip` as output. The line is released on drop.
+        pub fn new<P: AsRef<Path>>(chip: P, offset: u32) -> Result<GpioCdevPin, CoreError> {
+            let line = request(chip, offset, LineRequestFlags::OUTPUT)?;
+            Ok(GpioCdevPin { line })
+        }
+    }
+
+    impl Pin for GpioCdevPin {
+        fn set(&self, value: &Value) -> Result<(), CoreError> {
+            let value = match value {
+                Value::High => 1,
+                Value::Low => 0,
+            };
+            self.line.set_value(value).map_err(CoreError::gpio)
+        }
+    }
+
+    pub struct GpioCdevInputPin {
+        line: LineHandle,
+    }
+
+    impl GpioCdevInputPin {
+        /// Request line `offset` of `chip` as input. The line is released on drop.
+        pub fn new<P: AsRef<Path>>(chip: P, offset: u32) -> Result<GpioCdevInputPin, CoreError> {
+            let line = request(chip, offset, LineRequestFlags::INPUT)?;
+            Ok(GpioCdevInputPin { line })
+        }
+    }
+
+    impl InputPin for GpioCdevInputPin {
+        fn get(&self) -> Result<Value, CoreError> {
+            match self.line.get_value().map_err(CoreError::gpio)? {
+                0 => Ok(Value::Low),
+                _ => Ok(Value::High),
+            }
+        }
+    }
+}
+
+/// A implementation of Pin that sends through the pigpio daemon e.g on a remote Raspberry
+#[cfg(feature = "pigpiod")]
+pub mod pigpiod {
+    use super::{
+        error::CoreError,
+        plan::{TransmissionPlan, Waveform},
+        Pin, Value,
+    };
+    use std::{
+        io::{Read, Write},
+        net::{TcpStream, ToSocketAddrs},
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    /// Default address of the pigpio daemon
+    pub const ADDRESS: &str = "localhost:8888";
+
+    // Commands of the socket interface, see http://abyz.me.uk/rpi/pigpio/sif.html
+    const MODES: u32 = 0;
+    const WRITE: u32 = 4;
+    const WVBSY: u32 = 32;
+    const WVAG: u32 = 28;
+    const WVCRE: u32 = 49;
+    const WVDEL: u32 = 50;
+    const WVTX: u32 = 51;
+    const WVNEW: u32 = 53;
+
+    const OUTPUT: u32 = 1;
+
+    fn failed(reason: &str) -> CoreError {
+        CoreError::Gpio(format!("pigpiod: {}", reason).into())
+    }
+
+    /// A GPIO of a host running the pigpio daemon, which may be another host than the one
+    /// running this crate. Transmissions are sent as DMA waveforms with µs accuracy.
+    /// Clones share the connection.
+    #[derive(Clone, Debug)]
+    pub struct PigpiodPin {
+        stream: Arc<Mutex<TcpStream>>,
+        gpio: u32,
+    }
+
+    impl PigpiodPin {
+        /// Connect to the daemon at `address` e.g [`ADDRESS`] and use the Broadcom GPIO
+        /// `gpio` as output
+        pub fn new<A: ToSocketAddrs>(address: A, gpio: u32) -> Result<PigpiodPin, CoreError> {
+            let stream = TcpStream::connect(address).map_err(CoreError::gpio)?;
+            stream.set_nodelay(true).map_err(CoreError::gpio)?;
+            let pin = PigpiodPin {
+                stream: Arc::new(Mutex::new(stream)),
+                gpio,
+            };
+            pin.command(MODES, gpio, OUTPUT, &[])?;
+            Ok(pin)
+        }
+
+        /// Send all symbols of `plan` as one waveform and wait until it is sent. The GPIO
+        /// is left low.
+        pub fn transmit(&self, plan: &TransmissionPlan) -> Result<(), CoreError> {
+            self.send_wave(&Waveform::from(plan).pulses)
+        }
+
+        fn send_wave(&self, waveform: &[(Value, u32)]) -> Result<(), CoreError> {
+            let mask = 1u32 << self.gpio;
+            let mut pulses = Vec::with_capacity(waveform.len() * 12);
+            let mut duration = 0;
+            for (value, micros) in waveform {
+                let (on, off) = match value {
+                    Value::High => (mask, 0),
+                    Value::Low => (0, mask),
+                };
+                for word in [on, off, *micros] {
+                    pulses.extend_from_slice(&word.to_le_bytes());
+                }
+                duration += u64::from(*micros);
+            }
+
+            self.command(WVNEW, 0, 0, &[])?;
+            self.command(WVAG, 0, 0, &pulses)?;
+            let wave = self.command(WVCRE, 0, 0, &[])?;
+            let result = self.command(WVTX, wave, 0, &[]).and_then(|_| {
+                thread::sleep(Duration::from_micros(duration));
+                while self.command(WVBSY, 0, 0, &[])? != 0 {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                Ok(())
+            });
+            self.command(WVDEL, wave, 0, &[])?;
+            result.and_then(|_| self.set(&Value::Low))
+        }
+
+        /// Send a command with the extension `ext` and return its non-negative result
+        fn command(&self, command: u32, p1: u32, p2: u32, ext: &[u8]) -> Result<u32, CoreError> {
+            let mut request = Vec::with_capacity(16 + ext.len());
+            for word in [command, p1, p2, ext.len() as u32] {
+                request.extend_from_slice(&word.to_le_bytes());
+            }
+            request.extend_from_slice(ext);
+
+            // A panic while sending leaves the stream usable for the next command
+            let mut stream = self.stream.lock().unwrap_or_else(|e| e.into_inner());
+            stream.write_all(&request).map_err(CoreError::gpio)?;
+            let mut response = [0u8; 16];
+            stream.read_exact(&mut response).map_err(CoreError::gpio)?;
+            let result =
+                i32::from_le_bytes([response[12], response[13], response[14], response[15]]);
+            if result < 0 {
+                Err(failed(&format!(
+                    "command {} failed with {}",
+                    command, result
+                )))
+            } else {
+                Ok(result as u32)
+            }
+        }
+    }
+
+    impl Pin for PigpiodPin {
+        fn set(&self, value: &Value) -> Result<(), CoreError> {
+            let level = match value {
+                Value::High => 1,
+                Value::Low => 0,
+            };
+            self.command(WRITE, self.gpio, level, &[]).map(|_| ())
+        }
+
+        fn transmit_waveform(&self, pulses: &[(Value, u32)]) -> Option<Result<(), CoreError>> {
+            Some(self.send_wave(pulses))
+        }
+    }
 }
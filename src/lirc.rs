@@ -0,0 +1,164 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Import of raw codes from LIRC configurations
+//!
+//! Only the `raw_codes` sections of `lircd.conf` files are read. Each raw code is a list of
+//! alternating pulse and space durations in µs starting and ending with a pulse. The `gap`
+//! of the remote is used as the space after the last pulse.
+//!
+//! ```
+//! use funksteckdose::lirc;
+//!
+//! let config = "
+//! begin remote
+//!   name  rf
+//!   flags RAW_CODES
+//!   gap   10850
+//!   begin raw_codes
+//!     name on
+//!       1050 350 350 1050 1050 350 350
+//!   end raw_codes
+//! end remote";
+//! let codes = lirc::parse(config).unwrap();
+//! let (values, code, length) = codes[0].protocol().unwrap();
+//! assert_eq!(values.pulse_length(), 350);
+//! assert_eq!((code, length), (0b101, 3));
+//! ```
+
+use crate::{
+    error::Error,
+    plan::{Symbol, TransmissionPlan},
+    HighLow, ProtocolValues,
+};
+use std::{fs, path::Path};
+
+/// A raw code of a LIRC remote
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawCode {
+    /// Name of the remote
+    pub remote: String,
+    /// Name of the code
+    pub name: String,
+    /// Alternating pulse and space durations in µs
+    pub timings: Vec<u32>,
+    /// Space after the last pulse in µs
+    pub gap: u32,
+}
+
+impl RawCode {
+    /// Pulse and space pairs. The last pulse is followed by the gap.
+    pub fn symbols(&self) -> Vec<Symbol> {
+        self.timings
+            .chunks(2)
+            .map(|pair| Symbol {
+                high: pair[0],
+                low: pair.get(1).cloned().unwrap_or(self.gap),
+            })
+            .collect()
+    }
+
+    /// Plan to send the recorded symbols `repeats` times
+    pub fn plan(&self, repeats: usize) -> TransmissionPlan {
+        TransmissionPlan::from_symbols(self.symbols(), repeats)
+    }
+
+    /// Derive an rc-switch like protocol definition from the timings. The last symbol is
+    /// the sync, all other symbols must be one of two data symbols. The one with the shorter
+    /// pulse is a zero. Returns the protocol, the code and its length in bits.
+    pub fn protocol(&self) -> Option<(ProtocolValues, u64, usize)> {
+        let symbols = self.symbols();
+        let (sync, data) = symbols.split_last()?;
+        let pulse_length = data.iter().flat_map(|s| vec![s.high, s.low]).min()?;
+        if pulse_length == 0 || data.len() > 64 {
+            return None;
+        }
+        let pulses = |s: &Symbol| {
+            let round = |d: u32| u64::from((d + pulse_length / 2) / pulse_length);
+            HighLow::new(round(s.high), round(s.low))
+        };
+
+        let mut kinds: Vec<HighLow> = Vec::new();
+        let data = data.iter().map(pulses).collect::<Vec<_>>();
+        for s in &data {
+            if !kinds.contains(s) {
+                kinds.push(s.clone());
+            }
+        }
+        kinds.sort_by_key(|s| s.high);
+        let (zero, one) = match kinds.as_slice() {
+            [zero, one] => (zero.clone(), one.clone()),
+            _ => return None,
+        };
+        let code = data
+            .iter()
+            .fold(0u64, |code, s| (code << 1) | u64::from(*s == one));
+        let values = ProtocolValues::new(u64::from(pulse_length), pulses(sync), zero, one, false);
+        Some((values, code, data.len()))
+    }
+}
+
+/// Parse the raw codes of all remotes in a LIRC configuration
+pub fn parse(config: &str) -> Result<Vec<RawCode>, Error> {
+    let mut codes: Vec<RawCode> = Vec::new();
+    let mut remote = String::new();
+    let mut gap = 0;
+    let mut raw = false;
+
+    for (number, line) in config.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        let invalid = || Error::InvalidLirc(format!("line {}: {}", number + 1, line.trim()));
+        match tokens.as_slice() {
+            [] => (),
+            ["begin", "remote"] => {
+                remote.clear();
+                gap = 0;
+            }
+            ["begin", "raw_codes"] => raw = true,
+            ["end", "raw_codes"] => raw = false,
+            ["name", name] if raw => codes.push(RawCode {
+                remote: remote.clone(),
+                name: name.to_string(),
+                timings: Vec::new(),
+                gap,
+            }),
+            ["name", name] => remote = name.to_string(),
+            ["gap", value, ..] if !raw => gap = value.parse().map_err(|_| invalid())?,
+            timings if raw => {
+                let code = codes.last_mut().ok_or_else(invalid)?;
+                for timing in timings {
+                    code.timings.push(timing.parse().map_err(|_| invalid())?);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    match codes.iter().find(|code| code.timings.is_empty()) {
+        Some(code) => Err(Error::InvalidLirc(format!("{} has no timings", code.name))),
+        None => Ok(codes),
+    }
+}
+
+/// Read the raw codes from a LIRC configuration file
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<RawCode>, Error> {
+    parse(&fs::read_to_string(path)?)
+}
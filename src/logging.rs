@@ -0,0 +1,175 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Log output as plain text or as one JSON object per line. Key value pairs of log
+//! records e.g `event`, `group`, `code` or `duration_us` become fields.
+
+use crate::error::Error;
+use log::{
+    kv::{self, Key, Value, VisitSource},
+    Level, LevelFilter, Log, Metadata, Record, SetLoggerError,
+};
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Format of log lines
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// `INFO funksteckdose: Sent 10001 A on event=send ...`
+    Text,
+    /// `{"timestamp":1561500000000,"level":"INFO",...,"event":"send",...}`
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(Error::InvalidLogFormat(s.into())),
+        }
+    }
+}
+
+/// Logger writing to stderr
+#[derive(Debug)]
+pub struct Logger {
+    format: Format,
+    level: LevelFilter,
+}
+
+impl Logger {
+    pub fn new(format: Format, level: LevelFilter) -> Logger {
+        Logger { format, level }
+    }
+
+    /// Install this logger as the global logger
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+
+    /// Format `record` without the trailing newline
+    pub fn format(&self, record: &Record) -> String {
+        match self.format {
+            Format::Text => {
+                let mut line = format!("{} {}: {}", record.level(), record.target(), record.args());
+                let mut fields = TextFields(&mut line);
+                let _ = record.key_values().visit(&mut fields);
+                line
+            }
+            Format::Json => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis())
+                    .unwrap_or(0);
+                let mut line = format!(
+                    "{{\"timestamp\":{},\"level\":\"{}\",\"target\":",
+                    timestamp,
+                    record.level()
+                );
+                json_string(&mut line, record.target());
+                line.push_str(",\"message\":");
+                json_string(&mut line, &record.args().to_string());
+                let mut fields = JsonFields(&mut line);
+                let _ = record.key_values().visit(&mut fields);
+                line.push('}');
+                line
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = self.format(record);
+            let _ = writeln!(io::stderr(), "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Logger {
+        Logger::new(Format::Text, Level::Info.to_level_filter())
+    }
+}
+
+struct TextFields<'a>(&'a mut String);
+
+impl<'kvs, 'a> VisitSource<'kvs> for TextFields<'a> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let _ = write!(self.0, " {}={}", key, value);
+        Ok(())
+    }
+}
+
+struct JsonFields<'a>(&'a mut String);
+
+impl<'kvs, 'a> VisitSource<'kvs> for JsonFields<'a> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push(',');
+        json_string(self.0, key.as_str());
+        self.0.push(':');
+        if let Some(n) = value.to_u64() {
+            let _ = write!(self.0, "{}", n);
+        } else if let Some(n) = value.to_i64() {
+            let _ = write!(self.0, "{}", n);
+        } else if let Some(b) = value.to_bool() {
+            let _ = write!(self.0, "{}", b);
+        } else {
+            json_string(self.0, &value.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Append `s` as quoted and escaped JSON string
+fn json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,239 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//! Sending a command to a recording pin and decoding the recording with the receiver.
+//!
+//! Encoder and decoder are written independently. A command that is not decoded back into
+//! itself points to a bug in one of them, most often an off-by-one in the bit order or a
+//! sync that the decoder does not detect. [`Loopback`] lists the symbols of the
+//! transmission next to the bits the decoder saw:
+//!
+//! ```
+//! use funksteckdose::{loopback::Loopback, Device, EncodingA, Protocol, Protocol1, State};
+//!
+//! let loopback = Loopback::run::<EncodingA>(&Protocol1::values(), "10011", &Device::A, &State::On)
+//!     .unwrap();
+//! assert!(loopback.is_match());
+//! ```
+
+use crate::{
+    catalog,
+    error::Error,
+    mock::Recorder,
+    plan::Symbol,
+    receiver::{Decoder, Received},
+    Device, DynProtocol, Encoding, Funksteckdose, ProtocolValues, State,
+};
+use std::fmt;
+
+/// Number of frames sent. The decoder needs two.
+const REPEATS: usize = 3;
+
+/// One symbol of the transmission next to its decoding
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymbolDiff {
+    /// Sent bit. `None` for the sync and for bits the decoder saw in addition.
+    pub sent: Option<bool>,
+    /// Recorded durations
+    pub recorded: Option<Symbol>,
+    /// Decoded bit. `None` if the decoder saw fewer bits or nothing at all.
+    pub received: Option<bool>,
+}
+
+impl SymbolDiff {
+    /// True if sent and decoded bit differ
+    pub fn differs(&self) -> bool {
+        self.sent != self.received
+    }
+}
+
+/// A command sent to a recording pin and what the receiver made of it
+#[derive(Clone, Debug)]
+pub struct Loopback {
+    /// Timing of the transmission
+    pub values: ProtocolValues,
+    /// Sent code
+    pub code: u64,
+    /// Number of bits of `code`
+    pub length: usize,
+    /// Group, device and state of the command
+    pub command: (String, Device, State),
+    /// Symbols of the first recorded frame including the sync
+    pub recorded: Vec<Symbol>,
+    /// Decoded code. `None` if the decoder did not recognize the transmission.
+    pub received: Option<Received>,
+    /// Group, device and state decoded with the encoding of the command
+    pub decoded: Option<(String, Device, State)>,
+}
+
+impl Loopback {
+    /// Send a command encoded with `E` with the timing `values` and decode the recording
+    pub fn run<E: Encoding>(
+        values: &ProtocolValues,
+        group: &str,
+        device: &Device,
+        state: &State,
+    ) -> Result<Loopback, Error> {
+        let recorder = Recorder::default();
+        let mut funksteckdose: Funksteckdose<Recorder, E, DynProtocol, Recorder> =
+            Funksteckdose::with_delay(recorder.clone(), REPEATS, recorder.clone());
+        funksteckdose.set_protocol(values.clone());
+        funksteckdose.set_watchdog(None);
+        let plan = funksteckdose.plan(group, device, state)?;
+        funksteckdose.transmit(&plan)?;
+
+        let durations: Vec<u32> = recorder.pulses().iter().map(|(_, d)| *d).collect();
+        let recorded = durations
+            .chunks(2)
+            .take(plan.length + 1)
+            .filter(|pulse| pulse.len() == 2)
+            .map(|pulse| Symbol {
+                high: pulse[0],
+                low: pulse[1],
+            })
+            .collect();
+        let mut decoder = Decoder::default();
+        let received = durations.iter().find_map(|d| decoder.push(*d));
+        let decoded = received.as_ref().and_then(|r| r.decode::<E>());
+
+        Ok(Loopback {
+            values: values.clone(),
+            code: plan.code,
+            length: plan.length,
+            command: (group.to_string(), device.clone(), state.clone()),
+            recorded,
+            received,
+            decoded,
+        })
+    }
+
+    /// True if the decoder recovered the code, the protocol and the command
+    pub fn is_match(&self) -> bool {
+        let code = self
+            .received
+            .as_ref()
+            .map(|r| (r.code, r.length) == (self.code, self.length))
+            .unwrap_or(false);
+        code && self.protocol_matches() && self.decoded.as_ref() == Some(&self.command)
+    }
+
+    /// True if the decoder detected a protocol with the sent timing. Protocols that
+    /// differ only in the pulse length cannot be told apart.
+    pub fn protocol_matches(&self) -> bool {
+        let received = self
+            .received
+            .as_ref()
+            .and_then(|r| catalog::protocol(r.protocol));
+        match received {
+            Some(received) => {
+                received.sync_factor() == self.values.sync_factor()
+                    && received.zero() == self.values.zero()
+                    && received.one() == self.values.one()
+                    && received.inverted_signal() == self.values.inverted_signal()
+            }
+            None => false,
+        }
+    }
+
+    /// Sent and decoded bits from the most significant bit followed by the sync
+    pub fn diff(&self) -> Vec<SymbolDiff> {
+        let bit = |code: u64, length: usize, i: usize| code & (1 << (length - 1 - i)) != 0;
+        let received_length = self.received.as_ref().map(|r| r.length).unwrap_or(0);
+        (0..self.length.max(received_length) + 1)
+            .map(|i| {
+                let sent = if i < self.length {
+                    Some(bit(self.code, self.length, i))
+                } else {
+                    None
+                };
+                let received = self
+                    .received
+                    .as_ref()
+                    .filter(|r| i < r.length)
+                    .map(|r| bit(r.code, r.length, i));
+                SymbolDiff {
+                    sent,
+                    recorded: self.recorded.get(i).cloned(),
+                    received,
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Loopback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (group, device, state) = &self.command;
+        writeln!(f, "command:  group {} device {} {}", group, device, state)?;
+        writeln!(f, "sent:     {:#X} length {}", self.code, self.length)?;
+        match self.received {
+            Some(ref received) => writeln!(f, "received: {}", received)?,
+            None => writeln!(f, "received: -")?,
+        }
+        match self.decoded {
+            Some((ref group, ref device, ref state)) => {
+                writeln!(f, "decoded:  group {} device {} {}", group, device, state)?
+            }
+            None => writeln!(f, "decoded:  -")?,
+        }
+        if self.is_match() {
+            return write!(f, "match");
+        }
+        writeln!(f, "mismatch")?;
+        if self.received.is_some() && !self.protocol_matches() {
+            writeln!(f, "the decoder detected a protocol with a different timing")?;
+        }
+        writeln!(
+            f,
+            "{:>4}  {:<4} {:>12}  received",
+            "#", "sent", "recorded µs"
+        )?;
+        let bit = |b: Option<bool>| match b {
+            Some(true) => "1",
+            Some(false) => "0",
+            None => "-",
+        };
+        for (i, symbol) in self.diff().iter().enumerate() {
+            let sent = if i == self.length {
+                "sync"
+            } else {
+                bit(symbol.sent)
+            };
+            let recorded = symbol
+                .recorded
+                .as_ref()
+                .map(|s| format!("{}/{}", s.high, s.low))
+                .unwrap_or_else(|| "-".into());
+            writeln!(
+                f,
+                "{:>4}  {:<4} {:>12}  {:<8}{}",
+                i,
+                sent,
+                recorded,
+                bit(symbol.received),
+                if symbol.differs() && i != self.length {
+                    " <"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
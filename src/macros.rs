@@ -0,0 +1,148 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Timed sequences of commands e.g a pairing sequence or moving a blind to a position
+
+use crate::{
+    delay::Delay, error::Error, queue::Command, quiet::Priority, Encoding, Funksteckdose, Pin,
+    Protocol,
+};
+use log::debug;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A step of a macro
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    /// Send `command` `repeat` times with `gap` in between. The repetitions after the first
+    /// are background transmissions that are reduced during the quiet hours.
+    Send {
+        command: Command,
+        repeat: usize,
+        gap: Duration,
+    },
+    /// Pause
+    Wait(Duration),
+}
+
+/// Handle to cancel a running macro
+#[derive(Clone, Debug, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    /// Create a new handle
+    pub fn new() -> Cancel {
+        Cancel::default()
+    }
+
+    /// Stop the macro before its next frame
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// True if `cancel` was called
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// A named sequence of steps
+#[derive(Clone, Debug, PartialEq)]
+pub struct Macro {
+    pub name: String,
+    pub steps: Vec<Step>,
+    /// The macro pairs sockets and can only be run through `pairing::Pairing`
+    pub pairing: bool,
+}
+
+impl Macro {
+    /// Execute all steps. Fails with `Error::Cancelled` if `cancel` is triggered and with
+    /// `Error::Unconfirmed` for pairing macros.
+    pub fn run<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+        &self,
+        funksteckdose: &Funksteckdose<T, E, P, D>,
+        cancel: &Cancel,
+    ) -> Result<(), Error> {
+        if self.pairing {
+            return Err(Error::Unconfirmed(format!(
+                "macro {} pairs sockets",
+                self.name
+            )));
+        }
+        self.execute(funksteckdose, cancel)
+    }
+
+    pub(crate) fn execute<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+        &self,
+        funksteckdose: &Funksteckdose<T, E, P, D>,
+        cancel: &Cancel,
+    ) -> Result<(), Error> {
+        debug!("Running macro {}", self.name);
+        for step in &self.steps {
+            match step {
+                Step::Send {
+                    command,
+                    repeat,
+                    gap,
+                } => {
+                    for n in 0..*repeat {
+                        if n > 0 {
+                            wait(*gap, cancel)?;
+                        }
+                        if cancel.is_cancelled() {
+                            return Err(Error::Cancelled);
+                        }
+                        // Repetitions are re-sends without user interaction
+                        let priority = if n > 0 {
+                            Priority::Background
+                        } else {
+                            Priority::Interactive
+                        };
+                        command.send_with_priority(funksteckdose, priority)?;
+                    }
+                }
+                Step::Wait(duration) => wait(*duration, cancel)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sleep for `duration`. Fails with `Error::Cancelled` as soon as `cancel` is triggered.
+pub(crate) fn wait(duration: Duration, cancel: &Cancel) -> Result<(), Error> {
+    const SLICE: Duration = Duration::from_millis(10);
+    let start = Instant::now();
+    loop {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= duration {
+            return Ok(());
+        }
+        thread::sleep((duration - elapsed).min(SLICE));
+    }
+}
@@ -1,56 +1,2055 @@
-/// Copyright © 2019 Felix Obenhuber
-///
-/// Permission is hereby granted, free of charge, to any person obtaining a copy
-/// of this software and associated documentation files (the "Software"), to deal
-/// in the Software without restriction, including without limitation the rights
-/// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
-/// copies of the Software, and to permit persons to whom the Software is
-/// furnished to do so, subject to the following conditions:
-///
-/// The above copyright notice and this permission notice shall be included in all
-/// copies or substantial portions of the Software.
+// Copyright © 2019 Felix Obenhuber
 //
-/// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
-/// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
-/// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
-/// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
-/// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
-/// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
-/// SOFTWARE.
-
-#[cfg(not(target_os = "arm-unknown-linux-gnueabihf"))]
-fn main() {
-    println!("nop - sorry, this examples requires wiringpi...");
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use chrono::{DateTime, Local};
+use funksteckdose::{
+    catalog, daemon,
+    delay::{Delay, Strategy},
+    error::{CoreError, Error},
+    explain::Explanation,
+    history::{self, Entry, History},
+    hook::Hooks,
+    i18n::{self, Action, Lang, Message},
+    intertechno, lirc, logging,
+    loopback::Loopback,
+    mock::{NullInputPin, NullPin},
+    pairing::{self, Pairing},
+    plan::TransmissionPlan,
+    quiet::QuietHours,
+    receiver::{Decoder, Receiver},
+    signal::{Pattern, Signal},
+    with_encoding, Addresses, Code, Device, DynProtocol, Encoding, EncodingA, EncodingB, EncodingC,
+    EncodingREV, Funksteckdose, Group, InputPin, IntertechnoAddress, Pin, ProtocolValues,
+    RotaryAddress, State,
+};
+use std::{
+    cell::RefCell,
+    env,
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+    thread,
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "funksteckdose",
+    about = "Control 433Mhz wireless sockets",
+    raw(setting = "structopt::clap::AppSettings::SubcommandsNegateReqs")
+)]
+#[cfg_attr(
+    not(any(feature = "wiringpi", feature = "gpio-cdev")),
+    allow(dead_code)
+)]
+struct Opt {
+    /// Select group according to dip switches e.g "10011"
+    #[structopt(
+        short = "g",
+        long = "group",
+        raw(
+            required_unless_one = r#"&["replay", "show_history", "address", "intertechno", "name"]"#
+        )
+    )]
+    group: Option<Group>,
+    /// Select device according to dip switches e.g "10000" or "A" or "0"
+    #[structopt(
+        short = "d",
+        long = "device",
+        parse(try_from_str = "Device::from_str"),
+        raw(
+            required_unless_one = r#"&["replay", "show_history", "address", "intertechno", "name"]"#
+        )
+    )]
+    device: Option<Device>,
+    /// Select the rotary switch positions of an Encoding B socket e.g "3:2"
+    #[structopt(
+        short = "a",
+        long = "address",
+        parse(try_from_str = "RotaryAddress::from_str"),
+        raw(conflicts_with_all = r#"&["group", "device"]"#)
+    )]
+    address: Option<RotaryAddress>,
+    /// Select family, group and device of an Encoding C (Intertechno) socket e.g "c:2:4"
+    #[structopt(
+        short = "i",
+        long = "intertechno",
+        parse(try_from_str = "IntertechnoAddress::from_str"),
+        raw(conflicts_with_all = r#"&["group", "device", "address"]"#)
+    )]
+    intertechno: Option<IntertechnoAddress>,
+    /// Encoding of group and device: A, B, C, Brennenstuhl or REV. Default: A
+    #[structopt(
+        short = "e",
+        long = "encoding",
+        raw(conflicts_with_all = r#"&["address", "intertechno"]"#)
+    )]
+    encoding: Option<String>,
+    /// Select a socket of the configuration by name e.g "kitchen-lamp"
+    #[structopt(
+        short = "n",
+        long = "name",
+        raw(requires = r#""config""#),
+        raw(conflicts_with_all = r#"&["group", "device", "address", "intertechno", "encoding"]"#)
+    )]
+    name: Option<String>,
+    /// Configuration file with the sockets selected by --name
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    #[cfg_attr(not(feature = "config"), allow(dead_code))]
+    config: Option<PathBuf>,
+    /// Send command: on, off, toggle, true, false, 1, 0. Toggling needs --name and a store
+    /// in the configuration to know the last sent state
+    #[structopt(
+        short = "s",
+        long = "send",
+        parse(try_from_str = "State::from_str"),
+        raw(required_unless_one = r#"&["replay", "show_history"]"#)
+    )]
+    send: Option<State>,
+    /// Select WiringPI pin or the line offset of the GPIO chip. Default: 0 with wiringpi,
+    /// 17 with gpio-cdev
+    #[structopt(short = "p", long = "pin")]
+    pin: Option<u16>,
+    /// Pin driven high while sending e.g the enable line or a transistor switching the supply
+    /// of the transmitter. Numbered like --pin
+    #[structopt(long = "enable-pin")]
+    enable_pin: Option<u16>,
+    /// GPIO character device of the gpio-cdev backend. Default: /dev/gpiochip0
+    #[structopt(long = "chip", parse(from_os_str))]
+    #[cfg_attr(
+        not(all(feature = "gpio-cdev", not(feature = "wiringpi"))),
+        allow(dead_code)
+    )]
+    chip: Option<PathBuf>,
+    /// Protocol: 1 to 12, HT6P20B, HS2303
+    #[structopt(
+        long = "protocol",
+        default_value = "1",
+        parse(try_from_str = "parse_protocol")
+    )]
+    protocol: ProtocolValues,
+    /// Wait strategy between pulses: spin, yielding-spin, sleep, hybrid
+    #[structopt(
+        long = "delay",
+        default_value = "hybrid",
+        parse(try_from_str = "Strategy::from_str")
+    )]
+    delay: Strategy,
+    /// Do not access any hardware. Commands are sent to a simulated pin
+    #[structopt(long = "no-hardware")]
+    no_hardware: bool,
+    /// Wait before sending e.g until the transmitter is stable. Example: 5ms
+    #[structopt(long = "warm-up", parse(try_from_str = "history::parse_period"))]
+    warm_up: Option<Duration>,
+    /// Wait after sending e.g to give receivers a gap. Example: 20ms
+    #[structopt(long = "cool-down", parse(try_from_str = "history::parse_period"))]
+    cool_down: Option<Duration>,
+    /// Pause between repeated frames for receivers that miss frames sent back-to-back.
+    /// Example: 5ms
+    #[structopt(long = "repeat-gap", parse(try_from_str = "history::parse_period"))]
+    repeat_gap: Option<Duration>,
+    /// Send with real-time scheduling and locked memory to avoid preemption of the pulse
+    /// timing. Needs root or CAP_SYS_NICE
+    #[structopt(long = "realtime")]
+    realtime: bool,
+    /// Reject transmissions with a longer airtime e.g 30s. 0s disables the limit.
+    /// Default: 10s
+    #[structopt(long = "max-airtime", parse(try_from_str = "history::parse_period"))]
+    max_airtime: Option<Duration>,
+    /// Measure the timing overhead of the pin and compensate pulse durations
+    #[structopt(long = "calibrate")]
+    calibrate: bool,
+    /// Record sent commands in this file
+    #[structopt(long = "history", parse(from_os_str))]
+    history: Option<PathBuf>,
+    /// Number of commands kept in the history. Default: 100
+    #[structopt(long = "history-size")]
+    history_size: Option<usize>,
+    /// Print the history. Index 0 is the most recent command
+    #[structopt(long = "show-history", raw(requires = r#""history""#))]
+    show_history: bool,
+    /// Send the history entry with this index again
+    #[structopt(long = "replay", raw(requires = r#""history""#))]
+    replay: Option<usize>,
+    /// Log format: text, json
+    #[structopt(
+        long = "log-format",
+        default_value = "text",
+        parse(try_from_str = "logging::Format::from_str")
+    )]
+    log_format: logging::Format,
+    /// Log level: error, warn, info, debug, trace
+    #[structopt(long = "log-level", default_value = "warn")]
+    log_level: log::LevelFilter,
+    /// Language of messages: en, de. Default: from LC_ALL, LC_MESSAGES or LANG
+    #[structopt(long = "lang", parse(try_from_str = "Lang::from_str"))]
+    lang: Option<Lang>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
-#[cfg(all(target_os = "arm-unknown-linux-gnueabihf", cfg("wiringpi")))]
-fn main() {
-    use funksteckdose::{wiringpi::WiringPiPin, Device, EncodingA, Protocol1, State};
-    use std::str::FromStr;
-    use structopt::StructOpt;
+/// Language of messages selected with --lang or the locale
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// `message` in the selected language
+fn tr(message: Message) -> String {
+    message.text(LANG.get().copied().unwrap_or_default())
+}
+
+/// Print `message` and exit
+fn exit(message: Message) -> ! {
+    eprintln!("{}", tr(message));
+    std::process::exit(1);
+}
 
-    #[derive(Debug, StructOpt)]
-    #[structopt(name = "funksteckdose", about = "Control 433Mhz wireless sockets")]
-    struct Opt {
+/// Exit with a translated message instead of panicking
+trait OrExit<T> {
+    fn or_exit(self, action: Action) -> T;
+}
+
+impl<T, E: fmt::Display> OrExit<T> for Result<T, E> {
+    fn or_exit(self, action: Action) -> T {
+        self.unwrap_or_else(|e| exit(Message::Failed(action, &e)))
+    }
+}
+
+/// Transmitter configured on the command line
+type Transmitter<T, E> = Funksteckdose<T, E, DynProtocol, Box<dyn Delay + Send + Sync>>;
+
+#[derive(Debug, StructOpt)]
+enum StateAction {
+    /// Print the assumed states as JSON
+    #[structopt(name = "export")]
+    Export {
+        /// Write to this file instead of stdout
+        #[structopt(short = "o", long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Send the states of an export and store them
+    #[structopt(name = "import")]
+    Import {
+        /// File created with export
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Print the code word, pulses and airtime of a command without sending it
+    #[structopt(name = "explain")]
+    Explain {
         /// Select group according to dip switches e.g "10011"
-        #[structopt(short = "g", long = "group")]
-        group: String,
+        #[structopt(short = "g", long = "group", raw(required_unless = r#""address""#))]
+        group: Option<Group>,
         /// Select device according to dip switches e.g "10000" or "A" or "0"
-        #[structopt(short = "d", long = "device", parse(try_from_str = "Device::from_str"))]
-        device: Device,
-        /// Send command: on, off, true, false, 1, 0
+        #[structopt(
+            short = "d",
+            long = "device",
+            parse(try_from_str = "Device::from_str"),
+            raw(required_unless = r#""address""#)
+        )]
+        device: Option<Device>,
+        /// Select the rotary switch positions of an Encoding B socket e.g "3:2"
+        #[structopt(
+            short = "a",
+            long = "address",
+            parse(try_from_str = "RotaryAddress::from_str"),
+            raw(conflicts_with_all = r#"&["group", "device"]"#)
+        )]
+        address: Option<RotaryAddress>,
+        /// Command: on, off, true, false, 1, 0
         #[structopt(short = "s", long = "send", parse(try_from_str = "State::from_str"))]
         send: State,
-        /// Select WiringPI pin. Default: 0
-        #[structopt(short = "p", long = "pin")]
-        pin: Option<u16>,
+        /// Number of frames. Default: 10
+        #[structopt(short = "r", long = "repeat")]
+        repeat: Option<usize>,
+    },
+    /// Send a command to a simulated pin, decode it with the receiver and print the
+    /// symbols where both disagree
+    #[structopt(name = "loopback")]
+    Loopback {
+        /// Select group according to dip switches e.g "10011"
+        #[structopt(
+            short = "g",
+            long = "group",
+            raw(required_unless_one = r#"&["address", "intertechno"]"#)
+        )]
+        group: Option<Group>,
+        /// Select device according to dip switches e.g "10000" or "A" or "0"
+        #[structopt(
+            short = "d",
+            long = "device",
+            parse(try_from_str = "Device::from_str"),
+            raw(required_unless_one = r#"&["address", "intertechno"]"#)
+        )]
+        device: Option<Device>,
+        /// Select the rotary switch positions of an Encoding B socket e.g "3:2"
+        #[structopt(
+            short = "a",
+            long = "address",
+            parse(try_from_str = "RotaryAddress::from_str"),
+            raw(conflicts_with_all = r#"&["group", "device"]"#)
+        )]
+        address: Option<RotaryAddress>,
+        /// Select family, group and device of an Encoding C (Intertechno) socket e.g "c:2:4"
+        #[structopt(
+            short = "i",
+            long = "intertechno",
+            parse(try_from_str = "IntertechnoAddress::from_str"),
+            raw(conflicts_with_all = r#"&["group", "device", "address"]"#)
+        )]
+        intertechno: Option<IntertechnoAddress>,
+        /// Command: on, off, true, false, 1, 0
+        #[structopt(short = "s", long = "send", parse(try_from_str = "State::from_str"))]
+        send: State,
+    },
+    /// Send a command to a self-learning Intertechno, KlikAanKlikUit, Nexa or HomeEasy EU socket
+    #[structopt(name = "self-learning")]
+    SelfLearning {
+        /// Id of the remote learned by the socket, 0 to 67108863
+        #[structopt(long = "id")]
+        id: u32,
+        /// Unit 0 to 15
+        #[structopt(short = "u", long = "unit", raw(required_unless = r#""group""#))]
+        unit: Option<u8>,
+        /// Address all units of the id
+        #[structopt(long = "group", raw(conflicts_with = r#""unit""#))]
+        group: bool,
+        /// Command: on, off, true, false, 1, 0
+        #[structopt(
+            short = "s",
+            long = "send",
+            parse(try_from_str = "State::from_str"),
+            raw(required_unless = r#""dim""#)
+        )]
+        send: Option<State>,
+        /// Set a dimmer to the level 0 to 15
+        #[structopt(long = "dim", raw(conflicts_with = r#""send""#))]
+        dim: Option<u8>,
+        /// Number of frames. Default: 10
+        #[structopt(short = "r", long = "repeat")]
+        repeat: Option<usize>,
+    },
+    /// Send a decimal code like codesend of 433Utils. Call the binary through a link named
+    /// codesend to use it as a drop-in replacement.
+    #[structopt(name = "codesend")]
+    Codesend {
+        /// Decimal code. The lower 24 bits are sent
+        code: u64,
+        /// Protocol. 0 selects the default. Default: 1
+        protocol: Option<String>,
+        /// Pulse length in µs. 0 selects the pulse length of the protocol
+        pulse_length: Option<u64>,
+    },
+    /// Send a tri-state code word, binary or decimal code e.g captured from a remote
+    #[structopt(name = "send-code")]
+    SendCode {
+        /// Tri-state code word e.g 0FF0FFF0FF0F, binary or decimal code
+        code: String,
+        /// Number of bits of a decimal code. Default: 24
+        #[structopt(short = "l", long = "length")]
+        length: Option<usize>,
+        /// Number of frames. Default: 10
+        #[structopt(short = "r", long = "repeat")]
+        repeat: Option<usize>,
+    },
+    /// Send a raw code e.g captured with an SDR, without any group and device mapping
+    #[structopt(name = "raw")]
+    Raw {
+        /// Code as hexadecimal with 0x, binary with 0b or decimal number e.g 0x51515
+        #[structopt(short = "c", long = "code", parse(try_from_str = "parse_raw_code"))]
+        code: u64,
+        /// Number of bits. Default: 24
+        #[structopt(short = "l", long = "length", default_value = "24")]
+        length: usize,
+        /// Protocol: 1 to 12, HT6P20B, HS2303. Default: the protocol of the tool
+        #[structopt(long = "protocol", parse(try_from_str = "parse_protocol"))]
+        protocol: Option<ProtocolValues>,
+        /// Number of frames. Default: 10
+        #[structopt(short = "r", long = "repeat")]
+        repeat: Option<usize>,
+    },
+    /// Send a tri-state code word e.g captured with an SDR, without any group and device
+    /// mapping
+    #[structopt(name = "tristate")]
+    Tristate {
+        /// Tri-state code word of 0, 1 and F e.g 0FFF0FFF0FFF
+        #[structopt(
+            short = "w",
+            long = "word",
+            parse(try_from_str = "Code::from_tri_state")
+        )]
+        word: Code,
+        /// Protocol: 1 to 12, HT6P20B, HS2303. Default: the protocol of the tool
+        #[structopt(long = "protocol", parse(try_from_str = "parse_protocol"))]
+        protocol: Option<ProtocolValues>,
+        /// Number of frames. Default: 10
+        #[structopt(short = "r", long = "repeat")]
+        repeat: Option<usize>,
+    },
+    /// Convert a code between tri-state, binary and decimal and decode it
+    #[structopt(name = "code")]
+    Code {
+        /// Tri-state code word e.g 0FF0FFF0FF0F, binary or decimal code
+        code: String,
+        /// Number of bits of a decimal code. Default: 24
+        #[structopt(short = "l", long = "length")]
+        length: Option<usize>,
+    },
+    /// Send a command to every address of an encoding e.g to find the address of a socket
+    #[structopt(name = "scan")]
+    Scan {
+        /// Encoding: A, B or C. Default: A
+        #[structopt(short = "e", long = "encoding", default_value = "A")]
+        encoding: String,
+        /// Command: on, off, true, false, 1, 0
+        #[structopt(short = "s", long = "send", parse(try_from_str = "State::from_str"))]
+        send: State,
+        /// Pause between addresses in ms. Default: 1000
+        #[structopt(long = "pause")]
+        pause: Option<u64>,
+    },
+    /// Pair a self-learning socket in learning mode by sending "on" for a few seconds
+    #[structopt(name = "pair")]
+    Pair {
+        /// Select group according to dip switches e.g "10011"
+        #[structopt(
+            short = "g",
+            long = "group",
+            raw(required_unless_one = r#"&["address", "intertechno"]"#)
+        )]
+        group: Option<Group>,
+        /// Select device according to dip switches e.g "10000" or "A" or "0"
+        #[structopt(
+            short = "d",
+            long = "device",
+            parse(try_from_str = "Device::from_str"),
+            raw(required_unless_one = r#"&["address", "intertechno"]"#)
+        )]
+        device: Option<Device>,
+        /// Select the rotary switch positions of an Encoding B socket e.g "3:2"
+        #[structopt(
+            short = "a",
+            long = "address",
+            parse(try_from_str = "RotaryAddress::from_str"),
+            raw(conflicts_with_all = r#"&["group", "device"]"#)
+        )]
+        address: Option<RotaryAddress>,
+        /// Select family, group and device of an Encoding C (Intertechno) socket e.g "c:2:4"
+        #[structopt(
+            short = "i",
+            long = "intertechno",
+            parse(try_from_str = "IntertechnoAddress::from_str"),
+            raw(conflicts_with_all = r#"&["group", "device", "address"]"#)
+        )]
+        intertechno: Option<IntertechnoAddress>,
+        /// Confirm that every socket in learning mode within range may pair
+        #[structopt(long = "confirm")]
+        confirm: bool,
+        /// Length of the burst e.g 5s. Default: 3s
+        #[structopt(long = "duration", parse(try_from_str = "history::parse_period"))]
+        duration: Option<Duration>,
+    },
+    /// Send a diagnostic waveform e.g to check the transmitter with a scope
+    #[structopt(name = "test-signal")]
+    TestSignal {
+        /// Pattern: square, pulse or carrier. Default: square
+        #[structopt(
+            long = "pattern",
+            default_value = "square",
+            parse(try_from_str = "Pattern::from_str")
+        )]
+        pattern: Pattern,
+        /// Length of one period e.g 1ms. Default: 1ms
+        #[structopt(long = "period", parse(try_from_str = "history::parse_period"))]
+        period: Option<Duration>,
+        /// Length of the signal e.g 5s. Default: 5s
+        #[structopt(long = "duration", parse(try_from_str = "history::parse_period"))]
+        duration: Option<Duration>,
+    },
+    /// Print codes received e.g from the original remote of a socket
+    #[structopt(name = "receive")]
+    Receive {
+        /// Input pin of the receiver module (wiringpi pin or line offset). Default: 2 with
+        /// wiringpi, 27 with gpio-cdev
+        #[structopt(long = "input-pin")]
+        input_pin: Option<u16>,
+        /// Stop after this time e.g 30s. Default: never
+        #[structopt(long = "timeout", parse(try_from_str = "history::parse_period"))]
+        timeout: Option<Duration>,
+        /// Configuration with hooks to run on received codes
+        #[structopt(short = "c", long = "config", parse(from_os_str))]
+        config: Option<PathBuf>,
+        /// Print histograms of the pulse lengths and deviations per protocol at the end
+        /// e.g to tune the tolerance or the pulse length of a protocol
+        #[structopt(long = "stats", requires = "timeout")]
+        stats: bool,
+    },
+    /// Wait for a button press on the original remote of a socket and add the socket with
+    /// the decoded address to the configuration
+    #[structopt(name = "learn")]
+    Learn {
+        /// Name of the new socket
+        #[structopt(short = "n", long = "name")]
+        name: String,
+        /// Configuration the socket is added to. Created if missing
+        #[structopt(short = "c", long = "config", parse(from_os_str))]
+        config: PathBuf,
+        /// Encoding of the remote: A, B, C, Brennenstuhl or REV. Default: the first of A, B,
+        /// C and REV that decodes the code
+        #[structopt(short = "e", long = "encoding")]
+        encoding: Option<String>,
+        /// Input pin of the receiver module (wiringpi pin or line offset). Default: 2 with
+        /// wiringpi, 27 with gpio-cdev
+        #[structopt(long = "input-pin")]
+        input_pin: Option<u16>,
+        /// Give up after this time e.g 1m. Default: 30s
+        #[structopt(long = "timeout", parse(try_from_str = "history::parse_period"))]
+        timeout: Option<Duration>,
+    },
+    /// Export or restore the assumed states of the sockets in the configured store
+    #[structopt(name = "state")]
+    State {
+        /// Configuration with the store and sockets
+        #[structopt(short = "c", long = "config", parse(from_os_str))]
+        config: PathBuf,
+        #[structopt(subcommand)]
+        action: StateAction,
+    },
+    /// Keep the transmitter set up and send the commands read from stdin or a named pipe,
+    /// one per line: <name|group/device> on|off|toggle
+    #[structopt(name = "daemon")]
+    Daemon {
+        /// Named pipe to read the commands from instead of stdin. Create it with mkfifo
+        #[structopt(long = "fifo", parse(from_os_str))]
+        fifo: Option<PathBuf>,
+        /// Configuration with the sockets addressed by name
+        #[structopt(short = "c", long = "config", parse(from_os_str))]
+        config: Option<PathBuf>,
+    },
+    /// Switch sockets with HTTP requests e.g from the RESTful switch of Home Assistant:
+    /// POST /switch/{group}/{device} or /switch/{name} with {"state":"on"}
+    #[structopt(name = "http")]
+    Http {
+        /// Address to listen on. Default: the [http] section of the configuration or
+        /// 127.0.0.1:8080
+        #[structopt(long = "listen")]
+        listen: Option<String>,
+        /// Configuration with the sockets addressed by name and the [http] section
+        #[structopt(short = "c", long = "config", parse(from_os_str))]
+        config: Option<PathBuf>,
+    },
+    /// Receive commands from a MQTT broker and publish the states of the sockets
+    #[structopt(name = "mqtt")]
+    Mqtt {
+        /// Configuration with the broker, topics and sockets
+        #[structopt(short = "c", long = "config", parse(from_os_str))]
+        config: PathBuf,
+        /// Input pin of a receiver module to publish the states switched with the original
+        /// remotes of the sockets (wiringpi pin or line offset). Default: none
+        #[structopt(long = "input-pin")]
+        input_pin: Option<u16>,
+    },
+    /// Print switch counts, estimated on time and last activity per socket from a SQLite store
+    #[structopt(name = "history")]
+    History {
+        /// SQLite store
+        #[structopt(long = "store", parse(from_os_str))]
+        store: PathBuf,
+        /// Configuration with the names of the sockets
+        #[structopt(short = "c", long = "config", parse(from_os_str))]
+        config: Option<PathBuf>,
+        /// Only show the socket with this name
+        #[structopt(long = "socket", raw(requires = r#""config""#))]
+        socket: Option<String>,
+        /// Only consider commands of this period e.g 12h or 7d. Default: all
+        #[structopt(long = "since", parse(try_from_str = "history::parse_period"))]
+        since: Option<Duration>,
+    },
+    /// Print the raw codes of a lircd.conf and the protocols derived from them
+    #[structopt(name = "import-lirc")]
+    ImportLirc {
+        /// LIRC configuration file
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// Send the raw code with this name
+        #[structopt(short = "s", long = "send")]
+        send: Option<String>,
+        /// Number of frames. Default: 10
+        #[structopt(short = "r", long = "repeat")]
+        repeat: Option<usize>,
+    },
+}
+
+fn main() {
+    let opt = Opt::from_iter(args());
+    LANG.get_or_init(|| opt.lang.unwrap_or_else(Lang::from_env));
+    logging::Logger::new(opt.log_format, opt.log_level)
+        .init()
+        .or_exit(Action::InitLogger);
+
+    match opt.command {
+        Some(Command::Explain {
+            ref group,
+            ref device,
+            ref address,
+            ref send,
+            repeat,
+        }) => {
+            let repeat = repeat.unwrap_or(10);
+            let values = &opt.protocol;
+            let explanation = match address {
+                Some(address) => {
+                    let (group, device) = address.group_device();
+                    Explanation::with_values::<EncodingB>(values, &group, &device, send, repeat)
+                }
+                None => {
+                    let group = group.as_ref().unwrap().as_str();
+                    let device = device.as_ref().unwrap();
+                    with_encoding!(
+                        encoding(&opt),
+                        E => Explanation::with_values::<E>(values, group, device, send, repeat),
+                        _ => unknown_encoding(&opt)
+                    )
+                }
+            }
+            .or_exit(Action::Encode);
+            println!("{}", explanation);
+        }
+        Some(Command::Loopback {
+            ref group,
+            ref device,
+            ref address,
+            ref intertechno,
+            ref send,
+        }) => {
+            let values = &opt.protocol;
+            let loopback = match (address, intertechno) {
+                (Some(address), _) => {
+                    let (group, device) = address.group_device();
+                    Loopback::run::<EncodingB>(values, &group, &device, send)
+                }
+                (_, Some(address)) => {
+                    let (group, device) = address.group_device();
+                    Loopback::run::<EncodingC>(values, &group, &device, send)
+                }
+                _ => Loopback::run::<EncodingA>(
+                    values,
+                    group.as_ref().unwrap().as_str(),
+                    device.as_ref().unwrap(),
+                    send,
+                ),
+            }
+            .or_exit(Action::Encode);
+            println!("{}", loopback);
+            if !loopback.is_match() {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::SelfLearning {
+            id,
+            unit,
+            group,
+            ref send,
+            dim,
+            repeat,
+        }) => {
+            let action = match (send, dim) {
+                (_, Some(level)) => intertechno::Action::Dim(level),
+                (Some(state), None) => intertechno::Action::Switch(state.clone()),
+                (None, None) => unreachable!("required by the arguments"),
+            };
+            let command = match unit {
+                Some(unit) if !group => intertechno::Command::new(id, unit, action),
+                _ => intertechno::Command::group(id, action),
+            }
+            .or_exit(Action::Encode);
+            println!("{}", command);
+            let plan = command.plan(repeat.unwrap_or(10));
+            if opt.no_hardware {
+                transmit_plan(NullPin, &opt, &plan);
+            } else {
+                transmit_plan(hardware_pin(&opt), &opt, &plan);
+            }
+        }
+        Some(Command::Codesend {
+            code,
+            ref protocol,
+            pulse_length,
+        }) => codesend(
+            &opt,
+            code,
+            protocol.as_ref().map(String::as_str),
+            pulse_length,
+        ),
+        Some(Command::SendCode {
+            ref code,
+            length,
+            repeat,
+        }) => {
+            let code = parse_code(code, length);
+            if opt.no_hardware {
+                send_code(NullPin, &opt, &code, repeat);
+            } else {
+                send_code(hardware_pin(&opt), &opt, &code, repeat);
+            }
+        }
+        Some(Command::Raw {
+            code,
+            length,
+            ref protocol,
+            repeat,
+        }) => {
+            let code = Code::from_decimal(code, length).or_exit(Action::Encode);
+            send_raw(&opt, &code, protocol.as_ref(), repeat);
+        }
+        Some(Command::Tristate {
+            ref word,
+            ref protocol,
+            repeat,
+        }) => send_raw(&opt, word, protocol.as_ref(), repeat),
+        Some(Command::Code { ref code, length }) => {
+            let code = parse_code(code, length);
+            let tri_state = code.tri_state();
+            println!(
+                "{}",
+                tr(Message::Code {
+                    decimal: &code.decimal(),
+                    binary: &code.binary(),
+                    tri_state: tri_state.as_deref(),
+                })
+            );
+            if let Some(code_word) = tri_state {
+                print_decodings(code_word.as_bytes());
+            }
+        }
+        Some(Command::Scan {
+            ref encoding,
+            ref send,
+            pause,
+        }) => {
+            let pause = Duration::from_millis(pause.unwrap_or(1000));
+            match (encoding.to_ascii_uppercase().as_str(), opt.no_hardware) {
+                ("A", true) => {
+                    scan::<_, EncodingA>(NullPin, &opt, EncodingA::all_addresses(), send, pause)
+                }
+                ("A", false) => scan::<_, EncodingA>(
+                    hardware_pin(&opt),
+                    &opt,
+                    EncodingA::all_addresses(),
+                    send,
+                    pause,
+                ),
+                ("B", true) => {
+                    scan::<_, EncodingB>(NullPin, &opt, EncodingB::all_addresses(), send, pause)
+                }
+                ("B", false) => scan::<_, EncodingB>(
+                    hardware_pin(&opt),
+                    &opt,
+                    EncodingB::all_addresses(),
+                    send,
+                    pause,
+                ),
+                ("C", true) => {
+                    scan::<_, EncodingC>(NullPin, &opt, EncodingC::all_addresses(), send, pause)
+                }
+                ("C", false) => scan::<_, EncodingC>(
+                    hardware_pin(&opt),
+                    &opt,
+                    EncodingC::all_addresses(),
+                    send,
+                    pause,
+                ),
+                _ => exit(Message::UnscannableEncoding(encoding)),
+            }
+        }
+        Some(Command::ImportLirc {
+            ref file,
+            ref send,
+            repeat,
+        }) => import_lirc(&opt, file, send.as_ref(), repeat.unwrap_or(10)),
+        Some(Command::Pair {
+            ref group,
+            ref device,
+            ref address,
+            ref intertechno,
+            confirm,
+            duration,
+        }) => {
+            let duration = duration.unwrap_or(pairing::DURATION);
+            let pairing = Pairing::default();
+            let result = match (address, intertechno) {
+                (Some(address), _) => {
+                    let (group, device) = address.group_device();
+                    pair::<EncodingB>(&opt, &pairing, &group, &device, duration, confirm)
+                }
+                (_, Some(address)) => {
+                    let (group, device) = address.group_device();
+                    pair::<EncodingC>(&opt, &pairing, &group, &device, duration, confirm)
+                }
+                _ => pair::<EncodingA>(
+                    &opt,
+                    &pairing,
+                    group.as_ref().unwrap().as_str(),
+                    device.as_ref().unwrap(),
+                    duration,
+                    confirm,
+                ),
+            };
+            if let Err(e) = result {
+                eprintln!("{}", tr(Message::Failed(Action::Pair, &e)));
+                if let Error::Unconfirmed(_) = e {
+                    eprintln!("{}", tr(Message::ConfirmPairing));
+                }
+                std::process::exit(1);
+            }
+        }
+        Some(Command::TestSignal {
+            pattern,
+            period,
+            duration,
+        }) => {
+            let signal = Signal {
+                pattern,
+                period: period.unwrap_or_else(|| Duration::from_millis(1)),
+                duration: duration.unwrap_or_else(|| Duration::from_secs(5)),
+            };
+            let plan = signal.plan().unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+            println!(
+                "{}",
+                tr(Message::SendingSignal(&signal.pattern, plan.duration()))
+            );
+            if opt.no_hardware {
+                transmit_plan(NullPin, &opt, &plan);
+            } else {
+                transmit_plan(hardware_pin(&opt), &opt, &plan);
+            }
+        }
+        Some(Command::Receive {
+            input_pin,
+            timeout,
+            ref config,
+            stats,
+        }) => {
+            let hooks = configured_hooks(config.as_deref());
+            if opt.no_hardware {
+                receive(NullInputPin, timeout, hooks, stats);
+            } else {
+                receive(hardware_input_pin(&opt, input_pin), timeout, hooks, stats);
+            }
+        }
+        Some(Command::Learn {
+            ref name,
+            ref config,
+            ref encoding,
+            input_pin,
+            timeout,
+        }) => {
+            let (encoding, timeout) = (
+                encoding.as_deref(),
+                timeout.unwrap_or(Duration::from_secs(30)),
+            );
+            if opt.no_hardware {
+                learn(NullInputPin, name, config, encoding, timeout);
+            } else {
+                let pin = hardware_input_pin(&opt, input_pin);
+                learn(pin, name, config, encoding, timeout);
+            }
+        }
+        Some(Command::State {
+            ref config,
+            action: StateAction::Export { ref output },
+        }) => export_states(config, output.as_deref()),
+        Some(Command::State {
+            ref config,
+            action: StateAction::Import { ref file },
+        }) => {
+            if opt.no_hardware {
+                import_states(NullPin, &opt, config, file);
+            } else {
+                import_states(hardware_pin(&opt), &opt, config, file);
+            }
+        }
+        Some(Command::Daemon {
+            ref fifo,
+            ref config,
+        }) => {
+            if opt.no_hardware {
+                daemon(NullPin, &opt, fifo.as_deref(), config.as_deref());
+            } else {
+                daemon(hardware_pin(&opt), &opt, fifo.as_deref(), config.as_deref());
+            }
+        }
+        Some(Command::Http {
+            ref listen,
+            ref config,
+        }) => {
+            if opt.no_hardware {
+                http(NullPin, &opt, listen.as_deref(), config.as_deref());
+            } else {
+                http(
+                    hardware_pin(&opt),
+                    &opt,
+                    listen.as_deref(),
+                    config.as_deref(),
+                );
+            }
+        }
+        Some(Command::Mqtt {
+            ref config,
+            input_pin,
+        }) => {
+            if opt.no_hardware {
+                mqtt(NullPin, input_pin.map(|_| NullInputPin), &opt, config);
+            } else {
+                let input = input_pin.map(|pin| hardware_input_pin(&opt, Some(pin)));
+                mqtt(hardware_pin(&opt), input, &opt, config);
+            }
+        }
+        Some(Command::History {
+            ref store,
+            ref config,
+            ref socket,
+            since,
+        }) => show_activity(store, config.as_deref(), socket.as_deref(), since),
+        None => send(opt),
+    }
+}
+
+/// Command line arguments. Calls through a link named codesend are handled like the
+/// codesend subcommand.
+fn args() -> Vec<OsString> {
+    let mut args: Vec<OsString> = env::args_os().collect();
+    if args
+        .first()
+        .and_then(|arg0| Path::new(arg0).file_name())
+        .is_some_and(|name| name == "codesend")
+    {
+        args.insert(1, "codesend".into());
+    }
+    args
+}
+
+fn codesend(opt: &Opt, code: u64, protocol: Option<&str>, pulse_length: Option<u64>) {
+    let protocol = match protocol {
+        None | Some("0") => "1",
+        Some(protocol) => protocol,
+    };
+    let values =
+        catalog::protocol(protocol).unwrap_or_else(|| exit(Message::UnknownProtocol(protocol)));
+    let values = match pulse_length {
+        None | Some(0) => values,
+        Some(pulse_length) => values.with_pulse_length(pulse_length),
+    };
+    // codesend always sends 24 bits
+    let plan = TransmissionPlan::from_code(&values, code, 24, 10, None);
+
+    println!("{}", tr(Message::SendingCode(code)));
+    if opt.no_hardware {
+        transmit_plan(NullPin, opt, &plan);
+    } else {
+        transmit_plan(hardware_pin(opt), opt, &plan);
+    }
+}
+
+fn show_activity(
+    store: &Path,
+    config: Option<&Path>,
+    socket: Option<&str>,
+    since: Option<Duration>,
+) {
+    let sockets = configured_sockets(config);
+    let configured = |group: &str, device: &Device| {
+        sockets
+            .iter()
+            .find(|s| s.group == group && &s.device == device)
+    };
+    let filter = socket.map(|socket| {
+        sockets
+            .iter()
+            .find(|s| s.name == socket)
+            .map(|s| (s.group.clone(), s.device.clone()))
+            .unwrap_or_else(|| exit(Message::UnknownSocket(socket)))
+    });
+
+    // Columns are widened for longer translations of the titles
+    let lang = LANG.get().copied().unwrap_or_default();
+    let columns = i18n::activity_columns(lang);
+    let widths: Vec<usize> = [20, 8, 10, 10]
+        .iter()
+        .zip(&columns)
+        .map(|(width, column)| column.chars().count().max(*width))
+        .collect();
+    println!(
+        "{:<w0$} {:>w1$} {:>w2$} {:>w3$}  {}",
+        columns[0],
+        columns[1],
+        columns[2],
+        columns[3],
+        columns[4],
+        w0 = widths[0],
+        w1 = widths[1],
+        w2 = widths[2],
+        w3 = widths[3]
+    );
+    for activity in activity(store, since) {
+        if let Some((ref group, ref device)) = filter {
+            if &activity.group != group || &activity.device != device {
+                continue;
+            }
+        }
+        let socket = configured(&activity.group, &activity.device);
+        let name = socket
+            .map(|s| s.name.clone())
+            .unwrap_or_else(|| format!("{} {}", activity.group, activity.device));
+        let energy = socket
+            .and_then(|s| s.watts)
+            .map(|watts| {
+                let kwh = watts * activity.on_time.as_secs_f64() / 3_600_000.0;
+                format!("{} kWh", i18n::decimal(lang, kwh, 2))
+            })
+            .unwrap_or_else(|| "-".into());
+        let minutes = activity.on_time.as_secs() / 60;
+        let last: DateTime<Local> = activity.last.into();
+        println!(
+            "{:<w0$} {:>w1$} {:>w2$} {:>w3$}  {} ({})",
+            name,
+            activity.switches,
+            format!("{}h {:02}m", minutes / 60, minutes % 60),
+            energy,
+            last.format("%Y-%m-%d %H:%M"),
+            activity.state,
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3]
+        );
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn activity(store: &Path, since: Option<Duration>) -> Vec<history::Activity> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let store = funksteckdose::store::SqliteStore::open(store).or_exit(Action::OpenStore);
+    let now = SystemTime::now();
+    let since = since
+        .and_then(|since| now.checked_sub(since))
+        .unwrap_or(UNIX_EPOCH);
+    store.activity(since, now).or_exit(Action::QueryHistory)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn activity(_store: &Path, _since: Option<Duration>) -> Vec<history::Activity> {
+    exit(Message::NotBuilt(i18n::Feature::HistoryQueries));
+}
+
+/// A socket of the configuration
+struct ConfiguredSocket {
+    name: String,
+    group: String,
+    device: Device,
+    watts: Option<f64>,
+}
+
+#[cfg(feature = "config")]
+fn configured_sockets(config: Option<&Path>) -> Vec<ConfiguredSocket> {
+    config
+        .map(|path| {
+            funksteckdose::config::Config::load(path)
+                .or_exit(Action::LoadConfig)
+                .sockets
+                .into_iter()
+                .map(|s| ConfiguredSocket {
+                    name: s.name,
+                    group: s.group,
+                    device: s.device,
+                    watts: s.watts,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(not(feature = "config"))]
+fn configured_sockets(config: Option<&Path>) -> Vec<ConfiguredSocket> {
+    if config.is_some() {
+        exit(Message::NotBuilt(i18n::Feature::SocketNames));
+    }
+    Vec::new()
+}
+
+#[cfg(all(feature = "json", feature = "config"))]
+fn configured_store(
+    config: &funksteckdose::config::Config,
+) -> Box<dyn funksteckdose::store::Store> {
+    config
+        .store
+        .as_ref()
+        .unwrap_or_else(|| exit(Message::MissingSection("store")))
+        .open()
+        .or_exit(Action::OpenStore)
+}
+
+#[cfg(all(feature = "json", feature = "config"))]
+fn export_states(config: &Path, output: Option<&Path>) {
+    let config = funksteckdose::config::Config::load(config).or_exit(Action::LoadConfig);
+    let mut states = funksteckdose::state::StateStore::new();
+    configured_store(&config)
+        .load_states(&mut states)
+        .or_exit(Action::LoadStates);
+    let json = states.export();
+    match output {
+        Some(output) => std::fs::write(output, json + "\n").or_exit(Action::WriteStates),
+        None => println!("{}", json),
+    }
+}
+
+#[cfg(all(feature = "json", feature = "config"))]
+fn import_states<T: Pin>(pin: T, opt: &Opt, config: &Path, file: &Path) {
+    use funksteckdose::quiet::Priority;
+
+    let config = funksteckdose::config::Config::load(config).or_exit(Action::LoadConfig);
+    let json = std::fs::read_to_string(file).or_exit(Action::ReadStates);
+    let states = funksteckdose::state::StateStore::import(&json).or_exit(Action::ImportStates);
+    let mut store = configured_store(&config);
+    let d = transmitter::<T, EncodingA>(pin, opt, 10);
+    for (group, device, state) in states.iter() {
+        let state = match state {
+            Some(state) => state,
+            None => continue,
+        };
+        // Configured sockets are sent with their encoding and protocol. Restoring states is
+        // a refresh without user interaction.
+        let socket = config
+            .sockets
+            .iter()
+            .find(|s| s.group == group && &s.device == device);
+        println!("{} {} {}", group, device, state);
+        let result = match socket {
+            Some(socket) => d.send_socket_with_priority(socket, state, Priority::Background),
+            None => d.send_with_priority(group, device, state, Priority::Background),
+        };
+        match result {
+            // Suppressed during the quiet hours, the state is not stored
+            Err(Error::Quiet(_)) => continue,
+            result => result.or_exit(Action::Send),
+        }
+        store
+            .save_state(group, device, state)
+            .or_exit(Action::StoreState);
+    }
+}
+
+#[cfg(not(all(feature = "json", feature = "config")))]
+fn export_states(_config: &Path, _output: Option<&Path>) {
+    exit(Message::NotBuilt(i18n::Feature::StateSnapshots));
+}
+
+#[cfg(not(all(feature = "json", feature = "config")))]
+fn import_states<T: Pin>(_pin: T, _opt: &Opt, _config: &Path, _file: &Path) {
+    exit(Message::NotBuilt(i18n::Feature::StateSnapshots));
+}
+
+#[cfg(all(feature = "mqtt", feature = "config"))]
+fn mqtt<T: Pin, I: InputPin + Send + 'static>(pin: T, input: Option<I>, opt: &Opt, config: &Path) {
+    with_encoding!(
+        encoding(opt),
+        E => serve_mqtt::<T, I, E>(pin, input, opt, config),
+        _ => unknown_encoding(opt)
+    )
+}
+
+/// MQTT bridge switching addresses with the encoding `E`
+#[cfg(all(feature = "mqtt", feature = "config"))]
+fn serve_mqtt<T: Pin, I: InputPin + Send + 'static, E: Encoding>(
+    pin: T,
+    input: Option<I>,
+    opt: &Opt,
+    config: &Path,
+) {
+    use funksteckdose::{bridge::Bridge, plan::Preview};
+    use std::sync::mpsc;
+
+    let config = funksteckdose::config::Config::load(config).or_exit(Action::LoadConfig);
+    let broker = config
+        .mqtt
+        .clone()
+        .unwrap_or_else(|| exit(Message::MissingSection("mqtt")));
+    let mut d = transmitter::<T, E>(pin, opt, 10);
+    // Toggles resolve against the states sent since the start of the bridge
+    d.set_state_cache(Some(funksteckdose::state::StateCache::new()));
+
+    let options = broker.options().or_exit(Action::BrokerConfig);
+    let topics = broker.topics().or_exit(Action::TopicConfig);
+    let sockets = config.sockets.iter().map(|s| s.topic_vars()).collect();
+    let mut store = config
+        .store
+        .as_ref()
+        .map(|store| store.open().or_exit(Action::OpenStore));
+    let discovery = broker
+        .discovery(&config.sockets)
+        .or_exit(Action::TopicConfig);
+    let mut bridge = Bridge::new(options, topics)
+        .with_sockets(sockets)
+        .with_discovery(discovery);
+    if let Some(input) = input {
+        let (changes, received) = mpsc::channel();
+        let sockets = config.sockets.clone();
+        thread::spawn(move || observe_remotes(input, &sockets, changes));
+        bridge = bridge.with_changes(received);
+    }
+    bridge
+        .run(|vars, command| {
+            // Without configured sockets any group and device is accepted
+            let socket = config
+                .sockets
+                .iter()
+                .find(|s| vars.name == Some(s.name.clone()));
+            let entry = match socket {
+                Some(socket) => {
+                    if command.dry_run {
+                        return Ok(Some(Preview::from(&socket.plan(&command.state, 10)?)));
+                    }
+                    // Virtual sockets go through the whole pipeline but nothing is sent on air
+                    d.send_socket(socket, &command.state)?;
+                    Entry::new(&socket.group, &socket.device, &command.state)
+                        .sent_with(&socket.encoding, &socket.protocol)
+                }
+                None => {
+                    let group = vars.group.as_deref().unwrap_or_default();
+                    let device = vars.device.as_ref().ok_or_else(|| {
+                        Error::from(CoreError::InvalidCommand(format!(
+                            "no device in topic of group {}",
+                            group
+                        )))
+                    })?;
+                    if command.dry_run {
+                        return Ok(Some(Preview::from(&d.plan(
+                            group,
+                            device,
+                            &command.state,
+                        )?)));
+                    }
+                    d.send(group, device, &command.state)?;
+                    Entry::new(group, device, &command.state)
+                        .sent_with(&encoding(opt), protocol_name(opt))
+                }
+            };
+            if let Some(ref mut store) = store {
+                store.save_state(&entry.group, &entry.device, &entry.state)?;
+                store.append(&entry)?;
+            }
+            Ok(None)
+        })
+        .or_exit(Action::MqttBridge);
+}
+
+/// Mirror the codes of the original remotes of `sockets` into `changes`
+#[cfg(all(feature = "mqtt", feature = "config"))]
+fn observe_remotes<I: InputPin>(
+    pin: I,
+    sockets: &[funksteckdose::config::Socket],
+    changes: std::sync::mpsc::Sender<funksteckdose::state::StateChange>,
+) {
+    let mut states = funksteckdose::state::StateStore::new();
+    for socket in sockets {
+        states.insert(&socket.group, &socket.device);
+    }
+    let mut encodings: Vec<String> = sockets
+        .iter()
+        .map(|socket| socket.encoding.to_ascii_uppercase())
+        .collect();
+    encodings.sort();
+    encodings.dedup();
+    let mut receiver = Receiver::new(pin);
+    loop {
+        let received = receiver
+            .receive(Duration::from_secs(1))
+            .or_exit(Action::Receive);
+        let code_word = match received.and_then(|received| received.code_word()) {
+            Some(code_word) => code_word,
+            None => continue,
+        };
+        let change = encodings.iter().find_map(
+            |encoding| with_encoding!(encoding, E => states.observe::<E>(&code_word), _ => None),
+        );
+        if let Some(change) = change {
+            if changes.send(change).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(not(all(feature = "mqtt", feature = "config")))]
+fn mqtt<T: Pin, I: InputPin>(_pin: T, _input: Option<I>, _opt: &Opt, _config: &Path) {
+    exit(Message::NotBuilt(i18n::Feature::MqttBridge));
+}
+
+fn daemon<T: Pin>(pin: T, opt: &Opt, fifo: Option<&Path>, config: Option<&Path>) {
+    with_encoding!(
+        encoding(opt),
+        E => serve_commands::<T, E>(pin, opt, fifo, config),
+        _ => unknown_encoding(opt)
+    )
+}
+
+/// Daemon switching addresses with the encoding `E`
+fn serve_commands<T: Pin, E: Encoding>(
+    pin: T,
+    opt: &Opt,
+    fifo: Option<&Path>,
+    config: Option<&Path>,
+) {
+    let mut d = transmitter::<T, E>(pin, opt, 10);
+    // Toggles resolve against the states sent since the start of the daemon
+    d.set_state_cache(Some(funksteckdose::state::StateCache::new()));
+    let named = named_switch(config);
+    let history = RefCell::new(load_history(opt));
+    let handle = |request: &daemon::Request| {
+        switch(
+            &d,
+            &named,
+            opt,
+            &history,
+            &request.target,
+            Some(&request.state),
+        )
+        .map(|_| ())
+    };
+    match fifo {
+        Some(fifo) => daemon::serve_fifo(fifo, handle),
+        None => daemon::serve_stdin(handle),
+    }
+    .or_exit(Action::ReadCommands);
+}
+
+#[cfg(feature = "http")]
+fn http<T: Pin>(pin: T, opt: &Opt, listen: Option<&str>, config: Option<&Path>) {
+    with_encoding!(
+        encoding(opt),
+        E => serve_http::<T, E>(pin, opt, listen, config),
+        _ => unknown_encoding(opt)
+    )
+}
+
+/// HTTP server switching addresses with the encoding `E`
+#[cfg(feature = "http")]
+fn serve_http<T: Pin, E: Encoding>(pin: T, opt: &Opt, listen: Option<&str>, config: Option<&Path>) {
+    let mut d = transmitter::<T, E>(pin, opt, 10);
+    // Requests for the state are answered with the states sent since the start
+    d.set_state_cache(Some(funksteckdose::state::StateCache::new()));
+    let named = named_switch(config);
+    let (configured, credentials) = http_settings(config);
+    // Without a [http] section the server is only reachable from this host
+    let listen = listen
+        .map(str::to_string)
+        .or(configured)
+        .unwrap_or_else(|| "127.0.0.1:8080".into());
+    let mut server = funksteckdose::http::Server::bind(&listen).or_exit(Action::HttpServer);
+    if let Some((username, password)) = credentials {
+        server = server.with_credentials(&username, &password);
+    }
+    let history = RefCell::new(load_history(opt));
+    server
+        .run_with_history(
+            |target, state| switch(&d, &named, opt, &history, target, state),
+            || {
+                history
+                    .borrow()
+                    .as_ref()
+                    .map(|history| history.iter().cloned().collect())
+            },
+        )
+        .or_exit(Action::HttpServer);
+}
+
+/// Address and credentials of the [http] section of the configuration
+#[cfg(all(feature = "http", feature = "config"))]
+fn http_settings(config: Option<&Path>) -> (Option<String>, Option<(String, String)>) {
+    let http = config.and_then(|path| {
+        funksteckdose::config::Config::load(path)
+            .or_exit(Action::LoadConfig)
+            .http
+    });
+    match http {
+        Some(http) => {
+            let username = http.credentials.username().or_exit(Action::LoadConfig);
+            let password = http.credentials.password().or_exit(Action::LoadConfig);
+            let credentials = username.map(|username| (username, password.unwrap_or_default()));
+            (Some(http.listen), credentials)
+        }
+        None => (None, None),
+    }
+}
+
+#[cfg(all(feature = "http", not(feature = "config")))]
+fn http_settings(_config: Option<&Path>) -> (Option<String>, Option<(String, String)>) {
+    (None, None)
+}
+
+#[cfg(not(feature = "http"))]
+fn http<T: Pin>(_pin: T, _opt: &Opt, _listen: Option<&str>, _config: Option<&Path>) {
+    exit(Message::NotBuilt(i18n::Feature::HttpServer));
+}
+
+/// Send `state` to `target` if given and return the last sent state of the target. Sent
+/// commands are added to `history` and saved to --history.
+fn switch<T: Pin, E: Encoding>(
+    d: &Transmitter<T, E>,
+    named: &NamedSwitch<T, E>,
+    opt: &Opt,
+    history: &RefCell<Option<History>>,
+    target: &daemon::Target,
+    state: Option<&State>,
+) -> Result<Option<State>, Error> {
+    let (last, sent) = match target {
+        daemon::Target::Address(group, device) => {
+            let sent = match state {
+                Some(state) => {
+                    d.send_group(group, device, state)?;
+                    let entry = Entry::new(group.as_str(), device, state);
+                    Some(entry.sent_with(&encoding(opt), protocol_name(opt)))
+                }
+                None => None,
+            };
+            (d.last_state(group.as_str(), device), sent)
+        }
+        daemon::Target::Name(name) => named(d, name, state)?,
+    };
+    if let (Some(history), Some(sent)) = (history.borrow_mut().as_mut(), sent) {
+        history.record(sent);
+        if let Some(ref path) = opt.history {
+            history.save(path)?;
+        }
+    }
+    Ok(last)
+}
+
+/// `switch` for a socket of the configuration selected by name. Returns the last sent
+/// state and the entry of the sent command.
+type NamedSwitch<T, E> = Box<
+    dyn Fn(
+        &Transmitter<T, E>,
+        &str,
+        Option<&State>,
+    ) -> Result<(Option<State>, Option<Entry>), Error>,
+>;
+
+#[cfg(feature = "config")]
+fn named_switch<T: Pin, E: Encoding>(config: Option<&Path>) -> NamedSwitch<T, E> {
+    let config =
+        config.map(|path| funksteckdose::config::Config::load(path).or_exit(Action::LoadConfig));
+    Box::new(move |d, name, state| {
+        let socket = config
+            .as_ref()
+            .and_then(|config| config.socket(name))
+            .ok_or_else(|| CoreError::InvalidCommand(format!("unknown socket {}", name)))?;
+        let sent = match state {
+            Some(state) => {
+                d.send_socket(socket, state)?;
+                let entry = Entry::new(&socket.group, &socket.device, state);
+                Some(entry.sent_with(&socket.encoding, &socket.protocol))
+            }
+            None => None,
+        };
+        Ok((d.last_state(&socket.group, &socket.device), sent))
+    })
+}
+
+#[cfg(not(feature = "config"))]
+fn named_switch<T: Pin, E: Encoding>(config: Option<&Path>) -> NamedSwitch<T, E> {
+    if config.is_some() {
+        exit(Message::NotBuilt(i18n::Feature::SocketNames));
+    }
+    Box::new(|_, name, _| Err(CoreError::InvalidCommand(format!("unknown socket {}", name)).into()))
+}
+
+#[cfg(feature = "config")]
+fn configured_hooks(config: Option<&Path>) -> Hooks {
+    config
+        .map(|path| {
+            funksteckdose::config::Config::load(path)
+                .and_then(|config| config.hooks())
+                .or_exit(Action::LoadConfig)
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(not(feature = "config"))]
+fn configured_hooks(config: Option<&Path>) -> Hooks {
+    if config.is_some() {
+        exit(Message::NotBuilt(i18n::Feature::Hooks));
+    }
+    Hooks::default()
+}
+
+fn import_lirc(opt: &Opt, file: &Path, send: Option<&String>, repeat: usize) {
+    let codes = lirc::load(file).or_exit(Action::LoadLirc);
+    match send {
+        Some(name) => {
+            let code = codes
+                .iter()
+                .find(|code| &code.name == name)
+                .unwrap_or_else(|| exit(Message::UnknownRawCode(name)));
+            if opt.no_hardware {
+                transmit_plan(NullPin, opt, &code.plan(repeat));
+            } else {
+                transmit_plan(hardware_pin(opt), opt, &code.plan(repeat));
+            }
+        }
+        None => {
+            for code in &codes {
+                let protocol = code.protocol();
+                let message = match protocol {
+                    Some((ref values, value, length)) => Message::RawCode {
+                        remote: &code.remote,
+                        name: &code.name,
+                        values,
+                        code: value,
+                        length,
+                    },
+                    None => Message::RawSymbols {
+                        remote: &code.remote,
+                        name: &code.name,
+                        symbols: code.symbols().len(),
+                    },
+                };
+                println!("{}", tr(message));
+            }
+        }
+    }
+}
+
+fn scan<T: Pin, E: Encoding>(
+    pin: T,
+    opt: &Opt,
+    addresses: Addresses,
+    state: &State,
+    pause: Duration,
+) {
+    let d = transmitter::<T, E>(pin, opt, 10);
+    let total = addresses.len();
+    for (index, (group, device)) in addresses.enumerate() {
+        println!("{}/{}: {} {}", index + 1, total, group, device);
+        d.send(&group, &device, state).or_exit(Action::Send);
+        thread::sleep(pause);
+    }
+}
+
+fn pair<E: Encoding>(
+    opt: &Opt,
+    pairing: &Pairing,
+    group: &str,
+    device: &Device,
+    duration: Duration,
+    confirm: bool,
+) -> Result<(), Error> {
+    if opt.no_hardware {
+        let d = transmitter::<_, E>(NullPin, opt, 10);
+        pairing.pair(&d, group, device, duration, confirm)
+    } else {
+        let d = transmitter::<_, E>(hardware_pin(opt), opt, 10);
+        pairing.pair(&d, group, device, duration, confirm)
+    }
+}
+
+fn receive<T: InputPin>(pin: T, timeout: Option<Duration>, mut hooks: Hooks, stats: bool) {
+    let mut decoder = Decoder::default();
+    if stats {
+        decoder = decoder.with_stats();
+    }
+    let mut receiver = Receiver::with_decoder(pin, decoder);
+    let start = Instant::now();
+    loop {
+        let remaining = match timeout {
+            Some(timeout) if start.elapsed() >= timeout => {
+                if let Some(stats) = receiver.decoder().stats() {
+                    print!("{}", stats);
+                }
+                return;
+            }
+            Some(timeout) => timeout - start.elapsed(),
+            None => Duration::from_secs(1),
+        };
+        let received = match receiver
+            .receive(remaining.min(Duration::from_secs(1)))
+            .or_exit(Action::Receive)
+        {
+            Some(received) => received,
+            None => continue,
+        };
+        println!("{}", received);
+        if let Some(code_word) = received.code_word() {
+            print_decodings(&code_word);
+        }
+        if let Err(e) = hooks.handle(&received) {
+            eprintln!("{}", tr(Message::Failed(Action::RunHook, &e)));
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+fn learn<T: InputPin>(
+    pin: T,
+    name: &str,
+    config: &Path,
+    encoding: Option<&str>,
+    timeout: Duration,
+) {
+    use funksteckdose::config::{Config, Socket, LEARN_ENCODINGS};
+
+    if let Some(encoding) = encoding.filter(|encoding| !catalog::is_encoding(encoding)) {
+        exit(Message::UnknownEncoding(encoding));
+    }
+    let encodings = encoding.map_or(LEARN_ENCODINGS.to_vec(), |encoding| vec![encoding]);
+    let mut receiver = Receiver::new(pin);
+    let start = Instant::now();
+    println!("{}", tr(Message::Learning(name)));
+    while let Some(remaining) = timeout.checked_sub(start.elapsed()) {
+        let received = match receiver.receive(remaining).or_exit(Action::Receive) {
+            Some(received) => received,
+            None => break,
+        };
+        let candidates = Socket::candidates(name, &received, &encodings);
+        match candidates.as_slice() {
+            [] => println!("{}", tr(Message::Unlearnable(&received))),
+            [socket] => {
+                Config::append_socket(config, socket).or_exit(Action::LearnSocket);
+                println!("{}", tr(Message::Learned(name)));
+                print!("{}", socket.to_toml());
+                return;
+            }
+            _ => {
+                let encodings: Vec<&str> = candidates.iter().map(|s| s.encoding.as_str()).collect();
+                exit(Message::AmbiguousCode {
+                    received: &received,
+                    encodings: &encodings.join(", "),
+                })
+            }
+        }
+    }
+    exit(Message::NothingLearned);
+}
+
+#[cfg(not(feature = "config"))]
+fn learn<T: InputPin>(
+    _pin: T,
+    _name: &str,
+    _config: &Path,
+    _encoding: Option<&str>,
+    _timeout: Duration,
+) {
+    exit(Message::NotBuilt(i18n::Feature::SocketNames));
+}
+
+fn print_decodings(code_word: &[u8]) {
+    if let Some((group, device, state)) = <EncodingA>::decode(code_word) {
+        println!(
+            "{}",
+            tr(Message::DecodedA {
+                group: &group,
+                device: &device,
+                state: &state,
+            })
+        );
+    }
+    if let Some((group, device, state)) = EncodingB::decode(code_word) {
+        println!(
+            "{}",
+            tr(Message::DecodedB {
+                address: &group,
+                channel: u8::from(device),
+                state: &state,
+            })
+        );
+    }
+    if let Some((group, device, state)) = EncodingC::decode(code_word) {
+        let (family, group) = group.split_at(1);
+        println!(
+            "{}",
+            tr(Message::DecodedC {
+                family,
+                group,
+                device: u8::from(device),
+                state: &state,
+            })
+        );
+    }
+    if let Some((group, device, state)) = EncodingREV::decode(code_word) {
+        println!(
+            "{}",
+            tr(Message::DecodedREV {
+                group: &group,
+                device: u8::from(device),
+                state: &state,
+            })
+        );
+    }
+}
+
+fn transmit_plan<T: Pin>(pin: T, opt: &Opt, plan: &TransmissionPlan) {
+    // Encoding and protocol are part of the plan already
+    let d = transmitter::<T, EncodingA>(pin, opt, plan.repeats);
+    d.transmit(plan).or_exit(Action::Send);
+}
+
+/// Transmitter with the protocol, delay strategy and timing options of the command line
+fn transmitter<T: Pin, E: Encoding>(pin: T, opt: &Opt, repeat: usize) -> Transmitter<T, E> {
+    let mut builder = Funksteckdose::builder(pin)
+        .repeat_transmit(repeat)
+        .delay(opt.delay.delay())
+        .protocol(opt.protocol.clone())
+        .warm_up(opt.warm_up.unwrap_or_default())
+        .cool_down(opt.cool_down.unwrap_or_default())
+        .repeat_gap(opt.repeat_gap.unwrap_or_default());
+    if let Some(pin) = opt.enable_pin.filter(|_| !opt.no_hardware) {
+        builder = builder.enable_pin(enable_pin(opt, pin));
+    }
+    if let Some(max_airtime) = opt.max_airtime {
+        builder =
+            builder.max_airtime(Some(max_airtime).filter(|max_airtime| !max_airtime.is_zero()));
+    }
+    if let Some(quiet_hours) = configured_quiet_hours(opt) {
+        builder = builder.quiet_hours(quiet_hours);
+    }
+    let mut d = builder.build().or_exit(Action::Send);
+    if opt.realtime {
+        realtime(&mut d);
+    }
+    d
+}
+
+/// Configuration of the subcommand or --config
+#[cfg(feature = "config")]
+fn config_path(opt: &Opt) -> Option<&Path> {
+    match opt.command {
+        Some(Command::State { ref config, .. }) | Some(Command::Mqtt { ref config, .. }) => {
+            Some(config)
+        }
+        Some(Command::Daemon { ref config, .. }) | Some(Command::Http { ref config, .. }) => {
+            config.as_deref()
+        }
+        _ => opt.config.as_deref(),
+    }
+}
+
+/// Quiet hours of the configuration of the command line
+#[cfg(feature = "config")]
+fn configured_quiet_hours(opt: &Opt) -> Option<QuietHours> {
+    config_path(opt).and_then(|path| {
+        funksteckdose::config::Config::load(path)
+            .or_exit(Action::LoadConfig)
+            .quiet_hours
+    })
+}
+
+#[cfg(not(feature = "config"))]
+fn configured_quiet_hours(_opt: &Opt) -> Option<QuietHours> {
+    None
+}
+
+#[cfg(all(feature = "realtime", target_os = "linux"))]
+fn realtime<T: Pin, E: Encoding>(d: &mut Transmitter<T, E>) {
+    d.set_realtime(Some(funksteckdose::realtime::PRIORITY));
+}
+
+#[cfg(not(all(feature = "realtime", target_os = "linux")))]
+fn realtime<T: Pin, E: Encoding>(_d: &mut Transmitter<T, E>) {
+    exit(Message::NotBuilt(i18n::Feature::Realtime));
+}
+
+fn parse_code(code: &str, length: Option<usize>) -> Code {
+    match length {
+        Some(length) => code
+            .parse()
+            .map_err(|_| CoreError::InvalidCode(code.into()))
+            .and_then(|code| Code::from_decimal(code, length)),
+        None => Code::parse(code),
+    }
+    .unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+fn send_code<T: Pin>(pin: T, opt: &Opt, code: &Code, repeat: Option<usize>) {
+    let d = transmitter::<T, EncodingA>(pin, opt, repeat.unwrap_or(10));
+    d.send_code(code.code(), code.length())
+        .or_exit(Action::Send);
+}
+
+fn send_raw(opt: &Opt, code: &Code, protocol: Option<&ProtocolValues>, repeat: Option<usize>) {
+    let values = protocol.unwrap_or(&opt.protocol);
+    let plan = TransmissionPlan::from_code(
+        values,
+        code.code(),
+        code.length(),
+        repeat.unwrap_or(10),
+        None,
+    );
+    println!("{}", tr(Message::SendingCode(code.code())));
+    if opt.no_hardware {
+        transmit_plan(NullPin, opt, &plan);
+    } else {
+        transmit_plan(hardware_pin(opt), opt, &plan);
     }
+}
+
+/// Parse a hexadecimal number with 0x, a binary number with 0b or a decimal number
+fn parse_raw_code(code: &str) -> Result<u64, CoreError> {
+    let lower = code.to_ascii_lowercase();
+    match (lower.strip_prefix("0x"), lower.strip_prefix("0b")) {
+        (Some(hex), _) => u64::from_str_radix(hex, 16),
+        (_, Some(binary)) => u64::from_str_radix(binary, 2),
+        _ => lower.parse(),
+    }
+    .map_err(|_| CoreError::InvalidCode(code.into()))
+}
+
+fn parse_protocol(name: &str) -> Result<ProtocolValues, String> {
+    catalog::protocol(name).ok_or_else(|| {
+        let names: Vec<_> = catalog::protocols().iter().map(|p| p.name).collect();
+        format!("Unknown protocol: {}. Try {}", name, names.join(", "))
+    })
+}
+
+/// Name of the protocol selected with --protocol
+fn protocol_name(opt: &Opt) -> &'static str {
+    catalog::protocol_name(&opt.protocol).unwrap_or_default()
+}
+
+fn load_history(opt: &Opt) -> Option<History> {
+    opt.history.as_ref().map(|path| {
+        History::load(path, opt.history_size.unwrap_or(100)).or_exit(Action::LoadHistory)
+    })
+}
+
+fn send(opt: Opt) {
+    let mut history = load_history(&opt);
+
+    if opt.show_history {
+        for (index, entry) in history.iter().flat_map(History::iter).enumerate() {
+            println!("{}: {}", index, entry);
+        }
+        return;
+    }
+
+    if opt.name.is_some() {
+        return send_named(opt);
+    }
+
+    if opt.no_hardware {
+        dispatch(NullPin, &opt, history.as_mut());
+    } else {
+        dispatch(hardware_pin(&opt), &opt, history.as_mut());
+    }
+
+    if let (Some(path), Some(history)) = (opt.history, history) {
+        history.save(path).or_exit(Action::SaveHistory);
+    }
+}
+
+/// Send to the socket selected with --name
+#[cfg(feature = "config")]
+fn send_named(mut opt: Opt) {
+    let (name, config) = (opt.name.as_deref().unwrap(), opt.config.as_deref().unwrap());
+    let config = funksteckdose::config::Config::load(config).or_exit(Action::LoadConfig);
+    let socket = config
+        .socket(name)
+        .unwrap_or_else(|| exit(Message::UnknownSocket(name)));
+    // The pin and timing of the command line take precedence over the configuration
+    if opt.pin.is_none() {
+        opt.pin = socket.pin;
+    }
+    if let Some(transmitter) = opt.pin.and_then(|pin| config.transmitter(pin)) {
+        opt.warm_up = opt.warm_up.or(Some(transmitter.warm_up()));
+        opt.cool_down = opt.cool_down.or(Some(transmitter.cool_down()));
+        opt.enable_pin = opt.enable_pin.or(transmitter.enable_pin);
+    }
+    let state = opt.send.clone().unwrap();
+    // Toggling needs the last sent states from the configured store
+    let cache = match config.store {
+        Some(ref store) => {
+            let store = store.open().or_exit(Action::OpenStore);
+            Some(funksteckdose::state::StateCache::persistent(store).or_exit(Action::OpenStore))
+        }
+        None => None,
+    };
+    if opt.no_hardware {
+        let mut d = transmitter::<_, EncodingA>(NullPin, &opt, 10);
+        d.set_state_cache(cache);
+        d.send_socket(socket, &state)
+    } else {
+        let mut d = transmitter::<_, EncodingA>(hardware_pin(&opt), &opt, 10);
+        d.set_state_cache(cache);
+        d.send_socket(socket, &state)
+    }
+    .or_exit(Action::Send);
+}
+
+#[cfg(not(feature = "config"))]
+fn send_named(_opt: Opt) {
+    exit(Message::NotBuilt(i18n::Feature::SocketNames));
+}
 
-    let opt = Opt::from_args();
+#[cfg(feature = "wiringpi")]
+fn hardware_input_pin(_opt: &Opt, pin: Option<u16>) -> funksteckdose::wiringpi::WiringPiInputPin {
+    // wiringpi pin 2 is the customary pin of the receiver module
+    funksteckdose::wiringpi::WiringPiInputPin::new(pin.unwrap_or(2)).or_exit(Action::RequestGpio)
+}
+
+#[cfg(all(feature = "gpio-cdev", not(feature = "wiringpi")))]
+fn hardware_input_pin(opt: &Opt, pin: Option<u16>) -> funksteckdose::gpio_cdev::GpioCdevInputPin {
+    // Line 27 is the same pin as wiringpi pin 2 on a Raspberry Pi
+    let chip = opt
+        .chip
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("/dev/gpiochip0"));
+    funksteckdose::gpio_cdev::GpioCdevInputPin::new(&chip, u32::from(pin.unwrap_or(27)))
+        .or_exit(Action::RequestGpio)
+}
 
+#[cfg(not(any(feature = "wiringpi", feature = "gpio-cdev")))]
+fn hardware_input_pin(_opt: &Opt, _pin: Option<u16>) -> NullInputPin {
+    exit(Message::NotBuilt(i18n::Feature::Backend));
+}
+
+#[cfg(feature = "wiringpi")]
+fn hardware_pin(opt: &Opt) -> funksteckdose::wiringpi::WiringPiPin {
     // Use wiringpi pin 0. See http://wiringpi.com/pins/
-    type Funksteckdose = funksteckdose::Funksteckdose<WiringPiPin, EncodingA, Protocol1>;
-    let pin = WiringPiPin::new(opt.pin.unwrap_or(0));
-    let d: Funksteckdose = Funksteckdose::new(pin);
-    d.send(&opt.group, &opt.device, &opt.send).expect("Failed to send");
+    funksteckdose::wiringpi::WiringPiPin::new(opt.pin.unwrap_or(0)).or_exit(Action::RequestGpio)
+}
+
+#[cfg(all(feature = "gpio-cdev", not(feature = "wiringpi")))]
+fn hardware_pin(opt: &Opt) -> funksteckdose::gpio_cdev::GpioCdevPin {
+    // Line 17 is the same pin as wiringpi pin 0 on a Raspberry Pi
+    let chip = opt
+        .chip
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("/dev/gpiochip0"));
+    funksteckdose::gpio_cdev::GpioCdevPin::new(&chip, u32::from(opt.pin.unwrap_or(17)))
+        .or_exit(Action::RequestGpio)
+}
+
+#[cfg(not(any(feature = "wiringpi", feature = "gpio-cdev")))]
+fn hardware_pin(_opt: &Opt) -> NullPin {
+    exit(Message::NoBackend);
+}
+
+#[cfg(feature = "wiringpi")]
+fn enable_pin(_opt: &Opt, pin: u16) -> Box<dyn Pin + Send + Sync> {
+    Box::new(funksteckdose::wiringpi::WiringPiPin::new(pin).or_exit(Action::RequestGpio))
+}
+
+#[cfg(all(feature = "gpio-cdev", not(feature = "wiringpi")))]
+fn enable_pin(opt: &Opt, pin: u16) -> Box<dyn Pin + Send + Sync> {
+    let chip = opt
+        .chip
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("/dev/gpiochip0"));
+    Box::new(
+        funksteckdose::gpio_cdev::GpioCdevPin::new(&chip, u32::from(pin))
+            .or_exit(Action::RequestGpio),
+    )
+}
+
+#[cfg(not(any(feature = "wiringpi", feature = "gpio-cdev")))]
+fn enable_pin(_opt: &Opt, _pin: u16) -> Box<dyn Pin + Send + Sync> {
+    exit(Message::NoBackend);
+}
+
+/// Name of the encoding selected with `--encoding`, `--address` or `--intertechno` in
+/// upper case
+fn encoding(opt: &Opt) -> String {
+    match (&opt.address, &opt.intertechno, &opt.encoding) {
+        (Some(_), _, _) => "B".to_string(),
+        (_, Some(_), _) => "C".to_string(),
+        (_, _, encoding) => encoding.as_deref().unwrap_or("A").to_ascii_uppercase(),
+    }
+}
+
+fn unknown_encoding(opt: &Opt) -> ! {
+    exit(Message::UnknownEncoding(
+        opt.encoding.as_deref().unwrap_or_default(),
+    ))
+}
+
+fn dispatch<T: Pin>(pin: T, opt: &Opt, history: Option<&mut History>) {
+    // Replays use the encoding the entry was sent with
+    let replayed = match (opt.replay, &history) {
+        (Some(index), Some(history)) => history
+            .get(index)
+            .and_then(|entry| entry.encoding.as_deref())
+            .map(str::to_ascii_uppercase),
+        _ => None,
+    };
+    let encoding = replayed.unwrap_or_else(|| encoding(opt));
+    with_encoding!(
+        encoding,
+        E => transmit::<T, E>(pin, opt, history),
+        _ => exit(Message::UnknownEncoding(&encoding))
+    )
+}
+
+fn transmit<T: Pin, E: Encoding>(pin: T, opt: &Opt, history: Option<&mut History>) {
+    let mut d = transmitter::<T, E>(pin, opt, 10);
+    if opt.calibrate {
+        d.calibrate().or_exit(Action::Calibrate);
+    }
+
+    match (opt.replay, history) {
+        (Some(index), Some(history)) => {
+            let protocol = history
+                .get(index)
+                .and_then(|entry| entry.protocol.as_deref())
+                .map(|name| {
+                    catalog::protocol(name).unwrap_or_else(|| exit(Message::UnknownProtocol(name)))
+                });
+            if let Some(protocol) = protocol {
+                d.set_protocol(protocol);
+            }
+            history.replay(index, &d).or_exit(Action::Replay)
+        }
+        (_, history) => {
+            let (group, device) = match (&opt.address, &opt.intertechno) {
+                (Some(address), _) => address.group_device(),
+                (_, Some(address)) => address.group_device(),
+                _ => (
+                    opt.group.as_ref().unwrap().to_string(),
+                    opt.device.clone().unwrap(),
+                ),
+            };
+            let send = opt.send.clone().unwrap();
+            d.send(&group, &device, &send).or_exit(Action::Send);
+            if let Some(history) = history {
+                let entry = Entry::new(&group, &device, &send);
+                history.record(entry.sent_with(&encoding(opt), protocol_name(opt)));
+            }
+        }
+    }
 }
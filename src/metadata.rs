@@ -0,0 +1,92 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Presentation metadata of a socket e.g the icon and area shown by a dashboard.
+//!
+//! The metadata is not interpreted by funksteckdose. It is read from the `[socket.meta]`
+//! table of the configuration and passed through to the consumers e.g the Home Assistant
+//! discovery payloads so the UI is organized without a second mapping next to the
+//! configuration:
+//!
+//! ```toml
+//! [[socket]]
+//! name = "lamp"
+//! group = "10011"
+//! device = "A"
+//!
+//! [socket.meta]
+//! icon = "mdi:floor-lamp"
+//! area = "Living room"
+//! label = "Floor lamp"
+//! sort = 1
+//! color = "amber"
+//! ```
+
+use std::{cmp::Ordering, collections::BTreeMap};
+
+/// Freeform metadata of a socket
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Metadata {
+    /// Icon e.g "mdi:lamp"
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub icon: Option<String>,
+    /// Area the socket is shown in. Unlike the room it is not part of any topic.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub area: Option<String>,
+    /// Human readable name shown instead of the socket name
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub label: Option<String>,
+    /// Position within the area. Sockets without are shown last.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub sort: Option<i64>,
+    /// Any other string values, passed through unchanged
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub extra: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    /// The label or `name` if there is none
+    pub fn label_or<'a>(&'a self, name: &'a str) -> &'a str {
+        self.label.as_deref().unwrap_or(name)
+    }
+
+    /// True if nothing is set
+    pub fn is_empty(&self) -> bool {
+        *self == Metadata::default()
+    }
+
+    /// Order for presentation: by area, then sort position and finally label. Sockets without
+    /// area or position come after the others.
+    pub fn order(&self, name: &str, other: &Metadata, other_name: &str) -> Ordering {
+        fn last<T: Ord>(a: &Option<T>, b: &Option<T>) -> Ordering {
+            match (a, b) {
+                (Some(a), Some(b)) => a.cmp(b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+        last(&self.area, &other.area)
+            .then_with(|| last(&self.sort, &other.sort))
+            .then_with(|| self.label_or(name).cmp(other.label_or(other_name)))
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Counters that can be exported e.g in the Prometheus text format via `Display`
+
+use crate::error::{Category, Error};
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Counters of sent commands and errors by category
+#[derive(Debug, Default)]
+pub struct Metrics {
+    sent: AtomicU64,
+    errors: [AtomicU64; Category::ALL.len()],
+}
+
+impl Metrics {
+    /// Count the outcome of a send
+    pub fn record<T>(&self, result: &Result<T, Error>) {
+        match result {
+            Ok(_) => self.sent.fetch_add(1, Ordering::Relaxed),
+            Err(e) => self.errors[index(e.category())].fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    /// Number of successfully sent commands
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Number of errors of `category`
+    pub fn errors(&self, category: Category) -> u64 {
+        self.errors[index(category)].load(Ordering::Relaxed)
+    }
+}
+
+fn index(category: Category) -> usize {
+    Category::ALL
+        .iter()
+        .position(|c| *c == category)
+        .expect("unknown category")
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# TYPE funksteckdose_sent_total counter")?;
+        writeln!(f, "funksteckdose_sent_total {}", self.sent())?;
+        writeln!(f, "# TYPE funksteckdose_errors_total counter")?;
+        for category in Category::ALL.iter() {
+            writeln!(
+                f,
+                "funksteckdose_errors_total{{category=\"{}\"}} {}",
+                category.name(),
+                self.errors(*category)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Counters of transmissions deferred by listen-before-talk because the channel was busy.
+/// Many or long deferrals mean that sockets respond slowly because of RF congestion.
+#[derive(Debug, Default)]
+pub struct Deferrals {
+    checks: AtomicU64,
+    deferred: AtomicU64,
+    deferred_micros: AtomicU64,
+    forced: AtomicU64,
+}
+
+impl Deferrals {
+    /// Count a check of the channel. `waited` is the time the transmission was deferred,
+    /// `forced` is true if it started although the channel was still busy.
+    pub fn record(&self, waited: Option<Duration>, forced: bool) {
+        self.checks.fetch_add(1, Ordering::Relaxed);
+        if let Some(waited) = waited {
+            self.deferred.fetch_add(1, Ordering::Relaxed);
+            self.deferred_micros
+                .fetch_add(waited.as_micros() as u64, Ordering::Relaxed);
+        }
+        if forced {
+            self.forced.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Number of transmissions that checked the channel
+    pub fn checks(&self) -> u64 {
+        self.checks.load(Ordering::Relaxed)
+    }
+
+    /// Number of transmissions that found the channel busy
+    pub fn deferred(&self) -> u64 {
+        self.deferred.load(Ordering::Relaxed)
+    }
+
+    /// Total time transmissions were deferred
+    pub fn deferred_time(&self) -> Duration {
+        Duration::from_micros(self.deferred_micros.load(Ordering::Relaxed))
+    }
+
+    /// Number of transmissions started on a busy channel after the maximum deferral
+    pub fn forced(&self) -> u64 {
+        self.forced.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for Deferrals {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# TYPE funksteckdose_lbt_checks_total counter")?;
+        writeln!(f, "funksteckdose_lbt_checks_total {}", self.checks())?;
+        writeln!(f, "# TYPE funksteckdose_lbt_deferred_total counter")?;
+        writeln!(f, "funksteckdose_lbt_deferred_total {}", self.deferred())?;
+        writeln!(f, "# TYPE funksteckdose_lbt_deferred_seconds_total counter")?;
+        writeln!(
+            f,
+            "funksteckdose_lbt_deferred_seconds_total {}",
+            self.deferred_time().as_secs_f64()
+        )?;
+        writeln!(f, "# TYPE funksteckdose_lbt_forced_total counter")?;
+        writeln!(f, "funksteckdose_lbt_forced_total {}", self.forced())
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Pins without hardware e.g for containers, CI and tests
+
+use crate::{delay::Delay, error::CoreError, InputPin, Pin, Value};
+use log::trace;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A pin that is not connected to anything
+#[derive(Clone, Debug, Default)]
+pub struct NullPin;
+
+impl Pin for NullPin {
+    fn set(&self, value: &Value) -> Result<(), CoreError> {
+        trace!("Null pin set to {:?}", value);
+        Ok(())
+    }
+}
+
+/// An input pin that is not connected to anything. It always reads low.
+#[derive(Clone, Debug, Default)]
+pub struct NullInputPin;
+
+impl InputPin for NullInputPin {
+    fn get(&self) -> Result<Value, CoreError> {
+        Ok(Value::Low)
+    }
+}
+
+/// A call to a [`Recorder`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The pin was set
+    Set(Value),
+    /// A delay of the given µs was requested
+    Delay(u32),
+}
+
+/// Pin and delay that record all calls instead of waiting. Clones share the recording, so
+/// one clone is used as pin and another one as delay of a transmitter. Transmissions
+/// finish immediately.
+#[derive(Clone, Debug, Default)]
+pub struct Recorder {
+    events: Arc<Mutex<Vec<Event>>>,
+}
+
+impl Recorder {
+    /// All calls in order
+    pub fn events(&self) -> Vec<Event> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Levels of the pin and how long they were held in µs. Consecutive delays at the same
+    /// level are summed up. Setting the pin to its current level does not start a new pulse.
+    pub fn pulses(&self) -> Vec<(Value, u32)> {
+        let mut pulses: Vec<(Value, u32)> = Vec::new();
+        for event in self.events() {
+            match event {
+                Event::Set(value) => match pulses.last() {
+                    Some((last, _)) if *last == value => (),
+                    _ => pulses.push((value, 0)),
+                },
+                Event::Delay(micros) => {
+                    if let Some((_, duration)) = pulses.last_mut() {
+                        *duration += micros;
+                    }
+                }
+            }
+        }
+        pulses
+    }
+
+    /// Forget the recording
+    pub fn clear(&self) {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    fn record(&self, event: Event) {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(event);
+    }
+}
+
+impl Pin for Recorder {
+    fn set(&self, value: &Value) -> Result<(), CoreError> {
+        self.record(Event::Set(value.clone()));
+        Ok(())
+    }
+}
+
+impl Delay for Recorder {
+    fn delay(&self, micros: u32) {
+        self.record(Event::Delay(micros));
+    }
+}
+
+/// A `set` call captured by a [`RecordingPin`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// Number of the call starting at 0
+    pub tick: u64,
+    /// Time since the pin was created
+    pub elapsed: Duration,
+    /// Value the pin was set to
+    pub value: Value,
+}
+
+/// A pin that records every `set` with a timestamp e.g for dry runs or to test automations.
+/// Unlike [`Recorder`] it is used with any delay, so the timestamps show the actual timing
+/// of a transmission. Clones share the recording.
+/// ```
+/// use funksteckdose::{delay::Spin, mock::RecordingPin, Device, EncodingA, Funksteckdose};
+/// use funksteckdose::{Protocol1, State, Value};
+/// let pin = RecordingPin::new();
+/// let d = Funksteckdose::<_, EncodingA, Protocol1, _>::with_delay(pin.clone(), 1, Spin);
+/// d.send("10011", &Device::A, &State::On).unwrap();
+/// let samples = pin.samples();
+/// assert_eq!(samples[0].value, Value::High);
+/// assert!(samples.windows(2).all(|s| s[0].tick + 1 == s[1].tick));
+/// assert!(samples.windows(2).all(|s| s[0].elapsed <= s[1].elapsed));
+/// ```
+#[derive(Clone, Debug)]
+pub struct RecordingPin {
+    start: Instant,
+    samples: Arc<Mutex<Vec<Sample>>>,
+}
+
+impl Default for RecordingPin {
+    fn default() -> RecordingPin {
+        RecordingPin::new()
+    }
+}
+
+impl RecordingPin {
+    /// Create a pin with an empty recording. Timestamps are relative to now.
+    pub fn new() -> RecordingPin {
+        RecordingPin {
+            start: Instant::now(),
+            samples: Arc::default(),
+        }
+    }
+
+    /// All `set` calls in order
+    pub fn samples(&self) -> Vec<Sample> {
+        self.samples
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Values of all `set` calls in order
+    pub fn values(&self) -> Vec<Value> {
+        self.samples().into_iter().map(|s| s.value).collect()
+    }
+
+    /// Values and how long they were held until the next `set`. The last value is still
+    /// held and not included.
+    pub fn durations(&self) -> Vec<(Value, Duration)> {
+        self.samples()
+            .windows(2)
+            .map(|s| (s[0].value.clone(), s[1].elapsed - s[0].elapsed))
+            .collect()
+    }
+
+    /// Forget the recording. Ticks start at 0 again.
+    pub fn clear(&self) {
+        self.samples
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+impl Pin for RecordingPin {
+    fn set(&self, value: &Value) -> Result<(), CoreError> {
+        let elapsed = self.start.elapsed();
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let tick = samples.last().map_or(0, |s| s.tick + 1);
+        samples.push(Sample {
+            tick,
+            elapsed,
+            value: value.clone(),
+        });
+        Ok(())
+    }
+}
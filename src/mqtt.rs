@@ -0,0 +1,661 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! MQTT topic and payload conventions
+//!
+//! Topics are described by templates like `{prefix}/{room}/{name}/set`. Each placeholder
+//! must span a whole topic level so that a template can be rendered for a socket, turned
+//! into a subscription filter and matched against incoming topics.
+//!
+//! The bridge announces its version on `{prefix}/bridge` with a retained banner like
+//! `{"online":true,"version":"0.3.0","api":1,"payload":"json","capabilities":["dry_run"]}`.
+//! The last will replaces it with `"online":false`. Clients check `api` before relying on the
+//! topics and can put `"api"` into JSON commands, which the bridge rejects if they need a
+//! newer api than it implements.
+//!
+//! Home Assistant finds the sockets through the retained discovery payloads of
+//! [`Topics::switch_discovery`]. The banner is their availability topic.
+
+use crate::{
+    energy::Reading, error::Error, metadata::Metadata, plan::Preview, state::StateChange, Device,
+    State,
+};
+use serde_json::{json, Value as Json};
+use std::{fmt, str};
+
+/// Version of the topics and payloads of the bridge. Raised on incompatible changes.
+pub const API_VERSION: u64 = 1;
+
+/// Features of the bridge announced in the banner
+pub const CAPABILITIES: &[&str] = &["dry_run", "preview", "energy", "discovery"];
+
+/// Placeholders that can be used in a topic template
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Placeholder {
+    Prefix,
+    Room,
+    Name,
+    Group,
+    Device,
+}
+
+impl str::FromStr for Placeholder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prefix" => Ok(Placeholder::Prefix),
+            "room" => Ok(Placeholder::Room),
+            "name" => Ok(Placeholder::Name),
+            "group" => Ok(Placeholder::Group),
+            "device" => Ok(Placeholder::Device),
+            _ => Err(Error::InvalidTopicTemplate(format!(
+                "unknown placeholder {{{}}}",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Placeholder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Placeholder::Prefix => "prefix",
+            Placeholder::Room => "room",
+            Placeholder::Name => "name",
+            Placeholder::Group => "group",
+            Placeholder::Device => "device",
+        };
+        write!(f, "{{{}}}", s)
+    }
+}
+
+/// Values substituted into a topic template
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TopicVars {
+    pub prefix: Option<String>,
+    pub room: Option<String>,
+    pub name: Option<String>,
+    pub group: Option<String>,
+    pub device: Option<Device>,
+}
+
+impl TopicVars {
+    fn get(&self, placeholder: Placeholder) -> Option<String> {
+        match placeholder {
+            Placeholder::Prefix => self.prefix.clone(),
+            Placeholder::Room => self.room.clone(),
+            Placeholder::Name => self.name.clone(),
+            Placeholder::Group => self.group.clone(),
+            Placeholder::Device => self.device.as_ref().map(ToString::to_string),
+        }
+    }
+
+    fn set(&mut self, placeholder: Placeholder, value: &str) -> Result<(), Error> {
+        match placeholder {
+            Placeholder::Prefix => self.prefix = Some(value.into()),
+            Placeholder::Room => self.room = Some(value.into()),
+            Placeholder::Name => self.name = Some(value.into()),
+            Placeholder::Group => self.group = Some(value.into()),
+            Placeholder::Device => self.device = Some(value.parse()?),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Level {
+    Literal(String),
+    Var(Placeholder),
+}
+
+/// A MQTT topic template e.g `{prefix}/{room}/{name}/set`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TopicTemplate {
+    levels: Vec<Level>,
+}
+
+impl TopicTemplate {
+    /// Render the template. Fails if a used placeholder has no value.
+    pub fn render(&self, vars: &TopicVars) -> Result<String, Error> {
+        let levels = self
+            .levels
+            .iter()
+            .map(|level| match level {
+                Level::Literal(l) => Ok(l.clone()),
+                Level::Var(p) => vars
+                    .get(*p)
+                    .ok_or_else(|| Error::InvalidTopicTemplate(format!("no value for {}", p))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(levels.join("/"))
+    }
+
+    /// Replace placeholders that have a value in `vars` by literal levels
+    pub fn bind(&self, vars: &TopicVars) -> TopicTemplate {
+        let levels = self
+            .levels
+            .iter()
+            .flat_map(|level| match level {
+                Level::Var(p) => match vars.get(*p) {
+                    Some(v) => v.split('/').map(|l| Level::Literal(l.into())).collect(),
+                    None => vec![level.clone()],
+                },
+                Level::Literal(_) => vec![level.clone()],
+            })
+            .collect();
+        TopicTemplate { levels }
+    }
+
+    /// Subscription filter with every placeholder not set in `vars` replaced by `+`
+    pub fn filter(&self, vars: &TopicVars) -> String {
+        self.bind(vars)
+            .levels
+            .iter()
+            .map(|level| match level {
+                Level::Literal(l) => l.as_str(),
+                Level::Var(_) => "+",
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Match `topic` against the template and extract the placeholder values
+    pub fn matches(&self, topic: &str) -> Option<TopicVars> {
+        let parts = topic.split('/').collect::<Vec<_>>();
+        if parts.len() != self.levels.len() {
+            return None;
+        }
+        let mut vars = TopicVars::default();
+        for (level, part) in self.levels.iter().zip(parts) {
+            match level {
+                Level::Literal(l) if l == part => (),
+                Level::Literal(_) => return None,
+                Level::Var(p) => {
+                    if let Some(existing) = vars.get(*p) {
+                        if existing != part {
+                            return None;
+                        }
+                    }
+                    vars.set(*p, part).ok()?;
+                }
+            }
+        }
+        Some(vars)
+    }
+}
+
+impl str::FromStr for TopicTemplate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(Error::InvalidTopicTemplate("empty template".into()));
+        }
+        let levels = s
+            .split('/')
+            .map(|level| {
+                if level.starts_with('{') && level.ends_with('}') {
+                    level[1..level.len() - 1].parse().map(Level::Var)
+                } else if level.contains(['{', '}', '+', '#']) {
+                    Err(Error::InvalidTopicTemplate(format!(
+                        "invalid topic level \"{}\". Placeholders must span a whole level",
+                        level
+                    )))
+                } else {
+                    Ok(Level::Literal(level.into()))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TopicTemplate { levels })
+    }
+}
+
+impl fmt::Display for TopicTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (n, level) in self.levels.iter().enumerate() {
+            if n > 0 {
+                write!(f, "/")?;
+            }
+            match level {
+                Level::Literal(l) => write!(f, "{}", l)?,
+                Level::Var(p) => write!(f, "{}", p)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Format of state payloads
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PayloadFormat {
+    /// `ON` and `OFF`
+    OnOff,
+    /// `1` and `0`
+    Numeric,
+    /// `{"state":"ON"}` and `{"state":"OFF"}`
+    Json,
+}
+
+impl PayloadFormat {
+    /// Encode `state` into a payload
+    pub fn encode(self, state: &State) -> String {
+        let on = *state == State::On;
+        match self {
+            PayloadFormat::OnOff if on => "ON".into(),
+            PayloadFormat::OnOff => "OFF".into(),
+            PayloadFormat::Numeric if on => "1".into(),
+            PayloadFormat::Numeric => "0".into(),
+            PayloadFormat::Json => json!({ "state": if on { "ON" } else { "OFF" } }).to_string(),
+        }
+    }
+
+    /// Decode a command payload. Only JSON payloads can request a dry run e.g
+    /// `{"state": "ON", "dry_run": true}`.
+    pub fn decode_command(self, payload: &[u8]) -> Result<CommandPayload, Error> {
+        let state = self.decode(payload)?;
+        let json = match self {
+            PayloadFormat::Json => serde_json::from_slice::<Json>(payload).ok(),
+            _ => None,
+        };
+        let field = |name: &str| json.as_ref().and_then(|value| value.get(name));
+        if let Some(api) = field("api").and_then(Json::as_u64) {
+            if api > API_VERSION {
+                return Err(Error::InvalidPayload(format!(
+                    "command needs api {} but the bridge implements api {}",
+                    api, API_VERSION
+                )));
+            }
+        }
+        let dry_run = field("dry_run").and_then(Json::as_bool).unwrap_or(false);
+        Ok(CommandPayload { state, dry_run })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            PayloadFormat::OnOff => "on-off",
+            PayloadFormat::Numeric => "numeric",
+            PayloadFormat::Json => "json",
+        }
+    }
+
+    /// Decode a payload into a state
+    pub fn decode(self, payload: &[u8]) -> Result<State, Error> {
+        let invalid = || Error::InvalidPayload(String::from_utf8_lossy(payload).into());
+        let payload = str::from_utf8(payload).map_err(|_| invalid())?.trim();
+        match self {
+            PayloadFormat::OnOff => match payload.to_ascii_uppercase().as_str() {
+                "ON" => Ok(State::On),
+                "OFF" => Ok(State::Off),
+                _ => Err(invalid()),
+            },
+            PayloadFormat::Numeric => match payload {
+                "1" => Ok(State::On),
+                "0" => Ok(State::Off),
+                _ => Err(invalid()),
+            },
+            PayloadFormat::Json => {
+                let value = serde_json::from_str::<Json>(payload).map_err(|_| invalid())?;
+                match value.get("state") {
+                    Some(Json::String(s)) => PayloadFormat::OnOff.decode(s.as_bytes()),
+                    Some(Json::Bool(true)) => Ok(State::On),
+                    Some(Json::Bool(false)) => Ok(State::Off),
+                    Some(Json::Number(n)) => {
+                        PayloadFormat::Numeric.decode(n.to_string().as_bytes())
+                    }
+                    _ => Err(invalid()),
+                }
+            }
+        }
+    }
+}
+
+impl str::FromStr for PayloadFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "on-off" | "ON/OFF" => Ok(PayloadFormat::OnOff),
+            "numeric" | "1/0" => Ok(PayloadFormat::Numeric),
+            "json" | "JSON" => Ok(PayloadFormat::Json),
+            _ => Err(Error::InvalidPayloadFormat(s.into())),
+        }
+    }
+}
+
+/// A received command
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandPayload {
+    /// Requested state
+    pub state: State,
+    /// Encode the command and publish a preview instead of sending it
+    pub dry_run: bool,
+}
+
+/// Topic and payload configuration of the bridge
+#[derive(Clone, Debug, PartialEq)]
+pub struct Topics {
+    /// Value of `{prefix}`
+    pub prefix: String,
+    /// Topic commands are received on
+    pub command: TopicTemplate,
+    /// Topic states are published to
+    pub state: TopicTemplate,
+    /// Topic previews of dry runs are published to
+    pub preview: TopicTemplate,
+    /// Topic on time and energy estimates are published to
+    pub energy: TopicTemplate,
+    /// Payload format for commands and states
+    pub payload: PayloadFormat,
+}
+
+impl Topics {
+    /// Topic the bridge announces its version and capabilities on
+    pub fn banner_topic(&self) -> String {
+        format!("{}/bridge", self.prefix)
+    }
+
+    /// Retained banner with the version and capabilities of the bridge. `online` is false
+    /// for the last will.
+    pub fn banner(&self, online: bool) -> String {
+        json!({
+            "online": online,
+            "version": env!("CARGO_PKG_VERSION"),
+            "api": API_VERSION,
+            "payload": self.payload.name(),
+            "capabilities": CAPABILITIES,
+        })
+        .to_string()
+    }
+
+    /// Topic to publish the state of a socket to
+    pub fn state_topic(&self, vars: &TopicVars) -> Result<String, Error> {
+        self.state.render(&self.with_prefix(vars))
+    }
+
+    /// Topic a socket receives its commands on
+    pub fn command_topic(&self, vars: &TopicVars) -> Result<String, Error> {
+        self.command.render(&self.with_prefix(vars))
+    }
+
+    /// Topic and payload to publish for a state change. The room and name of the socket are
+    /// taken from the matching entry of `sockets` e.g the `Socket::topic_vars` of a
+    /// configuration.
+    pub fn state_message(
+        &self,
+        sockets: &[TopicVars],
+        change: &StateChange,
+    ) -> Result<(String, String), Error> {
+        let group = Some(change.group.clone());
+        let device = Some(change.device.clone());
+        let vars = sockets
+            .iter()
+            .find(|socket| socket.group == group && socket.device == device)
+            .cloned()
+            .unwrap_or(TopicVars {
+                group,
+                device,
+                ..TopicVars::default()
+            });
+        Ok((self.state_topic(&vars)?, self.payload.encode(&change.state)))
+    }
+
+    /// Topic and payload to publish for the dry run of a command to the socket in `vars`
+    pub fn preview_message(
+        &self,
+        vars: &TopicVars,
+        preview: &Preview,
+    ) -> Result<(String, String), Error> {
+        let topic = self.preview.render(&self.with_prefix(vars))?;
+        let payload =
+            serde_json::to_string(preview).map_err(|e| Error::InvalidPayload(e.to_string()))?;
+        Ok((topic, payload))
+    }
+
+    /// Topic and payload to publish the on time and energy of a socket. The payload is a JSON
+    /// object with `on_time_s` and, if the wattage is known, `energy_wh`.
+    pub fn energy_message(&self, reading: &Reading) -> Result<(String, String), Error> {
+        let vars = TopicVars {
+            group: Some(reading.group.clone()),
+            device: Some(reading.device.clone()),
+            ..TopicVars::default()
+        };
+        let topic = self.energy.render(&self.with_prefix(&vars))?;
+        let payload = match reading.energy_wh() {
+            Some(energy) => json!({ "on_time_s": reading.on_time.as_secs(), "energy_wh": energy }),
+            None => json!({ "on_time_s": reading.on_time.as_secs() }),
+        };
+        Ok((topic, payload.to_string()))
+    }
+
+    /// Home Assistant discovery topic and payload of an energy sensor reading the energy
+    /// topic of a socket. `name` is the name of the socket. The label of `meta` replaces the
+    /// name, the icon and area are passed on.
+    pub fn energy_discovery(
+        &self,
+        name: &str,
+        meta: &Metadata,
+        reading: &Reading,
+    ) -> Result<(String, String), Error> {
+        let (state_topic, _) = self.energy_message(reading)?;
+        let device_id = self.device_id(&reading.group, &reading.device);
+        let id = format!("{}_energy", device_id);
+        let topic = format!("homeassistant/sensor/{}/config", id);
+        let name = meta.label_or(name);
+        let mut payload = json!({
+            "name": format!("{} energy", name),
+            "unique_id": id,
+            "state_topic": state_topic,
+            "unit_of_measurement": "Wh",
+            "device_class": "energy",
+            "state_class": "total_increasing",
+            "value_template": "{{ value_json.energy_wh }}",
+        });
+        if let Some(icon) = &meta.icon {
+            payload["icon"] = json!(icon);
+        }
+        if let Some(area) = &meta.area {
+            payload["device"] = json!({
+                "identifiers": [device_id],
+                "name": name,
+                "suggested_area": area,
+            });
+        }
+        Ok((topic, payload.to_string()))
+    }
+
+    /// Home Assistant discovery topic and payload of a switch for the socket in `vars`. The
+    /// label of `meta` replaces the name of the socket, the icon and area are passed on. The
+    /// switch is available while the banner of the bridge is online.
+    /// ```
+    /// use funksteckdose::{metadata::Metadata, mqtt::{TopicVars, Topics}, Device};
+    /// let vars = TopicVars {
+    ///     name: Some("lamp".into()),
+    ///     group: Some("10011".into()),
+    ///     device: Some(Device::A),
+    ///     ..TopicVars::default()
+    /// };
+    /// let (topic, payload) = Topics::default().switch_discovery(&vars, &Metadata::default())?;
+    /// assert_eq!(topic, "homeassistant/switch/funksteckdose_10011_A/config");
+    /// assert!(payload.contains("\"command_topic\":\"funksteckdose/10011/A/set\""));
+    /// # Ok::<(), funksteckdose::error::Error>(())
+    /// ```
+    pub fn switch_discovery(
+        &self,
+        vars: &TopicVars,
+        meta: &Metadata,
+    ) -> Result<(String, String), Error> {
+        let (group, device) = match (&vars.group, &vars.device) {
+            (Some(group), Some(device)) => (group, device),
+            _ => {
+                return Err(Error::InvalidTopicTemplate(
+                    "discovery needs the group and device of a socket".into(),
+                ))
+            }
+        };
+        let device_id = self.device_id(group, device);
+        let topic = format!("homeassistant/switch/{}/config", device_id);
+        let name = vars
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("{} {}", group, device));
+        let name = meta.label_or(&name);
+        let (on, off) = (
+            self.payload.encode(&State::On),
+            self.payload.encode(&State::Off),
+        );
+        let mut payload = json!({
+            "name": name,
+            "unique_id": device_id,
+            "command_topic": self.command_topic(vars)?,
+            "state_topic": self.state_topic(vars)?,
+            "payload_on": on,
+            "payload_off": off,
+            "state_on": on,
+            "state_off": off,
+            "availability_topic": self.banner_topic(),
+            "availability_template": "{{ 'online' if value_json.online else 'offline' }}",
+            "device": {
+                "identifiers": [device_id],
+                "name": name,
+            },
+        });
+        if self.payload == PayloadFormat::Json {
+            payload["value_template"] = json!("{{ value_json.state }}");
+            payload["state_on"] = json!("ON");
+            payload["state_off"] = json!("OFF");
+        }
+        if let Some(icon) = &meta.icon {
+            payload["icon"] = json!(icon);
+        }
+        if let Some(area) = &meta.area {
+            payload["device"]["suggested_area"] = json!(area);
+        }
+        Ok((topic, payload.to_string()))
+    }
+
+    /// Id of a socket in Home Assistant
+    fn device_id(&self, group: &str, device: &Device) -> String {
+        format!("{}_{}_{}", self.prefix, group, device)
+            .replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_")
+    }
+
+    /// Subscription filter for all command topics
+    pub fn command_filter(&self) -> String {
+        self.command
+            .filter(&self.with_prefix(&TopicVars::default()))
+    }
+
+    /// Parse a received command topic and payload
+    pub fn parse_command(
+        &self,
+        topic: &str,
+        payload: &[u8],
+    ) -> Option<(TopicVars, Result<CommandPayload, Error>)> {
+        let mut vars = self
+            .command
+            .bind(&self.with_prefix(&TopicVars::default()))
+            .matches(topic)?;
+        vars.prefix = Some(self.prefix.clone());
+        Some((vars, self.payload.decode_command(payload)))
+    }
+
+    fn with_prefix(&self, vars: &TopicVars) -> TopicVars {
+        TopicVars {
+            prefix: Some(self.prefix.clone()),
+            ..vars.clone()
+        }
+    }
+}
+
+impl Default for Topics {
+    fn default() -> Topics {
+        Topics {
+            prefix: "funksteckdose".into(),
+            command: "{prefix}/{group}/{device}/set"
+                .parse()
+                .expect("invalid default template"),
+            state: "{prefix}/{group}/{device}/state"
+                .parse()
+                .expect("invalid default template"),
+            preview: "{prefix}/{group}/{device}/preview"
+                .parse()
+                .expect("invalid default template"),
+            energy: "{prefix}/{group}/{device}/energy"
+                .parse()
+                .expect("invalid default template"),
+            payload: PayloadFormat::OnOff,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Origin;
+
+    fn change() -> StateChange {
+        StateChange {
+            group: "10001".into(),
+            device: Device::B,
+            state: State::On,
+            origin: Origin::Remote,
+        }
+    }
+
+    #[test]
+    fn state_message() {
+        let topics = Topics::default();
+        let (topic, payload) = topics.state_message(&[], &change()).unwrap();
+        assert_eq!(topic, "funksteckdose/10001/B/state");
+        assert_eq!(payload, "ON");
+    }
+
+    #[test]
+    fn state_message_of_named_socket() {
+        let topics = Topics {
+            state: "{prefix}/{room}/{name}/state".parse().unwrap(),
+            ..Topics::default()
+        };
+        let socket = TopicVars {
+            room: Some("kitchen".into()),
+            name: Some("kettle".into()),
+            group: Some("10001".into()),
+            device: Some(Device::B),
+            ..TopicVars::default()
+        };
+        let (topic, _) = topics.state_message(&[socket], &change()).unwrap();
+        assert_eq!(topic, "funksteckdose/kitchen/kettle/state");
+        // Unknown sockets have no room and name
+        assert!(topics.state_message(&[], &change()).is_err());
+    }
+
+    #[test]
+    fn parse_command() {
+        let topics = Topics::default();
+        let (vars, command) = topics
+            .parse_command("funksteckdose/10001/B/set", b"OFF")
+            .unwrap();
+        assert_eq!(vars.group.as_deref(), Some("10001"));
+        assert_eq!(vars.device, Some(Device::B));
+        assert_eq!(command.unwrap().state, State::Off);
+        assert!(topics.parse_command("other/10001/B/set", b"OFF").is_none());
+    }
+}
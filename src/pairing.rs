@@ -0,0 +1,157 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Protection against accidental pairing.
+//!
+//! Self-learning sockets pair with the first address they receive for a few seconds while
+//! in learning mode. A long burst of frames therefore pairs every socket in range that
+//! happens to listen, e.g after a power failure. Pairing transmissions must be confirmed
+//! explicitly and are rate limited so that an automation bug cannot pair sockets in a loop.
+
+use crate::{
+    delay::Delay,
+    error::Error,
+    macros::{Cancel, Macro},
+    Device, Encoding, Funksteckdose, Pin, Protocol, State,
+};
+use log::{info, warn};
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Default length of a pairing burst
+pub const DURATION: Duration = Duration::from_secs(3);
+
+/// Rate limits of pairing transmissions
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    /// Minimum time between two pairings
+    pub interval: Duration,
+    /// Maximum number of pairings per hour. 0 disables pairing.
+    pub per_hour: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            interval: Duration::from_secs(60),
+            per_hour: 5,
+        }
+    }
+}
+
+/// Gate for pairing transmissions
+#[derive(Debug, Default)]
+pub struct Pairing {
+    limits: Limits,
+    sent: Mutex<VecDeque<Instant>>,
+}
+
+impl Pairing {
+    /// Create a new gate with `limits`
+    pub fn new(limits: Limits) -> Pairing {
+        Pairing {
+            limits,
+            sent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Count a pairing if `confirmed` and the rate limits allow it
+    /// ```
+    /// use funksteckdose::{error::Error, pairing::{Limits, Pairing}};
+    ///
+    /// let pairing = Pairing::new(Limits::default());
+    /// assert!(pairing.admit(true).is_ok());
+    /// assert!(matches!(pairing.admit(true), Err(Error::RateLimited(_))));
+    ///
+    /// let disabled = Pairing::new(Limits { per_hour: 0, ..Limits::default() });
+    /// assert!(matches!(disabled.admit(true), Err(Error::Unconfirmed(_))));
+    /// ```
+    pub fn admit(&self, confirmed: bool) -> Result<(), Error> {
+        if !confirmed {
+            return Err(Error::Unconfirmed(
+                "pairing pairs every socket in learning mode within range".into(),
+            ));
+        }
+        const HOUR: Duration = Duration::from_secs(60 * 60);
+        let now = Instant::now();
+        let mut sent = self.sent.lock().unwrap_or_else(|e| e.into_inner());
+        while sent.front().is_some_and(|t| now.duration_since(*t) >= HOUR) {
+            sent.pop_front();
+        }
+        if let Some(last) = sent.back() {
+            let elapsed = now.duration_since(*last);
+            if elapsed < self.limits.interval {
+                return Err(Error::RateLimited(self.limits.interval - elapsed));
+            }
+        }
+        if sent.len() >= self.limits.per_hour {
+            return Err(match sent.front() {
+                Some(oldest) => Error::RateLimited(HOUR - now.duration_since(*oldest)),
+                None => Error::Unconfirmed("pairing is disabled with 0 pairings per hour".into()),
+            });
+        }
+        sent.push_back(now);
+        Ok(())
+    }
+
+    /// Send "on" to a socket for `duration` to pair a socket in learning mode
+    pub fn pair<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+        &self,
+        funksteckdose: &Funksteckdose<T, E, P, D>,
+        group: &str,
+        device: &Device,
+        duration: Duration,
+        confirmed: bool,
+    ) -> Result<(), Error> {
+        let mut plan = funksteckdose.plan(group, device, &State::On)?;
+        let frame = plan.frame_duration().as_micros().max(1);
+        plan.repeats = (duration.as_micros() / frame).max(1) as usize;
+        if let Err(e) = self.admit(confirmed) {
+            warn!("Refusing to pair {} {}: {}", group, device, e);
+            return Err(e);
+        }
+        info!(
+            "Pairing {} {} with {} frames in {:?}",
+            group,
+            device,
+            plan.repeats,
+            plan.duration()
+        );
+        funksteckdose.transmit(&plan)
+    }
+
+    /// Run a macro that is marked as pairing sequence
+    pub fn run<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+        &self,
+        m: &Macro,
+        funksteckdose: &Funksteckdose<T, E, P, D>,
+        cancel: &Cancel,
+        confirmed: bool,
+    ) -> Result<(), Error> {
+        if let Err(e) = self.admit(confirmed) {
+            warn!("Refusing to run pairing macro {}: {}", m.name, e);
+            return Err(e);
+        }
+        m.execute(funksteckdose, cancel)
+    }
+}
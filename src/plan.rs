@@ -0,0 +1,326 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A plan lists every pulse of a transmission. [`Funksteckdose::plan`] creates it and
+//! [`Funksteckdose::transmit`] executes it. Tools, tests and UIs can inspect or modify the
+//! plan in between e.g to change the number of repeats.
+//!
+//! ```
+//! use funksteckdose::{mock::NullPin, Device, EncodingA, Funksteckdose, Protocol1, State};
+//!
+//! let d: Funksteckdose<NullPin, EncodingA, Protocol1> = Funksteckdose::new(NullPin);
+//! let mut plan = d.plan("10001", &Device::A, &State::On).unwrap();
+//! assert_eq!(plan.frame.len(), 25);
+//! plan.repeats = 3;
+//! d.transmit(&plan).unwrap();
+//! ```
+//!
+//! [`Funksteckdose::plan`]: crate::Funksteckdose::plan
+//! [`Funksteckdose::transmit`]: crate::Funksteckdose::transmit
+
+use crate::{error::Error, tri_state_code, HighLow, Preamble, Protocol, ProtocolValues, Value};
+use std::{fmt, time::Duration};
+
+/// The first level for `high` µs followed by the second level for `low` µs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub high: u32,
+    pub low: u32,
+}
+
+impl Symbol {
+    /// Duration of the symbol in µs
+    pub fn micros(&self) -> u64 {
+        u64::from(self.high) + u64::from(self.low)
+    }
+}
+
+/// All pulses of a transmission
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransmissionPlan {
+    /// Tri-state code word. `None` for raw codes.
+    pub code_word: Option<String>,
+    /// Binary code
+    pub code: u64,
+    /// Number of bits of `code`
+    pub length: usize,
+    /// Start symbols with the low level
+    pub inverted: bool,
+    /// Sent once before the first frame
+    pub preamble: Vec<Symbol>,
+    /// Data symbols from MSB to LSB followed by the sync
+    pub frame: Vec<Symbol>,
+    /// Number of frames
+    pub repeats: usize,
+    /// Silence in µs after every frame but the last, in addition to the sync
+    pub gap: u32,
+}
+
+impl TransmissionPlan {
+    /// Plan the transmission of a tri-state code word with `P`. Fails unless the code word
+    /// has 1 to 32 characters of '0', '1' and 'F'.
+    /// ```
+    /// use funksteckdose::{plan::TransmissionPlan, Protocol1};
+    /// assert!(TransmissionPlan::new::<Protocol1>(b"0FFF0FFFFFFF", 1, None).is_ok());
+    /// assert!(TransmissionPlan::new::<Protocol1>(b"0FX", 1, None).is_err());
+    /// ```
+    pub fn new<P: Protocol>(
+        code_word: &[u8],
+        repeats: usize,
+        preamble: Option<&Preamble>,
+    ) -> Result<TransmissionPlan, Error> {
+        TransmissionPlan::with_values(&P::values(), code_word, repeats, preamble)
+    }
+
+    /// Plan the transmission of a tri-state code word with protocol `values` selected at
+    /// runtime. Fails like `new` on invalid code words.
+    pub fn with_values(
+        values: &ProtocolValues,
+        code_word: &[u8],
+        repeats: usize,
+        preamble: Option<&Preamble>,
+    ) -> Result<TransmissionPlan, Error> {
+        let code = tri_state_code(code_word)?;
+        let length = code_word.len() * 2;
+        Ok(TransmissionPlan {
+            code_word: Some(String::from_utf8_lossy(code_word).into()),
+            ..TransmissionPlan::from_code(values, code, length, repeats, preamble)
+        })
+    }
+
+    /// Plan the transmission of the `length` lower bits of a raw `code` e.g a code
+    /// captured from a remote.
+    pub fn from_code(
+        values: &ProtocolValues,
+        code: u64,
+        length: usize,
+        repeats: usize,
+        preamble: Option<&Preamble>,
+    ) -> TransmissionPlan {
+        let symbol = |pulses: &HighLow| Symbol {
+            high: (values.pulse_length() * pulses.high) as u32,
+            low: (values.pulse_length() * pulses.low) as u32,
+        };
+        let frame = (0..length)
+            .rev()
+            .map(|i| {
+                symbol(if code & (1 << i) != 0 {
+                    values.one()
+                } else {
+                    values.zero()
+                })
+            })
+            .chain(Some(symbol(values.sync_factor())))
+            .collect();
+        let preamble = preamble
+            .map(|preamble| {
+                (0..preamble.pulses)
+                    .map(|_| Symbol {
+                        high: preamble.high,
+                        low: preamble.low,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        TransmissionPlan {
+            code_word: None,
+            code,
+            length,
+            inverted: values.inverted_signal(),
+            preamble,
+            frame,
+            repeats,
+            gap: 0,
+        }
+    }
+
+    /// Plan the transmission of recorded symbols e.g imported from another tool. `code`
+    /// and `length` are 0.
+    pub fn from_symbols(frame: Vec<Symbol>, repeats: usize) -> TransmissionPlan {
+        TransmissionPlan {
+            code_word: None,
+            code: 0,
+            length: 0,
+            inverted: false,
+            preamble: Vec::new(),
+            frame,
+            repeats,
+            gap: 0,
+        }
+    }
+
+    /// Plan with `gap` µs of silence between the frames e.g for receivers that miss
+    /// frames sent back-to-back
+    /// ```
+    /// use funksteckdose::{plan::TransmissionPlan, Protocol1};
+    /// use std::time::Duration;
+    /// let plan = TransmissionPlan::new::<Protocol1>(b"0FFF0FFFFFFF", 3, None).unwrap();
+    /// let spaced = plan.clone().with_gap(5000);
+    /// assert_eq!(spaced.duration(), plan.duration() + Duration::from_millis(10));
+    /// ```
+    pub fn with_gap(self, gap: u32) -> TransmissionPlan {
+        TransmissionPlan { gap, ..self }
+    }
+
+    /// Duration of one frame
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_micros(self.frame.iter().map(Symbol::micros).sum())
+    }
+
+    /// Duration of the preamble, all frames and the gaps between them
+    pub fn duration(&self) -> Duration {
+        let preamble: u64 = self.preamble.iter().map(Symbol::micros).sum();
+        let gaps = u64::from(self.gap) * self.repeats.saturating_sub(1) as u64;
+        Duration::from_micros(preamble + gaps) + self.frame_duration() * self.repeats as u32
+    }
+
+    /// All symbols in transmission order
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        let repeats = self.repeats;
+        self.preamble
+            .iter()
+            .chain((0..repeats).flat_map(move |_| self.frame.iter()))
+    }
+}
+
+/// The levels of a transmission as (level, µs) pulses in transmission order, ready for a
+/// pin without any further computation e.g for DMA backends or tests of exact timings
+/// ```
+/// use funksteckdose::{plan::{TransmissionPlan, Waveform}, Protocol1, Value};
+/// let plan = TransmissionPlan::new::<Protocol1>(b"0FFF0FFFFFFF", 1, None).unwrap();
+/// let waveform = Waveform::from(&plan);
+/// assert_eq!(waveform.pulses.len(), 50);
+/// assert_eq!(waveform.pulses[0], (Value::High, 350));
+/// assert_eq!(waveform.duration(), plan.duration());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Waveform {
+    pub pulses: Vec<(Value, u32)>,
+}
+
+impl Waveform {
+    /// Interleave the frames of `plans` i.e the first frame of every plan, then the second
+    /// frame of every plan and so on. A preamble is sent before the first frame of its plan
+    /// and the gap of a plan after each of its frames but the last.
+    pub fn interleave(plans: &[TransmissionPlan]) -> Waveform {
+        let mut pulses = Vec::new();
+        let mut add = |symbols: &[Symbol], plan: &TransmissionPlan, gap: u32| {
+            let (first, second) = if plan.inverted {
+                (Value::Low, Value::High)
+            } else {
+                (Value::High, Value::Low)
+            };
+            for symbol in symbols {
+                pulses.push((first.clone(), symbol.high));
+                pulses.push((second.clone(), symbol.low));
+            }
+            // The gap continues the second level of the sync
+            if let Some((_, micros)) = pulses.last_mut() {
+                *micros += gap;
+            }
+        };
+        let rounds = plans.iter().map(|plan| plan.repeats).max().unwrap_or(0);
+        for round in 0..rounds.max(1) {
+            for plan in plans {
+                if round == 0 {
+                    add(&plan.preamble, plan, 0);
+                }
+                if round < plan.repeats {
+                    let gap = if round + 1 < plan.repeats {
+                        plan.gap
+                    } else {
+                        0
+                    };
+                    add(&plan.frame, plan, gap);
+                }
+            }
+        }
+        Waveform { pulses }
+    }
+
+    /// Duration of all pulses
+    pub fn duration(&self) -> Duration {
+        Duration::from_micros(
+            self.pulses
+                .iter()
+                .map(|(_, micros)| u64::from(*micros))
+                .sum(),
+        )
+    }
+
+    /// Number of pulses
+    pub fn len(&self) -> usize {
+        self.pulses.len()
+    }
+
+    /// True if the waveform has no pulses
+    pub fn is_empty(&self) -> bool {
+        self.pulses.is_empty()
+    }
+}
+
+impl<'a> From<&'a TransmissionPlan> for Waveform {
+    fn from(plan: &'a TransmissionPlan) -> Waveform {
+        Waveform::interleave(std::slice::from_ref(plan))
+    }
+}
+
+/// One pulse per line e.g `H 350` for 350µs high
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (value, micros) in &self.pulses {
+            let level = match value {
+                Value::High => 'H',
+                Value::Low => 'L',
+            };
+            writeln!(f, "{} {}", level, micros)?;
+        }
+        Ok(())
+    }
+}
+
+/// Summary of a plan e.g to answer dry runs of daemon APIs
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Preview {
+    /// Binary code
+    pub code: u64,
+    /// Tri-state code word. `None` for raw codes.
+    pub code_word: Option<String>,
+    /// Number of bits of `code`
+    pub length: usize,
+    /// Number of frames
+    pub repeats: usize,
+    /// Duration of the preamble and all frames in µs
+    pub airtime_us: u64,
+}
+
+impl<'a> From<&'a TransmissionPlan> for Preview {
+    fn from(plan: &'a TransmissionPlan) -> Preview {
+        Preview {
+            code: plan.code,
+            code_word: plan.code_word.clone(),
+            length: plan.length,
+            repeats: plan.repeats,
+            airtime_us: plan.duration().as_micros() as u64,
+        }
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Encoders loaded from WebAssembly modules
+//!
+//! Plugins run sandboxed: they get no imports and each call has a limited amount of fuel.
+//! A plugin exports:
+//!
+//! * `memory`: the linear memory
+//! * `input() -> i32`: address of a buffer of at least 64 bytes for the group
+//! * `output() -> i32`: address of the code word written by `encode`, at most 32 bytes
+//! * `encode(group_len: i32, device: i32, state: i32) -> i32`: encode the group in the
+//!   input buffer, the device 1-5 and the state 1 for on and 0 for off into a tri-state
+//!   code word of '0', '1' and 'F'. Returns the length of the code word or a negative value
+//!   if the arguments are invalid.
+//!
+//! Sockets of a configuration use a plugin with the `plugin` key:
+//!
+//! ```toml
+//! [[socket]]
+//! name = "weird"
+//! group = "10001"
+//! device = "A"
+//! plugin = "/etc/funksteckdose/weird.wasm"
+//! ```
+
+use crate::{
+    error::{CoreError, Error},
+    Device, State,
+};
+use std::{fs, path::Path};
+use wasmi::{Config, Engine, Instance, Linker, Memory, Module, Store, TypedFunc};
+
+/// Size of the input buffer
+pub const INPUT_SIZE: usize = 64;
+
+/// Maximum length of a code word, the longest tri-state code word that fits into a `Code`
+pub const OUTPUT_SIZE: usize = 32;
+
+/// Instructions a plugin may execute per call
+pub const FUEL: u64 = 1_000_000;
+
+/// An encoder implemented by a WebAssembly module
+pub struct WasmEncoder {
+    store: Store<()>,
+    memory: Memory,
+    input: TypedFunc<(), i32>,
+    output: TypedFunc<(), i32>,
+    encode: TypedFunc<(i32, i32, i32), i32>,
+}
+
+impl WasmEncoder {
+    /// Load a plugin from a `.wasm` file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<WasmEncoder, Error> {
+        WasmEncoder::new(&fs::read(path)?)
+    }
+
+    /// Instantiate a plugin from the binary of a module
+    pub fn new(wasm: &[u8]) -> Result<WasmEncoder, Error> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm).map_err(plugin_error)?;
+        let mut store = Store::new(&engine, ());
+        refuel(&mut store)?;
+        let instance: Instance = Linker::<()>::new(&engine)
+            .instantiate(&mut store, &module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(plugin_error)?;
+
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| Error::Plugin("missing export memory".into()))?;
+        let input = instance
+            .get_typed_func(&store, "input")
+            .map_err(plugin_error)?;
+        let output = instance
+            .get_typed_func(&store, "output")
+            .map_err(plugin_error)?;
+        let encode = instance
+            .get_typed_func(&store, "encode")
+            .map_err(plugin_error)?;
+
+        Ok(WasmEncoder {
+            store,
+            memory,
+            input,
+            output,
+            encode,
+        })
+    }
+
+    /// Encode a command into a tri-state code word
+    pub fn encode(
+        &mut self,
+        group: &str,
+        device: &Device,
+        state: &State,
+    ) -> Result<Vec<u8>, Error> {
+        if group.len() > INPUT_SIZE {
+            return Err(CoreError::InvalidGroup(group.into()).into());
+        }
+        refuel(&mut self.store)?;
+
+        let input = self.input.call(&mut self.store, ()).map_err(plugin_error)?;
+        self.memory
+            .write(&mut self.store, input as usize, group.as_bytes())
+            .map_err(plugin_error)?;
+        let device = i32::from(u8::from(device.clone()));
+        let state = match state {
+            State::On => 1,
+            State::Off => 0,
+            State::Toggle => return Err(CoreError::InvalidState(state.to_string()).into()),
+        };
+        let length = self
+            .encode
+            .call(&mut self.store, (group.len() as i32, device, state))
+            .map_err(plugin_error)?;
+        if length < 0 {
+            return Err(Error::Plugin(format!(
+                "cannot encode {} {} {}",
+                group, device, state
+            )));
+        }
+        if length as usize > OUTPUT_SIZE {
+            return Err(Error::Plugin(format!("code word of {} bytes", length)));
+        }
+
+        let output = self
+            .output
+            .call(&mut self.store, ())
+            .map_err(plugin_error)?;
+        let mut code_word = vec![0; length as usize];
+        self.memory
+            .read(&self.store, output as usize, &mut code_word)
+            .map_err(plugin_error)?;
+        if code_word.iter().any(|c| !b"01F".contains(c)) {
+            return Err(Error::Plugin(format!(
+                "invalid code word {}",
+                String::from_utf8_lossy(&code_word)
+            )));
+        }
+        Ok(code_word)
+    }
+}
+
+// Set the remaining fuel to `FUEL`. Fuel left over from earlier calls does not add up.
+fn refuel(store: &mut Store<()>) -> Result<(), Error> {
+    let remaining = store.consume_fuel(0).map_err(plugin_error)?;
+    if remaining < FUEL {
+        store.add_fuel(FUEL - remaining).map_err(plugin_error)
+    } else {
+        store
+            .consume_fuel(remaining - FUEL)
+            .map(drop)
+            .map_err(plugin_error)
+    }
+}
+
+fn plugin_error<E: std::fmt::Display>(e: E) -> Error {
+    Error::Plugin(e.to_string())
+}
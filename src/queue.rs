@@ -0,0 +1,601 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Commands waiting for transmission
+//!
+//! A queue can be persisted to a file so that pending commands survive a restart. The
+//! file contains one command per line: `<group> <device> <state> [<expiry as unix time in ms>]`.
+//!
+//! Some receivers ignore a command that arrives too soon after the previous one. A minimum
+//! gap per socket holds back its commands until the gap since its last popped command
+//! elapsed while commands for other sockets are popped in the meantime.
+//!
+//! A [`TransmitQueue`] sends commands of several threads one after the other from a
+//! background thread. Every submitted command returns a [`Receipt`] to wait for its
+//! transmission:
+//!
+//! ```
+//! use funksteckdose::{mock::NullPin, queue::{Command, TransmitQueue}, Device, EncodingA, Protocol1, State};
+//! use std::thread;
+//!
+//! type Funksteckdose = funksteckdose::Funksteckdose<NullPin, EncodingA, Protocol1>;
+//! let d: Funksteckdose = Funksteckdose::builder(NullPin).repeat_transmit(1).build().unwrap();
+//! let queue = TransmitQueue::new(d);
+//! thread::scope(|s| {
+//!     for device in [Device::A, Device::B, Device::C] {
+//!         let queue = &queue;
+//!         s.spawn(move || queue.submit(Command::new("10001", &device, &State::On)).wait());
+//!     }
+//! });
+//! let funksteckdose = queue.shutdown();
+//! ```
+
+use crate::{
+    delay::Delay,
+    error::{CoreError, Error},
+    quiet::Priority,
+    Device, Encoding, Funksteckdose, Pin, Protocol, State,
+};
+use log::{debug, info, warn};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str,
+    sync::mpsc::{self, RecvTimeoutError, TryRecvError},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// A command waiting for transmission
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    pub group: String,
+    pub device: Device,
+    pub state: State,
+    /// Point in time after which the command must not be sent anymore
+    pub expires: Option<SystemTime>,
+}
+
+impl Command {
+    /// Create a new command without expiry
+    pub fn new(group: &str, device: &Device, state: &State) -> Command {
+        Command {
+            group: group.into(),
+            device: device.clone(),
+            state: state.clone(),
+            expires: None,
+        }
+    }
+
+    /// Make the command valid for `ttl` from now on
+    /// ```
+    /// use funksteckdose::{queue::Command, Device, State};
+    /// use std::time::Duration;
+    /// let command = Command::new("10001", &Device::A, &State::On).with_ttl(Duration::from_secs(10));
+    /// assert!(command.expires.is_some());
+    /// ```
+    pub fn with_ttl(mut self, ttl: Duration) -> Command {
+        self.expires = Some(SystemTime::now() + ttl);
+        self
+    }
+
+    /// Remaining time to live. `None` if the command never expires.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.expires.map(|expires| {
+            expires
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Send the command unless it expired
+    pub fn send<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+        &self,
+        funksteckdose: &Funksteckdose<T, E, P, D>,
+    ) -> Result<(), Error> {
+        self.send_with_priority(funksteckdose, Priority::Interactive)
+    }
+
+    /// Send the command with `priority` unless it expired. See
+    /// `Funksteckdose::send_with_priority`.
+    pub fn send_with_priority<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+        &self,
+        funksteckdose: &Funksteckdose<T, E, P, D>,
+        priority: Priority,
+    ) -> Result<(), Error> {
+        if self.is_expired(SystemTime::now()) {
+            return Err(Error::Expired(self.to_string()));
+        }
+        funksteckdose.send_with_priority(&self.group, &self.device, &self.state, priority)
+    }
+
+    /// True if the command expired at `now`
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| now > expires)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.group, self.device, self.state)?;
+        if let Some(expires) = self.expires {
+            let millis = expires
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            write!(f, " {}", millis)?;
+        }
+        Ok(())
+    }
+}
+
+impl str::FromStr for Command {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::from(CoreError::InvalidCommand(s.into()));
+        let mut fields = s.split_whitespace();
+        let group = fields.next().ok_or_else(invalid)?.into();
+        let device = fields.next().ok_or_else(invalid)?.parse()?;
+        let state = fields.next().ok_or_else(invalid)?.parse()?;
+        let expires = match fields.next() {
+            Some(millis) => {
+                let millis = millis.parse::<u64>().map_err(|_| invalid())?;
+                Some(UNIX_EPOCH + Duration::from_millis(millis))
+            }
+            None => None,
+        };
+        if fields.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Command {
+            group,
+            device,
+            state,
+            expires,
+        })
+    }
+}
+
+/// Group and device of a receiver
+type Receiver = (String, Device);
+
+/// A FIFO of commands that is optionally backed by a file
+#[derive(Debug, Default)]
+pub struct Queue {
+    commands: VecDeque<Command>,
+    path: Option<PathBuf>,
+    min_gaps: HashMap<Receiver, Duration>,
+    last_popped: HashMap<Receiver, Instant>,
+    capacity: Option<usize>,
+}
+
+impl Queue {
+    /// Create a new in memory queue
+    pub fn new() -> Queue {
+        Queue::default()
+    }
+
+    /// Create a queue that is persisted to `path`. Commands found in `path` are loaded,
+    /// expired ones are discarded.
+    pub fn persistent<P: AsRef<Path>>(path: P) -> Result<Queue, Error> {
+        let path = path.as_ref().to_path_buf();
+        let commands = match fs::read_to_string(&path) {
+            Ok(content) => content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(str::parse)
+                .collect::<Result<VecDeque<Command>, _>>()?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => VecDeque::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut queue = Queue {
+            commands,
+            path: Some(path),
+            ..Queue::default()
+        };
+        queue.discard_expired();
+        info!("Restored {} queued commands", queue.len());
+        queue.persist()?;
+        Ok(queue)
+    }
+
+    /// Append a command. Fails with `Error::QueueFull` if the queue holds its capacity.
+    /// ```
+    /// use funksteckdose::{error::Error, queue::{Command, Queue}, Device, State};
+    /// let mut queue = Queue::new();
+    /// queue.set_capacity(Some(1));
+    /// queue.push(Command::new("10001", &Device::A, &State::On)).unwrap();
+    /// let off = Command::new("10001", &Device::A, &State::Off);
+    /// assert!(matches!(queue.push(off), Err(Error::QueueFull(1))));
+    /// ```
+    pub fn push(&mut self, command: Command) -> Result<(), Error> {
+        if self
+            .capacity
+            .is_some_and(|capacity| self.commands.len() >= capacity)
+        {
+            return Err(Error::QueueFull(self.commands.len()));
+        }
+        self.commands.push_back(command);
+        self.persist()
+    }
+
+    /// Limit the number of queued commands. Default: unbounded. Commands restored from a
+    /// file are kept even if they exceed the capacity.
+    pub fn set_capacity(&mut self, capacity: Option<usize>) {
+        self.capacity = capacity;
+    }
+
+    /// Remove the next command that is not expired and whose socket is outside of its
+    /// minimum gap. `None` if no command is ready, see `ready_in`.
+    /// ```
+    /// use funksteckdose::{queue::{Command, Queue}, Device, State};
+    /// use std::time::Duration;
+    /// let mut queue = Queue::new();
+    /// queue.set_min_gap("10001", &Device::A, Duration::from_secs(1));
+    /// queue.push(Command::new("10001", &Device::A, &State::On)).unwrap();
+    /// queue.push(Command::new("10001", &Device::A, &State::Off)).unwrap();
+    /// queue.push(Command::new("10001", &Device::B, &State::On)).unwrap();
+    /// assert_eq!(queue.pop().unwrap().unwrap().state, State::On);
+    /// // The second command of A waits for the gap, B is not held back
+    /// assert_eq!(queue.pop().unwrap().unwrap().device, Device::B);
+    /// assert_eq!(queue.pop().unwrap(), None);
+    /// assert!(queue.ready_in().unwrap() > Duration::from_millis(900));
+    /// ```
+    pub fn pop(&mut self) -> Result<Option<Command>, Error> {
+        self.discard_expired();
+        let now = Instant::now();
+        let command = self
+            .heads(now)
+            .into_iter()
+            .find(|(_, wait)| wait.is_zero())
+            .and_then(|(index, _)| self.commands.remove(index));
+        if let Some(ref command) = command {
+            self.last_popped.insert(receiver(command), now);
+        }
+        self.persist()?;
+        Ok(command)
+    }
+
+    /// Next command `pop` would remove without removing it. Unlike `pop` expired commands
+    /// are not skipped.
+    pub fn next_ready(&self) -> Option<&Command> {
+        self.heads(Instant::now())
+            .into_iter()
+            .find(|(_, wait)| wait.is_zero())
+            .map(|(index, _)| &self.commands[index])
+    }
+
+    /// Remove the first queued command equal to `command` e.g after sending the command
+    /// returned by `next_ready`. False if no such command is queued.
+    /// ```
+    /// use funksteckdose::{queue::{Command, Queue}, Device, State};
+    /// use std::time::Duration;
+    /// let mut queue = Queue::new();
+    /// queue.set_min_gap("10001", &Device::A, Duration::from_secs(1));
+    /// queue.push(Command::new("10001", &Device::A, &State::On)).unwrap();
+    /// queue.push(Command::new("10001", &Device::A, &State::Off)).unwrap();
+    /// queue.push(Command::new("10001", &Device::B, &State::On)).unwrap();
+    /// let next = queue.next_ready().unwrap().clone();
+    /// assert!(queue.remove(&next).unwrap());
+    /// // The second command of A waits for the gap
+    /// assert_eq!(queue.next_ready().unwrap().device, Device::B);
+    /// ```
+    pub fn remove(&mut self, command: &Command) -> Result<bool, Error> {
+        let index = match self.commands.iter().position(|queued| queued == command) {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        self.commands.remove(index);
+        self.last_popped.insert(receiver(command), Instant::now());
+        self.persist()?;
+        Ok(true)
+    }
+
+    /// Time until the next command is ready to be popped. `None` if the queue is empty.
+    pub fn ready_in(&self) -> Option<Duration> {
+        self.heads(Instant::now())
+            .into_iter()
+            .map(|(_, wait)| wait)
+            .min()
+    }
+
+    /// Hold back commands for the socket until `gap` elapsed since its last popped command.
+    /// A zero gap removes the limit.
+    pub fn set_min_gap(&mut self, group: &str, device: &Device, gap: Duration) {
+        let receiver = (group.to_string(), device.clone());
+        if gap.is_zero() {
+            self.min_gaps.remove(&receiver);
+        } else {
+            self.min_gaps.insert(receiver, gap);
+        }
+    }
+
+    /// Minimum gap between two commands for the socket
+    pub fn min_gap(&self, group: &str, device: &Device) -> Duration {
+        self.min_gaps
+            .get(&(group.to_string(), device.clone()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Next command without removing it
+    pub fn peek(&self) -> Option<&Command> {
+        self.commands.front()
+    }
+
+    /// Number of queued commands
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// True if no command is queued
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Iterate the queued commands in transmission order
+    pub fn iter(&self) -> impl Iterator<Item = &Command> {
+        self.commands.iter()
+    }
+
+    // Index and remaining gap of the first command of every socket. Later commands of a
+    // socket wait for the first one to keep their order.
+    fn heads(&self, now: Instant) -> Vec<(usize, Duration)> {
+        let mut seen = HashSet::new();
+        self.commands
+            .iter()
+            .enumerate()
+            .map(|(index, command)| (index, receiver(command)))
+            .filter(|(_, receiver)| seen.insert(receiver.clone()))
+            .map(|(index, receiver)| {
+                let wait = match (
+                    self.min_gaps.get(&receiver),
+                    self.last_popped.get(&receiver),
+                ) {
+                    (Some(gap), Some(popped)) => gap.saturating_sub(now.duration_since(*popped)),
+                    _ => Duration::ZERO,
+                };
+                (index, wait)
+            })
+            .collect()
+    }
+
+    fn discard_expired(&mut self) {
+        let now = SystemTime::now();
+        self.commands.retain(|command| {
+            let expired = command.is_expired(now);
+            if expired {
+                warn!("Discarding expired command: {}", command);
+            }
+            !expired
+        });
+    }
+
+    fn persist(&self) -> Result<(), Error> {
+        if let Some(ref path) = self.path {
+            // Write to a temporary file first so a crash never leaves a truncated queue
+            let tmp = path.with_extension("tmp");
+            let mut file = io::BufWriter::new(fs::File::create(&tmp)?);
+            for command in &self.commands {
+                writeln!(file, "{}", command)?;
+            }
+            file.flush()?;
+            drop(file);
+            fs::rename(tmp, path)?;
+        }
+        Ok(())
+    }
+}
+
+fn receiver(command: &Command) -> Receiver {
+    (command.group.clone(), command.device.clone())
+}
+
+/// Sending side of a [`Receipt`]
+type Completion = mpsc::Sender<Result<(), Error>>;
+
+/// A submitted command and the receipt to report its transmission to
+type Submission = (Command, Completion);
+
+/// Transmissions of several threads sent one after the other from a background thread
+/// that owns the [`Funksteckdose`]. A command that is submitted again while the same
+/// command for the socket is still waiting is sent once and both receipts get the result.
+#[derive(Debug)]
+pub struct TransmitQueue<T: Pin, E: Encoding, P: Protocol, D: Delay> {
+    submissions: mpsc::Sender<Submission>,
+    worker: thread::JoinHandle<Funksteckdose<T, E, P, D>>,
+}
+
+impl<T, E, P, D> TransmitQueue<T, E, P, D>
+where
+    T: Pin + Send + 'static,
+    E: Encoding + Send + 'static,
+    P: Protocol + Send + 'static,
+    D: Delay + Send + 'static,
+{
+    /// Start the background thread sending with `funksteckdose`
+    pub fn new(funksteckdose: Funksteckdose<T, E, P, D>) -> TransmitQueue<T, E, P, D> {
+        let (submissions, received) = mpsc::channel();
+        let worker = thread::spawn(move || work(funksteckdose, received));
+        TransmitQueue {
+            submissions,
+            worker,
+        }
+    }
+
+    /// Queue `command` for transmission. Expired commands are not sent, their receipt
+    /// reports `Error::Expired`.
+    pub fn submit(&self, command: Command) -> Receipt {
+        let (sender, receipt) = mpsc::channel();
+        // A worker that is gone drops the sender which cancels the receipt
+        self.submissions.send((command, sender)).ok();
+        Receipt(receipt)
+    }
+
+    /// Send all queued commands and return the transmitter
+    pub fn shutdown(self) -> Funksteckdose<T, E, P, D> {
+        drop(self.submissions);
+        self.worker
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+/// Completion of a command submitted to a [`TransmitQueue`]
+#[derive(Debug)]
+pub struct Receipt(mpsc::Receiver<Result<(), Error>>);
+
+impl Receipt {
+    /// Wait until the command is sent. Fails with the error of the transmission or with
+    /// `Error::Cancelled` if the queue stopped before sending it.
+    pub fn wait(self) -> Result<(), Error> {
+        self.0.recv().unwrap_or(Err(Error::Cancelled))
+    }
+
+    /// Like `wait` but fails with `Error::Timeout` if the command is not sent within
+    /// `timeout`. The command stays queued.
+    pub fn wait_timeout(self, timeout: Duration) -> Result<(), Error> {
+        match self.0.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(Error::Timeout(timeout)),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::Cancelled),
+        }
+    }
+
+    /// The result if the command was sent already. The result is returned only once.
+    pub fn try_wait(&self) -> Option<Result<(), Error>> {
+        match self.0.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(Error::Cancelled)),
+        }
+    }
+}
+
+// Loop of the background thread of a `TransmitQueue`. Ends when the queue is shut down and
+// all commands are sent.
+fn work<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+    funksteckdose: Funksteckdose<T, E, P, D>,
+    submissions: mpsc::Receiver<Submission>,
+) -> Funksteckdose<T, E, P, D> {
+    let mut pending: VecDeque<(Command, Vec<Completion>)> = VecDeque::new();
+    let mut open = true;
+    loop {
+        if open && pending.is_empty() {
+            match submissions.recv() {
+                Ok(submission) => coalesce(&mut pending, submission),
+                Err(_) => open = false,
+            }
+        }
+        // Take everything submitted during the last transmission to catch duplicates
+        while open {
+            match submissions.try_recv() {
+                Ok(submission) => coalesce(&mut pending, submission),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => open = false,
+            }
+        }
+        let (command, receipts) = match pending.pop_front() {
+            Some(next) => next,
+            None if open => continue,
+            None => break,
+        };
+        let result = command.send(&funksteckdose);
+        if let Err(ref e) = result {
+            warn!("Failed to send {}: {}", command, e);
+        }
+        let mut receipts = receipts.into_iter();
+        let first = receipts.next();
+        for receipt in receipts {
+            receipt
+                .send(result.as_ref().map_err(Error::clone).copied())
+                .ok();
+        }
+        if let Some(receipt) = first {
+            receipt.send(result).ok();
+        }
+    }
+    funksteckdose
+}
+
+// Merge a submission into the most recent pending command for the same socket if both
+// switch to the same state. Toggles are never merged, each one flips the socket.
+fn coalesce(pending: &mut VecDeque<(Command, Vec<Completion>)>, (command, receipt): Submission) {
+    let latest = pending
+        .iter_mut()
+        .rev()
+        .find(|(queued, _)| queued.group == command.group && queued.device == command.device);
+    match latest {
+        Some((queued, receipts))
+            if queued.state == command.state && command.state != State::Toggle =>
+        {
+            debug!("Coalescing {} with a queued command", command);
+            receipts.push(receipt);
+        }
+        _ => pending.push_back((command, vec![receipt])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submit(pending: &mut VecDeque<(Command, Vec<Completion>)>, state: State) {
+        let (receipt, _) = mpsc::channel();
+        coalesce(
+            pending,
+            (Command::new("10001", &Device::A, &state), receipt),
+        );
+    }
+
+    fn states(pending: &VecDeque<(Command, Vec<Completion>)>) -> Vec<(State, usize)> {
+        pending
+            .iter()
+            .map(|(command, receipts)| (command.state.clone(), receipts.len()))
+            .collect()
+    }
+
+    #[test]
+    fn coalesce_with_most_recent() {
+        let mut pending = VecDeque::new();
+        submit(&mut pending, State::On);
+        submit(&mut pending, State::Off);
+        submit(&mut pending, State::On);
+        submit(&mut pending, State::On);
+        assert_eq!(
+            states(&pending),
+            vec![(State::On, 1), (State::Off, 1), (State::On, 2)]
+        );
+    }
+
+    #[test]
+    fn coalesce_never_toggle() {
+        let mut pending = VecDeque::new();
+        submit(&mut pending, State::Toggle);
+        submit(&mut pending, State::Toggle);
+        assert_eq!(
+            states(&pending),
+            vec![(State::Toggle, 1), (State::Toggle, 1)]
+        );
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Quiet hours reduce the radio traffic of background transmissions e.g periodic state
+//! refreshes at night when sensors of the neighbours share the band. Interactive commands
+//! are never affected.
+
+use crate::error::Error;
+use chrono::{Local, NaiveTime};
+use std::{fmt, str};
+
+/// Origin of a transmission
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    /// Requested by a user
+    Interactive,
+    /// Sent without user interaction e.g to refresh states
+    Background,
+}
+
+/// A daily time window in local time. The window wraps around midnight if `end` is before
+/// `start`.
+/// ```
+/// use funksteckdose::quiet::{Priority, QuietHours};
+/// use chrono::NaiveTime;
+///
+/// let quiet: QuietHours = "22:00-07:00/2".parse().unwrap();
+/// let night = NaiveTime::from_hms_opt(23, 30, 0).unwrap();
+/// assert_eq!(quiet.repeats_at(Priority::Background, 10, night), 2);
+/// assert_eq!(quiet.repeats_at(Priority::Interactive, 10, night), 10);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct QuietHours {
+    /// Begin of the quiet hours
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "time", serialize_with = "time_string")
+    )]
+    pub start: NaiveTime,
+    /// End of the quiet hours
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "time", serialize_with = "time_string")
+    )]
+    pub end: NaiveTime,
+    /// Maximum number of frames of background transmissions. 0 suppresses them.
+    pub repeats: usize,
+}
+
+impl QuietHours {
+    /// True if `time` is within the quiet hours
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Number of frames for a transmission with `priority` at `time`
+    pub fn repeats_at(&self, priority: Priority, repeats: usize, time: NaiveTime) -> usize {
+        match priority {
+            Priority::Background if self.contains(time) => repeats.min(self.repeats),
+            _ => repeats,
+        }
+    }
+
+    /// Number of frames for a transmission with `priority` now
+    pub fn repeats(&self, priority: Priority, repeats: usize) -> usize {
+        self.repeats_at(priority, repeats, Local::now().time())
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}/{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+            self.repeats
+        )
+    }
+}
+
+/// Parse `start-end/repeats` e.g "22:00-07:00/2"
+impl str::FromStr for QuietHours {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidQuietHours(s.into());
+        let (window, repeats) = s.split_once('/').ok_or_else(invalid)?;
+        let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+        Ok(QuietHours {
+            start: parse_time(start).ok_or_else(invalid)?,
+            end: parse_time(end).ok_or_else(invalid)?,
+            repeats: repeats.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Parse a time of day e.g "22:00"
+pub fn parse_time(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").ok()
+}
+
+#[cfg(feature = "serde")]
+fn time<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+    use serde::{de::Error as _, Deserialize};
+    let s = String::deserialize(deserializer)?;
+    parse_time(&s).ok_or_else(|| D::Error::custom(format!("invalid time of day: {}", s)))
+}
+
+#[cfg(feature = "serde")]
+fn time_string<S: serde::Serializer>(time: &NaiveTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&time.format("%H:%M"))
+}
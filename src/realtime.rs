@@ -0,0 +1,96 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//! Real-time scheduling during transmissions on Linux
+//!
+//! The pulses are timed by busy waiting. On a loaded system the thread is preempted in the
+//! middle of a frame and the socket ignores the command. A [`Realtime`] guard raises the
+//! calling thread to `SCHED_FIFO` and locks the memory of the process with `mlockall` so
+//! no page fault stalls the loop. Both are restored when the guard is dropped.
+//!
+//! Needs root or the capabilities `CAP_SYS_NICE` and `CAP_IPC_LOCK`.
+
+use crate::error::Error;
+use log::debug;
+use std::{io, mem};
+
+/// Priority used if none is given. High enough to preempt ordinary real-time threads but
+/// below the kernel's interrupt threads at 50.
+pub const PRIORITY: i32 = 40;
+
+fn check(result: libc::c_int) -> Result<(), Error> {
+    match result {
+        0 => Ok(()),
+        // pthread functions return the error instead of setting errno
+        e if e > 0 => Err(io::Error::from_raw_os_error(e).into()),
+        _ => Err(io::Error::last_os_error().into()),
+    }
+}
+
+/// Real-time scheduling of the calling thread until dropped
+#[derive(Debug)]
+pub struct Realtime {
+    policy: libc::c_int,
+    param: libc::sched_param,
+    locked: bool,
+}
+
+impl Realtime {
+    /// Raise the calling thread to `SCHED_FIFO` with `priority` (1 to 99) and lock the
+    /// memory. Memory that cannot be locked is logged and tolerated.
+    pub fn enter(priority: i32) -> Result<Realtime, Error> {
+        let thread = unsafe { libc::pthread_self() };
+        let mut policy = 0;
+        let mut param: libc::sched_param = unsafe { mem::zeroed() };
+        check(unsafe { libc::pthread_getschedparam(thread, &mut policy, &mut param) })?;
+
+        let mut fifo: libc::sched_param = unsafe { mem::zeroed() };
+        fifo.sched_priority = priority;
+        check(unsafe { libc::pthread_setschedparam(thread, libc::SCHED_FIFO, &fifo) })?;
+
+        let locked = match check(unsafe { libc::mlockall(libc::MCL_CURRENT | libc::MCL_FUTURE) }) {
+            Ok(()) => true,
+            Err(e) => {
+                debug!("Failed to lock memory: {}", e);
+                false
+            }
+        };
+        Ok(Realtime {
+            policy,
+            param,
+            locked,
+        })
+    }
+
+    /// True if the memory of the process is locked
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+}
+
+impl Drop for Realtime {
+    fn drop(&mut self) {
+        unsafe {
+            if self.locked {
+                libc::munlockall();
+            }
+            libc::pthread_setschedparam(libc::pthread_self(), self.policy, &self.param);
+        }
+    }
+}
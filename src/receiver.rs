@@ -0,0 +1,440 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Reception of codes e.g from the original remotes of the sockets. This is the receiving
+//! half of [rc-switch](https://github.com/sui77/rc-switch/).
+//!
+//! The durations between level changes of a receiver module are collected until a long gap
+//! separates two frames. A frame is decoded when the same gap is seen twice i.e the remote
+//! repeats its frame. The gap before a frame is the sync of the protocol and determines the
+//! pulse length, so codes from remotes with a slightly different timing are decoded as well.
+
+use crate::{
+    catalog, error::Error, Code, Device, Encoding, HighLow, InputPin, ProtocolValues, State, Value,
+};
+use log::{debug, trace};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Gaps longer than this in µs separate frames
+pub const SEPARATION_LIMIT: u32 = 4300;
+
+/// Default tolerance of pulse durations in percent
+pub const TOLERANCE: u64 = 60;
+
+/// Maximum number of level changes in a frame
+const MAX_CHANGES: usize = 67;
+
+/// A code received over the air
+#[derive(Clone, Debug, PartialEq)]
+pub struct Received {
+    /// Name of the protocol as in `catalog::protocols`
+    pub protocol: &'static str,
+    /// Received bits
+    pub code: u64,
+    /// Number of bits
+    pub length: usize,
+    /// Pulse length in µs derived from the sync
+    pub pulse_length: u64,
+}
+
+impl Received {
+    /// Tri-state code word if every pair of bits is a tri-state symbol
+    pub fn code_word(&self) -> Option<Vec<u8>> {
+        Code::from_decimal(self.code, self.length)
+            .ok()?
+            .tri_state()
+            .map(String::into_bytes)
+    }
+
+    /// Group, device and state if the code is a code word of `E`
+    pub fn decode<E: Encoding>(&self) -> Option<(String, Device, State)> {
+        E::decode(&self.code_word()?)
+    }
+}
+
+impl fmt::Display for Received {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "code {} ({:#X}) length {} protocol {} pulse length {}µs",
+            self.code, self.code, self.length, self.protocol, self.pulse_length
+        )?;
+        if let Some(code_word) = self.code_word() {
+            write!(f, " code word {}", String::from_utf8_lossy(&code_word))?;
+        }
+        Ok(())
+    }
+}
+
+fn within(duration: u32, expected: u64, tolerance: u64) -> bool {
+    (u64::from(duration) as i64 - expected as i64).unsigned_abs() < tolerance
+}
+
+/// Decode a frame with `values`. `timings` are the durations between level changes in µs
+/// starting with the gap before the frame. Returns the code, its length and the pulse
+/// length.
+pub fn decode(
+    values: &ProtocolValues,
+    timings: &[u32],
+    tolerance: u64,
+) -> Option<(u64, usize, u64)> {
+    // Ignore very short transmissions
+    if timings.len() <= 7 {
+        return None;
+    }
+    let sync = values.sync_factor();
+    let pulse_length = u64::from(timings[0]) / sync.high.max(sync.low);
+    let tolerance = pulse_length * tolerance / 100;
+    let first = if values.inverted_signal() { 2 } else { 1 };
+
+    let mut code = 0u64;
+    let mut length = 0;
+    let mut i = first;
+    while i + 1 < timings.len() {
+        let (zero, one) = (values.zero(), values.one());
+        let bit = |pulses: &HighLow| {
+            within(timings[i], pulse_length * pulses.high, tolerance)
+                && within(timings[i + 1], pulse_length * pulses.low, tolerance)
+        };
+        code <<= 1;
+        if !bit(zero) {
+            if !bit(one) {
+                return None;
+            }
+            code |= 1;
+        }
+        length += 1;
+        i += 2;
+    }
+    Some((code, length, pulse_length))
+}
+
+/// Decode a frame with all registered protocols. The first matching protocol wins.
+pub fn decode_any(timings: &[u32], tolerance: u64) -> Option<Received> {
+    catalog::registry().iter().find_map(|(name, values)| {
+        let (code, length, pulse_length) = decode(values, timings, tolerance)?;
+        Some(Received {
+            protocol: name,
+            code,
+            length,
+            pulse_length,
+        })
+    })
+}
+
+/// Counts of values in buckets of a fixed width
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    width: u64,
+    buckets: BTreeMap<u64, u64>,
+}
+
+impl Histogram {
+    /// Create an empty histogram with buckets of `width`
+    pub fn new(width: u64) -> Histogram {
+        Histogram {
+            width: width.max(1),
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    /// Count `value`
+    pub fn add(&mut self, value: u64) {
+        *self
+            .buckets
+            .entry(value / self.width * self.width)
+            .or_insert(0) += 1;
+    }
+
+    /// Width of the buckets
+    pub fn width(&self) -> u64 {
+        self.width
+    }
+
+    /// Number of counted values
+    pub fn count(&self) -> u64 {
+        self.buckets.values().sum()
+    }
+
+    /// Lower bound and count of the non-empty buckets in ascending order
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.buckets.iter().map(|(bucket, count)| (*bucket, *count))
+    }
+
+    /// Lower bound of the bucket that contains the `percentile` of the values
+    pub fn percentile(&self, percentile: u64) -> Option<u64> {
+        let rank = (self.count() * percentile.min(100)).div_ceil(100);
+        let mut seen = 0;
+        self.buckets().find_map(|(bucket, count)| {
+            seen += count;
+            if seen >= rank.max(1) {
+                Some(bucket)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Upper bound of the highest non-empty bucket
+    pub fn max(&self) -> Option<u64> {
+        self.buckets
+            .keys()
+            .next_back()
+            .map(|bucket| bucket + self.width)
+    }
+}
+
+/// Timings of the frames decoded with one protocol
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProtocolStats {
+    /// Number of decoded frames
+    pub frames: u64,
+    /// Pulse lengths in µs derived from the sync
+    pub pulse_lengths: Histogram,
+    /// Deviation of the pulses of each bit from the closest symbol in percent of the pulse
+    /// length i.e the tolerance the bit needs to be decoded
+    pub deviations: Histogram,
+}
+
+impl ProtocolStats {
+    fn new() -> ProtocolStats {
+        ProtocolStats {
+            frames: 0,
+            pulse_lengths: Histogram::new(10),
+            deviations: Histogram::new(5),
+        }
+    }
+
+    /// Tolerance in percent that decodes all seen bits
+    pub fn required_tolerance(&self) -> Option<u64> {
+        self.deviations.max()
+    }
+}
+
+/// Statistics of the timings of decoded frames per protocol e.g to tune the tolerance
+/// of the decoder or the pulse length of a protocol to the actual remotes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TimingStats {
+    protocols: BTreeMap<&'static str, ProtocolStats>,
+}
+
+impl TimingStats {
+    /// Create empty statistics
+    pub fn new() -> TimingStats {
+        TimingStats::default()
+    }
+
+    /// Record the frame `timings` decoded as `received`
+    pub fn record(&mut self, received: &Received, timings: &[u32]) {
+        let values = match catalog::protocol(received.protocol) {
+            Some(values) => values,
+            None => return,
+        };
+        let pulse_length = received.pulse_length.max(1);
+        let stats = self
+            .protocols
+            .entry(received.protocol)
+            .or_insert_with(ProtocolStats::new);
+        stats.frames += 1;
+        stats.pulse_lengths.add(received.pulse_length);
+        let first = if values.inverted_signal() { 2 } else { 1 };
+        for pulses in timings[first.min(timings.len())..].chunks_exact(2) {
+            let deviation = |symbol: &HighLow| {
+                let high = (u64::from(pulses[0]) as i64 - (pulse_length * symbol.high) as i64)
+                    .unsigned_abs();
+                let low = (u64::from(pulses[1]) as i64 - (pulse_length * symbol.low) as i64)
+                    .unsigned_abs();
+                high.max(low)
+            };
+            let deviation = deviation(values.zero()).min(deviation(values.one()));
+            stats.deviations.add(deviation * 100 / pulse_length);
+        }
+    }
+
+    /// Statistics of `protocol`
+    pub fn get(&self, protocol: &str) -> Option<&ProtocolStats> {
+        self.protocols.get(protocol)
+    }
+
+    /// Protocols with at least one decoded frame and their statistics
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &ProtocolStats)> {
+        self.protocols.iter().map(|(name, stats)| (*name, stats))
+    }
+
+    /// True if no frame was recorded
+    pub fn is_empty(&self) -> bool {
+        self.protocols.is_empty()
+    }
+}
+
+fn write_histogram(f: &mut fmt::Formatter, histogram: &Histogram, unit: &str) -> fmt::Result {
+    let most = histogram
+        .buckets()
+        .map(|(_, count)| count)
+        .max()
+        .unwrap_or(1);
+    for (bucket, count) in histogram.buckets() {
+        let range = format!("{}..{}{}", bucket, bucket + histogram.width(), unit);
+        let bar = "#".repeat((count * 40).div_ceil(most) as usize);
+        writeln!(f, "    {:>12} {:<40} {}", range, bar, count)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for TimingStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, stats) in self.iter() {
+            writeln!(f, "protocol {}: {} frames", name, stats.frames)?;
+            if let Some(median) = stats.pulse_lengths.percentile(50) {
+                writeln!(f, "  pulse length (median {}µs)", median)?;
+            }
+            write_histogram(f, &stats.pulse_lengths, "µs")?;
+            if let Some(tolerance) = stats.required_tolerance() {
+                writeln!(f, "  deviation (tolerance needed {}%)", tolerance)?;
+            }
+            write_histogram(f, &stats.deviations, "%")?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the durations between level changes and decodes frames
+#[derive(Clone, Debug)]
+pub struct Decoder {
+    timings: Vec<u32>,
+    repeats: usize,
+    tolerance: u64,
+    stats: Option<TimingStats>,
+}
+
+impl Default for Decoder {
+    fn default() -> Decoder {
+        Decoder::new(TOLERANCE)
+    }
+}
+
+impl Decoder {
+    /// Create a new decoder accepting pulses that deviate `tolerance` percent
+    pub fn new(tolerance: u64) -> Decoder {
+        Decoder {
+            timings: Vec::with_capacity(MAX_CHANGES),
+            repeats: 0,
+            tolerance,
+            stats: None,
+        }
+    }
+
+    /// Collect statistics of the timings of decoded frames
+    pub fn with_stats(mut self) -> Decoder {
+        self.stats = Some(TimingStats::new());
+        self
+    }
+
+    /// Statistics of the timings if enabled with `with_stats`
+    pub fn stats(&self) -> Option<&TimingStats> {
+        self.stats.as_ref()
+    }
+
+    /// Feed the duration in µs since the previous level change e.g from an interrupt
+    /// handler. Returns a code once a frame is complete.
+    pub fn push(&mut self, duration: u32) -> Option<Received> {
+        let mut received = None;
+        if duration > SEPARATION_LIMIT {
+            // A gap close to the gap before the recorded frame is most probably the
+            // gap between two repeats of the same frame
+            let first = self.timings.first().cloned().unwrap_or(0);
+            if self.repeats == 0 || within(duration, u64::from(first), 200) {
+                self.repeats += 1;
+                if self.repeats == 2 {
+                    received = decode_any(&self.timings, self.tolerance);
+                    if let (Some(stats), Some(received)) = (&mut self.stats, &received) {
+                        stats.record(received, &self.timings);
+                    }
+                    self.repeats = 0;
+                }
+            }
+            self.timings.clear();
+        }
+        if self.timings.len() >= MAX_CHANGES {
+            self.timings.clear();
+            self.repeats = 0;
+        }
+        self.timings.push(duration);
+        received
+    }
+}
+
+/// Receiver on an input pin
+///
+/// The pin is polled, which needs a dedicated core on slow machines. Use a [`Decoder`]
+/// directly with interrupt or DMA based captures where available.
+pub struct Receiver<T: InputPin> {
+    pin: T,
+    decoder: Decoder,
+    level: Option<Value>,
+    last_change: Instant,
+}
+
+impl<T: InputPin> Receiver<T> {
+    /// Create a receiver with the default tolerance
+    pub fn new(pin: T) -> Receiver<T> {
+        Receiver::with_decoder(pin, Decoder::default())
+    }
+
+    /// Create a receiver with `decoder`
+    pub fn with_decoder(pin: T, decoder: Decoder) -> Receiver<T> {
+        Receiver {
+            pin,
+            decoder,
+            level: None,
+            last_change: Instant::now(),
+        }
+    }
+
+    /// The decoder of the receiver e.g to read its statistics
+    pub fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    /// Poll the pin until a code is received or `timeout` elapsed
+    pub fn receive(&mut self, timeout: Duration) -> Result<Option<Received>, Error> {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            let level = self.pin.get()?;
+            if self.level.as_ref() == Some(&level) {
+                continue;
+            }
+            let now = Instant::now();
+            let duration = now.duration_since(self.last_change).as_micros();
+            self.last_change = now;
+            self.level = Some(level);
+            trace!("Level change after {}µs", duration);
+            if let Some(received) = self.decoder.push(duration.min(u128::from(u32::MAX)) as u32) {
+                debug!("Received {}", received);
+                return Ok(Some(received));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Recovery of transmitters that disappear at runtime e.g an unplugged USB serial adapter
+//! or a network bridge that went offline.
+//!
+//! [`Reattach`] wraps a pin that is opened by a closure. When setting the pin fails, the pin
+//! is dropped and reopened in a background thread until this succeeds. Meanwhile the
+//! [`Policy`] decides whether commands are rejected or buffered in a [`Queue`].
+
+use crate::{
+    delay::Delay,
+    error::{Category, CoreError, Error},
+    queue::{Command, Queue},
+    quiet::Priority,
+    Encoding, Funksteckdose, Pin, Protocol, Value,
+};
+use log::{debug, info, warn};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Handling of commands while the transmitter is detached
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// Fail the command
+    Reject,
+    /// Queue the command until the transmitter is back
+    Buffer,
+}
+
+/// Pin that is reopened in the background after a failure
+pub struct Reattach<T> {
+    inner: Arc<Inner<T>>,
+}
+
+struct Inner<T> {
+    pin: Mutex<Option<T>>,
+    attach: Box<dyn Fn() -> Result<T, Error> + Send + Sync>,
+    interval: Duration,
+    retrying: AtomicBool,
+}
+
+impl<T> Inner<T> {
+    fn pin(&self) -> MutexGuard<'_, Option<T>> {
+        self.pin.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T: Pin + Send + 'static> Reattach<T> {
+    /// Open the pin with `attach`. If this fails the pin is detached and `attach` is retried
+    /// every `interval`.
+    pub fn new<F>(attach: F, interval: Duration) -> Reattach<T>
+    where
+        F: Fn() -> Result<T, Error> + Send + Sync + 'static,
+    {
+        let pin = attach();
+        let reattach = Reattach {
+            inner: Arc::new(Inner {
+                pin: Mutex::new(None),
+                attach: Box::new(attach),
+                interval,
+                retrying: AtomicBool::new(false),
+            }),
+        };
+        match pin {
+            Ok(pin) => *reattach.inner.pin() = Some(pin),
+            Err(e) => {
+                warn!("Failed to attach transmitter: {}", e);
+                reattach.retry();
+            }
+        }
+        reattach
+    }
+
+    /// False while the transmitter is detached
+    pub fn is_attached(&self) -> bool {
+        self.inner.pin().is_some()
+    }
+
+    fn retry(&self) {
+        if self.inner.retrying.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let inner = self.inner.clone();
+        thread::spawn(move || loop {
+            thread::sleep(inner.interval);
+            match (inner.attach)() {
+                Ok(pin) => {
+                    info!("Transmitter reattached");
+                    *inner.pin() = Some(pin);
+                    inner.retrying.store(false, Ordering::SeqCst);
+                    break;
+                }
+                Err(e) => debug!("Failed to reattach transmitter: {}", e),
+            }
+        });
+    }
+}
+
+impl<T: Pin + Send + 'static> Pin for Reattach<T> {
+    fn set(&self, value: &Value) -> Result<(), CoreError> {
+        let mut pin = self.inner.pin();
+        let result = match *pin {
+            Some(ref p) => p.set(value),
+            None => return Err(CoreError::Detached),
+        };
+        if let Err(ref e) = result {
+            warn!("Transmitter failed: {}. Marking it detached", e);
+            *pin = None;
+            drop(pin);
+            self.retry();
+        }
+        result
+    }
+}
+
+/// Send `command`. With `Policy::Buffer` the command is appended to `queue` if the
+/// transmitter failed. A full queue loses the command and is counted in the metrics of
+/// `funksteckdose`.
+/// ```
+/// use funksteckdose::{error::{Category, Error}, mock::NullPin, queue::{Command, Queue}};
+/// use funksteckdose::{recovery::{self, Policy, Reattach}, Device, EncodingA, Protocol1, State};
+/// use std::time::Duration;
+///
+/// let unplugged = || -> Result<NullPin, Error> { Err(Error::Store("unplugged".into())) };
+/// let pin = Reattach::new(unplugged, Duration::from_secs(60));
+/// type Funksteckdose = funksteckdose::Funksteckdose<Reattach<NullPin>, EncodingA, Protocol1>;
+/// let d: Funksteckdose = Funksteckdose::builder(pin).repeat_transmit(1).build().unwrap();
+/// let mut queue = Queue::new();
+/// queue.set_capacity(Some(1));
+/// let on = Command::new("10001", &Device::A, &State::On);
+/// recovery::deliver(&d, &mut queue, on.clone(), Policy::Buffer).unwrap();
+/// let result = recovery::deliver(&d, &mut queue, on, Policy::Buffer);
+/// assert!(matches!(result, Err(Error::QueueFull(1))));
+/// assert_eq!(d.metrics().errors(Category::QueueFull), 1);
+/// ```
+pub fn deliver<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+    funksteckdose: &Funksteckdose<T, E, P, D>,
+    queue: &mut Queue,
+    command: Command,
+    policy: Policy,
+) -> Result<(), Error> {
+    match command.send(funksteckdose) {
+        Err(ref e) if policy == Policy::Buffer && e.category() == Category::Backend => {
+            warn!("Buffering {} until the transmitter recovers", command);
+            let result = queue.push(command);
+            if result.is_err() {
+                funksteckdose.metrics().record(&result);
+            }
+            result
+        }
+        result => result,
+    }
+}
+
+/// Send buffered commands in order and return the number of sent commands. Stops at the
+/// first transmitter failure and keeps the remaining commands. Commands that fail for other
+/// reasons e.g because they expired are dropped. Commands held back by the minimum gap of
+/// their socket stay queued for the next flush. Buffered commands are sent as background
+/// transmissions and are reduced during the quiet hours.
+pub fn flush<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+    funksteckdose: &Funksteckdose<T, E, P, D>,
+    queue: &mut Queue,
+) -> Result<usize, Error> {
+    let mut sent = 0;
+    while let Some(command) = queue.next_ready().cloned() {
+        match command.send_with_priority(funksteckdose, Priority::Background) {
+            Ok(()) => sent += 1,
+            Err(e) => {
+                if e.category() == Category::Backend {
+                    return Err(e);
+                }
+                warn!("Dropping buffered command {}: {}", command, e);
+            }
+        }
+        queue.remove(&command)?;
+    }
+    Ok(sent)
+}
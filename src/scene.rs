@@ -0,0 +1,99 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Scenes switch several sockets at once e.g all lights of a room.
+//!
+//! By default the commands are sent one after the other. The last socket of a large scene
+//! then waits for all frames of the sockets before it. With `Order::Interleaved` the frames
+//! of all commands are sent in rounds so that every socket hears a frame early and the
+//! remaining frames only add redundancy.
+
+use crate::{delay::Delay, error::Error, queue::Command, Encoding, Funksteckdose, Pin, Protocol};
+use log::info;
+use std::{fmt, str::FromStr, time::SystemTime};
+
+/// Order of the frames of a scene
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+    /// All frames of a command before the next command
+    #[default]
+    Sequential,
+    /// One frame of every command per round
+    Interleaved,
+}
+
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Order::Sequential => write!(f, "sequential"),
+            Order::Interleaved => write!(f, "interleaved"),
+        }
+    }
+}
+
+impl FromStr for Order {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sequential" => Ok(Order::Sequential),
+            "interleaved" => Ok(Order::Interleaved),
+            _ => Err(Error::InvalidConfig(format!(
+                "unknown scene order \"{}\"",
+                s
+            ))),
+        }
+    }
+}
+
+/// A named set of commands
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scene {
+    pub name: String,
+    pub commands: Vec<Command>,
+    pub order: Order,
+}
+
+impl Scene {
+    /// Send all commands. Expired commands are skipped. A failing command aborts a
+    /// sequential scene.
+    pub fn run<T: Pin, E: Encoding, P: Protocol, D: Delay>(
+        &self,
+        funksteckdose: &Funksteckdose<T, E, P, D>,
+    ) -> Result<(), Error> {
+        let now = SystemTime::now();
+        let mut commands = self.commands.iter().filter(|c| !c.is_expired(now));
+        info!(
+            "Running scene {} with {} commands {}",
+            self.name,
+            self.commands.len(),
+            self.order
+        );
+        match self.order {
+            Order::Sequential => commands.try_for_each(|c| c.send(funksteckdose)),
+            Order::Interleaved => {
+                let plans = commands
+                    .map(|c| funksteckdose.plan(&c.group, &c.device, &c.state))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                funksteckdose.transmit_interleaved(&plans)
+            }
+        }
+    }
+}
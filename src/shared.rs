@@ -0,0 +1,213 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Sharing a transmitter between threads.
+//!
+//! A [`Funksteckdose`] is `Send` but not `Sync`. Two threads sending through the same pin at
+//! once would interleave their pulses and neither socket would understand its command.
+//! [`SharedTransmitter`] serializes all transmissions with a mutex and can be put into an
+//! `Arc`:
+//!
+//! ```
+//! use funksteckdose::{mock::NullPin, shared::SharedTransmitter, Device, EncodingA, Protocol1, State};
+//! use std::{sync::Arc, thread};
+//!
+//! type Funksteckdose = funksteckdose::Funksteckdose<NullPin, EncodingA, Protocol1>;
+//! let d: Funksteckdose = Funksteckdose::builder(NullPin).repeat_transmit(1).build().unwrap();
+//! let shared = Arc::new(SharedTransmitter::new(d));
+//! let other = shared.clone();
+//! thread::spawn(move || other.send("10011", &Device::A, &State::On))
+//!     .join()
+//!     .unwrap()
+//!     .unwrap();
+//! shared.send("10011", &Device::B, &State::On).unwrap();
+//! ```
+
+use crate::{
+    delay::Delay,
+    error::Error,
+    macros::{self, Cancel},
+    plan::TransmissionPlan,
+    queue::Command,
+    quiet::Priority,
+    Device, Encoding, Funksteckdose, Pin, Protocol, State,
+};
+use log::debug;
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A transmitter that can be used from several threads. Transmissions are sent one after
+/// the other.
+#[derive(Debug)]
+pub struct SharedTransmitter<T: Pin, E: Encoding, P: Protocol, D: Delay> {
+    funksteckdose: Mutex<Funksteckdose<T, E, P, D>>,
+}
+
+impl<T: Pin, E: Encoding, P: Protocol, D: Delay> SharedTransmitter<T, E, P, D> {
+    /// Share `funksteckdose`
+    pub fn new(funksteckdose: Funksteckdose<T, E, P, D>) -> SharedTransmitter<T, E, P, D> {
+        SharedTransmitter {
+            funksteckdose: Mutex::new(funksteckdose),
+        }
+    }
+
+    /// Exclusive access e.g to change settings. Transmissions of other threads wait until
+    /// the guard is dropped.
+    pub fn lock(&self) -> MutexGuard<'_, Funksteckdose<T, E, P, D>> {
+        // A panic while sending leaves the transmitter usable, the pin is set again with
+        // the next pulse
+        self.funksteckdose.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The shared transmitter
+    pub fn into_inner(self) -> Funksteckdose<T, E, P, D> {
+        self.funksteckdose
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// See [`Funksteckdose::send`]
+    pub fn send(&self, group: &str, device: &Device, state: &State) -> Result<(), Error> {
+        self.lock().send(group, device, state)
+    }
+
+    /// See [`Funksteckdose::send_with_priority`]
+    pub fn send_with_priority(
+        &self,
+        group: &str,
+        device: &Device,
+        state: &State,
+        priority: Priority,
+    ) -> Result<(), Error> {
+        self.lock()
+            .send_with_priority(group, device, state, priority)
+    }
+
+    /// See [`Funksteckdose::send_code`]
+    pub fn send_code(&self, code: u64, length: usize) -> Result<(), Error> {
+        self.lock().send_code(code, length)
+    }
+
+    /// See [`Funksteckdose::send_tri_state`]
+    pub fn send_tri_state(&self, code_word: &str) -> Result<(), Error> {
+        self.lock().send_tri_state(code_word)
+    }
+
+    /// See [`Funksteckdose::transmit`]
+    pub fn transmit(&self, plan: &TransmissionPlan) -> Result<(), Error> {
+        self.lock().transmit(plan)
+    }
+
+    /// See [`Funksteckdose::transmit_interleaved`]
+    pub fn transmit_interleaved(&self, plans: &[TransmissionPlan]) -> Result<(), Error> {
+        self.lock().transmit_interleaved(plans)
+    }
+
+    /// Send `command` at `at` from a background thread e.g "off in 45 minutes". A time in
+    /// the past sends right away. The command is dropped if it expired in the meantime and
+    /// is reduced like any background transmission during the quiet hours.
+    /// ```
+    /// use funksteckdose::{mock::NullPin, queue::Command, shared::SharedTransmitter};
+    /// use funksteckdose::{error::Error, Device, EncodingA, Protocol1, State};
+    /// use std::{sync::Arc, time::Duration};
+    ///
+    /// type Funksteckdose = funksteckdose::Funksteckdose<NullPin, EncodingA, Protocol1>;
+    /// let d: Funksteckdose = Funksteckdose::builder(NullPin).repeat_transmit(1).build().unwrap();
+    /// let shared = Arc::new(SharedTransmitter::new(d));
+    /// let off = Command::new("10011", &Device::A, &State::Off);
+    /// let scheduled = shared.send_after(Duration::from_secs(45 * 60), off);
+    /// scheduled.cancel();
+    /// assert!(matches!(scheduled.join(), Err(Error::Cancelled)));
+    /// ```
+    pub fn send_at(self: &Arc<Self>, at: Instant, command: Command) -> Scheduled
+    where
+        Self: Send + Sync + 'static,
+    {
+        let cancel = Cancel::new();
+        let shared = self.clone();
+        let canceller = cancel.clone();
+        let handle = thread::spawn(move || {
+            debug!(
+                "Sending {} in {:?}",
+                command,
+                at.saturating_duration_since(Instant::now())
+            );
+            macros::wait(at.saturating_duration_since(Instant::now()), &canceller)?;
+            command.send_with_priority(&shared.lock(), Priority::Background)
+        });
+        Scheduled { at, cancel, handle }
+    }
+
+    /// Send `command` after `delay`. See `send_at`.
+    pub fn send_after(self: &Arc<Self>, delay: Duration, command: Command) -> Scheduled
+    where
+        Self: Send + Sync + 'static,
+    {
+        self.send_at(Instant::now() + delay, command)
+    }
+}
+
+/// A command scheduled with [`SharedTransmitter::send_at`]
+#[derive(Debug)]
+pub struct Scheduled {
+    at: Instant,
+    cancel: Cancel,
+    handle: thread::JoinHandle<Result<(), Error>>,
+}
+
+impl Scheduled {
+    /// Point in time the command is sent at
+    pub fn at(&self) -> Instant {
+        self.at
+    }
+
+    /// Do not send the command unless it is already on air
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Handle to cancel the command from elsewhere e.g when the handle is joined
+    pub fn canceller(&self) -> Cancel {
+        self.cancel.clone()
+    }
+
+    /// True if the command was sent, failed or was cancelled
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Wait for the command. Fails with `Error::Cancelled` if it was cancelled.
+    pub fn join(self) -> Result<(), Error> {
+        self.handle
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+impl<T: Pin, E: Encoding, P: Protocol, D: Delay> From<Funksteckdose<T, E, P, D>>
+    for SharedTransmitter<T, E, P, D>
+{
+    fn from(funksteckdose: Funksteckdose<T, E, P, D>) -> SharedTransmitter<T, E, P, D> {
+        SharedTransmitter::new(funksteckdose)
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Diagnostic waveforms independent of any protocol e.g to check the transmitter and its
+//! antenna with a scope or a second receiver.
+
+use crate::{
+    error::Error,
+    plan::{Symbol, TransmissionPlan},
+};
+use std::{fmt, str::FromStr, time::Duration};
+
+/// Shape of a test signal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    /// High for half of the period
+    Square,
+    /// High for a tenth of the period
+    Pulse,
+    /// High for the whole duration
+    Carrier,
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Square => write!(f, "square"),
+            Pattern::Pulse => write!(f, "pulse"),
+            Pattern::Carrier => write!(f, "carrier"),
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(Pattern::Square),
+            "pulse" => Ok(Pattern::Pulse),
+            "carrier" => Ok(Pattern::Carrier),
+            _ => Err(Error::InvalidSignal(format!("unknown pattern \"{}\"", s))),
+        }
+    }
+}
+
+/// A test signal
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signal {
+    pub pattern: Pattern,
+    /// Length of one period. Ignored for `Pattern::Carrier`
+    pub period: Duration,
+    /// Total length of the signal
+    pub duration: Duration,
+}
+
+impl Signal {
+    /// Plan the signal. The period must be between 2µs and the duration, which must be
+    /// shorter than an hour.
+    pub fn plan(&self) -> Result<TransmissionPlan, Error> {
+        let micros = |d: Duration| d.as_micros() as u64;
+        if self.duration > Duration::from_secs(60 * 60) || self.duration.as_micros() == 0 {
+            return Err(Error::InvalidSignal(format!(
+                "duration {:?} not within 1µs and 1h",
+                self.duration
+            )));
+        }
+        if self.pattern == Pattern::Carrier {
+            let symbol = Symbol {
+                high: micros(self.duration) as u32,
+                low: 0,
+            };
+            return Ok(TransmissionPlan::from_symbols(vec![symbol], 1));
+        }
+        let period = micros(self.period);
+        if period < 2 || self.period > self.duration {
+            return Err(Error::InvalidSignal(format!(
+                "period {:?} not within 2µs and {:?}",
+                self.period, self.duration
+            )));
+        }
+        let high = match self.pattern {
+            Pattern::Square => period / 2,
+            _ => (period / 10).max(1),
+        };
+        let symbol = Symbol {
+            high: high as u32,
+            low: (period - high) as u32,
+        };
+        let repeats = (micros(self.duration) / period) as usize;
+        Ok(TransmissionPlan::from_symbols(vec![symbol], repeats))
+    }
+}
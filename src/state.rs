@@ -0,0 +1,228 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The sockets have no feedback channel. The states tracked here are what was last sent
+//! to a socket or what was last seen on air for it e.g from the original remote.
+
+use crate::{
+    error::{CoreError, Error},
+    store::Store,
+    Device, Encoding, State,
+};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Where a state change originates from
+#[derive(Clone, Debug, PartialEq)]
+pub enum Origin {
+    /// Sent by this crate
+    Command,
+    /// Received over the air e.g from a physical remote
+    Remote,
+}
+
+/// A change of the assumed state of a socket
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateChange {
+    pub group: String,
+    pub device: Device,
+    pub state: State,
+    pub origin: Origin,
+}
+
+/// Assumed states of a set of known sockets
+#[derive(Clone, Debug, Default)]
+pub struct StateStore {
+    states: HashMap<(String, Device), Option<State>>,
+}
+
+impl StateStore {
+    /// Create a new empty store
+    pub fn new() -> StateStore {
+        StateStore::default()
+    }
+
+    /// Register a socket. Codes for unknown sockets are ignored by `observe`.
+    pub fn insert(&mut self, group: &str, device: &Device) {
+        self.states
+            .entry((group.into(), device.clone()))
+            .or_insert(None);
+    }
+
+    /// Last known state of a socket
+    pub fn get(&self, group: &str, device: &Device) -> Option<&State> {
+        self.states
+            .get(&(group.into(), device.clone()))
+            .and_then(Option::as_ref)
+    }
+
+    /// Iterate all known sockets and their state
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Device, Option<&State>)> {
+        self.states
+            .iter()
+            .map(|((group, device), state)| (group.as_str(), device, state.as_ref()))
+    }
+
+    /// Update the state of a socket. Returns a change if the state differs from the
+    /// previously known one.
+    pub fn set(
+        &mut self,
+        group: &str,
+        device: &Device,
+        state: &State,
+        origin: Origin,
+    ) -> Option<StateChange> {
+        let current = self
+            .states
+            .entry((group.into(), device.clone()))
+            .or_insert(None);
+        if current.as_ref() == Some(state) {
+            return None;
+        }
+        *current = Some(state.clone());
+        Some(StateChange {
+            group: group.into(),
+            device: device.clone(),
+            state: state.clone(),
+            origin,
+        })
+    }
+
+    /// Mirror a code word received over the air. Codes that cannot be decoded with
+    /// `E` or that address an unknown socket are ignored.
+    pub fn observe<E: Encoding>(&mut self, code_word: &[u8]) -> Option<StateChange> {
+        let (group, device, state) = E::decode(code_word)?;
+        if !self.states.contains_key(&(group.clone(), device.clone())) {
+            return None;
+        }
+        self.set(&group, &device, &state, Origin::Remote)
+    }
+
+    /// Snapshot of all sockets with a known state as JSON array of objects with `group`,
+    /// `device` and `state` e.g to restore the states with `import` after maintenance
+    #[cfg(feature = "json")]
+    pub fn export(&self) -> String {
+        let mut states = self
+            .iter()
+            .filter_map(|(group, device, state)| Some((group, device, state?)))
+            .collect::<Vec<_>>();
+        states.sort_by_key(|(group, device, _)| (*group, u8::from((*device).clone())));
+        let states = states
+            .into_iter()
+            .map(|(group, device, state)| {
+                serde_json::json!({
+                    "group": group,
+                    "device": device.to_string(),
+                    "state": state.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::to_string_pretty(&states).expect("failed to serialize states")
+    }
+
+    /// Load a snapshot created with `export`
+    #[cfg(feature = "json")]
+    pub fn import(json: &str) -> Result<StateStore, Error> {
+        use crate::Group;
+
+        #[derive(serde::Deserialize)]
+        struct Snapshot {
+            group: String,
+            device: String,
+            state: String,
+        }
+
+        let snapshot = serde_json::from_str::<Vec<Snapshot>>(json)
+            .map_err(|e| Error::InvalidSnapshot(e.to_string()))?;
+        let mut states = StateStore::new();
+        for s in snapshot {
+            if s.group.parse::<Group>().is_err() {
+                return Err(Error::InvalidSnapshot(format!("invalid group {}", s.group)));
+            }
+            states.set(
+                &s.group,
+                &s.device.parse()?,
+                &s.state.parse()?,
+                Origin::Command,
+            );
+        }
+        Ok(states)
+    }
+}
+
+/// Last sent states used to resolve `State::Toggle`, optionally persisted to a `Store`
+/// so that toggling survives a restart
+#[derive(Default)]
+pub struct StateCache {
+    states: StateStore,
+    store: Option<Box<dyn Store>>,
+}
+
+impl StateCache {
+    /// Create a new in-memory cache
+    pub fn new() -> StateCache {
+        StateCache::default()
+    }
+
+    /// Create a cache backed by `store`. The states saved in `store` are loaded.
+    pub fn persistent(store: Box<dyn Store>) -> Result<StateCache, Error> {
+        let mut states = StateStore::new();
+        store.load_states(&mut states)?;
+        Ok(StateCache {
+            states,
+            store: Some(store),
+        })
+    }
+
+    /// Last sent state of a socket
+    pub fn get(&self, group: &str, device: &Device) -> Option<&State> {
+        self.states.get(group, device)
+    }
+
+    /// Concrete state to send for `state`. See `State::resolve`.
+    pub fn resolve(&self, group: &str, device: &Device, state: &State) -> State {
+        state.resolve(self.get(group, device))
+    }
+
+    /// Remember that `state` was sent to a socket. The state is saved if the cache is
+    /// persistent and the state changed.
+    pub fn record(&mut self, group: &str, device: &Device, state: &State) -> Result<(), Error> {
+        if *state == State::Toggle {
+            return Err(CoreError::InvalidState(state.to_string()).into());
+        }
+        let changed = self
+            .states
+            .set(group, device, state, Origin::Command)
+            .is_some();
+        match self.store {
+            Some(ref mut store) if changed => store.save_state(group, device, state),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for StateCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StateCache")
+            .field("states", &self.states)
+            .field("persistent", &self.store.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,608 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Persistence of the state of a long running process behind the [`Store`] trait.
+//!
+//! Values are stored as strings in the formats used elsewhere in this crate e.g `on` for
+//! states or the `Display` format of captures and history entries. The backends are
+//! selected with features:
+//!
+//! * `json`: [`FileStore`], a single JSON file that is rewritten on every change
+//! * `sled`: [`SledStore`], an embedded key value database
+//! * `sqlite`: [`SqliteStore`], a SQLite database
+//!
+//! [`open`] selects the backend at runtime e.g from the `[store]` table of the configuration.
+
+use crate::{
+    cloning::{Capture, Captures},
+    error::Error,
+    history::Entry,
+    state::{Origin, StateStore},
+    Device, State,
+};
+#[cfg(any(feature = "json", feature = "sled", feature = "sqlite"))]
+use std::time::UNIX_EPOCH;
+use std::{fmt, path::Path, str::FromStr, time::SystemTime};
+
+/// Key value tables of a store
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Table {
+    /// Last known state by `<group> <device>`
+    States,
+    /// Counters by name
+    Counters,
+    /// Captures by name
+    Captures,
+}
+
+impl Table {
+    /// All tables
+    pub const ALL: [Table; 3] = [Table::States, Table::Counters, Table::Captures];
+
+    /// Name of the table e.g in a database
+    pub fn name(self) -> &'static str {
+        match self {
+            Table::States => "states",
+            Table::Counters => "counters",
+            Table::Captures => "captures",
+        }
+    }
+}
+
+/// A persistence backend
+pub trait Store: Send {
+    /// Value of `key` in `table`
+    fn get(&self, table: Table, key: &str) -> Result<Option<String>, Error>;
+
+    /// Set `key` in `table` to `value`
+    fn put(&mut self, table: Table, key: &str, value: &str) -> Result<(), Error>;
+
+    /// Remove `key` from `table`. Removing a missing key is not an error.
+    fn remove(&mut self, table: Table, key: &str) -> Result<(), Error>;
+
+    /// All keys and values of `table` ordered by key
+    fn entries(&self, table: Table) -> Result<Vec<(String, String)>, Error>;
+
+    /// Append a history entry
+    fn append(&mut self, entry: &Entry) -> Result<(), Error>;
+
+    /// History entries not older than `since` starting with the most recent
+    fn history(&self, since: SystemTime) -> Result<Vec<Entry>, Error>;
+
+    /// Persist the state of a socket
+    fn save_state(&mut self, group: &str, device: &Device, state: &State) -> Result<(), Error> {
+        self.put(
+            Table::States,
+            &format!("{} {}", group, device),
+            &state.to_string(),
+        )
+    }
+
+    /// Restore the persisted states into `states`
+    fn load_states(&self, states: &mut StateStore) -> Result<(), Error> {
+        for (key, value) in self.entries(Table::States)? {
+            let mut fields = key.split_whitespace();
+            let (group, device) = match (fields.next(), fields.next()) {
+                (Some(group), Some(device)) => (group, device.parse::<Device>()?),
+                _ => return Err(Error::Store(format!("invalid state key \"{}\"", key))),
+            };
+            states.set(group, &device, &value.parse()?, Origin::Command);
+        }
+        Ok(())
+    }
+
+    /// Add `by` to the counter `name` and return the new value
+    fn increment(&mut self, name: &str, by: u64) -> Result<u64, Error> {
+        let value = self.counter(name)?.saturating_add(by);
+        self.put(Table::Counters, name, &value.to_string())?;
+        Ok(value)
+    }
+
+    /// Value of the counter `name`. Unknown counters are 0.
+    fn counter(&self, name: &str) -> Result<u64, Error> {
+        match self.get(Table::Counters, name)? {
+            Some(value) => value
+                .parse()
+                .map_err(|_| Error::Store(format!("invalid counter {} \"{}\"", name, value))),
+            None => Ok(0),
+        }
+    }
+
+    /// Persist a capture. A capture with the same name is replaced.
+    fn save_capture(&mut self, capture: &Capture) -> Result<(), Error> {
+        self.put(Table::Captures, &capture.name, &capture.to_string())
+    }
+
+    /// All persisted captures
+    fn load_captures(&self) -> Result<Captures, Error> {
+        let mut captures = Captures::default();
+        for (_, value) in self.entries(Table::Captures)? {
+            captures.insert(value.parse()?);
+        }
+        Ok(captures)
+    }
+}
+
+/// Available backends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Json,
+    Sled,
+    Sqlite,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Backend::Json => write!(f, "json"),
+            Backend::Sled => write!(f, "sled"),
+            Backend::Sqlite => write!(f, "sqlite"),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Backend::Json),
+            "sled" => Ok(Backend::Sled),
+            "sqlite" => Ok(Backend::Sqlite),
+            _ => Err(Error::InvalidConfig(format!(
+                "unknown store backend \"{}\"",
+                s
+            ))),
+        }
+    }
+}
+
+/// Open the store at `path` with `backend`. Backends that are not compiled in are a
+/// configuration error.
+pub fn open<P: AsRef<Path>>(backend: Backend, path: P) -> Result<Box<dyn Store>, Error> {
+    let _path = path.as_ref();
+    match backend {
+        #[cfg(feature = "json")]
+        Backend::Json => Ok(Box::new(FileStore::open(_path)?)),
+        #[cfg(feature = "sled")]
+        Backend::Sled => Ok(Box::new(SledStore::open(_path)?)),
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => Ok(Box::new(SqliteStore::open(_path)?)),
+        #[allow(unreachable_patterns)]
+        _ => Err(Error::InvalidConfig(format!(
+            "store backend {} is not enabled. Rebuild with --features {}",
+            backend, backend
+        ))),
+    }
+}
+
+#[cfg(any(feature = "json", feature = "sled", feature = "sqlite"))]
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(feature = "json")]
+pub use self::json::{FileStore, HISTORY_CAPACITY};
+
+#[cfg(feature = "json")]
+mod json {
+    use super::{millis, Store, Table};
+    use crate::{error::Error, history::Entry};
+    use serde::{Deserialize, Serialize};
+    use std::{
+        collections::BTreeMap,
+        fs, io,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    };
+
+    /// Maximum number of history entries kept by a [`FileStore`]
+    pub const HISTORY_CAPACITY: usize = 10_000;
+
+    #[derive(Debug, Default, Deserialize, Serialize)]
+    struct Contents {
+        #[serde(default)]
+        tables: BTreeMap<String, BTreeMap<String, String>>,
+        /// Entries in the history line format, oldest first
+        #[serde(default)]
+        history: Vec<String>,
+    }
+
+    /// Store in a single JSON file. Suited for a small number of sockets and changes. The
+    /// history is limited to [`HISTORY_CAPACITY`] entries.
+    #[derive(Debug)]
+    pub struct FileStore {
+        path: PathBuf,
+        contents: Contents,
+    }
+
+    impl FileStore {
+        /// Open the store in `path`. A missing file results in an empty store.
+        pub fn open<P: AsRef<Path>>(path: P) -> Result<FileStore, Error> {
+            let path = path.as_ref().to_path_buf();
+            let contents = match fs::read_to_string(&path) {
+                Ok(json) => serde_json::from_str(&json)
+                    .map_err(|e| Error::Store(format!("{}: {}", path.display(), e)))?,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => Contents::default(),
+                Err(e) => return Err(e.into()),
+            };
+            Ok(FileStore { path, contents })
+        }
+
+        /// Write to a temporary file and rename it to not lose the store on a crash
+        fn write(&self) -> Result<(), Error> {
+            let json = serde_json::to_string_pretty(&self.contents)
+                .map_err(|e| Error::Store(e.to_string()))?;
+            let tmp = self.path.with_extension("tmp");
+            fs::write(&tmp, json)?;
+            fs::rename(&tmp, &self.path)?;
+            Ok(())
+        }
+    }
+
+    impl Store for FileStore {
+        fn get(&self, table: Table, key: &str) -> Result<Option<String>, Error> {
+            Ok(self
+                .contents
+                .tables
+                .get(table.name())
+                .and_then(|t| t.get(key))
+                .cloned())
+        }
+
+        fn put(&mut self, table: Table, key: &str, value: &str) -> Result<(), Error> {
+            self.contents
+                .tables
+                .entry(table.name().into())
+                .or_default()
+                .insert(key.into(), value.into());
+            self.write()
+        }
+
+        fn remove(&mut self, table: Table, key: &str) -> Result<(), Error> {
+            let removed = self
+                .contents
+                .tables
+                .get_mut(table.name())
+                .and_then(|t| t.remove(key));
+            match removed {
+                Some(_) => self.write(),
+                None => Ok(()),
+            }
+        }
+
+        fn entries(&self, table: Table) -> Result<Vec<(String, String)>, Error> {
+            Ok(self
+                .contents
+                .tables
+                .get(table.name())
+                .map(|t| t.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+                .unwrap_or_default())
+        }
+
+        fn append(&mut self, entry: &Entry) -> Result<(), Error> {
+            let history = &mut self.contents.history;
+            history.push(entry.to_string());
+            if history.len() > HISTORY_CAPACITY {
+                let excess = history.len() - HISTORY_CAPACITY;
+                history.drain(..excess);
+            }
+            self.write()
+        }
+
+        fn history(&self, since: SystemTime) -> Result<Vec<Entry>, Error> {
+            let since = millis(since);
+            let mut entries = Vec::new();
+            for line in self.contents.history.iter().rev() {
+                let entry: Entry = line.parse()?;
+                if millis(entry.time) < since {
+                    break;
+                }
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+    }
+}
+
+#[cfg(feature = "sled")]
+pub use self::kv::SledStore;
+
+#[cfg(feature = "sled")]
+mod kv {
+    use super::{millis, Store, Table};
+    use crate::{error::Error, history::Entry};
+    use std::{path::Path, time::SystemTime};
+
+    fn store_error(e: sled::Error) -> Error {
+        Error::Store(e.to_string())
+    }
+
+    fn string(bytes: &[u8]) -> Result<String, Error> {
+        String::from_utf8(bytes.to_vec()).map_err(|e| Error::Store(e.to_string()))
+    }
+
+    /// Store in a sled database. Each table is a tree, history entries are keyed by time.
+    pub struct SledStore {
+        db: sled::Db,
+    }
+
+    impl SledStore {
+        /// Open or create the database in the directory `path`
+        pub fn open<P: AsRef<Path>>(path: P) -> Result<SledStore, Error> {
+            let db = sled::open(path).map_err(store_error)?;
+            Ok(SledStore { db })
+        }
+
+        fn tree(&self, name: &str) -> Result<sled::Tree, Error> {
+            self.db.open_tree(name).map_err(store_error)
+        }
+    }
+
+    impl Store for SledStore {
+        fn get(&self, table: Table, key: &str) -> Result<Option<String>, Error> {
+            match self.tree(table.name())?.get(key).map_err(store_error)? {
+                Some(value) => string(&value).map(Some),
+                None => Ok(None),
+            }
+        }
+
+        fn put(&mut self, table: Table, key: &str, value: &str) -> Result<(), Error> {
+            self.tree(table.name())?
+                .insert(key, value.as_bytes())
+                .map_err(store_error)?;
+            self.db.flush().map_err(store_error)?;
+            Ok(())
+        }
+
+        fn remove(&mut self, table: Table, key: &str) -> Result<(), Error> {
+            self.tree(table.name())?.remove(key).map_err(store_error)?;
+            self.db.flush().map_err(store_error)?;
+            Ok(())
+        }
+
+        fn entries(&self, table: Table) -> Result<Vec<(String, String)>, Error> {
+            self.tree(table.name())?
+                .iter()
+                .map(|item| {
+                    let (key, value) = item.map_err(store_error)?;
+                    Ok((string(&key)?, string(&value)?))
+                })
+                .collect()
+        }
+
+        fn append(&mut self, entry: &Entry) -> Result<(), Error> {
+            // Big endian time followed by a unique id keeps the keys sorted by time
+            let id = self.db.generate_id().map_err(store_error)?;
+            let mut key = millis(entry.time).to_be_bytes().to_vec();
+            key.extend_from_slice(&id.to_be_bytes());
+            self.tree("history")?
+                .insert(key, entry.to_string().as_bytes())
+                .map_err(store_error)?;
+            self.db.flush().map_err(store_error)?;
+            Ok(())
+        }
+
+        fn history(&self, since: SystemTime) -> Result<Vec<Entry>, Error> {
+            let start = millis(since).to_be_bytes();
+            self.tree("history")?
+                .range(start..)
+                .rev()
+                .map(|item| {
+                    let (_, value) = item.map_err(store_error)?;
+                    string(&value)?.parse()
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub use self::sql::SqliteStore;
+
+#[cfg(feature = "sqlite")]
+mod sql {
+    use super::{millis, Store, Table};
+    use crate::{
+        error::Error,
+        history::{Activity, Entry},
+    };
+    use rusqlite::{params, Connection, OptionalExtension};
+    use std::{
+        path::Path,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS kv (
+            tbl TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (tbl, key)
+        );
+        CREATE TABLE IF NOT EXISTS history (
+            time INTEGER NOT NULL,
+            grp TEXT NOT NULL,
+            device TEXT NOT NULL,
+            state TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS history_time ON history (time);
+    ";
+
+    /// Commands per socket with the on time derived from the gap to the next command
+    const ACTIVITY: &str = "
+        WITH h AS (
+            SELECT grp, device, state, time,
+                LEAD(time) OVER (PARTITION BY grp, device ORDER BY time, rowid) AS next
+            FROM history
+            WHERE time >= ?1
+        )
+        SELECT grp, device, COUNT(*),
+            SUM(CASE WHEN state = 'on' THEN MAX(COALESCE(next, ?2) - time, 0) ELSE 0 END),
+            MAX(time),
+            (SELECT l.state FROM h AS l
+                WHERE l.grp = h.grp AND l.device = h.device AND l.next IS NULL)
+        FROM h
+        GROUP BY grp, device
+        ORDER BY grp, device
+    ";
+
+    pub(super) fn store_error(e: rusqlite::Error) -> Error {
+        Error::Store(e.to_string())
+    }
+
+    /// Store in a SQLite database. History entries are rows of the `history` table and can
+    /// be queried with SQL.
+    pub struct SqliteStore {
+        pub(super) connection: Connection,
+    }
+
+    impl SqliteStore {
+        /// Open or create the database in `path`
+        pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteStore, Error> {
+            let connection = Connection::open(path).map_err(store_error)?;
+            connection.execute_batch(SCHEMA).map_err(store_error)?;
+            Ok(SqliteStore { connection })
+        }
+
+        /// Commands per socket since `since`. Sockets that are still on count as on until
+        /// `now`.
+        pub fn activity(&self, since: SystemTime, now: SystemTime) -> Result<Vec<Activity>, Error> {
+            let mut statement = self.connection.prepare(ACTIVITY).map_err(store_error)?;
+            let rows = statement
+                .query_map(params![millis(since) as i64, millis(now) as i64], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, i64>(3)?,
+                        row.get::<_, i64>(4)?,
+                        row.get::<_, String>(5)?,
+                    ))
+                })
+                .map_err(store_error)?;
+            rows.map(|row| {
+                let (group, device, switches, on_time, last, state) = row.map_err(store_error)?;
+                Ok(Activity {
+                    group,
+                    device: device.parse()?,
+                    switches: switches as u64,
+                    on_time: Duration::from_millis(on_time as u64),
+                    last: UNIX_EPOCH + Duration::from_millis(last as u64),
+                    state: state.parse()?,
+                })
+            })
+            .collect()
+        }
+    }
+
+    impl Store for SqliteStore {
+        fn get(&self, table: Table, key: &str) -> Result<Option<String>, Error> {
+            self.connection
+                .query_row(
+                    "SELECT value FROM kv WHERE tbl = ?1 AND key = ?2",
+                    params![table.name(), key],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(store_error)
+        }
+
+        fn put(&mut self, table: Table, key: &str, value: &str) -> Result<(), Error> {
+            self.connection
+                .execute(
+                    "INSERT OR REPLACE INTO kv (tbl, key, value) VALUES (?1, ?2, ?3)",
+                    params![table.name(), key, value],
+                )
+                .map_err(store_error)?;
+            Ok(())
+        }
+
+        fn remove(&mut self, table: Table, key: &str) -> Result<(), Error> {
+            self.connection
+                .execute(
+                    "DELETE FROM kv WHERE tbl = ?1 AND key = ?2",
+                    params![table.name(), key],
+                )
+                .map_err(store_error)?;
+            Ok(())
+        }
+
+        fn entries(&self, table: Table) -> Result<Vec<(String, String)>, Error> {
+            let mut statement = self
+                .connection
+                .prepare("SELECT key, value FROM kv WHERE tbl = ?1 ORDER BY key")
+                .map_err(store_error)?;
+            let rows = statement
+                .query_map(params![table.name()], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(store_error)?;
+            rows.map(|row| row.map_err(store_error)).collect()
+        }
+
+        fn append(&mut self, entry: &Entry) -> Result<(), Error> {
+            self.connection
+                .execute(
+                    "INSERT INTO history (time, grp, device, state) VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        millis(entry.time) as i64,
+                        entry.group,
+                        entry.device.to_string(),
+                        entry.state.to_string()
+                    ],
+                )
+                .map_err(store_error)?;
+            Ok(())
+        }
+
+        fn history(&self, since: SystemTime) -> Result<Vec<Entry>, Error> {
+            let mut statement = self
+                .connection
+                .prepare(
+                    "SELECT time, grp, device, state FROM history WHERE time >= ?1 \
+                     ORDER BY time DESC, rowid DESC",
+                )
+                .map_err(store_error)?;
+            let rows = statement
+                .query_map(params![millis(since) as i64], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                })
+                .map_err(store_error)?;
+            rows.map(|row| {
+                let (time, group, device, state) = row.map_err(store_error)?;
+                Ok(Entry {
+                    time: UNIX_EPOCH + Duration::from_millis(time as u64),
+                    group,
+                    device: device.parse()?,
+                    state: state.parse()?,
+                    encoding: None,
+                    protocol: None,
+                })
+            })
+            .collect()
+        }
+    }
+}
@@ -0,0 +1,183 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Triggers delay the start of a transmission until an external event happens. One use
+//! case is a zero-cross detector on the mains voltage: when switching inductive loads the
+//! transmission can be aligned so that the relay of the socket pulls in near a zero crossing.
+//! This is experimental: the right offset depends on the socket and has to be found by
+//! trying.
+//!
+//! [`ListenBeforeTalk`] defers a transmission while another transmitter uses the channel.
+
+use crate::{delay::Delay, error::Error, metrics::Deferrals, InputPin, Value};
+use log::warn;
+use std::{
+    fmt,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Blocks until a transmission may start
+pub trait Trigger {
+    fn wait(&self) -> Result<(), Error>;
+}
+
+impl fmt::Debug for dyn Trigger + Send + Sync {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Trigger")
+    }
+}
+
+/// Edge of a signal
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+/// Waits for an edge on an input pin e.g connected to a zero-cross detector
+#[derive(Debug)]
+pub struct EdgeTrigger<I: InputPin, D: Delay> {
+    pin: I,
+    edge: Edge,
+    offset: u32,
+    timeout: Duration,
+    delay: D,
+}
+
+impl<I: InputPin, D: Delay> EdgeTrigger<I, D> {
+    /// Create a new trigger that fires `offset` µs after `edge` on `pin`. Waiting fails
+    /// with a timeout if no edge is seen within `timeout`.
+    pub fn new(pin: I, edge: Edge, offset: u32, timeout: Duration, delay: D) -> EdgeTrigger<I, D> {
+        EdgeTrigger {
+            pin,
+            edge,
+            offset,
+            timeout,
+            delay,
+        }
+    }
+}
+
+impl<I: InputPin, D: Delay> Trigger for EdgeTrigger<I, D> {
+    fn wait(&self) -> Result<(), Error> {
+        let (before, after) = match self.edge {
+            Edge::Rising => (Value::Low, Value::High),
+            Edge::Falling => (Value::High, Value::Low),
+        };
+        let start = Instant::now();
+        let mut previous = self.pin.get()?;
+        loop {
+            let value = self.pin.get()?;
+            if previous == before && value == after {
+                break;
+            }
+            if start.elapsed() > self.timeout {
+                return Err(Error::Timeout(start.elapsed()));
+            }
+            previous = value;
+        }
+        self.delay.delay(self.offset);
+        Ok(())
+    }
+}
+
+/// Defers a transmission while the channel is busy. `pin` is a carrier sense output e.g the
+/// GDO pin of a CC1101 or a squelched receiver, and is `busy` while another transmitter is
+/// on air. A transmission starts once the channel was idle for `quiet` and starts anyway
+/// after `max_deferral`.
+/// ```
+/// use funksteckdose::{mock::NullInputPin, trigger::{ListenBeforeTalk, Trigger}, Value};
+/// use std::time::Duration;
+/// let lbt = ListenBeforeTalk::new(
+///     NullInputPin,
+///     Value::High,
+///     Duration::from_millis(1),
+///     Duration::from_secs(2),
+/// );
+/// let deferrals = lbt.deferrals();
+/// lbt.wait().unwrap();
+/// assert_eq!(deferrals.checks(), 1);
+/// assert_eq!(deferrals.deferred(), 0);
+/// ```
+#[derive(Debug)]
+pub struct ListenBeforeTalk<I: InputPin> {
+    pin: I,
+    busy: Value,
+    quiet: Duration,
+    max_deferral: Duration,
+    deferrals: Arc<Deferrals>,
+}
+
+impl<I: InputPin> ListenBeforeTalk<I> {
+    /// Create a new trigger that waits for `quiet` with `pin` not being `busy`
+    pub fn new(
+        pin: I,
+        busy: Value,
+        quiet: Duration,
+        max_deferral: Duration,
+    ) -> ListenBeforeTalk<I> {
+        ListenBeforeTalk {
+            pin,
+            busy,
+            quiet,
+            max_deferral,
+            deferrals: Arc::default(),
+        }
+    }
+
+    /// Counters of the deferred transmissions. Keep a clone before passing the trigger to
+    /// `Funksteckdose::set_trigger`.
+    pub fn deferrals(&self) -> Arc<Deferrals> {
+        self.deferrals.clone()
+    }
+
+    /// Use `deferrals` for counting e.g to aggregate several transmitters
+    pub fn set_deferrals(&mut self, deferrals: Arc<Deferrals>) {
+        self.deferrals = deferrals;
+    }
+}
+
+impl<I: InputPin> Trigger for ListenBeforeTalk<I> {
+    fn wait(&self) -> Result<(), Error> {
+        const POLL: Duration = Duration::from_micros(100);
+        let start = Instant::now();
+        let mut busy = false;
+        let mut idle_since = None;
+        loop {
+            let now = Instant::now();
+            if self.pin.get()? == self.busy {
+                busy = true;
+                idle_since = None;
+            } else if now.duration_since(*idle_since.get_or_insert(now)) >= self.quiet {
+                self.deferrals
+                    .record(Some(start.elapsed()).filter(|_| busy), false);
+                return Ok(());
+            }
+            if now.duration_since(start) >= self.max_deferral {
+                warn!("Channel busy for {:?}. Sending anyway", self.max_deferral);
+                self.deferrals.record(Some(start.elapsed()), true);
+                return Ok(());
+            }
+            thread::sleep(POLL);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//! Encoders loaded from WebAssembly modules
+#![cfg(feature = "wasm")]
+
+use funksteckdose::{error::Error, plugin::WasmEncoder, Device, State};
+
+// Writes the group followed by a digit for the device and the state. Device 4 never returns
+// and device 5 returns a code word of 33 bytes.
+const ENCODER: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "input") (result i32) i32.const 0)
+  (func (export "output") (result i32) i32.const 64)
+  (func (export "encode") (param $len i32) (param $device i32) (param $state i32) (result i32)
+    (if (i32.eq (local.get $device) (i32.const 4))
+      (then (loop $spin (br $spin))))
+    (if (i32.eq (local.get $device) (i32.const 5))
+      (then
+        (memory.fill (i32.const 64) (i32.const 70) (i32.const 33))
+        (return (i32.const 33))))
+    (memory.copy (i32.const 64) (i32.const 0) (local.get $len))
+    (i32.store8 (i32.add (i32.const 64) (local.get $len))
+      (select (i32.const 70) (i32.const 48) (i32.gt_s (local.get $device) (i32.const 2))))
+    (i32.store8 (i32.add (i32.const 65) (local.get $len))
+      (i32.add (i32.const 48) (local.get $state)))
+    (i32.add (local.get $len) (i32.const 2))))
+"#;
+
+fn encoder() -> WasmEncoder {
+    WasmEncoder::new(&wat::parse_str(ENCODER).unwrap()).unwrap()
+}
+
+#[test]
+fn encode() {
+    let mut encoder = encoder();
+    let code_word = encoder.encode("0F0F", &Device::C, &State::On).unwrap();
+    assert_eq!(code_word, b"0F0FF1");
+    let code_word = encoder.encode("1", &Device::A, &State::Off).unwrap();
+    assert_eq!(code_word, b"100");
+}
+
+#[test]
+fn oversized_code_word() {
+    let mut encoder = encoder();
+    assert!(matches!(
+        encoder.encode("0", &Device::E, &State::On),
+        Err(Error::Plugin(_))
+    ));
+}
+
+#[test]
+fn out_of_fuel() {
+    let mut encoder = encoder();
+    for _ in 0..3 {
+        assert!(matches!(
+            encoder.encode("0", &Device::D, &State::On),
+            Err(Error::Plugin(_))
+        ));
+    }
+    // Calls after a runaway call get the full fuel again
+    assert_eq!(encoder.encode("0", &Device::A, &State::On).unwrap(), b"001");
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn configured_socket() {
+    use funksteckdose::config::Config;
+
+    let path = std::env::temp_dir().join(format!("funksteckdose-{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(ENCODER).unwrap()).unwrap();
+    let config: Config = format!(
+        "[[socket]]\nname = \"weird\"\ngroup = \"0F0F\"\ndevice = \"C\"\nplugin = {:?}\n",
+        path
+    )
+    .parse()
+    .unwrap();
+    let plan = config.socket("weird").unwrap().plan(&State::Off, 1);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(plan.unwrap().code_word.as_deref(), Some("0F0FF0"));
+}
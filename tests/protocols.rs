@@ -0,0 +1,212 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//! Invariants of the pulses sent for every protocol of the catalog. A protocol added to
+//! the catalog is checked without further changes here.
+
+use funksteckdose::{
+    catalog,
+    mock::{Event, Recorder},
+    Code, DynProtocol, EncodingA, ProtocolValues, Value,
+};
+
+type Funksteckdose = funksteckdose::Funksteckdose<Recorder, EncodingA, DynProtocol, Recorder>;
+
+const REPEATS: usize = 3;
+
+// High and low pulse lengths of a symbol
+type Pulses = (u64, u64);
+
+// Sync, zero and one as specified by rc-switch
+const SPEC: &[(&str, Pulses, Pulses, Pulses)] = &[
+    ("1", (1, 31), (1, 3), (3, 1)),
+    ("2", (1, 10), (1, 2), (2, 1)),
+    ("3", (30, 71), (4, 11), (9, 6)),
+    ("4", (1, 6), (1, 3), (3, 1)),
+    ("5", (6, 14), (1, 2), (2, 1)),
+    ("HT6P20B", (23, 1), (1, 2), (2, 1)),
+    ("HS2303", (2, 62), (1, 6), (6, 1)),
+    ("6", (23, 1), (1, 2), (2, 1)),
+    ("7", (2, 62), (1, 6), (6, 1)),
+    ("8", (3, 130), (7, 16), (3, 16)),
+    ("9", (130, 7), (16, 7), (16, 3)),
+    ("10", (18, 1), (3, 1), (1, 3)),
+    ("11", (36, 1), (1, 2), (2, 1)),
+    ("12", (36, 1), (1, 2), (2, 1)),
+];
+
+// Protocols whose zero and one differ in length in rc-switch
+const UNEQUAL_SYMBOLS: &[&str] = &["8", "9"];
+
+fn values(name: &str) -> ProtocolValues {
+    catalog::protocol(name).expect("protocol of the catalog")
+}
+
+/// Send `code` with the protocol `name` and return the recorded frames as (high, low) µs
+/// of each symbol
+fn frames(name: &str, code: &Code) -> Vec<Vec<(u32, u32)>> {
+    let recorder = Recorder::default();
+    let mut funksteckdose = Funksteckdose::with_delay(recorder.clone(), REPEATS, recorder.clone());
+    funksteckdose.set_protocol(values(name));
+    funksteckdose.set_watchdog(None);
+    funksteckdose.send_code(code.code(), code.length()).unwrap();
+
+    let inverted = values(name).inverted_signal();
+    let (active, idle) = if inverted {
+        (Value::Low, Value::High)
+    } else {
+        (Value::High, Value::Low)
+    };
+    let pulses = recorder.pulses();
+    assert_eq!(pulses.first().map(|p| &p.0), Some(&active), "{}", name);
+    assert_eq!(
+        recorder.events().last(),
+        Some(&Event::Set(Value::Low)),
+        "{}: pin not left low",
+        name
+    );
+
+    let symbols: Vec<(u32, u32)> = pulses
+        .chunks(2)
+        .filter(|pulse| pulse.len() == 2)
+        .map(|pulse| {
+            assert_eq!(pulse[0].0, active, "{}", name);
+            assert_eq!(pulse[1].0, idle, "{}", name);
+            (pulse[0].1, pulse[1].1)
+        })
+        .collect();
+    symbols
+        .chunks(code.length() + 1)
+        .map(|frame| frame.to_vec())
+        .collect()
+}
+
+fn codes() -> Vec<Code> {
+    vec![
+        Code::parse("0FF0FFF0FF0F").unwrap(),
+        Code::from_decimal(0, 24).unwrap(),
+        Code::from_decimal(0xff_ffff, 24).unwrap(),
+        Code::from_binary("1").unwrap(),
+    ]
+}
+
+#[test]
+fn catalog_matches_spec() {
+    let names: Vec<&str> = catalog::protocols().iter().map(|p| p.name).collect();
+    for (name, sync, zero, one) in SPEC {
+        assert!(names.contains(name), "{} missing in the catalog", name);
+        let values = values(name);
+        let pulses = |h: &funksteckdose::HighLow| (h.high, h.low);
+        assert_eq!(pulses(values.sync_factor()), *sync, "{}: sync", name);
+        assert_eq!(pulses(values.zero()), *zero, "{}: zero", name);
+        assert_eq!(pulses(values.one()), *one, "{}: one", name);
+    }
+}
+
+#[test]
+fn repeat_count_honored() {
+    for protocol in catalog::protocols() {
+        for code in codes() {
+            let frames = frames(protocol.name, &code);
+            assert_eq!(frames.len(), REPEATS, "{}: {}", protocol.name, code);
+            for frame in &frames {
+                assert_eq!(
+                    frame.len(),
+                    code.length() + 1,
+                    "{}: {}",
+                    protocol.name,
+                    code
+                );
+            }
+            assert!(frames.windows(2).all(|f| f[0] == f[1]), "{}", protocol.name);
+        }
+    }
+}
+
+#[test]
+fn frame_duration_is_sum_of_symbols() {
+    for protocol in catalog::protocols() {
+        let values = values(protocol.name);
+        for code in codes() {
+            let frame = &frames(protocol.name, &code)[0];
+            let recorded: u64 = frame.iter().map(|(h, l)| u64::from(h + l)).sum();
+            assert_eq!(
+                recorded,
+                values.frame_micros(code.code(), code.length()),
+                "{}: {}",
+                protocol.name,
+                code
+            );
+        }
+    }
+}
+
+#[test]
+fn symbols_match_protocol() {
+    for protocol in catalog::protocols() {
+        let values = values(protocol.name);
+        let micros = |h: &funksteckdose::HighLow| {
+            (
+                (h.high * values.pulse_length()) as u32,
+                (h.low * values.pulse_length()) as u32,
+            )
+        };
+        for code in codes() {
+            let frame = &frames(protocol.name, &code)[0];
+            for (i, symbol) in frame[..code.length()].iter().enumerate() {
+                let bit = code.code() & (1 << (code.length() - 1 - i)) != 0;
+                let expected = if bit { values.one() } else { values.zero() };
+                assert_eq!(*symbol, micros(expected), "{}: bit {}", protocol.name, i);
+            }
+            assert_eq!(
+                frame[code.length()],
+                micros(values.sync_factor()),
+                "{}: sync",
+                protocol.name
+            );
+        }
+    }
+}
+
+#[test]
+fn symbols_distinguishable() {
+    for protocol in catalog::protocols() {
+        let (sync, zero, one) = (protocol.sync, protocol.zero, protocol.one);
+        assert_ne!(zero, one, "{}", protocol.name);
+        if !UNEQUAL_SYMBOLS.contains(&protocol.name) {
+            assert_eq!(
+                zero.high + zero.low,
+                one.high + one.low,
+                "{}: zero and one differ in length",
+                protocol.name
+            );
+        }
+        assert!(
+            sync.high + sync.low > zero.high + zero.low,
+            "{}: sync not longer than a bit",
+            protocol.name
+        );
+        let longest = |h: &funksteckdose::HighLow| h.high.max(h.low);
+        assert!(
+            longest(&sync) > longest(&zero).max(longest(&one)),
+            "{}: no pulse of the sync is longer than the pulses of the bits",
+            protocol.name
+        );
+    }
+}
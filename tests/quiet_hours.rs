@@ -0,0 +1,87 @@
+// Copyright © 2019 Felix Obenhuber
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Quiet hours of a configuration
+#![cfg(feature = "config")]
+
+use chrono::{Duration, Local};
+use funksteckdose::{
+    config::Config, error::Error, mock::NullPin, queue::Command, quiet::Priority, Device,
+    EncodingA, Funksteckdose, Protocol1, State,
+};
+
+// Quiet hours from an hour ago to in an hour that suppress background transmissions
+fn config() -> Config {
+    let now = Local::now();
+    format!(
+        r#"
+[[socket]]
+name = "lamp"
+group = "10011"
+device = "A"
+
+[quiet_hours]
+start = "{}"
+end = "{}"
+repeats = 0
+"#,
+        (now - Duration::hours(1)).format("%H:%M"),
+        (now + Duration::hours(1)).format("%H:%M"),
+    )
+    .parse()
+    .unwrap()
+}
+
+fn transmitter(config: &Config) -> Funksteckdose<NullPin, EncodingA, Protocol1> {
+    Funksteckdose::builder(NullPin)
+        .repeat_transmit(1)
+        .quiet_hours(config.quiet_hours.clone().unwrap())
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn suppress_background() {
+    let config = config();
+    let d = transmitter(&config);
+    let lamp = config.socket("lamp").unwrap();
+    assert!(matches!(
+        d.send_socket_with_priority(lamp, &State::On, Priority::Background),
+        Err(Error::Quiet(_))
+    ));
+    assert!(matches!(
+        d.send_with_priority("10011", &Device::A, &State::On, Priority::Background),
+        Err(Error::Quiet(_))
+    ));
+    let command = Command::new("10011", &Device::A, &State::On);
+    assert!(matches!(
+        command.send_with_priority(&d, Priority::Background),
+        Err(Error::Quiet(_))
+    ));
+}
+
+#[test]
+fn send_interactive() {
+    let config = config();
+    let d = transmitter(&config);
+    d.send_socket(config.socket("lamp").unwrap(), &State::On)
+        .unwrap();
+    d.send("10011", &Device::A, &State::Off).unwrap();
+}